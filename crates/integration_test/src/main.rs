@@ -1,10 +1,14 @@
 mod tests;
 mod utils;
 
+use crate::tests::detach::get_detach_test;
+use crate::tests::devpts::get_devpts_test;
 use crate::tests::lifecycle::{ContainerCreate, ContainerLifecycle};
 use crate::tests::linux_ns_itype::get_ns_itype_tests;
+use crate::tests::masked_paths::get_masked_paths_test;
 use crate::tests::pidfile::get_pidfile_test;
 use crate::tests::readonly_paths::get_ro_paths_test;
+use crate::tests::readonly_root::get_ro_root_test;
 use crate::tests::seccomp_notify::get_seccomp_notify_test;
 use crate::tests::tlb::get_tlb_test;
 use crate::utils::support::{set_runtime_path, set_runtimetest_path};
@@ -86,6 +90,10 @@ fn main() -> Result<()> {
     let cgroup_v1_blkio = cgroups::blkio::get_test_group();
     let seccomp_notify = get_seccomp_notify_test();
     let ro_paths = get_ro_paths_test();
+    let ro_root = get_ro_root_test();
+    let masked_paths = get_masked_paths_test();
+    let devpts = get_devpts_test();
+    let detach = get_detach_test();
 
     tm.add_test_group(&cl);
     tm.add_test_group(&cc);
@@ -100,6 +108,10 @@ fn main() -> Result<()> {
     tm.add_test_group(&cgroup_v1_blkio);
     tm.add_test_group(&seccomp_notify);
     tm.add_test_group(&ro_paths);
+    tm.add_test_group(&ro_root);
+    tm.add_test_group(&masked_paths);
+    tm.add_test_group(&devpts);
+    tm.add_test_group(&detach);
 
     tm.add_cleanup(Box::new(cgroups::cleanup_v1));
     tm.add_cleanup(Box::new(cgroups::cleanup_v2));
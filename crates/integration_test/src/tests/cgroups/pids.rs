@@ -1,6 +1,8 @@
 use std::{
     fs,
+    io::Write,
     path::{Path, PathBuf},
+    process::{Command, Stdio},
 };
 
 use anyhow::{bail, Context, Result};
@@ -75,6 +77,7 @@ fn test_negative_limit() -> TestResult {
     test_outside_container(spec, &|data| {
         test_result!(check_container_created(&data));
         test_result!(check_pids_are_unlimited(cgroup_name));
+        test_result!(check_fork_not_constrained(cgroup_name));
         TestResult::Passed
     })
 }
@@ -146,6 +149,45 @@ fn check_pids_are_unlimited(cgroup_name: &str) -> Result<()> {
     Ok(())
 }
 
+// Forks a number of processes in the pids cgroup of the container, which
+// would fail if any small limit was set
+fn check_fork_not_constrained(cgroup_name: &str) -> Result<()> {
+    let procs_path = PathBuf::from(CGROUP_ROOT)
+        .join("pids/runtime-test")
+        .join(cgroup_name)
+        .join("cgroup.procs");
+    // the shell waits until it has been moved into the cgroup
+    let mut shell = Command::new("sh")
+        .arg("-c")
+        .arg("read line; for i in $(seq 100); do sleep 1 & done; wait")
+        .stdin(Stdio::piped())
+        .spawn()
+        .context("failed to spawn shell")?;
+
+    let moved = fs::write(&procs_path, shell.id().to_string())
+        .with_context(|| format!("failed to write to {:?}", procs_path))
+        .and_then(|_| {
+            let mut stdin = shell.stdin.take().context("no stdin for shell")?;
+            writeln!(stdin, "fork").context("failed to write to shell")
+        });
+    if let Err(e) = moved {
+        let _ = shell.kill();
+        let _ = shell.wait();
+        return Err(e);
+    }
+
+    let status = shell.wait().context("failed to wait for shell")?;
+    if !status.success() {
+        bail!(
+            "expected to fork 100 processes in {:?}, but the shell failed with {}",
+            procs_path,
+            status
+        );
+    }
+
+    Ok(())
+}
+
 fn can_run() -> bool {
     Path::new("/sys/fs/cgroup/pids").exists()
 }
@@ -0,0 +1,119 @@
+use crate::utils::{
+    delete_container, generate_uuid, get_runtime_path, get_state, kill_container, prepare_bundle,
+    State, TempDir,
+};
+use anyhow::{bail, Context, Result};
+use procfs::process::Process;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+use test_framework::{Test, TestGroup, TestResult};
+
+/// Time the init process has to exit after it has been killed
+const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+fn run_detached(id: &str, bundle: &TempDir) -> Result<()> {
+    // the detached init process inherits the stdio, so it must not be piped,
+    // otherwise waiting for youki would block until the container exits
+    let status = Command::new(get_runtime_path())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .arg("--root")
+        .arg(bundle.as_ref().join("runtime"))
+        .arg("run")
+        .arg("--detach")
+        .arg(id)
+        .arg("--bundle")
+        .arg(bundle.as_ref().join("bundle"))
+        .status()
+        .context("could not run container")?;
+    if !status.success() {
+        bail!("run failed with {}", status);
+    }
+    Ok(())
+}
+
+fn load_state(id: &str, bundle: &TempDir) -> Result<State> {
+    let (out, err) = get_state(id, bundle)?;
+    if !err.is_empty() {
+        bail!("error in state : {}", err);
+    }
+    serde_json::from_str(&out).context("failed to parse state")
+}
+
+fn check_detach(id: &str, bundle: &TempDir) -> Result<()> {
+    run_detached(id, bundle)?;
+
+    // youki has returned after persisting the state of the container
+    let state = load_state(id, bundle)?;
+    if state.status != "running" {
+        bail!("expected status 'running', got {}", state.status);
+    }
+    let pid = state.pid.context("state has no pid")?;
+    let ppid = Process::new(pid)
+        .with_context(|| format!("failed to read process {}", pid))?
+        .stat
+        .ppid;
+    if ppid != 1 {
+        bail!(
+            "expected init {} to be reparented to pid 1, found {}",
+            pid,
+            ppid
+        );
+    }
+
+    let kill = kill_container(id, bundle)?.wait()?;
+    if !kill.success() {
+        bail!("kill failed with {}", kill);
+    }
+    // the state is refreshed on every query, so wait for the init to exit
+    let deadline = Instant::now() + STOP_TIMEOUT;
+    let mut status = load_state(id, bundle)?.status;
+    while status != "stopped" && Instant::now() < deadline {
+        thread::sleep(POLL_INTERVAL);
+        status = load_state(id, bundle)?.status;
+    }
+    if status != "stopped" {
+        bail!("expected status 'stopped' after kill, got {}", status);
+    }
+
+    let delete = delete_container(id, bundle)?.wait()?;
+    if !delete.success() {
+        bail!("delete failed with {}", delete);
+    }
+    if bundle.as_ref().join("runtime").join(id).exists() {
+        bail!("state directory of {} has not been removed", id);
+    }
+    Ok(())
+}
+
+fn test_detach() -> TestResult {
+    let container_id = generate_uuid();
+    let bundle = prepare_bundle(&container_id).unwrap();
+    let id = container_id.to_string();
+
+    let result = check_detach(&id, &bundle);
+    if result.is_err() {
+        // clean up whatever is left of the container
+        if let Ok(mut kill) = kill_container(&id, &bundle) {
+            let _ = kill.wait();
+        }
+        if let Ok(mut delete) = delete_container(&id, &bundle) {
+            let _ = delete.wait();
+        }
+    }
+
+    match result {
+        Ok(_) => TestResult::Passed,
+        Err(e) => TestResult::Failed(e),
+    }
+}
+
+pub fn get_detach_test<'a>() -> TestGroup<'a> {
+    let detach = Test::new("detach", Box::new(test_detach));
+    let mut tg = TestGroup::new("detach");
+    tg.add(vec![Box::new(detach)]);
+    tg
+}
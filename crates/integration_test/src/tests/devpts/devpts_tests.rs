@@ -0,0 +1,29 @@
+use crate::utils::{runtimetest_group, runtimetest_spec};
+use oci_spec::runtime::{Spec, SpecBuilder};
+use std::path::Path;
+use test_framework::TestGroup;
+
+fn get_spec() -> Spec {
+    // Without a gid option the runtime has to derive it from the tty group
+    let mut mounts = oci_spec::runtime::get_default_mounts();
+    for mount in &mut mounts {
+        if mount.destination() == Path::new("/dev/pts") {
+            let options = mount
+                .options()
+                .clone()
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|o| !o.starts_with("gid="))
+                .collect();
+            mount.set_options(Some(options));
+        }
+    }
+
+    runtimetest_spec(SpecBuilder::default().mounts(mounts))
+}
+
+// The runtimetest allocates a pseudoterminal and checks the owner and mode
+// of its device
+pub fn get_devpts_test<'a>() -> TestGroup<'a> {
+    runtimetest_group("devpts", get_spec, |_| Ok(()))
+}
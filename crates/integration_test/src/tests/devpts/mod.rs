@@ -0,0 +1,2 @@
+mod devpts_tests;
+pub use devpts_tests::get_devpts_test;
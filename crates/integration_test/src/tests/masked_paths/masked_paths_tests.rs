@@ -0,0 +1,38 @@
+use crate::utils::{runtimetest_group, runtimetest_spec};
+use anyhow::Result;
+use oci_spec::runtime::{LinuxBuilder, Spec, SpecBuilder};
+use std::{fs, path::Path};
+use test_framework::TestGroup;
+
+const MASKED_DIR: &str = "masked_irq";
+const MASKED_FILE: &str = "masked_file";
+
+fn get_spec() -> Spec {
+    let masked_paths = vec![
+        format!("/{}", MASKED_DIR),
+        format!("/{}", MASKED_FILE),
+        "/masked_does_not_exist".to_string(),
+    ];
+
+    runtimetest_spec(
+        SpecBuilder::default().linux(
+            LinuxBuilder::default()
+                .masked_paths(masked_paths)
+                .build()
+                .expect("could not build"),
+        ),
+    )
+}
+
+fn setup_masked_paths(rootfs: &Path) -> Result<()> {
+    fs::create_dir_all(rootfs.join(MASKED_DIR).join("0"))?;
+    fs::write(rootfs.join(MASKED_DIR).join("default_smp_affinity"), "f")?;
+    fs::write(rootfs.join(MASKED_FILE), "secret")?;
+    Ok(())
+}
+
+// The runtimetest checks that the masked directory appears empty and read
+// only, and that the masked file has no content.
+pub fn get_masked_paths_test<'a>() -> TestGroup<'a> {
+    runtimetest_group("masked_paths", get_spec, setup_masked_paths)
+}
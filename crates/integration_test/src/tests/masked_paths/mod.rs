@@ -0,0 +1,2 @@
+mod masked_paths_tests;
+pub use masked_paths_tests::get_masked_paths_test;
@@ -1,7 +1,11 @@
 pub mod cgroups;
+pub mod detach;
+pub mod devpts;
 pub mod lifecycle;
 pub mod linux_ns_itype;
+pub mod masked_paths;
 pub mod pidfile;
 pub mod readonly_paths;
+pub mod readonly_root;
 pub mod seccomp_notify;
 pub mod tlb;
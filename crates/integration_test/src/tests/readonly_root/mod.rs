@@ -0,0 +1,2 @@
+mod readonly_root_tests;
+pub use readonly_root_tests::get_ro_root_test;
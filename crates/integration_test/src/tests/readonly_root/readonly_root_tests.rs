@@ -0,0 +1,34 @@
+use crate::utils::{runtimetest_group, runtimetest_spec};
+use oci_spec::runtime::{MountBuilder, RootBuilder, Spec, SpecBuilder};
+use test_framework::TestGroup;
+
+fn get_spec() -> Spec {
+    let mut mounts = oci_spec::runtime::get_default_mounts();
+    mounts.push(
+        MountBuilder::default()
+            .destination("/tmp")
+            .typ("tmpfs")
+            .source("tmpfs")
+            .options(vec!["nosuid".to_string(), "nodev".to_string()])
+            .build()
+            .unwrap(),
+    );
+
+    runtimetest_spec(
+        SpecBuilder::default()
+            .root(
+                RootBuilder::default()
+                    .path("rootfs")
+                    .readonly(true)
+                    .build()
+                    .unwrap(),
+            )
+            .mounts(mounts),
+    )
+}
+
+// The runtimetest checks that the root rejects writes, while the tmpfs
+// mounted on top of it is still writable
+pub fn get_ro_root_test<'a>() -> TestGroup<'a> {
+    runtimetest_group("readonly_root", get_spec, |_| Ok(()))
+}
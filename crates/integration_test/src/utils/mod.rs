@@ -7,6 +7,6 @@ pub use support::{
 };
 pub use temp_dir::{create_temp_dir, TempDir};
 pub use test_utils::{
-    create_container, delete_container, get_state, kill_container, test_inside_container,
-    test_outside_container, ContainerData, State,
+    create_container, delete_container, get_state, kill_container, runtimetest_group,
+    runtimetest_spec, test_inside_container, test_outside_container, ContainerData, State,
 };
@@ -3,14 +3,14 @@
 use super::{generate_uuid, prepare_bundle, set_config};
 use super::{get_runtime_path, get_runtimetest_path};
 use anyhow::{anyhow, bail, Context, Result};
-use oci_spec::runtime::Spec;
+use oci_spec::runtime::{ProcessBuilder, Spec, SpecBuilder};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::process::{Child, Command, ExitStatus, Stdio};
 use std::thread::sleep;
 use std::time::Duration;
-use test_framework::{test_result, TestResult};
+use test_framework::{test_result, Test, TestGroup, TestResult};
 
 const SLEEP_TIME: Duration = Duration::from_millis(150);
 pub const CGROUP_ROOT: &str = "/sys/fs/cgroup";
@@ -227,6 +227,36 @@ pub fn test_inside_container(
     TestResult::Passed
 }
 
+/// Completes the spec with the runtimetest as the process of the container
+pub fn runtimetest_spec(builder: SpecBuilder) -> Spec {
+    builder
+        .process(
+            ProcessBuilder::default()
+                .args(vec!["runtimetest".to_string()])
+                .build()
+                .unwrap(),
+        )
+        .build()
+        .unwrap()
+}
+
+/// Creates a group with a single test of the same name. The test runs the
+/// runtimetest inside a container of the spec, which checks the container
+/// from inside, after the rootfs has been prepared by setup_for_test.
+pub fn runtimetest_group<'a>(
+    name: &'a str,
+    get_spec: fn() -> Spec,
+    setup_for_test: fn(&Path) -> Result<()>,
+) -> TestGroup<'a> {
+    let test = Test::new(
+        name,
+        Box::new(move || test_inside_container(get_spec(), &setup_for_test)),
+    );
+    let mut tg = TestGroup::new(name);
+    tg.add(vec![Box::new(test)]);
+    tg
+}
+
 pub fn check_container_created(data: &ContainerData) -> Result<()> {
     match &data.create_result {
         Ok(exit_status) => {
@@ -1,21 +1,26 @@
 use std::{
     fmt::{Debug, Display},
     fs::{self, File},
-    io::{BufRead, BufReader, Write},
+    io::{BufRead, BufReader, ErrorKind, Write},
+    ops::RangeInclusive,
+    os::unix::{fs::MetadataExt, io::RawFd},
     path::{Path, PathBuf},
     time::Duration,
 };
 
 use anyhow::{bail, Context, Result};
 use nix::{
-    sys::statfs::{statfs, CGROUP2_SUPER_MAGIC, TMPFS_MAGIC},
-    unistd::Pid,
+    errno::Errno,
+    sys::signal::{self, Signal},
+    unistd::{Pid, Uid},
 };
 use oci_spec::runtime::{
-    LinuxDevice, LinuxDeviceBuilder, LinuxDeviceCgroup, LinuxDeviceCgroupBuilder, LinuxDeviceType,
-    LinuxResources,
+    LinuxBlockIo, LinuxDevice, LinuxDeviceBuilder, LinuxDeviceCgroup, LinuxDeviceCgroupBuilder,
+    LinuxDeviceType, LinuxResources,
 };
 
+#[cfg(all(feature = "v1", feature = "v2"))]
+use super::hybrid;
 #[cfg(feature = "systemd")]
 use super::systemd;
 #[cfg(feature = "v1")]
@@ -27,6 +32,16 @@ use super::stats::Stats;
 
 pub const CGROUP_PROCS: &str = "cgroup.procs";
 pub const DEFAULT_CGROUP_ROOT: &str = "/sys/fs/cgroup";
+const MOUNTINFO: &str = "/proc/self/mountinfo";
+/// Range of the block io weights of the runtime spec, which are the ones of
+/// cgroup v1 and are converted for cgroup v2
+pub const BLKIO_WEIGHT_RANGE: RangeInclusive<u16> = 10..=1000;
+/// Whether the cgroup v1 manager has been compiled in
+pub const V1_SUPPORTED: bool = cfg!(feature = "v1");
+/// Whether the cgroup v2 manager has been compiled in
+pub const V2_SUPPORTED: bool = cfg!(feature = "v2");
+/// Whether the systemd cgroup manager has been compiled in
+pub const SYSTEMD_SUPPORTED: bool = cfg!(feature = "systemd");
 
 pub trait CgroupManager {
     /// Adds a task specified by its pid to the cgroup
@@ -35,17 +50,26 @@ pub trait CgroupManager {
     /// Applies resource restrictions to the cgroup
     fn apply(&self, controller_opt: &ControllerOpt) -> Result<()>;
 
-    /// Removes the cgroup
-    fn remove(&self) -> Result<()>;
+    /// Removes the cgroup. Processes still running in the cgroup are only
+    /// killed if the removal is forced, otherwise a busy cgroup is an error
+    fn remove(&self, force: bool) -> Result<()>;
 
     /// Sets the freezer cgroup to the specified state
     fn freeze(&self, state: FreezerState) -> Result<()>;
 
+    /// Returns the current state of the freezer cgroup, which is undefined
+    /// while the cgroup is still being frozen
+    fn freezer_state(&self) -> Result<FreezerState>;
+
     /// Retrieve statistics for the cgroup
     fn stats(&self) -> Result<Stats>;
 
     /// Gets the PIDs inside the cgroup
     fn get_all_pids(&self) -> Result<Vec<Pid>>;
+
+    /// Attaches an eBPF LSM program, which audits the processes of the
+    /// cgroup, e.g. their device opens and mounts
+    fn attach_bpf_audit(&self, prog_fd: RawFd) -> Result<()>;
 }
 
 #[derive(Debug)]
@@ -132,43 +156,51 @@ pub fn read_cgroup_file<P: AsRef<Path>>(path: P) -> Result<String> {
 /// - Legacy: Pure cgroup v1 system.
 /// - Hybrid: Hybrid is basically a cgroup v1 system, except for
 ///   an additional unified hierarchy which doesn't have any
-///   controllers attached. Resource control can purely be achieved
-///   through the cgroup v1 hierarchy, not through the cgroup v2 hierarchy.
+///   controllers attached. Resource control is mainly achieved through
+///   the cgroup v1 hierarchy, the unified hierarchy is only used for
+///   controllers which are not available under v1.
 pub fn get_cgroup_setup() -> Result<CgroupSetup> {
-    let default_root = Path::new(DEFAULT_CGROUP_ROOT);
-    match default_root.exists() {
-        true => {
-            // If the filesystem is of type cgroup2, the system is in unified mode.
-            // If the filesystem is tmpfs instead the system is either in legacy or
-            // hybrid mode. If a cgroup2 filesystem has been mounted under the "unified"
-            // folder we are in hybrid mode, otherwise we are in legacy mode.
-            let stat = statfs(default_root).with_context(|| {
-                format!(
-                    "failed to stat default cgroup root {}",
-                    &default_root.display()
-                )
-            })?;
-            if stat.filesystem_type() == CGROUP2_SUPER_MAGIC {
-                return Ok(CgroupSetup::Unified);
-            }
+    let mountinfo = fs::read_to_string(MOUNTINFO).context("failed to read mountinfo")?;
+    let setup = cgroup_setup_from_mountinfo(&mountinfo)?;
+    log::debug!("detected {} cgroup setup", setup);
+    Ok(setup)
+}
 
-            if stat.filesystem_type() == TMPFS_MAGIC {
-                let unified = Path::new("/sys/fs/cgroup/unified");
-                if Path::new(unified).exists() {
-                    let stat = statfs(unified)
-                        .with_context(|| format!("failed to stat {}", unified.display()))?;
-                    if stat.filesystem_type() == CGROUP2_SUPER_MAGIC {
-                        return Ok(CgroupSetup::Hybrid);
-                    }
-                }
-
-                return Ok(CgroupSetup::Legacy);
-            }
+/// Determines the cgroup setup from the cgroup filesystems mounted under the
+/// default cgroup root. If a cgroup2 filesystem is mounted at the root, the
+/// system is in unified mode. Otherwise the v1 controllers are mounted below
+/// the root and the system is in hybrid mode if a cgroup2 filesystem has been
+/// mounted alongside them (usually at "unified"), otherwise in legacy mode.
+fn cgroup_setup_from_mountinfo(mountinfo: &str) -> Result<CgroupSetup> {
+    let default_root = Path::new(DEFAULT_CGROUP_ROOT);
+    let mut has_v1 = false;
+    let mut has_unified = false;
+
+    for line in mountinfo.lines() {
+        let mut fields = line.split_whitespace();
+        let mount_point = match fields.nth(4) {
+            Some(mount_point) => Path::new(mount_point),
+            None => continue,
+        };
+        // the filesystem type follows the separator of the optional fields
+        let fs_type = fields.skip_while(|f| *f != "-").nth(1);
+
+        match fs_type {
+            Some("cgroup2") if mount_point == default_root => return Ok(CgroupSetup::Unified),
+            Some("cgroup2") if mount_point.starts_with(default_root) => has_unified = true,
+            Some("cgroup") if mount_point.starts_with(default_root) => has_v1 = true,
+            _ => {}
         }
-        false => bail!("non default cgroup root not supported"),
     }
 
-    bail!("failed to detect cgroup setup");
+    match (has_v1, has_unified) {
+        (true, true) => Ok(CgroupSetup::Hybrid),
+        (true, false) => Ok(CgroupSetup::Legacy),
+        _ => bail!(
+            "failed to detect cgroup setup, no cgroup filesystem is mounted at {}",
+            DEFAULT_CGROUP_ROOT
+        ),
+    }
 }
 
 pub fn create_cgroup_manager<P: Into<PathBuf>>(
@@ -178,9 +210,11 @@ pub fn create_cgroup_manager<P: Into<PathBuf>>(
 ) -> Result<Box<dyn CgroupManager>> {
     let cgroup_setup = get_cgroup_setup()?;
     let cgroup_path = cgroup_path.into();
+    validate_cgroup_setup(&cgroup_setup, &cgroup_path, systemd_cgroup)?;
 
     match cgroup_setup {
-        CgroupSetup::Legacy | CgroupSetup::Hybrid => create_v1_cgroup_manager(cgroup_path),
+        CgroupSetup::Legacy => create_v1_cgroup_manager(cgroup_path),
+        CgroupSetup::Hybrid => create_hybrid_cgroup_manager(cgroup_path),
         CgroupSetup::Unified => {
             if systemd_cgroup {
                 return create_systemd_cgroup_manager(cgroup_path, container_name);
@@ -191,6 +225,282 @@ pub fn create_cgroup_manager<P: Into<PathBuf>>(
     }
 }
 
+/// Validates the cgroups path for every setup. The systemd cgroup driver is
+/// only used on the unified hierarchy, so it can not be requested otherwise.
+fn validate_cgroup_setup(
+    cgroup_setup: &CgroupSetup,
+    cgroup_path: &Path,
+    systemd_cgroup: bool,
+) -> Result<()> {
+    validate_cgroups_path(cgroup_path, systemd_cgroup)?;
+    if systemd_cgroup && !matches!(cgroup_setup, CgroupSetup::Unified) {
+        bail!(
+            "the systemd cgroup driver (--systemd-cgroup) requires the unified cgroup hierarchy, but the {} setup is used",
+            cgroup_setup
+        );
+    }
+    Ok(())
+}
+
+/// Creates the cgroup manager of a rootless container. Without systemd, an
+/// unprivileged user can only manage the cgroup subtree, which has been
+/// delegated to it, so the container cgroup has to be placed below it.
+pub fn create_rootless_cgroup_manager<P: Into<PathBuf>>(
+    cgroup_path: P,
+    systemd_cgroup: bool,
+    container_name: &str,
+) -> Result<Box<dyn CgroupManager>> {
+    let cgroup_path = cgroup_path.into();
+    if systemd_cgroup || !matches!(get_cgroup_setup()?, CgroupSetup::Unified) {
+        return create_cgroup_manager(cgroup_path, systemd_cgroup, container_name);
+    }
+
+    validate_cgroups_path(&cgroup_path, false)?;
+    create_delegated_cgroup_manager(cgroup_path)
+}
+
+/// Returns the cgroup subtree delegated to the current user, below which
+/// rootless containers are placed if systemd is not used. This is the topmost
+/// cgroup owned by the user, which contains the cgroup of the current
+/// process, e.g. `/user.slice/user-1000.slice/user@1000.service`.
+pub fn delegation_boundary() -> Result<PathBuf> {
+    let own_cgroup = own_cgroup().context("failed to get cgroup of the current process")?;
+    find_delegation_boundary(
+        Path::new(DEFAULT_CGROUP_ROOT),
+        &own_cgroup,
+        nix::unistd::geteuid(),
+    )
+}
+
+fn find_delegation_boundary(root: &Path, cgroup: &Path, uid: Uid) -> Result<PathBuf> {
+    let is_owned = |path: &Path| {
+        let relative = path.strip_prefix("/").unwrap_or(path);
+        fs::metadata(root.join(relative))
+            .map(|metadata| metadata.uid() == uid.as_raw())
+            .unwrap_or(false)
+    };
+
+    // the root cgroup is never delegated
+    let boundary = cgroup
+        .ancestors()
+        .filter(|ancestor| ancestor.parent().is_some())
+        .take_while(|ancestor| is_owned(ancestor))
+        .last()
+        .with_context(|| format!("cgroup {:?} has not been delegated to the user", cgroup))?;
+
+    if boundary == cgroup {
+        // processes may only be in the leaves of the cgroup tree, so the
+        // controllers cannot be enabled for the container cgroup
+        log::warn!(
+            "the current process runs in the delegated cgroup {:?}, no controllers are available",
+            boundary
+        );
+    }
+    Ok(boundary.to_path_buf())
+}
+
+/// Checks that the block io weights of the runtime spec are in range. A weight
+/// of 0 is the same as no weight.
+pub fn validate_blkio_weights(blkio: &LinuxBlockIo) -> Result<()> {
+    validate_blkio_weight(blkio.weight(), "blkio weight")?;
+    validate_blkio_weight(blkio.leaf_weight(), "blkio leaf weight")?;
+    for device in blkio.weight_device().iter().flatten() {
+        let device_number = format!("{}:{}", device.major(), device.minor());
+        validate_blkio_weight(
+            device.weight(),
+            &format!("weight of device {}", device_number),
+        )?;
+        validate_blkio_weight(
+            device.leaf_weight(),
+            &format!("leaf weight of device {}", device_number),
+        )?;
+    }
+    Ok(())
+}
+
+fn validate_blkio_weight(weight: Option<u16>, name: &str) -> Result<()> {
+    match weight {
+        Some(weight) if weight != 0 && !BLKIO_WEIGHT_RANGE.contains(&weight) => bail!(
+            "{} must be in range {}-{}, but is {}",
+            name,
+            BLKIO_WEIGHT_RANGE.start(),
+            BLKIO_WEIGHT_RANGE.end(),
+            weight
+        ),
+        _ => Ok(()),
+    }
+}
+
+/// Checks that the cgroups path has the form the cgroup driver expects, which
+/// is `[slice]:[prefix]:[name]` for systemd and a filesystem path otherwise.
+pub fn validate_cgroups_path(cgroups_path: &Path, systemd_cgroup: bool) -> Result<()> {
+    let is_systemd_path = is_systemd_path(cgroups_path);
+
+    if systemd_cgroup && !is_systemd_path {
+        bail!(
+            "cgroups path {:?} is a filesystem path, but the systemd cgroup driver expects the form [slice]:[prefix]:[name]",
+            cgroups_path
+        );
+    }
+    if !systemd_cgroup && is_systemd_path {
+        bail!(
+            "cgroups path {:?} has the form [slice]:[prefix]:[name], which requires the systemd cgroup driver (--systemd-cgroup)",
+            cgroups_path
+        );
+    }
+    Ok(())
+}
+
+/// Resolves the cgroups path of a container the way runc does. A relative
+/// path is placed under the cgroup of the calling process, an absolute path is
+/// relative to the root of the cgroup mount. Paths of the systemd cgroup
+/// driver are returned unchanged.
+pub fn resolve_cgroups_path(cgroups_path: &Path) -> Result<PathBuf> {
+    if cgroups_path.is_absolute() || is_systemd_path(cgroups_path) {
+        return Ok(cgroups_path.to_path_buf());
+    }
+
+    let own_cgroup = own_cgroup().context("failed to get cgroup of the current process")?;
+    Ok(join_cgroups_path(&own_cgroup, cgroups_path))
+}
+
+fn join_cgroups_path(parent: &Path, cgroups_path: &Path) -> PathBuf {
+    if cgroups_path.is_absolute() {
+        return cgroups_path.to_path_buf();
+    }
+    Path::new("/").join(parent).join(cgroups_path)
+}
+
+/// Returns the cgroup of the current process in the unified hierarchy, or in
+/// the cpu hierarchy if there is none.
+fn own_cgroup() -> Result<PathBuf> {
+    let cgroups = procfs::process::Process::myself()?.cgroups()?;
+    let cgroup = cgroups
+        .iter()
+        .find(|c| c.hierarchy == 0)
+        .or_else(|| {
+            cgroups
+                .iter()
+                .find(|c| c.controllers.iter().any(|ctrl| ctrl == "cpu"))
+        })
+        .context("no cgroup found in /proc/self/cgroup")?;
+    Ok(PathBuf::from(&cgroup.pathname))
+}
+
+/// Reads the freezer state of a cgroup from the cgroup filesystem, without
+/// creating a cgroup manager for it. With systemd, the cgroups path names the
+/// unit instead of the cgroup, so the cgroup of the process in it is used.
+pub fn get_freezer_state(
+    cgroup_path: &Path,
+    systemd_cgroup: bool,
+    pid: Pid,
+) -> Result<FreezerState> {
+    let cgroup_setup = get_cgroup_setup()?;
+    let cgroup_path = container_cgroup(cgroup_path, systemd_cgroup, pid, "freezer")?;
+
+    match cgroup_setup {
+        CgroupSetup::Legacy | CgroupSetup::Hybrid => get_v1_freezer_state(&cgroup_path),
+        CgroupSetup::Unified => get_v2_freezer_state(&cgroup_path),
+    }
+}
+
+/// Calls the handler whenever the OOM killer has been invoked in a cgroup,
+/// until the cgroup is removed. As with [get_freezer_state], the cgroup is
+/// read from the cgroup filesystem without creating a cgroup manager.
+pub fn watch_oom_events<F: FnMut()>(
+    cgroup_path: &Path,
+    systemd_cgroup: bool,
+    pid: Pid,
+    on_oom: F,
+) -> Result<()> {
+    let cgroup_setup = get_cgroup_setup()?;
+    let cgroup_path = container_cgroup(cgroup_path, systemd_cgroup, pid, "memory")?;
+
+    match cgroup_setup {
+        CgroupSetup::Legacy | CgroupSetup::Hybrid => watch_v1_oom_events(&cgroup_path, on_oom),
+        CgroupSetup::Unified => watch_v2_oom_events(&cgroup_path, on_oom),
+    }
+}
+
+/// Returns the cgroup of a container relative to the root of a hierarchy.
+/// With systemd, the cgroups path names the unit instead of the cgroup, so
+/// the cgroup of the process in it is used.
+fn container_cgroup(
+    cgroup_path: &Path,
+    systemd_cgroup: bool,
+    pid: Pid,
+    controller: &str,
+) -> Result<PathBuf> {
+    let cgroup_path = if systemd_cgroup {
+        process_cgroup(pid, controller)?
+    } else {
+        cgroup_path.to_owned()
+    };
+    Ok(cgroup_path
+        .strip_prefix("/")
+        .unwrap_or(&cgroup_path)
+        .to_owned())
+}
+
+/// Returns the cgroup of a process in the hierarchy of a controller, which is
+/// the unified one if it is not a cgroup v1 controller
+fn process_cgroup(pid: Pid, controller: &str) -> Result<PathBuf> {
+    let cgroups = procfs::process::Process::new(pid.as_raw())?.cgroups()?;
+    let cgroup = cgroups
+        .iter()
+        .find(|c| c.controllers.iter().any(|ctrl| ctrl == controller))
+        .or_else(|| cgroups.iter().find(|c| c.hierarchy == 0))
+        .with_context(|| format!("no {} cgroup found for process {}", controller, pid))?;
+    Ok(PathBuf::from(&cgroup.pathname))
+}
+
+#[cfg(feature = "v1")]
+fn get_v1_freezer_state(cgroup_path: &Path) -> Result<FreezerState> {
+    let root = v1::util::get_subsystem_mount_point(&v1::ControllerType::Freezer)?;
+    v1::freezer::Freezer::state(&root.join(cgroup_path))
+}
+
+#[cfg(not(feature = "v1"))]
+fn get_v1_freezer_state(_cgroup_path: &Path) -> Result<FreezerState> {
+    bail!("cgroup v1 feature is required, but was not enabled during compile time");
+}
+
+#[cfg(feature = "v2")]
+fn get_v2_freezer_state(cgroup_path: &Path) -> Result<FreezerState> {
+    v2::freezer::Freezer::state(&Path::new(DEFAULT_CGROUP_ROOT).join(cgroup_path))
+}
+
+#[cfg(not(feature = "v2"))]
+fn get_v2_freezer_state(_cgroup_path: &Path) -> Result<FreezerState> {
+    bail!("cgroup v2 feature is required, but was not enabled during compile time");
+}
+
+#[cfg(feature = "v1")]
+fn watch_v1_oom_events<F: FnMut()>(cgroup_path: &Path, on_oom: F) -> Result<()> {
+    let root = v1::util::get_subsystem_mount_point(&v1::ControllerType::Memory)?;
+    v1::memory::Memory::watch_oom_events(&root.join(cgroup_path), on_oom)
+}
+
+#[cfg(not(feature = "v1"))]
+fn watch_v1_oom_events<F: FnMut()>(_cgroup_path: &Path, _on_oom: F) -> Result<()> {
+    bail!("cgroup v1 feature is required, but was not enabled during compile time");
+}
+
+#[cfg(feature = "v2")]
+fn watch_v2_oom_events<F: FnMut()>(cgroup_path: &Path, on_oom: F) -> Result<()> {
+    v2::memory::Memory::watch_oom_events(&Path::new(DEFAULT_CGROUP_ROOT).join(cgroup_path), on_oom)
+}
+
+#[cfg(not(feature = "v2"))]
+fn watch_v2_oom_events<F: FnMut()>(_cgroup_path: &Path, _on_oom: F) -> Result<()> {
+    bail!("cgroup v2 feature is required, but was not enabled during compile time");
+}
+
+fn is_systemd_path(cgroups_path: &Path) -> bool {
+    let path = cgroups_path.to_string_lossy();
+    path.contains(':') && !path.contains('/')
+}
+
 #[cfg(feature = "v1")]
 fn create_v1_cgroup_manager(cgroup_path: PathBuf) -> Result<Box<dyn CgroupManager>> {
     log::info!("cgroup manager V1 will be used");
@@ -202,6 +512,19 @@ fn create_v1_cgroup_manager(_cgroup_path: PathBuf) -> Result<Box<dyn CgroupManag
     bail!("cgroup v1 feature is required, but was not enabled during compile time");
 }
 
+#[cfg(all(feature = "v1", feature = "v2"))]
+fn create_hybrid_cgroup_manager(cgroup_path: PathBuf) -> Result<Box<dyn CgroupManager>> {
+    log::info!("cgroup manager hybrid will be used");
+    let unified_root = v2::util::get_unified_mount_point()?;
+    Ok(Box::new(hybrid::Manager::new(cgroup_path, unified_root)?))
+}
+
+#[cfg(not(all(feature = "v1", feature = "v2")))]
+fn create_hybrid_cgroup_manager(cgroup_path: PathBuf) -> Result<Box<dyn CgroupManager>> {
+    // without v2 support the unified hierarchy is left unused
+    create_v1_cgroup_manager(cgroup_path)
+}
+
 #[cfg(feature = "v2")]
 fn create_v2_cgroup_manager(cgroup_path: PathBuf) -> Result<Box<dyn CgroupManager>> {
     log::info!("cgroup manager V2 will be used");
@@ -216,6 +539,25 @@ fn create_v2_cgroup_manager(_cgroup_path: PathBuf) -> Result<Box<dyn CgroupManag
     bail!("cgroup v2 feature is required, but was not enabled during compile time");
 }
 
+#[cfg(feature = "v2")]
+fn create_delegated_cgroup_manager(cgroup_path: PathBuf) -> Result<Box<dyn CgroupManager>> {
+    let delegation_boundary = delegation_boundary()?;
+    log::info!(
+        "cgroup manager V2 will be used with the cgroup {:?} delegated to the user",
+        delegation_boundary
+    );
+    Ok(Box::new(v2::manager::Manager::new_delegated(
+        DEFAULT_CGROUP_ROOT.into(),
+        cgroup_path,
+        delegation_boundary,
+    )?))
+}
+
+#[cfg(not(feature = "v2"))]
+fn create_delegated_cgroup_manager(_cgroup_path: PathBuf) -> Result<Box<dyn CgroupManager>> {
+    bail!("cgroup v2 feature is required, but was not enabled during compile time");
+}
+
 #[cfg(feature = "systemd")]
 fn create_systemd_cgroup_manager(
     cgroup_path: PathBuf,
@@ -403,29 +745,258 @@ pub(crate) fn default_devices() -> Vec<LinuxDevice> {
     ]
 }
 
-/// Attempts to delete the path the requested number of times.
+/// Removes the cgroup and its child cgroups depth-first. A cgroup is busy as
+/// long as processes are running in it, the removal is retried with backoff
+/// the requested number of times. If the removal is forced, the remaining
+/// processes are killed before each retry, otherwise they are left alone and
+/// the cgroup stays in place.
 pub(crate) fn delete_with_retry<P: AsRef<Path>, L: Into<Option<Duration>>>(
     path: P,
     retries: u32,
     limit_backoff: L,
+    force: bool,
 ) -> Result<()> {
-    let mut attempts = 0;
-    let mut delay = Duration::from_millis(10);
     let path = path.as_ref();
     let limit = limit_backoff.into().unwrap_or(Duration::MAX);
+    if !path.exists() {
+        return Ok(());
+    }
 
-    while attempts < retries {
-        if fs::remove_dir(path).is_ok() {
-            return Ok(());
+    for entry in fs::read_dir(path).with_context(|| format!("failed to read {:?}", path))? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            delete_with_retry(entry.path(), retries, limit, force)?;
         }
+    }
 
+    let mut attempts = 0;
+    let mut delay = Duration::from_millis(10);
+    loop {
+        let busy = match fs::remove_dir(path) {
+            Ok(_) => return Ok(()),
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(e) if e.raw_os_error() == Some(Errno::EBUSY as i32) => e,
+            Err(e) => return Err(e).with_context(|| format!("failed to remove {:?}", path)),
+        };
+
+        let pids = get_all_pids(path).unwrap_or_default();
+        attempts += 1;
+        if attempts >= retries {
+            return Err(busy).with_context(|| {
+                format!(
+                    "failed to remove cgroup {:?}, it is still in use by the pids {:?}",
+                    path, pids
+                )
+            });
+        }
+
+        if force {
+            log::debug!("cgroup {:?} is busy, killing the pids {:?}", path, pids);
+            for pid in pids {
+                let _ = signal::kill(pid, Signal::SIGKILL);
+            }
+        } else {
+            log::debug!("cgroup {:?} is busy, waiting for the pids {:?}", path, pids);
+        }
         std::thread::sleep(delay);
-        attempts += attempts;
-        delay *= attempts;
-        if delay > limit {
-            delay = limit;
+        delay = (delay * 2).min(limit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_blkio_weights() {
+        use oci_spec::runtime::{LinuxBlockIoBuilder, LinuxWeightDeviceBuilder};
+
+        let device = |weight: u16, leaf_weight: u16| {
+            LinuxWeightDeviceBuilder::default()
+                .major(8)
+                .minor(16)
+                .weight(weight)
+                .leaf_weight(leaf_weight)
+                .build()
+                .unwrap()
+        };
+        let blkio = LinuxBlockIoBuilder::default()
+            .weight(10u16)
+            .leaf_weight(1000u16)
+            .weight_device(vec![device(500, 0)])
+            .build()
+            .unwrap();
+        assert!(validate_blkio_weights(&blkio).is_ok());
+
+        let blkio = LinuxBlockIoBuilder::default()
+            .weight(1001u16)
+            .build()
+            .unwrap();
+        assert!(validate_blkio_weights(&blkio).is_err());
+
+        let blkio = LinuxBlockIoBuilder::default()
+            .weight_device(vec![device(100, 5)])
+            .build()
+            .unwrap();
+        let err = validate_blkio_weights(&blkio).unwrap_err().to_string();
+        assert_eq!(
+            err,
+            "leaf weight of device 8:16 must be in range 10-1000, but is 5"
+        );
+    }
+
+    #[test]
+    fn test_validate_cgroups_path() {
+        assert!(validate_cgroups_path(Path::new("system.slice:docker:1234"), true).is_ok());
+        assert!(validate_cgroups_path(Path::new(":youki:1234"), true).is_ok());
+        assert!(validate_cgroups_path(Path::new("/youki/1234"), true).is_err());
+        assert!(validate_cgroups_path(Path::new("1234"), true).is_err());
+
+        assert!(validate_cgroups_path(Path::new("/youki/1234"), false).is_ok());
+        assert!(validate_cgroups_path(Path::new("1234"), false).is_ok());
+        assert!(validate_cgroups_path(Path::new("system.slice:docker:1234"), false).is_err());
+    }
+
+    #[test]
+    fn test_validate_cgroup_setup() {
+        let systemd_path = Path::new("system.slice:docker:1234");
+        let fs_path = Path::new("/youki/1234");
+        for setup in [CgroupSetup::Legacy, CgroupSetup::Hybrid] {
+            assert!(validate_cgroup_setup(&setup, fs_path, false).is_ok());
+            assert!(validate_cgroup_setup(&setup, systemd_path, false).is_err());
+            assert!(validate_cgroup_setup(&setup, systemd_path, true).is_err());
         }
+
+        assert!(validate_cgroup_setup(&CgroupSetup::Unified, systemd_path, true).is_ok());
+        assert!(validate_cgroup_setup(&CgroupSetup::Unified, fs_path, true).is_err());
+    }
+
+    #[test]
+    fn test_join_cgroups_path() {
+        let parent = Path::new("/user.slice/user-1000.slice/session-1.scope");
+        assert_eq!(
+            join_cgroups_path(parent, Path::new("youki/1234")),
+            PathBuf::from("/user.slice/user-1000.slice/session-1.scope/youki/1234")
+        );
+        assert_eq!(
+            join_cgroups_path(Path::new("/"), Path::new("1234")),
+            PathBuf::from("/1234")
+        );
+        assert_eq!(
+            join_cgroups_path(parent, Path::new("/youki/1234")),
+            PathBuf::from("/youki/1234")
+        );
     }
 
-    bail!("could not delete {:?}", path)
+    #[test]
+    fn test_resolve_cgroups_path() -> Result<()> {
+        assert_eq!(
+            resolve_cgroups_path(Path::new("/youki/1234"))?,
+            PathBuf::from("/youki/1234")
+        );
+        assert_eq!(
+            resolve_cgroups_path(Path::new("system.slice:docker:1234"))?,
+            PathBuf::from("system.slice:docker:1234")
+        );
+
+        let resolved = resolve_cgroups_path(Path::new("youki/1234"))?;
+        assert!(resolved.is_absolute());
+        assert!(resolved.starts_with(own_cgroup()?));
+        assert!(resolved.ends_with("youki/1234"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_delegation_boundary() -> Result<()> {
+        let tmp = crate::test::create_temp_dir("test_find_delegation_boundary")?;
+        let cgroup = Path::new("/user.slice/user@1000.service/app.slice/youki.scope");
+        fs::create_dir_all(tmp.join("user.slice/user@1000.service/app.slice/youki.scope"))?;
+
+        let uid = nix::unistd::geteuid();
+        assert_eq!(
+            find_delegation_boundary(&tmp, cgroup, uid)?,
+            PathBuf::from("/user.slice")
+        );
+        // nothing has been delegated to another user
+        let other = Uid::from_raw(uid.as_raw().wrapping_add(1));
+        assert!(find_delegation_boundary(&tmp, cgroup, other).is_err());
+        Ok(())
+    }
+
+    const MOUNTINFO_V1: &str = "\
+25 30 0:22 / /sys/fs/cgroup ro,nosuid,nodev,noexec shared:4 - tmpfs tmpfs ro,mode=755
+28 25 0:25 / /sys/fs/cgroup/memory rw,nosuid,nodev,noexec,relatime shared:9 - cgroup cgroup rw,memory
+29 25 0:26 / /sys/fs/cgroup/cpu,cpuacct rw,nosuid,nodev,noexec,relatime shared:10 - cgroup cgroup rw,cpu,cpuacct
+";
+
+    #[test]
+    fn test_cgroup_setup_legacy() {
+        let setup = cgroup_setup_from_mountinfo(MOUNTINFO_V1).expect("detect cgroup setup");
+        assert!(matches!(setup, CgroupSetup::Legacy));
+    }
+
+    #[test]
+    fn test_cgroup_setup_hybrid() {
+        let mountinfo = format!(
+            "{}{}",
+            MOUNTINFO_V1,
+            "26 25 0:23 / /sys/fs/cgroup/unified rw,nosuid,nodev,noexec,relatime shared:5 - cgroup2 cgroup2 rw,nsdelegate\n"
+        );
+        let setup = cgroup_setup_from_mountinfo(&mountinfo).expect("detect cgroup setup");
+        assert!(matches!(setup, CgroupSetup::Hybrid));
+    }
+
+    #[test]
+    fn test_cgroup_setup_unified() {
+        let mountinfo = "\
+22 1 259:2 / / rw,relatime shared:1 - ext4 /dev/nvme0n1p2 rw
+35 24 0:30 / /sys/fs/cgroup rw,nosuid,nodev,noexec,relatime shared:9 - cgroup2 cgroup2 rw,nsdelegate
+";
+        let setup = cgroup_setup_from_mountinfo(mountinfo).expect("detect cgroup setup");
+        assert!(matches!(setup, CgroupSetup::Unified));
+    }
+
+    #[test]
+    fn test_cgroup_setup_not_mounted() {
+        let mountinfo = "22 1 259:2 / / rw,relatime shared:1 - ext4 /dev/nvme0n1p2 rw\n";
+        assert!(cgroup_setup_from_mountinfo(mountinfo).is_err());
+    }
+
+    #[test]
+    fn test_delete_with_retry() {
+        let tmp = crate::test::create_temp_dir("test_delete_with_retry")
+            .expect("create temp directory for test");
+        let cgroup = tmp.join("youki");
+        fs::create_dir_all(cgroup.join("child/nested")).expect("create child cgroups");
+        fs::create_dir_all(cgroup.join("other")).expect("create child cgroup");
+
+        delete_with_retry(&cgroup, 4, Duration::from_millis(100), true).expect("delete cgroup");
+        assert!(!cgroup.exists());
+        // a cgroup which has already been removed is fine
+        delete_with_retry(&cgroup, 4, Duration::from_millis(100), true).expect("delete cgroup");
+    }
+
+    #[test]
+    fn test_delete_with_retry_not_forced() {
+        let tmp = crate::test::create_temp_dir("test_delete_with_retry_not_forced")
+            .expect("create temp directory for test");
+        let cgroup = tmp.join("youki");
+        fs::create_dir_all(cgroup.join("child")).expect("create child cgroup");
+
+        delete_with_retry(&cgroup, 4, Duration::from_millis(100), false).expect("delete cgroup");
+        assert!(!cgroup.exists());
+    }
+
+    #[test]
+    fn test_delete_with_retry_error() {
+        let tmp = crate::test::create_temp_dir("test_delete_with_retry_error")
+            .expect("create temp directory for test");
+        let cgroup = tmp.join("youki");
+        fs::create_dir_all(cgroup.join("child")).expect("create child cgroup");
+        fs::write(cgroup.join("child/file"), "").expect("create file");
+
+        let err = delete_with_retry(&cgroup, 4, Duration::from_millis(100), true)
+            .expect_err("cgroup is not empty");
+        assert!(format!("{:?}", err).contains("child"));
+    }
 }
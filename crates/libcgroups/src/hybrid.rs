@@ -0,0 +1,151 @@
+//! Cgroup manager for hybrid setups, where the controllers are mounted under
+//! v1 and the unified hierarchy is mounted alongside them. The container is
+//! placed into both hierarchies, resources are restricted through v1 and only
+//! controllers which are not available under v1 are handled by the unified
+//! hierarchy.
+use std::os::unix::io::RawFd;
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+use nix::unistd::Pid;
+
+use crate::common::{CgroupManager, ControllerOpt, FreezerState};
+use crate::stats::Stats;
+use crate::v1::{self, ControllerType as V1ControllerType};
+use crate::v2::{self, controller_type::ControllerType as V2ControllerType};
+
+pub struct Manager {
+    v1: v1::Manager,
+    unified: v2::manager::Manager,
+    /// Controllers attached to the unified hierarchy
+    unified_controllers: Vec<V2ControllerType>,
+}
+
+impl Manager {
+    /// Constructs a new cgroup manager with cgroups_path being relative to the
+    /// root of the v1 subsystems as well as the unified hierarchy.
+    pub fn new(cgroup_path: PathBuf, unified_root: PathBuf) -> Result<Self> {
+        let mut v1 = v1::Manager::new(cgroup_path.clone())?;
+
+        // A controller can only be attached to one hierarchy, so the ones of
+        // the unified hierarchy are not available under v1.
+        let unified_controllers = match v2::util::get_available_controllers(&unified_root) {
+            Ok(controllers) => controllers,
+            Err(e) => {
+                log::debug!(
+                    "no controllers are attached to the unified hierarchy: {}",
+                    e
+                );
+                Vec::new()
+            }
+        };
+        log::debug!(
+            "hybrid cgroup setup, controllers {:?} are handled by the unified hierarchy at {:?}",
+            unified_controllers,
+            unified_root
+        );
+        v1.set_unified_controllers(
+            unified_controllers
+                .iter()
+                .filter_map(|c| v1_controller(*c))
+                .collect(),
+        );
+
+        Ok(Self {
+            v1,
+            unified: v2::manager::Manager::new(unified_root, cgroup_path)?,
+            unified_controllers,
+        })
+    }
+}
+
+/// Returns the v1 controller corresponding to a controller of the unified
+/// hierarchy, if there is one
+fn v1_controller(controller: V2ControllerType) -> Option<V1ControllerType> {
+    match controller {
+        V2ControllerType::Cpu => Some(V1ControllerType::Cpu),
+        V2ControllerType::CpuSet => Some(V1ControllerType::CpuSet),
+        V2ControllerType::HugeTlb => Some(V1ControllerType::HugeTlb),
+        V2ControllerType::Io => Some(V1ControllerType::Blkio),
+        V2ControllerType::Memory => Some(V1ControllerType::Memory),
+        V2ControllerType::Pids => Some(V1ControllerType::Pids),
+        V2ControllerType::Rdma => None,
+    }
+}
+
+impl CgroupManager for Manager {
+    fn add_task(&self, pid: Pid) -> Result<()> {
+        self.v1.add_task(pid)?;
+        self.unified.add_task(pid)
+    }
+
+    fn apply(&self, controller_opt: &ControllerOpt) -> Result<()> {
+        self.v1.apply(controller_opt)?;
+        self.unified
+            .apply_controllers(controller_opt, &self.unified_controllers)
+    }
+
+    fn remove(&self, force: bool) -> Result<()> {
+        // both hierarchies are cleaned up, even if one of them fails
+        let mut errors = Vec::new();
+        if let Err(e) = self.v1.remove(force) {
+            errors.push(format!("failed to remove v1 cgroup: {:?}", e));
+        }
+        if let Err(e) = self.unified.remove(force) {
+            errors.push(format!("failed to remove unified cgroup: {:?}", e));
+        }
+
+        if !errors.is_empty() {
+            bail!("{}", errors.join(";"));
+        }
+
+        Ok(())
+    }
+
+    fn freeze(&self, state: FreezerState) -> Result<()> {
+        if self.v1.has_subsystem(&V1ControllerType::Freezer) {
+            return self.v1.freeze(state);
+        }
+
+        self.unified.freeze(state)
+    }
+
+    fn freezer_state(&self) -> Result<FreezerState> {
+        if self.v1.has_subsystem(&V1ControllerType::Freezer) {
+            return self.v1.freezer_state();
+        }
+
+        self.unified.freezer_state()
+    }
+
+    fn stats(&self) -> Result<Stats> {
+        self.v1.stats()
+    }
+
+    fn get_all_pids(&self) -> Result<Vec<Pid>> {
+        // all processes of the container are tracked by the unified hierarchy
+        self.unified.get_all_pids()
+    }
+
+    fn attach_bpf_audit(&self, prog_fd: RawFd) -> Result<()> {
+        self.unified.attach_bpf_audit(prog_fd)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_v1_controller() {
+        assert_eq!(
+            v1_controller(V2ControllerType::Io),
+            Some(V1ControllerType::Blkio)
+        );
+        assert_eq!(
+            v1_controller(V2ControllerType::Memory),
+            Some(V1ControllerType::Memory)
+        );
+        assert_eq!(v1_controller(V2ControllerType::Rdma), None);
+    }
+}
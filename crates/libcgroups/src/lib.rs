@@ -12,6 +12,8 @@ extern crate mockall;
 mod test;
 
 pub mod common;
+#[cfg(all(feature = "v1", feature = "v2"))]
+pub mod hybrid;
 pub mod stats;
 #[cfg(feature = "systemd")]
 pub mod systemd;
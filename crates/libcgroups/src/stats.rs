@@ -1,4 +1,5 @@
 use anyhow::{bail, Context, Result};
+use nix::unistd::Pid;
 use serde::Serialize;
 use std::{collections::HashMap, fmt::Display, fs, path::Path};
 
@@ -23,10 +24,16 @@ pub struct Stats {
     pub blkio: BlkioStats,
     /// Memory statistics for the cgroup
     pub memory: MemoryStats,
+    /// Network statistics for the interfaces of the network namespace
+    pub network: Vec<NetworkInterfaceStats>,
+    /// Rdma statistics for the cgroup
+    pub rdma: HashMap<String, RdmaStats>,
+    /// Pressure stall information for the cgroup (cgroup v2 only)
+    pub psi: PsiStats,
 }
 
 /// Reports the cpu statistics for a cgroup
-#[derive(Debug, Default, Serialize)]
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
 pub struct CpuStats {
     /// Cpu usage statistics for the cgroup
     pub usage: CpuUsage,
@@ -60,6 +67,26 @@ pub struct CpuThrottling {
     pub throttled_periods: u64,
     /// Total time duration for which tasks have been throttled
     pub throttled_time: u64,
+    /// Number of period intervals where tasks used the burst capacity beyond their quota
+    pub bursts: u64,
+    /// Total time duration tasks have spent in a burst beyond their quota
+    pub burst_time: u64,
+}
+
+impl CpuThrottling {
+    /// Sets the throttling counter of the cpu.stat key, the time keys differ
+    /// between cgroup v1 (nanoseconds) and v2 (microseconds). Other keys are
+    /// ignored.
+    pub(crate) fn set(&mut self, key: &str, value: u64) {
+        match key {
+            "nr_periods" => self.periods = value,
+            "nr_throttled" => self.throttled_periods = value,
+            "throttled_time" | "throttled_usec" => self.throttled_time = value,
+            "nr_bursts" => self.bursts = value,
+            "burst_time" | "burst_usec" => self.burst_time = value,
+            _ => {}
+        }
+    }
 }
 
 /// Reports memory stats for a cgroup
@@ -79,6 +106,8 @@ pub struct MemoryStats {
     pub hierarchy: bool,
     /// Various memory statistics
     pub stats: HashMap<String, u64>,
+    /// Number of processes killed by the OOM killer
+    pub oom_kill: u64,
 }
 
 /// Reports memory stats for one type of memory
@@ -94,13 +123,46 @@ pub struct MemoryData {
     pub limit: u64,
 }
 
+/// Reports the pressure stall information of a cgroup
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct PsiStats {
+    /// Pressure of the cpu
+    pub cpu: Pressure,
+    /// Pressure of the memory
+    pub memory: Pressure,
+    /// Pressure of the block io
+    pub io: Pressure,
+}
+
+/// Reports the pressure of one resource
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct Pressure {
+    /// Time some tasks have been stalled on the resource
+    pub some: PressureData,
+    /// Time all non-idle tasks have been stalled on the resource at once
+    pub full: PressureData,
+}
+
+/// Reports the share of time tasks have been stalled on a resource
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct PressureData {
+    /// Percentage of time stalled in the last 10 seconds
+    pub avg10: f64,
+    /// Percentage of time stalled in the last 60 seconds
+    pub avg60: f64,
+    /// Percentage of time stalled in the last 300 seconds
+    pub avg300: f64,
+    /// Total time stalled in microseconds
+    pub total: u64,
+}
+
 /// Reports pid stats for a cgroup
 #[derive(Debug, Default, PartialEq, Eq, Serialize)]
 pub struct PidStats {
     /// Current number of active pids
     pub current: u64,
-    /// Allowed number of active pids (0 means no limit)
-    pub limit: u64,
+    /// Allowed number of active pids, None if the number is unlimited
+    pub limit: Option<u64>,
 }
 
 /// Reports block io stats for a cgroup
@@ -122,6 +184,43 @@ pub struct BlkioStats {
     pub queued: Vec<BlkioDeviceStat>,
     // Number of requests merged into requests for I/O operations
     pub merged: Vec<BlkioDeviceStat>,
+    // Proportional weight of the cgroup as set in the cgroup, None if the
+    // weight is not supported
+    pub weight: Option<u16>,
+    // Weights of the devices overriding the weight of the cgroup
+    pub weight_device: Vec<BlkioDeviceStat>,
+}
+
+/// Reports the rdma resources used by a cgroup on a device
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct RdmaStats {
+    /// Number of HCA handles in use
+    pub hca_handles: u64,
+    /// Number of HCA objects in use
+    pub hca_objects: u64,
+}
+
+/// Reports the counters of a network interface
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct NetworkInterfaceStats {
+    /// Name of the interface
+    pub name: String,
+    /// Number of received bytes
+    pub rx_bytes: u64,
+    /// Number of received packets
+    pub rx_packets: u64,
+    /// Number of receive errors
+    pub rx_errors: u64,
+    /// Number of dropped received packets
+    pub rx_dropped: u64,
+    /// Number of transmitted bytes
+    pub tx_bytes: u64,
+    /// Number of transmitted packets
+    pub tx_packets: u64,
+    /// Number of transmit errors
+    pub tx_errors: u64,
+    /// Number of dropped transmitted packets
+    pub tx_dropped: u64,
 }
 
 /// Reports single stat value for a specific device
@@ -180,6 +279,20 @@ pub fn supported_page_sizes() -> Result<Vec<String>> {
     Ok(sizes)
 }
 
+/// Ensures that hugepages of the requested size are supported by the system,
+/// as the corresponding cgroup files do not exist otherwise
+pub(crate) fn ensure_page_size_supported(page_size: &str, supported: &[String]) -> Result<()> {
+    if !supported.iter().any(|s| s == page_size) {
+        bail!(
+            "hugepage size {} is not supported by this system, supported sizes are [{}]",
+            page_size,
+            supported.join(", ")
+        );
+    }
+
+    Ok(())
+}
+
 fn extract_page_size(dir_name: &str) -> Result<String> {
     if let Some(size) = dir_name
         .strip_prefix("hugepages-")
@@ -308,6 +421,46 @@ pub fn parse_device_number(device: &str) -> Result<(u64, u64)> {
     Ok((numbers[0].parse()?, numbers[1].parse()?))
 }
 
+/// Parses the content of a weight file, which contains the default weight,
+/// either as plain value or prefixed with `default`, and the weights of the
+/// devices in the form `Major:Minor weight`
+/// # Example
+/// ```
+/// use libcgroups::stats::parse_weights;
+///
+/// let (weight, devices) = parse_weights("default 100\n8:0 200\n").unwrap();
+/// assert_eq!(weight, Some(100));
+/// assert_eq!((devices[0].major, devices[0].minor, devices[0].value), (8, 0, 200));
+/// ```
+pub fn parse_weights(content: &str) -> Result<(Option<u16>, Vec<BlkioDeviceStat>)> {
+    let mut weight = None;
+    let mut devices = Vec::new();
+    for line in content.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match fields[..] {
+            [] => {}
+            [value] | ["default", value] => {
+                weight = Some(
+                    value
+                        .parse()
+                        .with_context(|| format!("failed to parse weight {}", value))?,
+                )
+            }
+            [device, value] => {
+                let (major, minor) = parse_device_number(device)?;
+                devices.push(BlkioDeviceStat {
+                    major,
+                    minor,
+                    op_type: None,
+                    value: parse_value(value)?,
+                });
+            }
+            _ => bail!("failed to parse weight entry {}", line),
+        }
+    }
+    Ok((weight, devices))
+}
+
 /// Returns cgroup pid statistics
 pub fn pid_stats(cgroup_path: &Path) -> Result<PidStats> {
     let mut stats = PidStats::default();
@@ -321,12 +474,114 @@ pub fn pid_stats(cgroup_path: &Path) -> Result<PidStats> {
     let limit =
         common::read_cgroup_file(cgroup_path.join("pids.max")).map(|l| l.trim().to_owned())?;
     if limit != "max" {
-        stats.limit = limit.parse().context("failed to parse pids limit")?;
+        stats.limit = Some(limit.parse().context("failed to parse pids limit")?);
     }
 
     Ok(stats)
 }
 
+/// Returns the pressure stall information of a cgroup v2. The pressure files
+/// are missing if the kernel has been built without PSI support, in which
+/// case the values are zero.
+pub fn psi_stats(cgroup_path: &Path) -> Result<PsiStats> {
+    Ok(PsiStats {
+        cpu: pressure(&cgroup_path.join("cpu.pressure"))?,
+        memory: pressure(&cgroup_path.join("memory.pressure"))?,
+        io: pressure(&cgroup_path.join("io.pressure"))?,
+    })
+}
+
+fn pressure(file_path: &Path) -> Result<Pressure> {
+    let mut pressure = Pressure::default();
+    if !file_path.exists() {
+        return Ok(pressure);
+    }
+
+    for (kind, fields) in parse_nested_keyed_data(file_path)? {
+        let data = match kind.as_str() {
+            "some" => &mut pressure.some,
+            "full" => &mut pressure.full,
+            _ => continue,
+        };
+
+        for field in fields {
+            let (key, value) = field.split_once('=').unwrap_or_default();
+            let parse_avg = || -> Result<f64> {
+                value
+                    .parse()
+                    .with_context(|| format!("failed to parse {} in {:?}", key, file_path))
+            };
+            match key {
+                "avg10" => data.avg10 = parse_avg()?,
+                "avg60" => data.avg60 = parse_avg()?,
+                "avg300" => data.avg300 = parse_avg()?,
+                "total" => data.total = parse_value(value)?,
+                _ => {}
+            }
+        }
+    }
+
+    Ok(pressure)
+}
+
+/// Returns the statistics of the network interfaces in the network namespace
+/// of the process. If the process shares the network namespace with the
+/// caller, e.g. a container using the host network, the counters do not
+/// belong to the container and no statistics are returned.
+pub fn network_stats(pid: Pid) -> Result<Vec<NetworkInterfaceStats>> {
+    let proc_path = Path::new("/proc").join(pid.to_string());
+    let net_ns = fs::read_link(proc_path.join("ns/net"))
+        .with_context(|| format!("failed to read network namespace of {}", pid))?;
+    if net_ns == fs::read_link("/proc/self/ns/net")? {
+        log::debug!("{} uses the host network namespace", pid);
+        return Ok(Vec::new());
+    }
+
+    // /proc/<pid>/net is a view of the network namespace of the process, so
+    // there is no need to enter the namespace
+    let net_dev = proc_path.join("net/dev");
+    let content =
+        fs::read_to_string(&net_dev).with_context(|| format!("failed to read {:?}", net_dev))?;
+    parse_net_dev(&content).with_context(|| format!("failed to parse {:?}", net_dev))
+}
+
+fn parse_net_dev(content: &str) -> Result<Vec<NetworkInterfaceStats>> {
+    // the first two lines contain the header
+    content
+        .lines()
+        .skip(2)
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let (name, counters) = line
+                .split_once(':')
+                .with_context(|| format!("invalid interface statistics {:?}", line))?;
+            let counters = counters
+                .split_whitespace()
+                .map(|c| c.parse::<u64>().map_err(Into::into))
+                .collect::<Result<Vec<u64>>>()?;
+            if counters.len() < 16 {
+                bail!(
+                    "expected 16 counters, found {} in {:?}",
+                    counters.len(),
+                    line
+                );
+            }
+
+            Ok(NetworkInterfaceStats {
+                name: name.trim().to_owned(),
+                rx_bytes: counters[0],
+                rx_packets: counters[1],
+                rx_errors: counters[2],
+                rx_dropped: counters[3],
+                tx_bytes: counters[8],
+                tx_packets: counters[9],
+                tx_errors: counters[10],
+                tx_dropped: counters[11],
+            })
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use crate::test::{create_temp_dir, set_fixture};
@@ -351,6 +606,14 @@ mod tests {
         assert_eq!(page_size, "512KB");
     }
 
+    #[test]
+    fn test_ensure_page_size_supported() {
+        let supported = vec!["2MB".to_owned(), "1GB".to_owned()];
+        assert!(ensure_page_size_supported("1GB", &supported).is_ok());
+        assert!(ensure_page_size_supported("64KB", &supported).is_err());
+        assert!(ensure_page_size_supported("2MB", &[]).is_err());
+    }
+
     #[test]
     fn test_parse_single_value_valid() {
         let tmp = create_temp_dir("test_parse_single_value_valid").unwrap();
@@ -393,6 +656,41 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn test_psi_stats() -> Result<()> {
+        let tmp = create_temp_dir("test_psi_stats")?;
+        set_fixture(
+            &tmp,
+            "memory.pressure",
+            &[
+                "some avg10=1.50 avg60=0.75 avg300=0.10 total=12345",
+                "full avg10=0.50 avg60=0.25 avg300=0.00 total=678",
+            ]
+            .join("\n"),
+        )?;
+
+        let stats = psi_stats(&tmp)?;
+        let expected = Pressure {
+            some: PressureData {
+                avg10: 1.5,
+                avg60: 0.75,
+                avg300: 0.1,
+                total: 12345,
+            },
+            full: PressureData {
+                avg10: 0.5,
+                avg60: 0.25,
+                avg300: 0.0,
+                total: 678,
+            },
+        };
+        assert_eq!(stats.memory, expected);
+        // the kernel does not provide pressure information for cpu and io
+        assert_eq!(stats.cpu, Pressure::default());
+        assert_eq!(stats.io, Pressure::default());
+        Ok(())
+    }
+
     #[test]
     fn test_parse_flat_keyed_data_with_characters() {
         let tmp = create_temp_dir("test_parse_flat_keyed_data_with_characters").unwrap();
@@ -515,6 +813,69 @@ mod tests {
         assert_eq!((major, minor), (8, 0));
     }
 
+    #[test]
+    fn test_parse_weights() {
+        let (weight, devices) = parse_weights("500\n").unwrap();
+        assert_eq!(weight, Some(500));
+        assert!(devices.is_empty());
+
+        let (weight, devices) = parse_weights("default 100\n8:0 200\n8:16 300\n").unwrap();
+        assert_eq!(weight, Some(100));
+        assert_eq!(
+            devices,
+            vec![
+                BlkioDeviceStat {
+                    major: 8,
+                    minor: 0,
+                    op_type: None,
+                    value: 200,
+                },
+                BlkioDeviceStat {
+                    major: 8,
+                    minor: 16,
+                    op_type: None,
+                    value: 300,
+                },
+            ]
+        );
+
+        assert!(parse_weights("8:0 200 300").is_err());
+        assert!(parse_weights("default high").is_err());
+    }
+
+    #[test]
+    fn test_parse_net_dev() {
+        let content = ["Inter-|   Receive                                                |  Transmit",
+            " face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed",
+            "    lo:    1200      12    0    0    0     0          0         0     1200      12    0    0    0     0       0          0",
+            "  eth0: 4016775    3105    1    2    0     0          0         0   320294    2486    3    4    0     0       0          0",
+        ].join("\n");
+        let stats = parse_net_dev(&content).unwrap();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(
+            stats[1],
+            NetworkInterfaceStats {
+                name: "eth0".to_owned(),
+                rx_bytes: 4016775,
+                rx_packets: 3105,
+                rx_errors: 1,
+                rx_dropped: 2,
+                tx_bytes: 320294,
+                tx_packets: 2486,
+                tx_errors: 3,
+                tx_dropped: 4,
+            }
+        );
+
+        assert!(parse_net_dev("header\nheader\neth0: 1 2 3").is_err());
+    }
+
+    #[test]
+    fn test_network_stats_host_network() {
+        let stats = network_stats(nix::unistd::getpid()).unwrap();
+        assert!(stats.is_empty());
+    }
+
     #[test]
     fn test_parse_invalid_device_number() {
         let result = parse_device_number("a:b");
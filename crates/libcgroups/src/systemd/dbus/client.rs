@@ -137,6 +137,11 @@ impl SystemdClient for Client {
         proxy
             .stop_unit(unit_name, "replace")
             .with_context(|| format!("failed to stop unit {}", unit_name))?;
+        // A scope, whose processes have been killed, may stay around in failed
+        // state, which prevents a new container with the same name from starting.
+        if let Err(e) = proxy.reset_failed_unit(unit_name) {
+            log::debug!("failed to reset failed unit {}: {}", unit_name, e);
+        }
         Ok(())
     }
 
@@ -8,6 +8,7 @@ use std::{
 use anyhow::{anyhow, bail, Context, Result};
 use dbus::arg::RefArg;
 use nix::{unistd::Pid, NixPath};
+use std::os::unix::io::RawFd;
 use std::path::{Path, PathBuf};
 
 use super::{
@@ -91,6 +92,14 @@ impl TryFrom<&Path> for CgroupsPath {
             _ => bail!("cgroup path {:?} is invalid", cgroups_path),
         };
 
+        if destructured_path.name.is_empty() {
+            bail!("cgroup path {:?} does not contain a name", cgroups_path);
+        }
+        // the prefix is only used to construct the name of a scope
+        if destructured_path.prefix.is_empty() && !destructured_path.name.ends_with(".slice") {
+            bail!("cgroup path {:?} does not contain a prefix", cgroups_path);
+        }
+
         Ok(destructured_path)
     }
 }
@@ -215,7 +224,7 @@ impl Manager {
         }
         for component in slice_name.split('-') {
             if component.is_empty() {
-                anyhow!("invalid slice name: {}", slice);
+                bail!("invalid slice name: {}", slice);
             }
             // Append the component to the path and to the prefix.
             path = format!("{}/{}{}{}", path, prefix, component, suffix);
@@ -368,7 +377,8 @@ impl CgroupManager for Manager {
         Ok(())
     }
 
-    fn remove(&self) -> Result<()> {
+    // stopping the unit always kills the processes in it
+    fn remove(&self, _force: bool) -> Result<()> {
         log::debug!("remove {}", self.unit_name);
         if self.client.transient_unit_exists(&self.unit_name) {
             self.client
@@ -385,6 +395,10 @@ impl CgroupManager for Manager {
         self.fs_manager.freeze(state)
     }
 
+    fn freezer_state(&self) -> Result<FreezerState> {
+        self.fs_manager.freezer_state()
+    }
+
     fn stats(&self) -> Result<Stats> {
         self.fs_manager.stats()
     }
@@ -392,6 +406,10 @@ impl CgroupManager for Manager {
     fn get_all_pids(&self) -> Result<Vec<Pid>> {
         common::get_all_pids(&self.full_path)
     }
+
+    fn attach_bpf_audit(&self, prog_fd: RawFd) -> Result<()> {
+        self.fs_manager.attach_bpf_audit(prog_fd)
+    }
 }
 
 #[cfg(test)]
@@ -452,6 +470,37 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn expand_slice_rejects_invalid_names() {
+        assert!(Manager::expand_slice("test--a.slice").is_err());
+        assert!(Manager::expand_slice("test.scope").is_err());
+        assert!(Manager::expand_slice("test/a.slice").is_err());
+    }
+
+    #[test]
+    fn destructure_cgroups_path() -> Result<()> {
+        let cgroups_path: CgroupsPath = Path::new("system.slice:youki:foo").try_into()?;
+        assert_eq!(cgroups_path.parent, "system.slice");
+        assert_eq!(cgroups_path.prefix, "youki");
+        assert_eq!(cgroups_path.name, "foo");
+        assert_eq!(Manager::get_unit_name(&cgroups_path), "youki-foo.scope");
+
+        let cgroups_path: CgroupsPath = Path::new("system.slice::foo.slice").try_into()?;
+        assert_eq!(Manager::get_unit_name(&cgroups_path), "foo.slice");
+
+        for invalid in [
+            "",
+            "foo",
+            "system.slice:youki:",
+            "system.slice::foo",
+            "a:b:c:d",
+        ] {
+            let result: Result<CgroupsPath> = Path::new(invalid).try_into();
+            assert!(result.is_err(), "{}", invalid);
+        }
+        Ok(())
+    }
+
     #[test]
     fn get_cgroups_path_works_with_a_complex_slice() -> Result<()> {
         let cgroups_path = Path::new("test-a-b.slice:docker:foo")
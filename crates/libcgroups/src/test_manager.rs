@@ -1,4 +1,4 @@
-use std::cell::RefCell;
+use std::{cell::RefCell, os::unix::io::RawFd};
 
 use anyhow::Result;
 use nix::unistd::Pid;
@@ -12,6 +12,7 @@ use crate::{
 pub struct TestManager {
     add_task_args: RefCell<Vec<Pid>>,
     pub apply_called: RefCell<bool>,
+    attach_bpf_audit_args: RefCell<Vec<RawFd>>,
 }
 
 impl Default for TestManager {
@@ -19,6 +20,7 @@ impl Default for TestManager {
         Self {
             add_task_args: RefCell::new(vec![]),
             apply_called: RefCell::new(false),
+            attach_bpf_audit_args: RefCell::new(vec![]),
         }
     }
 }
@@ -35,7 +37,7 @@ impl CgroupManager for TestManager {
         Ok(())
     }
 
-    fn remove(&self) -> Result<()> {
+    fn remove(&self, _force: bool) -> Result<()> {
         unimplemented!()
     }
 
@@ -43,6 +45,10 @@ impl CgroupManager for TestManager {
         unimplemented!()
     }
 
+    fn freezer_state(&self) -> Result<FreezerState> {
+        unimplemented!()
+    }
+
     fn stats(&self) -> anyhow::Result<Stats> {
         unimplemented!()
     }
@@ -50,6 +56,11 @@ impl CgroupManager for TestManager {
     fn get_all_pids(&self) -> Result<Vec<Pid>> {
         unimplemented!()
     }
+
+    fn attach_bpf_audit(&self, prog_fd: RawFd) -> Result<()> {
+        self.attach_bpf_audit_args.borrow_mut().push(prog_fd);
+        Ok(())
+    }
 }
 
 impl TestManager {
@@ -60,4 +71,8 @@ impl TestManager {
     pub fn apply_called(&self) -> bool {
         *self.apply_called.borrow_mut()
     }
+
+    pub fn get_attach_bpf_audit_args(&self) -> Vec<RawFd> {
+        self.attach_bpf_audit_args.borrow_mut().clone()
+    }
 }
@@ -6,8 +6,12 @@ use crate::{
     v1::Controller,
 };
 
-use anyhow::{Context, Result};
-use oci_spec::runtime::LinuxBlockIo;
+use anyhow::{bail, Context, Result};
+use oci_spec::runtime::{LinuxBlockIo, LinuxThrottleDevice};
+
+// Block devices of the system, named by their device number
+// Format: Major:Minor
+const SYSFS_BLOCK_DEVICES: &str = "/sys/dev/block";
 
 // Throttling/upper limit policy
 // ---------------------------------------
@@ -41,9 +45,17 @@ const BLKIO_WEIGHT: &str = "blkio.weight";
 const BLKIO_BFQ_WEIGHT: &str = "blkio.bfq.weight";
 // Specifies the relative proportion of block I/O access for specific devices available
 // to the cgroup. This overrides the the blkio.weight value for the specified device
-// Format: Major:Minor weight (weight can range from 100 to 1000)
-#[allow(dead_code)]
+// Format: Major:Minor weight (weight can range from 10 to 1000)
 const BLKIO_WEIGHT_DEVICE: &str = "blkio.weight_device";
+// Similar to BLKIO_WEIGHT_DEVICE, but for the BFQ I/O scheduler
+// Format: Major:Minor weight (weight can range from 1 to 10000)
+const BLKIO_BFQ_WEIGHT_DEVICE: &str = "blkio.bfq.weight_device";
+// Weight of the tasks of the cgroup itself, when competing with the child cgroups
+// Format: weight (weight can range from 10 to 1000)
+const BLKIO_LEAF_WEIGHT: &str = "blkio.leaf_weight";
+// Same as BLKIO_LEAF_WEIGHT for specific devices
+// Format: Major:Minor weight (weight can range from 10 to 1000)
+const BLKIO_LEAF_WEIGHT_DEVICE: &str = "blkio.leaf_weight_device";
 
 // Common parameters which may be used for either policy but seem to be used only for
 // proportional weight division policy in practice
@@ -82,7 +94,7 @@ impl Controller for Blkio {
         log::debug!("Apply blkio cgroup config");
 
         if let Some(blkio) = Self::needs_to_handle(controller_opt) {
-            Self::apply(cgroup_root, blkio)?;
+            Self::apply(cgroup_root, blkio, Path::new(SYSFS_BLOCK_DEVICES))?;
         }
 
         Ok(())
@@ -106,7 +118,9 @@ impl StatsProvider for Blkio {
 }
 
 impl Blkio {
-    fn apply(root_path: &Path, blkio: &LinuxBlockIo) -> Result<()> {
+    fn apply(root_path: &Path, blkio: &LinuxBlockIo, block_devices: &Path) -> Result<()> {
+        common::validate_blkio_weights(blkio)?;
+
         if let Some(blkio_weight) = blkio.weight() {
             // be aligned with what runc does
             // See also: https://github.com/opencontainers/runc/blob/81044ad7c902f3fc153cb8ffadaf4da62855193f/libcontainer/cgroups/fs/blkio.go#L28-L33
@@ -120,45 +134,124 @@ impl Blkio {
             }
         }
 
-        if let Some(throttle_read_bps_device) = blkio.throttle_read_bps_device().as_ref() {
-            for trbd in throttle_read_bps_device {
-                common::write_cgroup_file_str(
-                    &root_path.join(BLKIO_THROTTLE_READ_BPS),
-                    &format!("{}:{} {}", trbd.major(), trbd.minor(), trbd.rate()),
-                )?;
+        if let Some(leaf_weight) = blkio.leaf_weight() {
+            if leaf_weight != 0 {
+                common::write_cgroup_file(&root_path.join(BLKIO_LEAF_WEIGHT), leaf_weight)?;
             }
         }
 
-        if let Some(throttle_write_bps_device) = blkio.throttle_write_bps_device().as_ref() {
-            for twbd in throttle_write_bps_device {
-                common::write_cgroup_file_str(
-                    &root_path.join(BLKIO_THROTTLE_WRITE_BPS),
-                    &format!("{}:{} {}", twbd.major(), twbd.minor(), twbd.rate()),
-                )?;
+        if let Some(weight_device) = blkio.weight_device() {
+            let weight_file = if root_path.join(BLKIO_WEIGHT).exists() {
+                BLKIO_WEIGHT_DEVICE
+            } else {
+                BLKIO_BFQ_WEIGHT_DEVICE
+            };
+            for wd in weight_device {
+                if let Some(weight) = wd.weight().filter(|w| *w != 0) {
+                    common::write_cgroup_file_str(
+                        &root_path.join(weight_file),
+                        &format!("{}:{} {}", wd.major(), wd.minor(), weight),
+                    )?;
+                }
+                if let Some(leaf_weight) = wd.leaf_weight().filter(|w| *w != 0) {
+                    common::write_cgroup_file_str(
+                        &root_path.join(BLKIO_LEAF_WEIGHT_DEVICE),
+                        &format!("{}:{} {}", wd.major(), wd.minor(), leaf_weight),
+                    )?;
+                }
             }
         }
 
+        if let Some(throttle_read_bps_device) = blkio.throttle_read_bps_device().as_ref() {
+            Self::apply_throttle(
+                root_path,
+                block_devices,
+                BLKIO_THROTTLE_READ_BPS,
+                throttle_read_bps_device,
+            )?;
+        }
+
+        if let Some(throttle_write_bps_device) = blkio.throttle_write_bps_device().as_ref() {
+            Self::apply_throttle(
+                root_path,
+                block_devices,
+                BLKIO_THROTTLE_WRITE_BPS,
+                throttle_write_bps_device,
+            )?;
+        }
+
         if let Some(throttle_read_iops_device) = blkio.throttle_read_iops_device().as_ref() {
-            for trid in throttle_read_iops_device {
-                common::write_cgroup_file_str(
-                    &root_path.join(BLKIO_THROTTLE_READ_IOPS),
-                    &format!("{}:{} {}", trid.major(), trid.minor(), trid.rate()),
-                )?;
-            }
+            Self::apply_throttle(
+                root_path,
+                block_devices,
+                BLKIO_THROTTLE_READ_IOPS,
+                throttle_read_iops_device,
+            )?;
         }
 
         if let Some(throttle_write_iops_device) = blkio.throttle_write_iops_device().as_ref() {
-            for twid in throttle_write_iops_device {
-                common::write_cgroup_file_str(
-                    &root_path.join(BLKIO_THROTTLE_WRITE_IOPS),
-                    &format!("{}:{} {}", twid.major(), twid.minor(), twid.rate()),
-                )?;
+            Self::apply_throttle(
+                root_path,
+                block_devices,
+                BLKIO_THROTTLE_WRITE_IOPS,
+                throttle_write_iops_device,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the throttling rules of the devices. Rules of devices, which are
+    /// not specified anymore, e.g. during update, are removed. The devices
+    /// have to exist in block_devices, which is /sys/dev/block outside of tests.
+    fn apply_throttle(
+        root_path: &Path,
+        block_devices: &Path,
+        throttle_file: &str,
+        devices: &[LinuxThrottleDevice],
+    ) -> Result<()> {
+        for device in devices {
+            let sysfs_path = block_devices.join(format!("{}:{}", device.major(), device.minor()));
+            if !sysfs_path.exists() {
+                bail!(
+                    "block device {}:{} for {} does not exist",
+                    device.major(),
+                    device.minor(),
+                    throttle_file
+                );
             }
         }
 
+        let cgroup_file = root_path.join(throttle_file);
+        let current = common::read_cgroup_file(&cgroup_file)?;
+        for device in Self::stale_devices(&current, devices) {
+            // a rate of 0 removes the rule of the device
+            common::write_cgroup_file_str(&cgroup_file, &format!("{} 0", device))?;
+        }
+
+        for device in devices {
+            common::write_cgroup_file_str(
+                &cgroup_file,
+                &format!("{}:{} {}", device.major(), device.minor(), device.rate()),
+            )?;
+        }
+
         Ok(())
     }
 
+    /// Returns the devices with a throttling rule, which are not in devices
+    fn stale_devices<'a>(current: &'a str, devices: &[LinuxThrottleDevice]) -> Vec<&'a str> {
+        current
+            .lines()
+            .filter_map(|line| line.split_whitespace().next())
+            .filter(|device| {
+                !devices
+                    .iter()
+                    .any(|d| *device == format!("{}:{}", d.major(), d.minor()))
+            })
+            .collect()
+    }
+
     fn get_throttling_policy_stats(cgroup_path: &Path) -> Result<BlkioStats> {
         let stats = BlkioStats {
             service_bytes: Self::parse_blkio_file(
@@ -172,6 +265,8 @@ impl Blkio {
     }
 
     fn get_weight_division_policy_stats(cgroup_path: &Path) -> Result<BlkioStats> {
+        let (weight, _) =
+            stats::parse_weights(&common::read_cgroup_file(cgroup_path.join(BLKIO_WEIGHT))?)?;
         let stats = BlkioStats {
             time: Self::parse_blkio_file(&cgroup_path.join(BLKIO_TIME))?,
             sectors: Self::parse_blkio_file(&cgroup_path.join(BLKIO_SECTORS))?,
@@ -181,11 +276,22 @@ impl Blkio {
             wait_time: Self::parse_blkio_file(&cgroup_path.join(BLKIO_WAIT_TIME))?,
             queued: Self::parse_blkio_file(&cgroup_path.join(BLKIO_QUEUED))?,
             merged: Self::parse_blkio_file(&cgroup_path.join(BLKIO_MERGED))?,
+            weight,
+            weight_device: Self::get_weight_device_stats(cgroup_path)?,
         };
 
         Ok(stats)
     }
 
+    /// The weights of the devices are not supported by every kernel
+    fn get_weight_device_stats(cgroup_path: &Path) -> Result<Vec<BlkioDeviceStat>> {
+        let weight_device = cgroup_path.join(BLKIO_WEIGHT_DEVICE);
+        if !weight_device.exists() {
+            return Ok(Vec::new());
+        }
+        Ok(stats::parse_weights(&common::read_cgroup_file(&weight_device)?)?.1)
+    }
+
     fn parse_blkio_file(blkio_file: &Path) -> Result<Vec<BlkioDeviceStat>> {
         let content = common::read_cgroup_file(blkio_file)?;
         let mut stats = Vec::new();
@@ -235,13 +341,23 @@ impl Blkio {
 
 #[cfg(test)]
 mod tests {
-    use std::fs;
+    use std::{fs, path::PathBuf};
 
     use super::*;
     use crate::test::{create_temp_dir, set_fixture, setup};
 
     use anyhow::Result;
-    use oci_spec::runtime::{LinuxBlockIoBuilder, LinuxThrottleDeviceBuilder};
+    use oci_spec::runtime::{
+        LinuxBlockIoBuilder, LinuxThrottleDeviceBuilder, LinuxWeightDeviceBuilder,
+    };
+
+    /// Creates a fake /sys/dev/block in the temp dir, which only knows 8:0
+    fn block_devices(tmp: &Path) -> PathBuf {
+        let block_devices = tmp.join("block");
+        fs::create_dir_all(&block_devices).expect("create block devices");
+        set_fixture(&block_devices, "8:0", "").expect("set block device fixture");
+        block_devices
+    }
 
     #[test]
     fn test_set_blkio_weight() {
@@ -252,7 +368,7 @@ mod tests {
                 .build()
                 .unwrap();
 
-            Blkio::apply(&tmp, &blkio).expect("apply blkio");
+            Blkio::apply(&tmp, &blkio, &block_devices(&tmp)).expect("apply blkio");
             let content = fs::read_to_string(weight_file).expect("read blkio weight");
             assert_eq!("200", content);
         }
@@ -272,7 +388,7 @@ mod tests {
             .build()
             .unwrap();
 
-        Blkio::apply(&tmp, &blkio).expect("apply blkio");
+        Blkio::apply(&tmp, &blkio, &block_devices(&tmp)).expect("apply blkio");
         let content = fs::read_to_string(throttle)
             .unwrap_or_else(|_| panic!("read {} content", BLKIO_THROTTLE_READ_BPS));
 
@@ -293,7 +409,7 @@ mod tests {
             .build()
             .unwrap();
 
-        Blkio::apply(&tmp, &blkio).expect("apply blkio");
+        Blkio::apply(&tmp, &blkio, &block_devices(&tmp)).expect("apply blkio");
         let content = fs::read_to_string(throttle)
             .unwrap_or_else(|_| panic!("read {} content", BLKIO_THROTTLE_WRITE_BPS));
 
@@ -314,7 +430,7 @@ mod tests {
             .build()
             .unwrap();
 
-        Blkio::apply(&tmp, &blkio).expect("apply blkio");
+        Blkio::apply(&tmp, &blkio, &block_devices(&tmp)).expect("apply blkio");
         let content = fs::read_to_string(throttle)
             .unwrap_or_else(|_| panic!("read {} content", BLKIO_THROTTLE_READ_IOPS));
 
@@ -335,13 +451,77 @@ mod tests {
             .build()
             .unwrap();
 
-        Blkio::apply(&tmp, &blkio).expect("apply blkio");
+        Blkio::apply(&tmp, &blkio, &block_devices(&tmp)).expect("apply blkio");
         let content = fs::read_to_string(throttle)
             .unwrap_or_else(|_| panic!("read {} content", BLKIO_THROTTLE_WRITE_IOPS));
 
         assert_eq!("8:0 102400", content);
     }
 
+    #[test]
+    fn test_set_blkio_missing_device() {
+        let (tmp, _) = setup("test_set_blkio_missing_device", BLKIO_THROTTLE_READ_BPS);
+
+        let blkio = LinuxBlockIoBuilder::default()
+            .throttle_read_bps_device(vec![LinuxThrottleDeviceBuilder::default()
+                .major(8)
+                .minor(1)
+                .rate(102400u64)
+                .build()
+                .unwrap()])
+            .build()
+            .unwrap();
+
+        assert!(Blkio::apply(&tmp, &blkio, &block_devices(&tmp)).is_err());
+    }
+
+    #[test]
+    fn test_set_blkio_weight_device() {
+        let (tmp, _) = setup("test_set_blkio_weight_device", BLKIO_WEIGHT);
+        let weight_device = set_fixture(&tmp, BLKIO_WEIGHT_DEVICE, "").unwrap();
+        let leaf_weight = set_fixture(&tmp, BLKIO_LEAF_WEIGHT, "").unwrap();
+        let leaf_weight_device = set_fixture(&tmp, BLKIO_LEAF_WEIGHT_DEVICE, "").unwrap();
+        let blkio = LinuxBlockIoBuilder::default()
+            .leaf_weight(300u16)
+            .weight_device(vec![LinuxWeightDeviceBuilder::default()
+                .major(8)
+                .minor(0)
+                .weight(500u16)
+                .leaf_weight(200u16)
+                .build()
+                .unwrap()])
+            .build()
+            .unwrap();
+
+        Blkio::apply(&tmp, &blkio, &block_devices(&tmp)).expect("apply blkio");
+        assert_eq!(fs::read_to_string(weight_device).unwrap(), "8:0 500");
+        assert_eq!(fs::read_to_string(leaf_weight).unwrap(), "300");
+        assert_eq!(fs::read_to_string(leaf_weight_device).unwrap(), "8:0 200");
+    }
+
+    #[test]
+    fn test_set_blkio_weight_out_of_range() {
+        let (tmp, weight_file) = setup("test_set_blkio_weight_out_of_range", BLKIO_WEIGHT);
+        let blkio = LinuxBlockIoBuilder::default().weight(5u16).build().unwrap();
+
+        assert!(Blkio::apply(&tmp, &blkio, &block_devices(&tmp)).is_err());
+        assert!(fs::read_to_string(weight_file).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_stale_devices() {
+        let devices = vec![LinuxThrottleDeviceBuilder::default()
+            .major(8)
+            .minor(0)
+            .rate(102400u64)
+            .build()
+            .unwrap()];
+
+        let current = "8:0 1024\n8:1 2048\n7:0 4096\n";
+        assert_eq!(Blkio::stale_devices(current, &devices), vec!["8:1", "7:0"]);
+        assert!(Blkio::stale_devices("", &devices).is_empty());
+    }
+
     #[test]
     fn test_stat_throttling_policy() -> Result<()> {
         let tmp = create_temp_dir("test_stat_throttling_policy").expect("create test directory");
@@ -377,4 +557,36 @@ mod tests {
         assert_eq!(expected, actual);
         Ok(())
     }
+
+    #[test]
+    fn test_stat_weight_division_policy() -> Result<()> {
+        let tmp = create_temp_dir("test_stat_weight_division_policy")?;
+        for file in &[
+            BLKIO_TIME,
+            BLKIO_SECTORS,
+            BLKIO_IO_SERVICE_BYTES,
+            BLKIO_IO_SERVICED,
+            BLKIO_IO_SERVICE_TIME,
+            BLKIO_WAIT_TIME,
+            BLKIO_QUEUED,
+            BLKIO_MERGED,
+        ] {
+            set_fixture(&tmp, file, "")?;
+        }
+        set_fixture(&tmp, BLKIO_WEIGHT, "500\n")?;
+        set_fixture(&tmp, BLKIO_WEIGHT_DEVICE, "8:0 200\n")?;
+
+        let actual = Blkio::stats(&tmp)?;
+        assert_eq!(actual.weight, Some(500));
+        assert_eq!(
+            actual.weight_device,
+            vec![BlkioDeviceStat {
+                major: 8,
+                minor: 0,
+                op_type: None,
+                value: 200,
+            }]
+        );
+        Ok(())
+    }
 }
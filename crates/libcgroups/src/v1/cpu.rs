@@ -1,11 +1,11 @@
 use std::path::Path;
 
-use anyhow::{bail, Context, Result};
+use anyhow::{Context, Result};
 use oci_spec::runtime::LinuxCpu;
 
 use crate::{
     common::{self, ControllerOpt},
-    stats::{CpuThrottling, StatsProvider},
+    stats::{self, CpuThrottling, StatsProvider},
 };
 
 use super::Controller;
@@ -52,43 +52,14 @@ impl StatsProvider for Cpu {
     type Stats = CpuThrottling;
 
     fn stats(cgroup_path: &Path) -> Result<Self::Stats> {
+        // The counters only exist if the kernel supports CFS bandwidth
+        // control, the burst counters only on newer kernels.
         let mut stats = CpuThrottling::default();
         let stat_path = cgroup_path.join(CGROUP_CPU_STAT);
-        let stat_content = common::read_cgroup_file(&stat_path)?;
-
-        let parts: Vec<&str> = stat_content.split_ascii_whitespace().collect();
-        if parts.len() < 6 {
-            bail!(
-                "{} contains less than the expected number of entries",
-                stat_path.display()
-            );
+        for (key, value) in stats::parse_flat_keyed_data(&stat_path)? {
+            stats.set(&key, value);
         }
 
-        if parts[0] != "nr_periods" {
-            bail!(
-                "{} does not contain the number of elapsed periods",
-                stat_path.display()
-            );
-        }
-
-        if parts[2] != "nr_throttled" {
-            bail!(
-                "{} does not contain the number of throttled periods",
-                stat_path.display()
-            );
-        }
-
-        if parts[4] != "throttled_time" {
-            bail!(
-                "{} does not contain the total time tasks have spent throttled",
-                stat_path.display()
-            );
-        }
-
-        stats.periods = parts[1].parse().context("failed to parse nr_periods")?;
-        stats.throttled_periods = parts[3].parse().context("failed to parse nr_throttled")?;
-        stats.throttled_time = parts[5].parse().context("failed to parse throttled time")?;
-
         Ok(stats)
     }
 }
@@ -239,7 +210,27 @@ mod tests {
             periods: 165000,
             throttled_periods: 27,
             throttled_time: 1080,
+            ..Default::default()
         };
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn test_stat_cpu_throttling_bursts() {
+        let tmp =
+            create_temp_dir("test_stat_cpu_throttling_bursts").expect("create test directory");
+        let stat_content = &[
+            "nr_periods 165000",
+            "nr_throttled 27",
+            "throttled_time 1080",
+            "nr_bursts 3",
+            "burst_time 500",
+        ]
+        .join("\n");
+        set_fixture(&tmp, CGROUP_CPU_STAT, stat_content).expect("create stat file");
+
+        let actual = Cpu::stats(&tmp).expect("get cgroup stats");
+        assert_eq!(actual.bursts, 3);
+        assert_eq!(actual.burst_time, 500);
+    }
 }
@@ -46,6 +46,29 @@ impl Freezer {
                     cgroup_root.join(CGROUP_FREEZER_STATE),
                     FREEZER_STATE_THAWED,
                 )?;
+
+                // Thawing happens asynchronously as well, so make sure all
+                // processes of the cgroup are running again before returning.
+                let mut state = String::new();
+                for i in 0..1000 {
+                    if i % 25 == 24 {
+                        thread::sleep(time::Duration::from_millis(10));
+                    }
+
+                    state = Self::read_freezer_state(cgroup_root)?.trim().to_owned();
+                    if state == FREEZER_STATE_THAWED {
+                        if i > 1 {
+                            log::debug!("thawed after {} retries", i)
+                        }
+                        return Ok(());
+                    }
+                }
+                bail!(
+                    "timed out waiting for {:?} to be {}, the state is still {}",
+                    cgroup_root,
+                    FREEZER_STATE_THAWED,
+                    state
+                );
             }
             FreezerState::Frozen => {
                 let r = || -> Result<()> {
@@ -88,7 +111,12 @@ impl Freezer {
                             }
                         }
                     }
-                    bail!("unbale to freeze");
+                    bail!(
+                        "timed out waiting for {:?} to be {}, the state is still {}",
+                        cgroup_root,
+                        FREEZER_STATE_FROZEN,
+                        FREEZER_STATE_FREEZING
+                    );
                 }();
 
                 if r.is_err() {
@@ -105,6 +133,18 @@ impl Freezer {
         Ok(())
     }
 
+    /// Returns the state of the freezer without waiting for a freeze in
+    /// progress to complete
+    pub fn state(cgroup_root: &Path) -> Result<FreezerState> {
+        let state = Self::read_freezer_state(cgroup_root)?;
+        match state.trim() {
+            FREEZER_STATE_THAWED => Ok(FreezerState::Thawed),
+            FREEZER_STATE_FROZEN => Ok(FreezerState::Frozen),
+            FREEZER_STATE_FREEZING => Ok(FreezerState::Undefined),
+            state => bail!("unknown freezer state {}", state),
+        }
+    }
+
     fn read_freezer_state(cgroup_root: &Path) -> Result<String> {
         let path = cgroup_root.join(CGROUP_FREEZER_STATE);
         let mut content = String::new();
@@ -164,6 +204,23 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_freezer_state() -> Result<()> {
+        let tmp = create_temp_dir("test_freezer_state")?;
+        for (content, expected) in [
+            ("THAWED\n", FreezerState::Thawed),
+            ("FROZEN\n", FreezerState::Frozen),
+            ("FREEZING\n", FreezerState::Undefined),
+        ] {
+            set_fixture(&tmp, CGROUP_FREEZER_STATE, content)?;
+            assert_eq!(Freezer::state(&tmp)?, expected);
+        }
+
+        set_fixture(&tmp, CGROUP_FREEZER_STATE, "UNKNOWN\n")?;
+        assert!(Freezer::state(&tmp).is_err());
+        Ok(())
+    }
+
     #[test]
     fn test_add_and_apply() {
         let tmp = create_temp_dir("test_add_task").expect("create temp directory for test");
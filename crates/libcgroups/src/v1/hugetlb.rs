@@ -4,7 +4,7 @@ use anyhow::{bail, Context, Result};
 
 use crate::{
     common::{self, ControllerOpt},
-    stats::{supported_page_sizes, HugeTlbStats, StatsProvider},
+    stats::{ensure_page_size_supported, supported_page_sizes, HugeTlbStats, StatsProvider},
 };
 
 use super::Controller;
@@ -19,7 +19,10 @@ impl Controller for HugeTlb {
         log::debug!("Apply Hugetlb cgroup config");
 
         if let Some(hugepage_limits) = Self::needs_to_handle(controller_opt) {
+            let supported =
+                supported_page_sizes().context("failed to determine supported hugepage sizes")?;
             for hugetlb in hugepage_limits {
+                ensure_page_size_supported(hugetlb.page_size(), &supported)?;
                 Self::apply(cgroup_root, hugetlb)
                     .context("failed to apply hugetlb resource restrictions")?
             }
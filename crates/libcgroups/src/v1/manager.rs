@@ -1,9 +1,11 @@
 use std::fs;
+use std::os::unix::io::RawFd;
 use std::path::Path;
 use std::time::Duration;
 use std::{collections::HashMap, path::PathBuf};
 
 use anyhow::bail;
+use anyhow::Context;
 use anyhow::Result;
 use nix::unistd::Pid;
 
@@ -22,6 +24,9 @@ use crate::stats::{Stats, StatsProvider};
 
 pub struct Manager {
     subsystems: HashMap<CtrlType, PathBuf>,
+    /// Controllers which are not mounted under v1, but are handled through
+    /// the unified hierarchy of a hybrid setup
+    unified_controllers: Vec<CtrlType>,
 }
 
 impl Manager {
@@ -36,7 +41,27 @@ impl Manager {
             }
         }
 
-        Ok(Manager { subsystems })
+        Ok(Manager {
+            subsystems,
+            unified_controllers: Vec::new(),
+        })
+    }
+
+    /// Marks controllers as handled through the unified hierarchy, they are
+    /// not required to be mounted under v1 anymore.
+    pub(crate) fn set_unified_controllers(&mut self, controllers: Vec<CtrlType>) {
+        self.unified_controllers = controllers;
+    }
+
+    /// Returns true if the controller is mounted under v1
+    pub(crate) fn has_subsystem(&self, subsystem: &CtrlType) -> bool {
+        self.subsystems.contains_key(subsystem)
+    }
+
+    fn freezer_path(&self) -> Result<&PathBuf> {
+        self.subsystems
+            .get(&CtrlType::Freezer)
+            .context("freezer subsystem is not mounted")
     }
 
     fn get_subsystem_path(cgroup_path: &Path, subsystem: &CtrlType) -> Result<PathBuf> {
@@ -87,6 +112,18 @@ impl Manager {
             if required {
                 if let Some(subsystem_path) = self.subsystems.get(controller) {
                     required_controllers.insert(controller, subsystem_path);
+                } else if self.unified_controllers.contains(controller) {
+                    log::debug!("cgroup {} is handled by the unified hierarchy", controller);
+                } else if matches!(
+                    controller,
+                    CtrlType::NetworkPriority | CtrlType::NetworkClassifier
+                ) {
+                    // network restrictions are best effort, as these controllers
+                    // are often not mounted at all
+                    log::warn!(
+                        "cgroup {} is not mounted, ignoring network resource restrictions",
+                        controller
+                    );
                 } else {
                     bail!("cgroup {} is required to fulfill the request, but is not supported by this system", controller);
                 }
@@ -148,19 +185,22 @@ impl CgroupManager for Manager {
         Ok(())
     }
 
-    fn remove(&self) -> Result<()> {
+    fn remove(&self, force: bool) -> Result<()> {
         for cgroup_path in &self.subsystems {
             if cgroup_path.1.exists() {
                 log::debug!("remove cgroup {:?}", cgroup_path.1);
-                let procs_path = cgroup_path.1.join(CGROUP_PROCS);
-                let procs = fs::read_to_string(&procs_path)?;
-
-                for line in procs.lines() {
-                    let pid: i32 = line.parse()?;
-                    let _ = nix::sys::signal::kill(Pid::from_raw(pid), nix::sys::signal::SIGKILL);
+                if force {
+                    let procs_path = cgroup_path.1.join(CGROUP_PROCS);
+                    let procs = fs::read_to_string(&procs_path)?;
+
+                    for line in procs.lines() {
+                        let pid: i32 = line.parse()?;
+                        let _ =
+                            nix::sys::signal::kill(Pid::from_raw(pid), nix::sys::signal::SIGKILL);
+                    }
                 }
 
-                common::delete_with_retry(cgroup_path.1, 4, Duration::from_millis(100))?;
+                common::delete_with_retry(cgroup_path.1, 4, Duration::from_millis(100), force)?;
             }
         }
 
@@ -174,10 +214,11 @@ impl CgroupManager for Manager {
             oom_score_adj: None,
             disable_oom_killer: false,
         };
-        Freezer::apply(
-            &controller_opt,
-            self.subsystems.get(&CtrlType::Freezer).unwrap(),
-        )
+        Freezer::apply(&controller_opt, self.freezer_path()?)
+    }
+
+    fn freezer_state(&self) -> Result<FreezerState> {
+        Freezer::state(self.freezer_path()?)
     }
 
     fn stats(&self) -> Result<Stats> {
@@ -197,4 +238,11 @@ impl CgroupManager for Manager {
 
         Ok(stats)
     }
+
+    fn attach_bpf_audit(&self, prog_fd: RawFd) -> Result<()> {
+        bail!(
+            "cannot attach eBPF audit program {}, eBPF programs can only be attached to cgroup v2",
+            prog_fd
+        )
+    }
 }
@@ -1,9 +1,12 @@
 use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
 use std::io::{prelude::*, Write};
-use std::{fs::OpenOptions, path::Path};
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::path::{Path, PathBuf};
 
-use anyhow::{anyhow, bail, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use nix::errno::Errno;
+use nix::sys::eventfd::{eventfd, EfdFlags};
 
 use super::Controller;
 use crate::common::{self, ControllerOpt};
@@ -18,6 +21,7 @@ const CGROUP_MEMORY_MAX_USAGE: &str = "memory.max_usage_in_bytes";
 const CGROUP_MEMORY_SWAPPINESS: &str = "memory.swappiness";
 const CGROUP_MEMORY_RESERVATION: &str = "memory.soft_limit_in_bytes";
 const CGROUP_MEMORY_OOM_CONTROL: &str = "memory.oom_control";
+const CGROUP_EVENT_CONTROL: &str = "cgroup.event_control";
 
 const CGROUP_KERNEL_MEMORY_LIMIT: &str = "memory.kmem.limit_in_bytes";
 const CGROUP_KERNEL_TCP_MEMORY_LIMIT: &str = "memory.kmem.tcp.limit_in_bytes";
@@ -52,6 +56,15 @@ impl Controller for Memory {
         log::debug!("Apply Memory cgroup config");
 
         if let Some(memory) = &controller_opt.resources.memory() {
+            if let Some(swappiness) = memory.swappiness() {
+                if swappiness > 100 {
+                    bail!(
+                        "invalid swappiness value: {}. Valid range is 0-100",
+                        swappiness
+                    );
+                }
+            }
+
             let reservation = memory.reservation().unwrap_or(0);
 
             Self::apply(memory, cgroup_root)?;
@@ -70,31 +83,16 @@ impl Controller for Memory {
             }
 
             if let Some(swappiness) = memory.swappiness() {
-                if swappiness <= 100 {
-                    common::write_cgroup_file(
-                        cgroup_root.join(CGROUP_MEMORY_SWAPPINESS),
-                        swappiness,
-                    )?;
-                } else {
-                    // invalid swappiness value
-                    return Err(anyhow!(
-                        "Invalid swappiness value: {}. Valid range is 0-100",
-                        swappiness
-                    ));
-                }
+                common::write_cgroup_file(cgroup_root.join(CGROUP_MEMORY_SWAPPINESS), swappiness)?;
             }
 
-            // NOTE: Seems as though kernel and kernelTCP are both deprecated
-            // neither are implemented by runc. Tests pass without this, but
-            // kept in per the spec.
+            // The kernel memory limits are deprecated and cannot be set
+            // anymore on newer kernels, so failing to set them is not fatal.
             if let Some(kmem) = memory.kernel() {
-                common::write_cgroup_file(cgroup_root.join(CGROUP_KERNEL_MEMORY_LIMIT), kmem)?;
+                Self::set_kernel_limit(cgroup_root.join(CGROUP_KERNEL_MEMORY_LIMIT), kmem);
             }
             if let Some(tcp_mem) = memory.kernel_tcp() {
-                common::write_cgroup_file(
-                    cgroup_root.join(CGROUP_KERNEL_TCP_MEMORY_LIMIT),
-                    tcp_mem,
-                )?;
+                Self::set_kernel_limit(cgroup_root.join(CGROUP_KERNEL_TCP_MEMORY_LIMIT), tcp_mem);
             }
         }
 
@@ -111,11 +109,12 @@ impl StatsProvider for Memory {
 
     fn stats(cgroup_path: &Path) -> Result<Self::Stats> {
         let memory = Self::get_memory_data(cgroup_path, MEMORY_PREFIX)?;
-        let memswap = Self::get_memory_data(cgroup_path, MEMORY_AND_SWAP_PREFIX)?;
-        let kernel = Self::get_memory_data(cgroup_path, MEMORY_KERNEL_PREFIX)?;
-        let kernel_tcp = Self::get_memory_data(cgroup_path, MEMORY_KERNEL_TCP_PREFIX)?;
+        let memswap = Self::get_optional_memory_data(cgroup_path, MEMORY_AND_SWAP_PREFIX)?;
+        let kernel = Self::get_optional_memory_data(cgroup_path, MEMORY_KERNEL_PREFIX)?;
+        let kernel_tcp = Self::get_optional_memory_data(cgroup_path, MEMORY_KERNEL_TCP_PREFIX)?;
         let hierarchy = Self::hierarchy_enabled(cgroup_path)?;
         let stats = Self::get_stat_data(cgroup_path)?;
+        let oom_kill = Self::get_oom_kill_count(cgroup_path)?;
 
         Ok(MemoryStats {
             memory,
@@ -125,11 +124,52 @@ impl StatsProvider for Memory {
             cache: stats["cache"],
             hierarchy,
             stats,
+            oom_kill,
         })
     }
 }
 
 impl Memory {
+    // Older kernels do not report the oom_kill counter in oom_control, or do
+    // not have the file at all, in which case there is no OOM information.
+    fn get_oom_kill_count(cgroup_path: &Path) -> Result<u64> {
+        let oom_control_path = cgroup_path.join(CGROUP_MEMORY_OOM_CONTROL);
+        if !oom_control_path.exists() {
+            return Ok(0);
+        }
+        let oom_control = stats::parse_flat_keyed_data(&oom_control_path)?;
+        Ok(oom_control.get("oom_kill").copied().unwrap_or_default())
+    }
+
+    /// Calls the handler whenever the OOM killer has been invoked in the
+    /// cgroup, until the cgroup is removed. The kernel signals an eventfd,
+    /// which has been registered for memory.oom_control.
+    pub fn watch_oom_events<F: FnMut()>(cgroup_path: &Path, mut on_oom: F) -> Result<()> {
+        let oom_control_path = cgroup_path.join(CGROUP_MEMORY_OOM_CONTROL);
+        let oom_control = File::open(&oom_control_path)
+            .with_context(|| format!("failed to open {:?}", oom_control_path))?;
+        let event_fd =
+            eventfd(0, EfdFlags::EFD_CLOEXEC).context("failed to create eventfd for OOM events")?;
+        let mut event = unsafe { File::from_raw_fd(event_fd) };
+        fs::write(
+            cgroup_path.join(CGROUP_EVENT_CONTROL),
+            format!("{} {}", event.as_raw_fd(), oom_control.as_raw_fd()),
+        )
+        .context("failed to register for OOM events")?;
+
+        let mut count = [0; 8];
+        loop {
+            event
+                .read_exact(&mut count)
+                .context("failed to read OOM events")?;
+            // the eventfd is signaled as well, when the cgroup is removed
+            if !cgroup_path.exists() {
+                return Ok(());
+            }
+            on_oom();
+        }
+    }
+
     fn get_memory_data(cgroup_path: &Path, file_prefix: &str) -> Result<MemoryData> {
         let memory_data = MemoryData {
             usage: parse_single_value(
@@ -149,6 +189,19 @@ impl Memory {
         Ok(memory_data)
     }
 
+    // The swap accounting can be disabled and newer kernels do not account
+    // kernel memory separately anymore, in which case the files do not exist
+    fn get_optional_memory_data(cgroup_path: &Path, file_prefix: &str) -> Result<MemoryData> {
+        if !cgroup_path
+            .join(format!("{}{}", file_prefix, MEMORY_USAGE_IN_BYTES))
+            .exists()
+        {
+            return Ok(MemoryData::default());
+        }
+
+        Self::get_memory_data(cgroup_path, file_prefix)
+    }
+
     fn hierarchy_enabled(cgroup_path: &Path) -> Result<bool> {
         let hierarchy_path = cgroup_path.join(MEMORY_USE_HIERARCHY);
         let hierarchy = common::read_cgroup_file(hierarchy_path)?;
@@ -234,28 +287,35 @@ impl Memory {
         }
         let path = cgroup_root.join(CGROUP_MEMORY_LIMIT);
 
-        match Self::set(val, &path) {
-            Ok(_) => Ok(()),
-            Err(e) => {
-                // we need to look into the raw OS error for an EBUSY status
-                match e.raw_os_error() {
-                    Some(code) => match Errno::from_i32(code) {
-                        Errno::EBUSY => {
-                            let usage = Self::get_memory_usage(cgroup_root)?;
-                            let max_usage = Self::get_memory_max_usage(cgroup_root)?;
-                            bail!(
-                                    "unable to set memory limit to {} (current usage: {}, peak usage: {})",
-                                    val,
-                                    usage,
-                                    max_usage,
-                            )
-                        }
-                        _ => bail!(e),
-                    },
-                    None => bail!(e),
-                }
+        Self::set(val, &path).map_err(|e| Self::set_memory_error(val, cgroup_root, e))
+    }
+
+    // The kernel refuses to lower the limit with EBUSY, if it can not reclaim
+    // enough memory to get below the new limit. The usage is reported the same
+    // way as on cgroup v2 then, other errors are returned as they are.
+    fn set_memory_error(val: i64, cgroup_root: &Path, err: std::io::Error) -> anyhow::Error {
+        if err.raw_os_error() == Some(Errno::EBUSY as i32) {
+            if let (Ok(usage), Ok(max_usage)) = (
+                Self::get_memory_usage(cgroup_root),
+                Self::get_memory_max_usage(cgroup_root),
+            ) {
+                return anyhow!(
+                    "unable to set memory limit to {} (current usage: {}, peak usage: {}): {}",
+                    val,
+                    usage,
+                    max_usage,
+                    err
+                );
             }
         }
+
+        err.into()
+    }
+
+    fn set_kernel_limit(path: PathBuf, limit: i64) {
+        if let Err(e) = common::write_cgroup_file(&path, limit) {
+            log::warn!("failed to set deprecated kernel memory limit: {:?}", e);
+        }
     }
 
     fn set_swap(swap: i64, cgroup_root: &Path) -> Result<()> {
@@ -335,6 +395,26 @@ mod tests {
         assert_eq!(limit.to_string(), content)
     }
 
+    #[test]
+    fn test_set_memory_below_usage() {
+        let tmp = create_temp_dir("test_set_memory_below_usage_v1")
+            .expect("create temp directory for test");
+        set_fixture(&tmp, CGROUP_MEMORY_USAGE, "4096\n").expect("set fixture for memory usage");
+        set_fixture(&tmp, CGROUP_MEMORY_MAX_USAGE, "8192\n")
+            .expect("set fixture for max memory usage");
+
+        // the kernel refuses the limit with EBUSY, as it is below the usage
+        let busy = std::io::Error::from_raw_os_error(Errno::EBUSY as i32);
+        let err = Memory::set_memory_error(1024, &tmp, busy);
+        assert!(err
+            .to_string()
+            .contains("current usage: 4096, peak usage: 8192"));
+
+        // memory.limit_in_bytes is missing, which is not related to the usage
+        let err = Memory::set_memory(1024, &tmp).expect_err("limit file is missing");
+        assert!(!err.to_string().contains("current usage"));
+    }
+
     #[test]
     fn pass_set_memory_if_limit_is_zero() {
         let sample_val = "1024";
@@ -405,6 +485,68 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_set_kernel_limit_failure_is_not_fatal() {
+        let tmp = create_temp_dir("test_set_kernel_limit_failure_is_not_fatal")
+            .expect("create temp directory for test");
+        set_fixture(&tmp, CGROUP_MEMORY_OOM_CONTROL, "0").expect("set fixture for oom control");
+        set_fixture(&tmp, CGROUP_MEMORY_SWAPPINESS, "0").expect("set fixture for swappiness");
+        set_fixture(&tmp, CGROUP_MEMORY_LIMIT, "0").expect("set fixture for memory limit");
+
+        // the kernel memory limit files do not exist
+        let linux_memory = LinuxMemoryBuilder::default()
+            .swappiness(60u64)
+            .kernel(1024 * 1024)
+            .kernel_tcp(1024 * 1024)
+            .build()
+            .unwrap();
+        let linux_resources = LinuxResourcesBuilder::default()
+            .memory(linux_memory)
+            .build()
+            .unwrap();
+        let controller_opt = ControllerOpt {
+            resources: &linux_resources,
+            disable_oom_killer: false,
+            oom_score_adj: None,
+            freezer_state: None,
+        };
+
+        <Memory as Controller>::apply(&controller_opt, &tmp).expect("apply memory");
+        let swappiness =
+            std::fs::read_to_string(tmp.join(CGROUP_MEMORY_SWAPPINESS)).expect("read swappiness");
+        assert_eq!(swappiness, "60");
+        assert!(!tmp.join(CGROUP_KERNEL_MEMORY_LIMIT).exists());
+    }
+
+    #[test]
+    fn test_invalid_swappiness() {
+        let tmp =
+            create_temp_dir("test_invalid_swappiness").expect("create temp directory for test");
+        set_fixture(&tmp, CGROUP_MEMORY_SWAPPINESS, "0").expect("set fixture for swappiness");
+        set_fixture(&tmp, CGROUP_MEMORY_LIMIT, "0").expect("set fixture for memory limit");
+
+        let linux_memory = LinuxMemoryBuilder::default()
+            .limit(1024)
+            .swappiness(101u64)
+            .build()
+            .unwrap();
+        let linux_resources = LinuxResourcesBuilder::default()
+            .memory(linux_memory)
+            .build()
+            .unwrap();
+        let controller_opt = ControllerOpt {
+            resources: &linux_resources,
+            disable_oom_killer: false,
+            oom_score_adj: None,
+            freezer_state: None,
+        };
+
+        assert!(<Memory as Controller>::apply(&controller_opt, &tmp).is_err());
+        // nothing has been written
+        let limit = std::fs::read_to_string(tmp.join(CGROUP_MEMORY_LIMIT)).expect("read limit");
+        assert_eq!(limit, "0");
+    }
+
     quickcheck! {
             fn property_test_set_memory(linux_memory: LinuxMemory, disable_oom_killer: bool) -> bool {
                 let tmp =
@@ -571,6 +713,50 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn test_stat_optional_memory_data() {
+        let tmp = create_temp_dir("test_stat_optional_memory_data").expect("create test directory");
+        for (file, value) in [
+            (MEMORY_USAGE_IN_BYTES, "1024\n"),
+            (MEMORY_MAX_USAGE_IN_BYTES, "2048\n"),
+            (MEMORY_LIMIT_IN_BYTES, "4096\n"),
+            (MEMORY_FAIL_COUNT, "5\n"),
+        ] {
+            set_fixture(&tmp, &format!("{}{}", MEMORY_AND_SWAP_PREFIX, file), value).unwrap();
+        }
+
+        let memswap =
+            Memory::get_optional_memory_data(&tmp, MEMORY_AND_SWAP_PREFIX).expect("get swap stats");
+        assert_eq!(
+            memswap,
+            MemoryData {
+                usage: 1024,
+                max_usage: 2048,
+                limit: 4096,
+                fail_count: 5,
+            }
+        );
+
+        // kernel memory is not accounted by this kernel
+        let kernel = Memory::get_optional_memory_data(&tmp, MEMORY_KERNEL_PREFIX)
+            .expect("get kernel memory stats");
+        assert_eq!(kernel, MemoryData::default());
+    }
+
+    #[test]
+    fn test_stat_oom_kill_count() {
+        let tmp = create_temp_dir("test_stat_oom_kill_count").expect("create test directory");
+        let content = ["oom_kill_disable 0", "under_oom 0", "oom_kill 4"].join("\n");
+        set_fixture(&tmp, CGROUP_MEMORY_OOM_CONTROL, &content).unwrap();
+
+        let actual = Memory::get_oom_kill_count(&tmp).expect("get oom kill count");
+        assert_eq!(actual, 4);
+
+        fs::remove_file(tmp.join(CGROUP_MEMORY_OOM_CONTROL)).unwrap();
+        let actual = Memory::get_oom_kill_count(&tmp).expect("get oom kill count");
+        assert_eq!(actual, 0);
+    }
+
     #[test]
     fn test_stat_hierarchy_enabled() {
         let tmp = create_temp_dir("test_stat_hierarchy_enabled").expect("create test directory");
@@ -5,10 +5,10 @@ mod cpu;
 mod cpuacct;
 mod cpuset;
 mod devices;
-mod freezer;
+pub(crate) mod freezer;
 mod hugetlb;
 pub mod manager;
-mod memory;
+pub(crate) mod memory;
 mod network_classifier;
 mod network_priority;
 pub mod perf_event;
@@ -4,7 +4,9 @@ use anyhow::{Context, Result};
 
 use super::Controller;
 use crate::common::{self, ControllerOpt};
-use oci_spec::runtime::LinuxNetwork;
+use oci_spec::runtime::{LinuxInterfacePriority, LinuxNetwork};
+
+const CGROUP_NET_PRIO_IFPRIOMAP: &str = "net_prio.ifpriomap";
 
 pub struct NetworkPriority {}
 
@@ -30,12 +32,41 @@ impl Controller for NetworkPriority {
 impl NetworkPriority {
     fn apply(root_path: &Path, network: &LinuxNetwork) -> Result<()> {
         if let Some(ni_priorities) = network.priorities() {
-            let priorities: String = ni_priorities.iter().map(|p| p.to_string()).collect();
-            common::write_cgroup_file_str(root_path.join("net_prio.ifpriomap"), priorities.trim())?;
+            let path = root_path.join(CGROUP_NET_PRIO_IFPRIOMAP);
+            let current = common::read_cgroup_file(&path)?;
+            // the kernel only parses a single entry per write
+            for entry in Self::priority_updates(&current, ni_priorities) {
+                common::write_cgroup_file_str(&path, &entry)?;
+            }
         }
 
         Ok(())
     }
+
+    /// Returns the entries which need to be written to the priority map.
+    /// Interfaces with a priority which is no longer configured are reset to
+    /// the default priority. The map is read from the network namespace of the
+    /// runtime, not the one of the container, so configured interfaces may not
+    /// be part of it.
+    fn priority_updates(current: &str, priorities: &[LinuxInterfacePriority]) -> Vec<String> {
+        let current: Vec<(&str, &str)> = current
+            .lines()
+            .filter_map(|line| line.split_once(' '))
+            .collect();
+
+        let mut updates = Vec::new();
+        for (name, priority) in &current {
+            if *priority != "0" && !priorities.iter().any(|p| p.name() == name) {
+                updates.push(format!("{} 0", name));
+            }
+        }
+
+        for priority in priorities {
+            updates.push(format!("{} {}", priority.name(), priority.priority()));
+        }
+
+        updates
+    }
 }
 
 #[cfg(test)]
@@ -44,33 +75,59 @@ mod tests {
     use crate::test::{create_temp_dir, set_fixture};
     use oci_spec::runtime::{LinuxInterfacePriorityBuilder, LinuxNetworkBuilder};
 
+    fn priority(name: &str, priority: u32) -> LinuxInterfacePriority {
+        LinuxInterfacePriorityBuilder::default()
+            .name(name)
+            .priority(priority)
+            .build()
+            .unwrap()
+    }
+
     #[test]
     fn test_apply_network_priorites() {
         let tmp = create_temp_dir("test_apply_network_priorites")
             .expect("create temp directory for test");
-        set_fixture(&tmp, "net_prio.ifpriomap", "").expect("set fixture for priority map");
-        let priorities = vec![
-            LinuxInterfacePriorityBuilder::default()
-                .name("a")
-                .priority(1u32)
-                .build()
-                .unwrap(),
-            LinuxInterfacePriorityBuilder::default()
-                .name("b")
-                .priority(2u32)
-                .build()
-                .unwrap(),
-        ];
-        let priorities_string = priorities.iter().map(|p| p.to_string()).collect::<String>();
+        set_fixture(&tmp, CGROUP_NET_PRIO_IFPRIOMAP, "a 0\n")
+            .expect("set fixture for priority map");
         let network = LinuxNetworkBuilder::default()
-            .priorities(priorities)
+            .priorities(vec![priority("a", 1)])
             .build()
             .unwrap();
 
         NetworkPriority::apply(&tmp, &network).expect("apply network priorities");
 
-        let content =
-            std::fs::read_to_string(tmp.join("net_prio.ifpriomap")).expect("Read classID contents");
-        assert_eq!(priorities_string.trim(), content);
+        let content = std::fs::read_to_string(tmp.join(CGROUP_NET_PRIO_IFPRIOMAP))
+            .expect("Read priority map contents");
+        assert_eq!("a 1", content.trim());
+    }
+
+    #[test]
+    fn test_apply_network_priorities_unknown_interface() {
+        let tmp = create_temp_dir("test_apply_network_priorities_unknown_interface")
+            .expect("create temp directory for test");
+        set_fixture(&tmp, CGROUP_NET_PRIO_IFPRIOMAP, "lo 0\n")
+            .expect("set fixture for priority map");
+        let network = LinuxNetworkBuilder::default()
+            .priorities(vec![priority("eth0", 1)])
+            .build()
+            .unwrap();
+
+        // the interface may only exist in the network namespace of the container
+        NetworkPriority::apply(&tmp, &network).expect("apply network priorities");
+
+        let content = std::fs::read_to_string(tmp.join(CGROUP_NET_PRIO_IFPRIOMAP))
+            .expect("Read priority map contents");
+        assert_eq!("eth0 1", content.trim());
+    }
+
+    #[test]
+    fn test_priority_updates() {
+        let current = "lo 0\neth0 5\neth1 3\n";
+        let updates =
+            NetworkPriority::priority_updates(current, &[priority("lo", 2), priority("eth1", 4)]);
+        assert_eq!(updates, vec!["eth0 0", "lo 2", "eth1 4"]);
+
+        let updates = NetworkPriority::priority_updates(current, &[]);
+        assert_eq!(updates, vec!["eth0 0", "eth1 0"]);
     }
 }
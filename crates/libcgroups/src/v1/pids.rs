@@ -89,6 +89,21 @@ mod tests {
         assert_eq!("max".to_string(), content);
     }
 
+    #[test]
+    fn test_set_pids_unlimited() {
+        let tmp =
+            create_temp_dir("test_set_pids_unlimited").expect("create temp directory for test");
+        set_fixture(&tmp, CGROUP_PIDS_MAX, "1000").expect("set fixture for 1000 pids");
+
+        let pids = LinuxPidsBuilder::default().limit(-1).build().unwrap();
+
+        Pids::apply(&tmp, &pids).expect("apply pids");
+
+        let content =
+            std::fs::read_to_string(tmp.join(CGROUP_PIDS_MAX)).expect("Read pids contents");
+        assert_eq!("max".to_string(), content);
+    }
+
     #[test]
     fn test_stat_pids() {
         let tmp = create_temp_dir("test_stat_pids").expect("create temp dir for test");
@@ -98,7 +113,7 @@ mod tests {
         let stats = Pids::stats(&tmp).expect("get cgroup stats");
 
         assert_eq!(stats.current, 5);
-        assert_eq!(stats.limit, 30);
+        assert_eq!(stats.limit, Some(30));
     }
 
     #[test]
@@ -110,6 +125,6 @@ mod tests {
         let stats = Pids::stats(&tmp).expect("get cgroup stats");
 
         assert_eq!(stats.current, 5);
-        assert_eq!(stats.limit, 0);
+        assert_eq!(stats.limit, None);
     }
 }
@@ -8,6 +8,7 @@ pub enum ControllerType {
     Memory,
     HugeTlb,
     Pids,
+    Rdma,
 }
 
 impl Display for ControllerType {
@@ -19,6 +20,7 @@ impl Display for ControllerType {
             Self::Memory => "memory",
             Self::HugeTlb => "hugetlb",
             Self::Pids => "pids",
+            Self::Rdma => "rdma",
         };
 
         write!(f, "{}", print)
@@ -32,6 +34,7 @@ pub const CONTROLLER_TYPES: &[ControllerType] = &[
     ControllerType::Io,
     ControllerType::Memory,
     ControllerType::Pids,
+    ControllerType::Rdma,
 ];
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash)]
@@ -3,7 +3,7 @@ use std::{borrow::Cow, path::Path};
 
 use crate::{
     common::{self, ControllerOpt},
-    stats::{CpuUsage, StatsProvider},
+    stats::{CpuStats, StatsProvider},
 };
 
 use oci_spec::runtime::LinuxCpu;
@@ -11,6 +11,7 @@ use oci_spec::runtime::LinuxCpu;
 use super::controller::Controller;
 
 const CGROUP_CPU_WEIGHT: &str = "cpu.weight";
+const CGROUP_CPU_IDLE: &str = "cpu.idle";
 const CGROUP_CPU_MAX: &str = "cpu.max";
 const UNRESTRICTED_QUOTA: &str = "max";
 const MAX_CPU_WEIGHT: u64 = 10000;
@@ -30,10 +31,12 @@ impl Controller for Cpu {
 }
 
 impl StatsProvider for Cpu {
-    type Stats = CpuUsage;
+    type Stats = CpuStats;
 
     fn stats(cgroup_path: &Path) -> Result<Self::Stats> {
-        let mut stats = CpuUsage::default();
+        // The throttling counters are only reported if the cpu controller is
+        // enabled, otherwise they stay zero.
+        let mut stats = CpuStats::default();
 
         let stat_content = common::read_cgroup_file(cgroup_path.join(CPU_STAT))?;
         for entry in stat_content.lines() {
@@ -44,10 +47,10 @@ impl StatsProvider for Cpu {
 
             let value = parts[1].parse()?;
             match parts[0] {
-                "usage_usec" => stats.usage_total = value,
-                "user_usec" => stats.usage_user = value,
-                "system_usec" => stats.usage_kernel = value,
-                _ => continue,
+                "usage_usec" => stats.usage.usage_total = value,
+                "user_usec" => stats.usage.usage_user = value,
+                "system_usec" => stats.usage.usage_kernel = value,
+                key => stats.throttling.set(key, value),
             }
         }
 
@@ -61,9 +64,21 @@ impl Cpu {
             bail!("realtime is not supported on cgroup v2 yet");
         }
 
+        // an idle cgroup is scheduled with SCHED_IDLE semantics, which means
+        // with the minimum weight
+        let is_idle = cpu.idle() == Some(1);
+        if let Some(idle) = cpu.idle() {
+            Self::apply_idle(path, idle)?;
+        }
+
         if let Some(mut shares) = cpu.shares() {
             shares = Self::convert_shares_to_cgroup2(shares);
-            if shares != 0 {
+            if is_idle {
+                log::warn!(
+                    "cpu weight {} is ignored, because the cgroup is idle",
+                    shares
+                );
+            } else if shares != 0 {
                 // will result in Erno 34 (numerical result out of range) otherwise
                 common::write_cgroup_file(path.join(CGROUP_CPU_WEIGHT), shares)?;
             }
@@ -94,6 +109,23 @@ impl Cpu {
         Ok(())
     }
 
+    fn apply_idle(path: &Path, idle: i64) -> Result<()> {
+        if idle != 0 && idle != 1 {
+            bail!("invalid cpu idle value {}, it must be 0 or 1", idle);
+        }
+
+        let idle_file = path.join(CGROUP_CPU_IDLE);
+        if !idle_file.exists() {
+            // a cgroup is not idle by default, so only setting it requires
+            // the kernel support
+            if idle == 0 {
+                return Ok(());
+            }
+            bail!("cpu idle is not supported, it requires at least linux 5.15");
+        }
+        common::write_cgroup_file(idle_file, idle)
+    }
+
     fn convert_shares_to_cgroup2(shares: u64) -> u64 {
         if shares == 0 {
             return 0;
@@ -127,6 +159,7 @@ impl Cpu {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::stats::{CpuThrottling, CpuUsage};
     use crate::test::{create_temp_dir, set_fixture, setup};
     use oci_spec::runtime::LinuxCpuBuilder;
     use std::fs;
@@ -148,6 +181,57 @@ mod tests {
         assert_eq!(content, 840.to_string());
     }
 
+    #[test]
+    fn test_set_idle() -> Result<()> {
+        let (tmp, weight) = setup("test_set_idle", CGROUP_CPU_WEIGHT);
+        let idle = set_fixture(&tmp, CGROUP_CPU_IDLE, "")?;
+        let cpu = LinuxCpuBuilder::default()
+            .idle(1)
+            .shares(22000u64)
+            .build()?;
+
+        Cpu::apply(&tmp, &cpu)?;
+
+        assert_eq!(fs::read_to_string(idle)?, "1");
+        // the weight does not apply to an idle cgroup
+        assert_eq!(fs::read_to_string(weight)?, "");
+        Ok(())
+    }
+
+    #[test]
+    fn test_unset_idle() -> Result<()> {
+        let (tmp, weight) = setup("test_unset_idle", CGROUP_CPU_WEIGHT);
+        let idle = set_fixture(&tmp, CGROUP_CPU_IDLE, "1")?;
+        let cpu = LinuxCpuBuilder::default()
+            .idle(0)
+            .shares(22000u64)
+            .build()?;
+
+        Cpu::apply(&tmp, &cpu)?;
+
+        assert_eq!(fs::read_to_string(idle)?, "0");
+        assert_eq!(fs::read_to_string(weight)?, "840");
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_idle_error() -> Result<()> {
+        let tmp = create_temp_dir("test_set_idle_error")?;
+        // the kernel is too old to support idle cgroups
+        let cpu = LinuxCpuBuilder::default().idle(1).build()?;
+        assert!(Cpu::apply(&tmp, &cpu).is_err());
+        // which does not matter, if the cgroup should not be idle anyway
+        let cpu = LinuxCpuBuilder::default().idle(0).build()?;
+        Cpu::apply(&tmp, &cpu)?;
+        assert!(!tmp.join(CGROUP_CPU_IDLE).exists());
+
+        set_fixture(&tmp, CGROUP_CPU_IDLE, "0")?;
+        let cpu = LinuxCpuBuilder::default().idle(2).build()?;
+        assert!(Cpu::apply(&tmp, &cpu).is_err());
+        assert_eq!(fs::read_to_string(tmp.join(CGROUP_CPU_IDLE))?, "0");
+        Ok(())
+    }
+
     #[test]
     fn test_set_positive_quota() {
         // arrange
@@ -272,6 +356,34 @@ mod tests {
             ..Default::default()
         };
 
-        assert_eq!(actual, expected);
+        assert_eq!(actual.usage, expected);
+        assert_eq!(actual.throttling, CpuThrottling::default());
+    }
+
+    #[test]
+    fn test_stat_throttling() {
+        let tmp = create_temp_dir("test_stat_throttling").expect("create temp directory for test");
+        let content = [
+            "usage_usec 7730",
+            "user_usec 4387",
+            "system_usec 3498",
+            "nr_periods 400",
+            "nr_throttled 20",
+            "throttled_usec 1200",
+            "nr_bursts 2",
+            "burst_usec 300",
+        ]
+        .join("\n");
+        set_fixture(&tmp, CPU_STAT, &content).expect("create stat file");
+
+        let actual = Cpu::stats(&tmp).expect("get cgroup stats");
+        let expected = CpuThrottling {
+            periods: 400,
+            throttled_periods: 20,
+            throttled_time: 1200,
+            bursts: 2,
+            burst_time: 300,
+        };
+        assert_eq!(actual.throttling, expected);
     }
 }
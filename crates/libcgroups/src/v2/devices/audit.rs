@@ -0,0 +1,139 @@
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use nix::fcntl::OFlag;
+use nix::sys::stat::Mode;
+
+#[cfg(test)]
+use super::bpf::mock_prog as bpf_prog;
+
+#[cfg(not(test))]
+use super::bpf::prog as bpf_prog;
+
+/// Attaches a user provided eBPF LSM program to the cgroup, so that it can
+/// audit e.g. device opens and mounts of the container. The program is owned
+/// by the caller and only attached, without a link, as the attaching process
+/// exits after the container has been created. It is detached again by
+/// [detach_audit_programs] when the cgroup is removed.
+pub fn attach_audit_program(prog_fd: RawFd, cgroup_root: &Path) -> Result<()> {
+    log::debug!("Attach eBPF audit program to {:?}", cgroup_root);
+    let fd = nix::dir::Dir::open(
+        cgroup_root.as_os_str(),
+        OFlag::O_RDONLY | OFlag::O_DIRECTORY,
+        Mode::empty(),
+    )
+    .with_context(|| format!("failed to open cgroup {:?}", cgroup_root))?;
+
+    bpf_prog::attach_lsm(prog_fd, fd.as_raw_fd()).with_context(|| {
+        format!(
+            "failed to attach eBPF audit program {} to {:?}, it has to be an LSM program \
+            with expected attach type BPF_LSM_CGROUP",
+            prog_fd, cgroup_root
+        )
+    })
+}
+
+/// Detaches the eBPF LSM programs attached to the cgroup. If the kernel does
+/// not support them or the user is not permitted to query them, none can have
+/// been attached.
+pub fn detach_audit_programs(cgroup_root: &Path) -> Result<()> {
+    let fd = nix::dir::Dir::open(
+        cgroup_root.as_os_str(),
+        OFlag::O_RDONLY | OFlag::O_DIRECTORY,
+        Mode::empty(),
+    )
+    .with_context(|| format!("failed to open cgroup {:?}", cgroup_root))?;
+
+    let progs = match bpf_prog::query_lsm(fd.as_raw_fd()) {
+        Ok(progs) => progs,
+        Err(e)
+            if matches!(
+                e.downcast_ref::<errno::Errno>(),
+                Some(errno::Errno(libc::EINVAL | libc::EPERM))
+            ) =>
+        {
+            return Ok(())
+        }
+        Err(e) => {
+            return Err(e).with_context(|| {
+                format!("failed to query eBPF audit programs of {:?}", cgroup_root)
+            })
+        }
+    };
+    for prog in progs {
+        log::debug!(
+            "Detach eBPF audit program {} from {:?}",
+            prog.id,
+            cgroup_root
+        );
+        let detached = bpf_prog::detach_lsm(prog.fd, fd.as_raw_fd());
+        let _ = nix::unistd::close(prog.fd);
+        detached.with_context(|| format!("failed to detach eBPF audit program {}", prog.id))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::create_temp_dir;
+    use crate::v2::devices::bpf::ProgramInfo;
+    use serial_test::serial;
+
+    #[test]
+    #[serial(bpf)] // mock contexts are shared
+    fn test_attach_audit_program() -> Result<()> {
+        let tmp = create_temp_dir("test_attach_audit_program")?;
+        let attach_lsm = bpf_prog::attach_lsm_context();
+
+        attach_lsm
+            .expect()
+            .once()
+            .withf(|prog_fd, _| *prog_fd == 42)
+            .returning(|_, _| Ok(()));
+        attach_audit_program(42, &tmp)?;
+        attach_lsm.checkpoint();
+
+        attach_lsm
+            .expect()
+            .once()
+            .returning(|_, _| Err(nix::errno::Errno::EINVAL.into()));
+        let err = attach_audit_program(42, &tmp).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("failed to attach eBPF audit program 42"));
+        Ok(())
+    }
+
+    #[test]
+    #[serial(bpf)] // mock contexts are shared
+    fn test_detach_audit_programs() -> Result<()> {
+        let tmp = create_temp_dir("test_detach_audit_programs")?;
+        let query_lsm = bpf_prog::query_lsm_context();
+        let detach_lsm = bpf_prog::detach_lsm_context();
+
+        query_lsm
+            .expect()
+            .once()
+            .returning(|_| Ok(vec![ProgramInfo { id: 7, fd: -1 }]));
+        detach_lsm
+            .expect()
+            .once()
+            .withf(|prog_fd, _| *prog_fd == -1)
+            .returning(|_, _| Ok(()));
+        detach_audit_programs(&tmp)?;
+        query_lsm.checkpoint();
+        detach_lsm.checkpoint();
+
+        // kernels before 6.0 do not know the attach type
+        query_lsm
+            .expect()
+            .once()
+            .returning(|_| Err(errno::Errno(libc::EINVAL).into()));
+        detach_lsm.expect().never();
+        detach_audit_programs(&tmp)?;
+        Ok(())
+    }
+}
@@ -1,12 +1,72 @@
+use anyhow::Result;
+use std::os::unix::io::RawFd;
+
 #[derive(Clone)]
 pub struct ProgramInfo {
     pub id: u32,
     pub fd: i32,
 }
 
+/// Attach type of eBPF LSM programs scoped to a cgroup (linux 6.0+), which
+/// is not defined by libbpf-sys yet
+pub const BPF_LSM_CGROUP: libbpf_sys::bpf_attach_type = 43;
+
+/// Collects the programs of an attach type, which are attached to a cgroup
+fn query_attached(
+    cgroup_fd: RawFd,
+    attach_type: libbpf_sys::bpf_attach_type,
+) -> Result<Vec<ProgramInfo>> {
+    let mut prog_ids: Vec<u32> = vec![0_u32; 64];
+    let mut attach_flags = 0_u32;
+    for _ in 0..10 {
+        let mut prog_cnt = prog_ids.len() as u32;
+        let ret = unsafe {
+            // collect ids for bpf programs
+            libbpf_sys::bpf_prog_query(
+                cgroup_fd,
+                attach_type,
+                0,
+                &mut attach_flags,
+                &prog_ids[0] as *const u32 as *mut u32,
+                &mut prog_cnt,
+            )
+        };
+        if ret != 0 {
+            let err = errno::errno();
+            if err.0 == libc::ENOSPC {
+                assert!(prog_cnt as usize > prog_ids.len());
+
+                // allocate more space and try again
+                prog_ids.resize(prog_cnt as usize, 0);
+                continue;
+            }
+
+            return Err(err.into());
+        }
+
+        prog_ids.resize(prog_cnt as usize, 0);
+        break;
+    }
+
+    let mut prog_fds = Vec::with_capacity(prog_ids.len());
+    for prog_id in &prog_ids {
+        // collect fds for programs by getting their ids
+        let prog_fd = unsafe { libbpf_sys::bpf_prog_get_fd_by_id(*prog_id) };
+        if prog_fd < 0 {
+            log::debug!("bpf_prog_get_fd_by_id failed: {}", errno::errno());
+            continue;
+        }
+        prog_fds.push(ProgramInfo {
+            id: *prog_id,
+            fd: prog_fd,
+        });
+    }
+    Ok(prog_fds)
+}
+
 #[cfg_attr(test, automock)]
 pub mod prog {
-    use super::ProgramInfo;
+    use super::{ProgramInfo, BPF_LSM_CGROUP};
     use anyhow::{bail, Result};
     use std::os::unix::io::RawFd;
     use std::ptr;
@@ -35,52 +95,12 @@ pub mod prog {
 
     /// Given a fd for a cgroup, collect the programs associated with it
     pub fn query(cgroup_fd: RawFd) -> Result<Vec<ProgramInfo>> {
-        let mut prog_ids: Vec<u32> = vec![0_u32; 64];
-        let mut attach_flags = 0_u32;
-        for _ in 0..10 {
-            let mut prog_cnt = prog_ids.len() as u32;
-            let ret = unsafe {
-                // collect ids for bpf programs
-                libbpf_sys::bpf_prog_query(
-                    cgroup_fd,
-                    libbpf_sys::BPF_CGROUP_DEVICE,
-                    0,
-                    &mut attach_flags,
-                    &prog_ids[0] as *const u32 as *mut u32,
-                    &mut prog_cnt,
-                )
-            };
-            if ret != 0 {
-                let err = errno::errno();
-                if err.0 == libc::ENOSPC {
-                    assert!(prog_cnt as usize > prog_ids.len());
-
-                    // allocate more space and try again
-                    prog_ids.resize(prog_cnt as usize, 0);
-                    continue;
-                }
-
-                return Err(err.into());
-            }
-
-            prog_ids.resize(prog_cnt as usize, 0);
-            break;
-        }
+        super::query_attached(cgroup_fd, libbpf_sys::BPF_CGROUP_DEVICE)
+    }
 
-        let mut prog_fds = Vec::with_capacity(prog_ids.len());
-        for prog_id in &prog_ids {
-            // collect fds for programs by getting their ids
-            let prog_fd = unsafe { libbpf_sys::bpf_prog_get_fd_by_id(*prog_id) };
-            if prog_fd < 0 {
-                log::debug!("bpf_prog_get_fd_by_id failed: {}", errno::errno());
-                continue;
-            }
-            prog_fds.push(ProgramInfo {
-                id: *prog_id,
-                fd: prog_fd,
-            });
-        }
-        Ok(prog_fds)
+    /// Given a fd for a cgroup, collect the LSM programs attached to it
+    pub fn query_lsm(cgroup_fd: RawFd) -> Result<Vec<ProgramInfo>> {
+        super::query_attached(cgroup_fd, BPF_LSM_CGROUP)
     }
 
     pub fn detach2(prog_fd: RawFd, cgroup_fd: RawFd) -> Result<()> {
@@ -93,6 +113,14 @@ pub mod prog {
         Ok(())
     }
 
+    pub fn detach_lsm(prog_fd: RawFd, cgroup_fd: RawFd) -> Result<()> {
+        let ret = unsafe { libbpf_sys::bpf_prog_detach2(prog_fd, cgroup_fd, BPF_LSM_CGROUP) };
+        if ret != 0 {
+            return Err(errno::errno().into());
+        }
+        Ok(())
+    }
+
     pub fn attach(prog_fd: RawFd, cgroup_fd: RawFd) -> Result<()> {
         let ret = unsafe {
             libbpf_sys::bpf_prog_attach(
@@ -109,6 +137,24 @@ pub mod prog {
         Ok(())
     }
 
+    /// Attaches an eBPF LSM program, e.g. one auditing device opens and
+    /// mounts, to the cgroup
+    pub fn attach_lsm(prog_fd: RawFd, cgroup_fd: RawFd) -> Result<()> {
+        let ret = unsafe {
+            libbpf_sys::bpf_prog_attach(
+                prog_fd,
+                cgroup_fd,
+                BPF_LSM_CGROUP,
+                libbpf_sys::BPF_F_ALLOW_MULTI,
+            )
+        };
+
+        if ret != 0 {
+            return Err(errno::errno().into());
+        }
+        Ok(())
+    }
+
     pub fn bump_memlock_rlimit() -> Result<()> {
         let rlimit = libc::rlimit {
             rlim_cur: 128 << 20,
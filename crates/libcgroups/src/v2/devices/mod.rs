@@ -1,3 +1,4 @@
+pub mod audit;
 pub mod bpf;
 pub mod controller;
 pub mod emulator;
@@ -1,7 +1,7 @@
 use anyhow::{bail, Context, Result};
 use std::{
-    fs::OpenOptions,
-    io::{BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    fs::{self, OpenOptions},
+    io::{BufRead, BufReader, ErrorKind, Read, Seek, SeekFrom, Write},
     path::Path,
     str, thread,
     time::Duration,
@@ -61,6 +61,34 @@ impl Freezer {
         Ok(())
     }
 
+    /// Returns the state of the freezer without waiting for a freeze in
+    /// progress to complete
+    pub fn state(path: &Path) -> Result<FreezerState> {
+        let freeze = match fs::read_to_string(path.join(CGROUP_FREEZE)) {
+            Ok(freeze) => freeze,
+            // kernels before 5.2 have no freezer in the unified hierarchy
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(FreezerState::Undefined),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("failed to read {:?}", path.join(CGROUP_FREEZE)))
+            }
+        };
+
+        match freeze.trim() {
+            "0" => Ok(FreezerState::Thawed),
+            "1" => {
+                let events = fs::read_to_string(path.join(CGROUP_EVENTS))
+                    .with_context(|| format!("failed to read {:?}", path.join(CGROUP_EVENTS)))?;
+                if events.lines().any(|line| line.trim() == "frozen 1") {
+                    Ok(FreezerState::Frozen)
+                } else {
+                    Ok(FreezerState::Undefined)
+                }
+            }
+            state => bail!("unknown \"cgroup.freeze\" state: {}", state),
+        }
+    }
+
     fn read_freezer_state(path: &Path) -> Result<FreezerState> {
         let mut buf = [0; 1];
         OpenOptions::new()
@@ -175,6 +203,24 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_freezer_state() -> Result<()> {
+        let tmp = create_temp_dir("test_freezer_state")?;
+        assert_eq!(Freezer::state(&tmp)?, FreezerState::Undefined);
+
+        set_fixture(&tmp, CGROUP_FREEZE, "0\n")?;
+        set_fixture(&tmp, CGROUP_EVENTS, "populated 1\nfrozen 0\n")?;
+        assert_eq!(Freezer::state(&tmp)?, FreezerState::Thawed);
+
+        // the freeze has been requested, but is not complete yet
+        set_fixture(&tmp, CGROUP_FREEZE, "1\n")?;
+        assert_eq!(Freezer::state(&tmp)?, FreezerState::Undefined);
+
+        set_fixture(&tmp, CGROUP_EVENTS, "populated 1\nfrozen 1\n")?;
+        assert_eq!(Freezer::state(&tmp)?, FreezerState::Frozen);
+        Ok(())
+    }
+
     #[test]
     fn test_set_freezer_state_error() {
         let tmp = create_temp_dir("test_set_freezer_state_error")
@@ -4,7 +4,10 @@ use std::{collections::HashMap, path::Path};
 use super::controller::Controller;
 use crate::{
     common::{self, ControllerOpt},
-    stats::{parse_single_value, supported_page_sizes, HugeTlbStats, StatsProvider},
+    stats::{
+        ensure_page_size_supported, parse_single_value, supported_page_sizes, HugeTlbStats,
+        StatsProvider,
+    },
 };
 
 use oci_spec::runtime::LinuxHugepageLimit;
@@ -14,8 +17,16 @@ pub struct HugeTlb {}
 impl Controller for HugeTlb {
     fn apply(controller_opt: &ControllerOpt, cgroup_root: &std::path::Path) -> Result<()> {
         log::debug!("Apply hugetlb cgroup v2 config");
-        if let Some(hugepage_limits) = controller_opt.resources.hugepage_limits() {
+        if let Some(hugepage_limits) = controller_opt
+            .resources
+            .hugepage_limits()
+            .as_ref()
+            .filter(|limits| !limits.is_empty())
+        {
+            let supported =
+                supported_page_sizes().context("failed to determine supported hugepage sizes")?;
             for hugetlb in hugepage_limits {
+                ensure_page_size_supported(hugetlb.page_size(), &supported)?;
                 Self::apply(cgroup_root, hugetlb)
                     .context("failed to apply hugetlb resource restrictions")?
             }
@@ -68,9 +68,22 @@ impl StatsProvider for Io {
             }
         }
 
+        // the weights are written to the bfq file, if the scheduler is used
+        let mut weight_file = cgroup_path.join(CGROUP_BFQ_IO_WEIGHT);
+        if !weight_file.exists() {
+            weight_file = cgroup_path.join(CGROUP_IO_WEIGHT);
+        }
+        let (weight, weight_device) = if weight_file.exists() {
+            stats::parse_weights(&common::read_cgroup_file(&weight_file)?)?
+        } else {
+            (None, Vec::new())
+        };
+
         let stats = BlkioStats {
             service_bytes,
             serviced,
+            weight,
+            weight_device,
             ..Default::default()
         };
 
@@ -87,7 +100,8 @@ impl Io {
         if v == 0 {
             return 0;
         }
-        1 + (v - 10) * 9999 / 990
+        // computed in u32, as the product does not fit into u16
+        (1 + (u32::from(v) - 10) * 9999 / 990) as u16
     }
 
     fn io_max_path(path: &Path) -> PathBuf {
@@ -96,12 +110,32 @@ impl Io {
 
     // linux kernel doc: https://www.kernel.org/doc/html/latest/admin-guide/cgroup-v2.html#io
     fn apply(root_path: &Path, blkio: &LinuxBlockIo) -> Result<()> {
+        common::validate_blkio_weights(blkio)?;
+
         if let Some(weight_device) = blkio.weight_device() {
+            // bfq uses the range of cgroup v1, io.weight needs to be converted
+            let bfq_file = root_path.join(CGROUP_BFQ_IO_WEIGHT);
             for wd in weight_device {
-                common::write_cgroup_file(
-                    root_path.join(CGROUP_BFQ_IO_WEIGHT),
-                    &format!("{}:{} {}", wd.major(), wd.minor(), wd.weight().unwrap()),
-                )?;
+                let weight = match wd.weight() {
+                    Some(weight) if weight > 0 => weight,
+                    _ => continue,
+                };
+                if bfq_file.exists() {
+                    common::write_cgroup_file(
+                        &bfq_file,
+                        &format!("{}:{} {}", wd.major(), wd.minor(), weight),
+                    )?;
+                } else {
+                    common::write_cgroup_file(
+                        root_path.join(CGROUP_IO_WEIGHT),
+                        &format!(
+                            "{}:{} {}",
+                            wd.major(),
+                            wd.minor(),
+                            Self::convert_cfq_io_weight_to_bfq(weight)
+                        ),
+                    )?;
+                }
             }
         }
         if let Some(leaf_weight) = blkio.leaf_weight() {
@@ -275,6 +309,44 @@ mod test {
         assert_eq!("8:0 80", content);
     }
 
+    #[test]
+    fn test_set_ioweight_device_converted() {
+        for (weight, converted) in [(10u16, 1), (500, 4950), (1000, 10000)] {
+            let (tmp, weight_file) = setup("test_set_ioweight_device_converted", CGROUP_IO_WEIGHT);
+            let blkio = LinuxBlockIoBuilder::default()
+                .weight_device(vec![LinuxWeightDeviceBuilder::default()
+                    .major(8)
+                    .minor(0)
+                    .weight(weight)
+                    .build()
+                    .unwrap()])
+                .build()
+                .unwrap();
+
+            Io::apply(&tmp, &blkio).expect("apply blkio");
+            let content = fs::read_to_string(weight_file).expect("read io weight");
+            assert_eq!(format!("8:0 {}", converted), content);
+        }
+    }
+
+    #[test]
+    fn test_set_ioweight_out_of_range() {
+        let (tmp, weight_file) = setup("test_set_ioweight_out_of_range", CGROUP_IO_WEIGHT);
+        let blkio = LinuxBlockIoBuilder::default()
+            .weight_device(vec![LinuxWeightDeviceBuilder::default()
+                .major(8)
+                .minor(0)
+                .weight(5u16)
+                .build()
+                .unwrap()])
+            .build()
+            .unwrap();
+
+        let err = Io::apply(&tmp, &blkio).unwrap_err().to_string();
+        assert!(err.contains("device 8:0"), "{}", err);
+        assert!(fs::read_to_string(weight_file).unwrap().is_empty());
+    }
+
     #[test]
     fn test_set_ioweight() {
         struct TestCase {
@@ -378,4 +450,23 @@ mod test {
 
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn test_stat_io_weight() {
+        let tmp = create_temp_dir("test_stat_io_weight").expect("create test directory");
+        set_fixture(&tmp, "io.stat", "").unwrap();
+        set_fixture(&tmp, CGROUP_IO_WEIGHT, "default 100\n8:0 5000\n").unwrap();
+
+        let stats = Io::stats(&tmp).expect("get cgroup stats");
+        assert_eq!(stats.weight, Some(100));
+        assert_eq!(
+            stats.weight_device,
+            vec![BlkioDeviceStat {
+                major: 8,
+                minor: 0,
+                op_type: None,
+                value: 5000,
+            }]
+        );
+    }
 }
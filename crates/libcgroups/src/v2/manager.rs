@@ -1,16 +1,16 @@
 use std::{
     fs::{self},
-    os::unix::fs::PermissionsExt,
+    os::unix::{fs::PermissionsExt, io::RawFd},
     path::{Component::RootDir, Path, PathBuf},
     time::Duration,
 };
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 
 use nix::unistd::Pid;
 
 #[cfg(feature = "cgroupsv2_devices")]
-use super::devices::Devices;
+use super::devices::{self, Devices};
 use super::{
     controller::Controller,
     controller_type::{
@@ -23,12 +23,13 @@ use super::{
     io::Io,
     memory::Memory,
     pids::Pids,
+    rdma::Rdma,
     unified::Unified,
     util::{self, CGROUP_SUBTREE_CONTROL},
 };
 use crate::{
     common::{self, CgroupManager, ControllerOpt, FreezerState, PathBufExt, CGROUP_PROCS},
-    stats::{Stats, StatsProvider},
+    stats::{self, Stats, StatsProvider},
 };
 
 pub const CGROUP_KILL: &str = "cgroup.kill";
@@ -37,6 +38,10 @@ pub struct Manager {
     root_path: PathBuf,
     cgroup_path: PathBuf,
     full_path: PathBuf,
+    /// Cgroup, which has been delegated to an unprivileged user, below which
+    /// the container cgroup is created. Only the controllers enabled by the
+    /// delegation are used.
+    delegation_boundary: Option<PathBuf>,
 }
 
 impl Manager {
@@ -49,20 +54,53 @@ impl Manager {
             root_path,
             cgroup_path,
             full_path,
+            delegation_boundary: None,
         })
     }
 
+    /// Constructs a cgroup manager for an unprivileged user, which can only
+    /// manage the cgroup subtree below the delegation boundary, see
+    /// [`common::delegation_boundary`].
+    pub fn new_delegated(
+        root_path: PathBuf,
+        cgroup_path: PathBuf,
+        delegation_boundary: PathBuf,
+    ) -> Result<Self> {
+        if !cgroup_path.starts_with(&delegation_boundary) {
+            bail!(
+                "cgroup path {:?} is not below the delegated cgroup {:?}",
+                cgroup_path,
+                delegation_boundary
+            );
+        }
+
+        let mut manager = Self::new(root_path, cgroup_path)?;
+        manager.delegation_boundary = Some(delegation_boundary);
+        Ok(manager)
+    }
+
     fn create_unified_cgroup(&self, pid: Pid) -> Result<()> {
-        let controllers: Vec<String> = util::get_available_controllers(&self.root_path)?
+        let (mut current_path, relative_path) = match &self.delegation_boundary {
+            Some(boundary) => (
+                self.root_path.join_safely(boundary)?,
+                self.cgroup_path.strip_prefix(boundary)?,
+            ),
+            None => (self.root_path.clone(), self.cgroup_path.as_path()),
+        };
+
+        let controllers: Vec<String> = util::get_available_controllers(&current_path)?
             .iter()
             .map(|c| format!("{}{}", "+", c))
             .collect();
 
-        Self::write_controllers(&self.root_path, &controllers)?;
+        let controllers = if self.delegation_boundary.is_some() {
+            Self::write_delegated_controllers(&current_path, &controllers)
+        } else {
+            Self::write_controllers(&current_path, &controllers)?;
+            controllers
+        };
 
-        let mut current_path = self.root_path.clone();
-        let mut components = self
-            .cgroup_path
+        let mut components = relative_path
             .components()
             .filter(|c| c.ne(&RootDir))
             .peekable();
@@ -84,6 +122,61 @@ impl Manager {
         Ok(())
     }
 
+    /// Enables the controllers at the delegation boundary as far as possible
+    /// and returns the ones which have been enabled.
+    fn write_delegated_controllers(path: &Path, controllers: &[String]) -> Vec<String> {
+        controllers
+            .iter()
+            .filter(|controller| {
+                match common::write_cgroup_file_str(path.join(CGROUP_SUBTREE_CONTROL), controller) {
+                    Ok(_) => true,
+                    Err(e) => {
+                        log::warn!("controller {} can not be delegated: {:?}", controller, e);
+                        false
+                    }
+                }
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the controllers the resource restrictions are applied for.
+    /// Without delegation these are all of them, otherwise only the ones which
+    /// are enabled for the container cgroup, the others are skipped.
+    fn usable_controllers(&self) -> Result<Vec<ControllerType>> {
+        if self.delegation_boundary.is_none() {
+            return Ok(CONTROLLER_TYPES.to_vec());
+        }
+
+        let delegated = util::get_available_controllers(&self.full_path)?;
+        Ok(CONTROLLER_TYPES
+            .iter()
+            .filter(|controller| delegated.contains(controller))
+            .cloned()
+            .collect())
+    }
+
+    /// Applies the resource restrictions of the given controllers only
+    pub(crate) fn apply_controllers(
+        &self,
+        controller_opt: &ControllerOpt,
+        controllers: &[ControllerType],
+    ) -> Result<()> {
+        for controller in controllers {
+            match controller {
+                ControllerType::Cpu => Cpu::apply(controller_opt, &self.full_path)?,
+                ControllerType::CpuSet => CpuSet::apply(controller_opt, &self.full_path)?,
+                ControllerType::HugeTlb => HugeTlb::apply(controller_opt, &self.full_path)?,
+                ControllerType::Io => Io::apply(controller_opt, &self.full_path)?,
+                ControllerType::Memory => Memory::apply(controller_opt, &self.full_path)?,
+                ControllerType::Pids => Pids::apply(controller_opt, &self.full_path)?,
+                ControllerType::Rdma => Rdma::apply(controller_opt, &self.full_path)?,
+            }
+        }
+
+        Ok(())
+    }
+
     fn write_controllers(path: &Path, controllers: &[String]) -> Result<()> {
         for controller in controllers {
             common::write_cgroup_file_str(path.join(CGROUP_SUBTREE_CONTROL), controller)?;
@@ -100,16 +193,17 @@ impl CgroupManager for Manager {
     }
 
     fn apply(&self, controller_opt: &ControllerOpt) -> Result<()> {
+        let controllers = self.usable_controllers()?;
         for controller in CONTROLLER_TYPES {
-            match controller {
-                ControllerType::Cpu => Cpu::apply(controller_opt, &self.full_path)?,
-                ControllerType::CpuSet => CpuSet::apply(controller_opt, &self.full_path)?,
-                ControllerType::HugeTlb => HugeTlb::apply(controller_opt, &self.full_path)?,
-                ControllerType::Io => Io::apply(controller_opt, &self.full_path)?,
-                ControllerType::Memory => Memory::apply(controller_opt, &self.full_path)?,
-                ControllerType::Pids => Pids::apply(controller_opt, &self.full_path)?,
+            if !controllers.contains(controller) {
+                log::warn!(
+                    "controller {} is not delegated to {:?}, skipping",
+                    controller,
+                    self.full_path
+                );
             }
         }
+        self.apply_controllers(controller_opt, &controllers)?;
 
         #[cfg(feature = "cgroupsv2_devices")]
         Devices::apply(controller_opt, &self.cgroup_path)?;
@@ -119,7 +213,7 @@ impl CgroupManager for Manager {
                 Unified::apply(
                     controller_opt,
                     &self.full_path,
-                    util::get_available_controllers(&self.root_path)?,
+                    util::get_available_controllers(&self.full_path)?,
                 )?;
             }
         }
@@ -127,23 +221,31 @@ impl CgroupManager for Manager {
         Ok(())
     }
 
-    fn remove(&self) -> Result<()> {
+    fn remove(&self, force: bool) -> Result<()> {
         if self.full_path.exists() {
             log::debug!("remove cgroup {:?}", self.full_path);
-            let kill_file = self.full_path.join(CGROUP_KILL);
-            if kill_file.exists() {
-                fs::write(kill_file, "1").context("failed to kill cgroup")?;
-            } else {
-                let procs_path = self.full_path.join(CGROUP_PROCS);
-                let procs = fs::read_to_string(&procs_path)?;
-
-                for line in procs.lines() {
-                    let pid: i32 = line.parse()?;
-                    let _ = nix::sys::signal::kill(Pid::from_raw(pid), nix::sys::signal::SIGKILL);
+            if force {
+                let kill_file = self.full_path.join(CGROUP_KILL);
+                if kill_file.exists() {
+                    fs::write(kill_file, "1").context("failed to kill cgroup")?;
+                } else {
+                    let procs_path = self.full_path.join(CGROUP_PROCS);
+                    let procs = fs::read_to_string(&procs_path)?;
+
+                    for line in procs.lines() {
+                        let pid: i32 = line.parse()?;
+                        let _ =
+                            nix::sys::signal::kill(Pid::from_raw(pid), nix::sys::signal::SIGKILL);
+                    }
                 }
             }
 
-            common::delete_with_retry(&self.full_path, 4, Duration::from_millis(100))?;
+            // the cgroup is removed, even if the programs can not be detached
+            #[cfg(feature = "cgroupsv2_devices")]
+            if let Err(e) = devices::audit::detach_audit_programs(&self.full_path) {
+                log::warn!("failed to detach audit programs: {:?}", e);
+            }
+            common::delete_with_retry(&self.full_path, 4, Duration::from_millis(100), force)?;
         }
 
         Ok(())
@@ -159,19 +261,25 @@ impl CgroupManager for Manager {
         Freezer::apply(&controller_opt, &self.full_path)
     }
 
+    fn freezer_state(&self) -> Result<FreezerState> {
+        Freezer::state(&self.full_path)
+    }
+
     fn stats(&self) -> Result<Stats> {
         let mut stats = Stats::default();
 
-        for subsystem in CONTROLLER_TYPES {
+        for subsystem in &self.usable_controllers()? {
             match subsystem {
-                ControllerType::Cpu => stats.cpu.usage = Cpu::stats(&self.full_path)?,
+                ControllerType::Cpu => stats.cpu = Cpu::stats(&self.full_path)?,
                 ControllerType::HugeTlb => stats.hugetlb = HugeTlb::stats(&self.full_path)?,
                 ControllerType::Pids => stats.pids = Pids::stats(&self.full_path)?,
                 ControllerType::Memory => stats.memory = Memory::stats(&self.full_path)?,
                 ControllerType::Io => stats.blkio = Io::stats(&self.full_path)?,
+                ControllerType::Rdma => stats.rdma = Rdma::stats(&self.full_path)?,
                 _ => continue,
             }
         }
+        stats.psi = stats::psi_stats(&self.full_path)?;
 
         Ok(stats)
     }
@@ -179,4 +287,120 @@ impl CgroupManager for Manager {
     fn get_all_pids(&self) -> Result<Vec<Pid>> {
         common::get_all_pids(&self.full_path)
     }
+
+    fn attach_bpf_audit(&self, prog_fd: RawFd) -> Result<()> {
+        #[cfg(feature = "cgroupsv2_devices")]
+        return devices::audit::attach_audit_program(prog_fd, &self.full_path);
+
+        #[cfg(not(feature = "cgroupsv2_devices"))]
+        bail!(
+            "cannot attach eBPF audit program {}, eBPF support (feature cgroupsv2_devices) is not enabled",
+            prog_fd
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::{create_temp_dir, set_fixture};
+    use oci_spec::runtime::{LinuxMemoryBuilder, LinuxPidsBuilder, LinuxResourcesBuilder};
+
+    fn setup_delegated(
+        test_name: &str,
+        controllers: &str,
+    ) -> Result<(crate::test::TempDir, Manager)> {
+        let tmp = create_temp_dir(test_name)?;
+        let container_path = tmp.join("user.slice/container");
+        fs::create_dir_all(&container_path)?;
+        set_fixture(&tmp.join("user.slice"), "cgroup.controllers", "pids memory")?;
+        set_fixture(&container_path, "cgroup.controllers", controllers)?;
+        set_fixture(&container_path, "pids.max", "")?;
+
+        let manager = Manager::new_delegated(
+            tmp.to_path_buf(),
+            PathBuf::from("/user.slice/container"),
+            PathBuf::from("/user.slice"),
+        )?;
+        Ok((tmp, manager))
+    }
+
+    #[test]
+    fn test_apply_delegated_controllers_only() -> Result<()> {
+        let (tmp, manager) = setup_delegated("test_apply_delegated_controllers_only", "pids")?;
+        let resources = LinuxResourcesBuilder::default()
+            .pids(LinuxPidsBuilder::default().limit(100).build()?)
+            .memory(LinuxMemoryBuilder::default().limit(1024 * 1024).build()?)
+            .build()?;
+
+        manager.apply(&ControllerOpt {
+            resources: &resources,
+            disable_oom_killer: false,
+            oom_score_adj: None,
+            freezer_state: None,
+        })?;
+
+        let pids_max = fs::read_to_string(tmp.join("user.slice/container/pids.max"))?;
+        assert_eq!(pids_max, "100");
+        assert!(!tmp.join("user.slice/container/memory.max").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_nothing_delegated() -> Result<()> {
+        let (tmp, manager) = setup_delegated("test_apply_nothing_delegated", "")?;
+        let resources = LinuxResourcesBuilder::default()
+            .pids(LinuxPidsBuilder::default().limit(100).build()?)
+            .build()?;
+
+        assert!(manager.usable_controllers()?.is_empty());
+        manager.apply(&ControllerOpt {
+            resources: &resources,
+            disable_oom_killer: false,
+            oom_score_adj: None,
+            freezer_state: None,
+        })?;
+
+        let pids_max = fs::read_to_string(tmp.join("user.slice/container/pids.max"))?;
+        assert!(pids_max.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_task_delegated() -> Result<()> {
+        let tmp = create_temp_dir("test_add_task_delegated")?;
+        let boundary = tmp.join("user.slice");
+        let container_path = boundary.join("youki/container");
+        fs::create_dir_all(&container_path)?;
+        set_fixture(&boundary, "cgroup.controllers", "pids")?;
+        set_fixture(&boundary, CGROUP_SUBTREE_CONTROL, "")?;
+        set_fixture(&boundary.join("youki"), CGROUP_SUBTREE_CONTROL, "")?;
+        set_fixture(&container_path, CGROUP_PROCS, "")?;
+
+        let manager = Manager::new_delegated(
+            tmp.to_path_buf(),
+            PathBuf::from("/user.slice/youki/container"),
+            PathBuf::from("/user.slice"),
+        )?;
+        manager.add_task(Pid::from_raw(1234))?;
+
+        let subtree_control = |path: &Path| fs::read_to_string(path.join(CGROUP_SUBTREE_CONTROL));
+        assert_eq!(subtree_control(&boundary)?, "+pids");
+        assert_eq!(subtree_control(&boundary.join("youki"))?, "+pids");
+        assert_eq!(
+            fs::read_to_string(container_path.join(CGROUP_PROCS))?,
+            "1234"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_new_delegated_outside_boundary() {
+        let result = Manager::new_delegated(
+            PathBuf::from("/sys/fs/cgroup"),
+            PathBuf::from("/system.slice/container"),
+            PathBuf::from("/user.slice"),
+        );
+        assert!(result.is_err());
+    }
 }
@@ -1,5 +1,10 @@
-use anyhow::{bail, Context, Result};
-use std::path::Path;
+use anyhow::{anyhow, bail, Context, Result};
+use nix::{
+    errno::Errno,
+    sys::inotify::{AddWatchFlags, InitFlags, Inotify},
+    unistd,
+};
+use std::{os::unix::io::AsRawFd, path::Path};
 
 use oci_spec::runtime::LinuxMemory;
 
@@ -14,6 +19,7 @@ const CGROUP_MEMORY_SWAP: &str = "memory.swap.max";
 const CGROUP_MEMORY_MAX: &str = "memory.max";
 const CGROUP_MEMORY_LOW: &str = "memory.low";
 const MEMORY_STAT: &str = "memory.stat";
+const MEMORY_EVENTS: &str = "memory.events";
 
 pub struct Memory {}
 
@@ -37,6 +43,7 @@ impl StatsProvider for Memory {
             memswap: Self::get_memory_data(cgroup_path, "memory.swap", "fail")?,
             hierarchy: true,
             stats: stats::parse_flat_keyed_data(&cgroup_path.join(MEMORY_STAT))?,
+            oom_kill: Self::get_oom_kill_count(cgroup_path)?,
             ..Default::default()
         };
 
@@ -45,6 +52,43 @@ impl StatsProvider for Memory {
 }
 
 impl Memory {
+    fn get_oom_kill_count(cgroup_path: &Path) -> Result<u64> {
+        let events = stats::parse_flat_keyed_data(&cgroup_path.join(MEMORY_EVENTS))?;
+        Ok(events.get("oom_kill").copied().unwrap_or_default())
+    }
+
+    /// Calls the handler whenever the OOM killer has been invoked in the
+    /// cgroup, until the cgroup is removed. The kernel notifies about changes
+    /// of memory.events, after which the oom_kill counter is compared.
+    pub fn watch_oom_events<F: FnMut()>(cgroup_path: &Path, mut on_oom: F) -> Result<()> {
+        let inotify = Inotify::init(InitFlags::IN_CLOEXEC)
+            .context("failed to create inotify instance for OOM events")?;
+        let result = (|| -> Result<()> {
+            inotify
+                .add_watch(&cgroup_path.join(MEMORY_EVENTS), AddWatchFlags::IN_MODIFY)
+                .context("failed to watch for OOM events")?;
+            let mut oom_kill = Self::get_oom_kill_count(cgroup_path)?;
+            loop {
+                let events = inotify.read_events().context("failed to read OOM events")?;
+                // the watch is removed along with the cgroup
+                if events
+                    .iter()
+                    .any(|event| event.mask.contains(AddWatchFlags::IN_IGNORED))
+                {
+                    return Ok(());
+                }
+
+                let count = Self::get_oom_kill_count(cgroup_path)?;
+                for _ in oom_kill..count {
+                    on_oom();
+                }
+                oom_kill = count;
+            }
+        })();
+        let _ = unistd::close(inotify.as_raw_fd());
+        result
+    }
+
     fn get_memory_data(
         cgroup_path: &Path,
         file_prefix: &str,
@@ -82,6 +126,33 @@ impl Memory {
         }
     }
 
+    fn set_max(path: &Path, limit: i64) -> Result<()> {
+        Memory::set(path.join(CGROUP_MEMORY_MAX), limit)
+            .map_err(|e| Memory::set_max_error(path, limit, e))
+    }
+
+    // The kernel refuses to lower the limit with EBUSY, if it can not reclaim
+    // enough memory to get below the new limit. The usage is reported then,
+    // other errors are returned as they are.
+    fn set_max_error(path: &Path, limit: i64, err: anyhow::Error) -> anyhow::Error {
+        let busy = err
+            .downcast_ref::<std::io::Error>()
+            .and_then(|e| e.raw_os_error())
+            == Some(Errno::EBUSY as i32);
+        if busy {
+            if let Ok(usage) = stats::parse_single_value(&path.join("memory.current")) {
+                return anyhow!(
+                    "unable to set memory limit to {} (current usage: {}): {}",
+                    limit,
+                    usage,
+                    err
+                );
+            }
+        }
+
+        err
+    }
+
     fn apply(path: &Path, memory: &LinuxMemory) -> Result<()> {
         // if nothing is set just exit right away
         if memory.reservation().is_none() && memory.limit().is_none() && memory.swap().is_none() {
@@ -115,13 +186,13 @@ impl Memory {
                         // by subtracting limit from swap
                         Memory::set(path.join(CGROUP_MEMORY_SWAP), swap - limit)?;
                     }
-                    Memory::set(path.join(CGROUP_MEMORY_MAX), limit)?;
+                    Memory::set_max(path, limit)?;
                 }
                 None => {
                     if limit == -1 {
                         Memory::set(path.join(CGROUP_MEMORY_SWAP), -1)?;
                     }
-                    Memory::set(path.join(CGROUP_MEMORY_MAX), limit)?;
+                    Memory::set_max(path, limit)?;
                 }
             },
             None => {
@@ -149,6 +220,23 @@ mod tests {
     use oci_spec::runtime::LinuxMemoryBuilder;
     use std::fs::read_to_string;
 
+    #[test]
+    fn test_set_memory_below_usage() {
+        let tmp = create_temp_dir("test_set_memory_below_usage").expect("create temp directory");
+        set_fixture(&tmp, "memory.current", "4096\n").expect("set fixture for memory usage");
+
+        // the kernel refuses the limit with EBUSY, as it is below the usage,
+        // which is wrapped the same way as by write_cgroup_file
+        let busy = anyhow::Error::from(std::io::Error::from_raw_os_error(Errno::EBUSY as i32))
+            .context("failed to write 1024 to memory.max");
+        let err = Memory::set_max_error(&tmp, 1024, busy);
+        assert!(err.to_string().contains("current usage: 4096"));
+
+        // memory.max is missing, which is not related to the usage
+        let err = Memory::set_max(&tmp, 1024).expect_err("limit file is missing");
+        assert!(!err.to_string().contains("current usage"));
+    }
+
     #[test]
     fn test_set_memory() {
         let tmp = create_temp_dir("test_set_memory_v2").expect("create temp directory for test");
@@ -330,6 +418,16 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_get_oom_kill_count() {
+        let tmp = create_temp_dir("test_get_oom_kill_count").expect("create test directory");
+        let events = ["oom 3", "oom_kill 2"].join("\n");
+        set_fixture(&tmp, MEMORY_EVENTS, &events).unwrap();
+
+        let actual = Memory::get_oom_kill_count(&tmp).expect("get oom kill count");
+        assert_eq!(actual, 2);
+    }
+
     #[test]
     fn test_get_memory_data() {
         let tmp = create_temp_dir("test_stat_memory").expect("create test directory");
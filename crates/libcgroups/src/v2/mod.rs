@@ -4,11 +4,12 @@ mod cpu;
 mod cpuset;
 #[cfg(feature = "cgroupsv2_devices")]
 pub mod devices;
-mod freezer;
+pub(crate) mod freezer;
 mod hugetlb;
 mod io;
 pub mod manager;
-mod memory;
+pub(crate) mod memory;
 mod pids;
+mod rdma;
 mod unified;
 pub mod util;
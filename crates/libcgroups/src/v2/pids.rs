@@ -75,4 +75,32 @@ mod tests {
             std::fs::read_to_string(tmp.join(pids_file_name)).expect("Read pids contents");
         assert_eq!("max".to_string(), content);
     }
+
+    #[test]
+    fn test_set_pids_unlimited() {
+        let pids_file_name = "pids.max";
+        let tmp =
+            create_temp_dir("v2_test_set_pids_unlimited").expect("create temp directory for test");
+        set_fixture(&tmp, pids_file_name, "1000").expect("set fixture for 1000 pids");
+
+        let pids = LinuxPidsBuilder::default().limit(-1).build().unwrap();
+
+        Pids::apply(&tmp, &pids).expect("apply pids");
+
+        let content =
+            std::fs::read_to_string(tmp.join(pids_file_name)).expect("Read pids contents");
+        assert_eq!("max".to_string(), content);
+    }
+
+    #[test]
+    fn test_stat_pids_unlimited() {
+        let tmp = create_temp_dir("v2_test_stat_pids_unlimited").expect("create temp dir for test");
+        set_fixture(&tmp, "pids.current", "5\n").unwrap();
+        set_fixture(&tmp, "pids.max", "max\n").unwrap();
+
+        let stats = Pids::stats(&tmp).expect("get cgroup stats");
+
+        assert_eq!(stats.current, 5);
+        assert_eq!(stats.limit, None);
+    }
 }
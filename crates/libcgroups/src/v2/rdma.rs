@@ -0,0 +1,186 @@
+use std::{collections::HashMap, path::Path};
+
+use anyhow::{bail, Context, Result};
+
+use crate::{
+    common::{self, ControllerOpt},
+    stats::{RdmaStats, StatsProvider},
+};
+
+use super::controller::Controller;
+use oci_spec::runtime::LinuxRdma;
+
+const CGROUP_RDMA_MAX: &str = "rdma.max";
+const CGROUP_RDMA_CURRENT: &str = "rdma.current";
+const SYSFS_INFINIBAND: &str = "/sys/class/infiniband";
+
+pub struct Rdma {}
+
+impl Controller for Rdma {
+    fn apply(controller_opt: &ControllerOpt, cgroup_root: &Path) -> Result<()> {
+        log::debug!("Apply rdma cgroup v2 config");
+        if let Some(rdma) = controller_opt.resources.rdma() {
+            if !rdma.is_empty() {
+                Self::apply(cgroup_root, Path::new(SYSFS_INFINIBAND), rdma)
+                    .context("failed to apply rdma resource restrictions")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl StatsProvider for Rdma {
+    type Stats = HashMap<String, RdmaStats>;
+
+    fn stats(cgroup_path: &Path) -> Result<Self::Stats> {
+        let current = cgroup_path.join(CGROUP_RDMA_CURRENT);
+        // the file only exists if the rdma controller is enabled for the cgroup
+        if !current.exists() {
+            return Ok(HashMap::new());
+        }
+
+        parse_rdma_current(&common::read_cgroup_file(current)?)
+    }
+}
+
+impl Rdma {
+    fn apply(
+        root_path: &Path,
+        devices_path: &Path,
+        rdma: &HashMap<String, LinuxRdma>,
+    ) -> Result<()> {
+        for (device, limits) in rdma {
+            if device.is_empty() || device.contains('/') || !devices_path.join(device).exists() {
+                bail!("rdma device {:?} does not exist", device);
+            }
+
+            common::write_cgroup_file_str(
+                root_path.join(CGROUP_RDMA_MAX),
+                &limit_entry(device, limits),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Creates the rdma.max entry of a device. Resources without a limit are set
+/// to max, which removes the entry of the device if neither is limited.
+fn limit_entry(device: &str, limits: &LinuxRdma) -> String {
+    let limit = |value: Option<u32>| value.map_or_else(|| "max".to_owned(), |v| v.to_string());
+    format!(
+        "{} hca_handle={} hca_object={}",
+        device,
+        limit(limits.hca_handles()),
+        limit(limits.hca_objects())
+    )
+}
+
+/// Parses the content of rdma.current, e.g. `mlx4_0 hca_handle=2 hca_object=2000`
+fn parse_rdma_current(content: &str) -> Result<HashMap<String, RdmaStats>> {
+    let mut stats = HashMap::new();
+    for line in content.lines() {
+        let mut fields = line.split_whitespace();
+        let device = match fields.next() {
+            Some(device) => device,
+            None => continue,
+        };
+
+        let mut device_stats = RdmaStats::default();
+        for field in fields {
+            let (key, value) = field
+                .split_once('=')
+                .with_context(|| format!("invalid rdma entry {:?}", line))?;
+            let value = value.parse().with_context(|| {
+                format!("failed to parse {:?} of rdma device {}", value, device)
+            })?;
+            match key {
+                "hca_handle" => device_stats.hca_handles = value,
+                "hca_object" => device_stats.hca_objects = value,
+                _ => continue,
+            }
+        }
+
+        stats.insert(device.to_owned(), device_stats);
+    }
+
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::{create_temp_dir, set_fixture};
+    use oci_spec::runtime::LinuxRdmaBuilder;
+    use std::fs;
+
+    #[test]
+    fn test_set_rdma() {
+        let tmp = create_temp_dir("test_set_rdma").expect("create temp directory for test");
+        let devices = tmp.join("infiniband");
+        fs::create_dir_all(devices.join("mlx5_0")).expect("create rdma device");
+        set_fixture(&tmp, CGROUP_RDMA_MAX, "").expect("set fixture for rdma.max");
+
+        let limits = LinuxRdmaBuilder::default()
+            .hca_handles(10u32)
+            .hca_objects(1000u32)
+            .build()
+            .unwrap();
+        let rdma = HashMap::from([("mlx5_0".to_owned(), limits)]);
+
+        Rdma::apply(&tmp, &devices, &rdma).expect("apply rdma");
+        let content = fs::read_to_string(tmp.join(CGROUP_RDMA_MAX)).expect("read rdma.max");
+        assert_eq!(content, "mlx5_0 hca_handle=10 hca_object=1000");
+    }
+
+    #[test]
+    fn test_set_rdma_unknown_device() {
+        let tmp = create_temp_dir("test_set_rdma_unknown_device")
+            .expect("create temp directory for test");
+        set_fixture(&tmp, CGROUP_RDMA_MAX, "").expect("set fixture for rdma.max");
+
+        let rdma = HashMap::from([("mlx5_0".to_owned(), LinuxRdma::default())]);
+        assert!(Rdma::apply(&tmp, &tmp.join("infiniband"), &rdma).is_err());
+    }
+
+    #[test]
+    fn test_limit_entry() {
+        let limits = LinuxRdmaBuilder::default()
+            .hca_handles(3u32)
+            .build()
+            .unwrap();
+        assert_eq!(
+            limit_entry("mlx4_0", &limits),
+            "mlx4_0 hca_handle=3 hca_object=max"
+        );
+        assert_eq!(
+            limit_entry("mlx4_0", &LinuxRdma::default()),
+            "mlx4_0 hca_handle=max hca_object=max"
+        );
+    }
+
+    #[test]
+    fn test_stat_rdma() {
+        let tmp = create_temp_dir("test_stat_rdma").expect("create temp directory for test");
+        set_fixture(
+            &tmp,
+            CGROUP_RDMA_CURRENT,
+            "mlx4_0 hca_handle=2 hca_object=2000\nocrdma1 hca_handle=3 hca_object=0\n",
+        )
+        .expect("set fixture for rdma.current");
+
+        let stats = Rdma::stats(&tmp).expect("get cgroup stats");
+        assert_eq!(
+            stats.get("mlx4_0"),
+            Some(&RdmaStats {
+                hca_handles: 2,
+                hca_objects: 2000
+            })
+        );
+        assert_eq!(stats.get("ocrdma1").map(|s| s.hca_handles), Some(3));
+
+        let tmp =
+            create_temp_dir("test_stat_rdma_disabled").expect("create temp directory for test");
+        assert!(Rdma::stats(&tmp).expect("get cgroup stats").is_empty());
+    }
+}
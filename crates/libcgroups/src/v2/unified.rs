@@ -1,8 +1,8 @@
 use std::{collections::HashMap, path::Path};
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 
-use super::controller_type::ControllerType;
+use super::controller_type::{ControllerType, CONTROLLER_TYPES};
 use crate::common::{self, ControllerOpt};
 
 pub struct Unified {}
@@ -29,31 +29,37 @@ impl Unified {
         {
             log::debug!("Apply unified cgroup config");
             for (cgroup_file, value) in unified {
-                common::write_cgroup_file_str(cgroup_path.join(cgroup_file), value).map_err(
-                    |e| {
-                        let (subsystem, _) = cgroup_file
-                            .split_once('.')
-                            .with_context(|| {
-                                format!("failed to split {} with {}", cgroup_file, ".")
-                            })
-                            .unwrap();
-                        let context = if !controllers.iter().any(|c| c.to_string() == subsystem) {
-                            format!(
-                                "failed to set {} to {}: subsystem {} is not available",
-                                cgroup_file, value, subsystem
-                            )
-                        } else {
-                            format!("failed to set {} to {}: {}", cgroup_file, value, e)
-                        };
-
-                        e.context(context)
-                    },
-                )?;
+                Self::validate(cgroup_file, controllers)?;
+                common::write_cgroup_file_str(cgroup_path.join(cgroup_file), value)
+                    .with_context(|| format!("failed to set {} to {}", cgroup_file, value))?;
             }
         }
 
         Ok(())
     }
+
+    /// Checks that the key names a file of the cgroup directory, which
+    /// belongs to an available controller. Files of controllers youki does
+    /// not know about, e.g. misc, are passed through to the kernel.
+    fn validate(cgroup_file: &str, controllers: &[ControllerType]) -> Result<()> {
+        if cgroup_file.contains('/') || cgroup_file == ".." {
+            bail!("invalid unified cgroup file {}", cgroup_file);
+        }
+
+        let (subsystem, _) = cgroup_file
+            .split_once('.')
+            .with_context(|| format!("invalid unified cgroup file {}", cgroup_file))?;
+        let known = CONTROLLER_TYPES.iter().any(|c| c.to_string() == subsystem);
+        if known && !controllers.iter().any(|c| c.to_string() == subsystem) {
+            bail!(
+                "failed to set {}: subsystem {} is not available",
+                cgroup_file,
+                subsystem
+            );
+        }
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -98,7 +104,12 @@ mod tests {
         };
 
         // act
-        Unified::apply(&controller_opt, &tmp, vec![]).expect("apply unified");
+        Unified::apply(
+            &controller_opt,
+            &tmp,
+            vec![ControllerType::HugeTlb, ControllerType::Cpu],
+        )
+        .expect("apply unified");
 
         // assert
         let hugetlb_limit = fs::read_to_string(hugetlb_limit_path).expect("read hugetlb limit");
@@ -179,4 +190,42 @@ mod tests {
         // assert
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_set_unified_unknown_subsystem() -> Result<()> {
+        let tmp = create_temp_dir("test_set_unified_unknown_subsystem")?;
+        let misc_max_path = set_fixture(&tmp, "misc.max", "")?;
+        let unified = HashMap::from([("misc.max".to_owned(), "res_a 1".to_owned())]);
+        let resources = LinuxResourcesBuilder::default().unified(unified).build()?;
+        let controller_opt = ControllerOpt {
+            resources: &resources,
+            oom_score_adj: None,
+            disable_oom_killer: false,
+            freezer_state: None,
+        };
+
+        Unified::apply(&controller_opt, &tmp, vec![])?;
+        assert_eq!(fs::read_to_string(misc_max_path)?, "res_a 1");
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_unified_invalid_file() -> Result<()> {
+        let tmp = create_temp_dir("test_set_unified_invalid_file")?;
+        for cgroup_file in ["../cpu.weight", "..", "weight"] {
+            let unified = HashMap::from([(cgroup_file.to_owned(), "5000".to_owned())]);
+            let resources = LinuxResourcesBuilder::default().unified(unified).build()?;
+            let controller_opt = ControllerOpt {
+                resources: &resources,
+                oom_score_adj: None,
+                disable_oom_killer: false,
+                freezer_state: None,
+            };
+
+            let err = Unified::apply(&controller_opt, &tmp, vec![ControllerType::Cpu])
+                .expect_err(cgroup_file);
+            assert!(format!("{:?}", err).contains(cgroup_file));
+        }
+        Ok(())
+    }
 }
@@ -38,6 +38,7 @@ pub fn get_available_controllers<P: AsRef<Path>>(root_path: P) -> Result<Vec<Con
             "io" => controllers.push(ControllerType::Io),
             "memory" => controllers.push(ControllerType::Memory),
             "pids" => controllers.push(ControllerType::Pids),
+            "rdma" => controllers.push(ControllerType::Rdma),
             tpe => log::warn!("Controller {} is not yet implemented.", tpe),
         }
     }
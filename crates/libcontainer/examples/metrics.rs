@@ -0,0 +1,55 @@
+//! Serves the metrics of a container to be scraped by Prometheus
+//!
+//! ```sh
+//! cargo run --example metrics -- /run/youki/<container id> 127.0.0.1:9100
+//! curl http://127.0.0.1:9100/metrics
+//! ```
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use libcontainer::container::Container;
+
+fn main() -> Result<()> {
+    let mut args = std::env::args().skip(1);
+    let container_root = PathBuf::from(args.next().context("missing container directory")?);
+    let address = args.next().unwrap_or_else(|| "127.0.0.1:9100".to_owned());
+
+    let listener =
+        TcpListener::bind(&address).with_context(|| format!("failed to bind to {}", address))?;
+    for stream in listener.incoming() {
+        if let Err(e) = serve(stream?, &container_root) {
+            eprintln!("failed to serve metrics: {:?}", e);
+        }
+    }
+
+    Ok(())
+}
+
+fn serve(mut stream: TcpStream, container_root: &Path) -> Result<()> {
+    // only the request line matters, any path is answered with the metrics
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+
+    let response = match Container::load(container_root.to_path_buf())
+        .and_then(|mut container| container.metrics_prometheus())
+    {
+        Ok(metrics) => format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\n\r\n{}",
+            metrics.len(),
+            metrics
+        ),
+        Err(e) => {
+            let body = format!("{:?}\n", e);
+            format!(
+                "HTTP/1.1 500 Internal Server Error\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            )
+        }
+    };
+    stream.write_all(response.as_bytes())?;
+
+    Ok(())
+}
@@ -1,12 +1,15 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use std::{
     fs::{self},
+    io::ErrorKind,
     path::Path,
 };
 
 use crate::utils;
 
 const ENABLED_PARAMETER_PATH: &str = "/sys/module/apparmor/parameters/enabled";
+const EXEC_ATTR_PATH: &str = "/proc/self/attr/apparmor/exec";
+const LEGACY_EXEC_ATTR_PATH: &str = "/proc/self/attr/exec";
 
 /// Checks if AppArmor has been enabled on the system.
 pub fn is_enabled() -> Result<bool> {
@@ -15,23 +18,119 @@ pub fn is_enabled() -> Result<bool> {
     Ok(aa_enabled.starts_with('Y'))
 }
 
-/// Applies an AppArmor profile to the container.
+/// Applies an AppArmor profile to the container. The profile only takes
+/// effect on the next execve, so this has to be called before the container
+/// payload is executed.
 pub fn apply_profile(profile: &str) -> Result<()> {
     if profile.is_empty() {
         return Ok(());
     }
 
-    // Try the module specific subdirectory. This is the recommended way to configure
-    // LSMs since Linux 5.1. AppArmor has such a directory since Linux 5.8.
-    if activate_profile(Path::new("/proc/self/attr/apparmor/exec"), profile).is_ok() {
-        return Ok(());
+    match exec_attr_path() {
+        Some(path) => activate_profile(path, profile),
+        None => bail!(
+            "cannot apply apparmor profile {}: apparmor is not enabled on this host",
+            profile
+        ),
+    }
+}
+
+// Try the module specific subdirectory. This is the recommended way to configure
+// LSMs since Linux 5.1. AppArmor has such a directory since Linux 5.8. Only if
+// it does not exist we fall back to the legacy interface. It is shared by all
+// LSMs, e.g. SELinux, so it only belongs to AppArmor if it is enabled.
+fn exec_attr_path() -> Option<&'static Path> {
+    let path = Path::new(EXEC_ATTR_PATH);
+    if path.exists() {
+        return Some(path);
+    }
+
+    let legacy_path = Path::new(LEGACY_EXEC_ATTR_PATH);
+    if is_enabled().unwrap_or(false) && legacy_path.exists() {
+        return Some(legacy_path);
     }
 
-    // try the legacy interface
-    activate_profile(Path::new("/proc/self/attr/exec"), profile)
+    None
 }
 
 fn activate_profile(path: &Path, profile: &str) -> Result<()> {
     utils::ensure_procfs(path)?;
-    utils::write_file(path, format!("exec {}", profile))
+    if let Err(err) = fs::write(path, format!("exec {}", profile)) {
+        // The kernel rejects the transition with EINVAL if the profile has
+        // not been loaded.
+        if err.raw_os_error() == Some(libc::EINVAL) {
+            bail!("apparmor profile {} is not loaded", profile);
+        }
+
+        if err.kind() == ErrorKind::NotFound {
+            bail!(
+                "cannot apply apparmor profile {}: apparmor is not enabled on this host",
+                profile
+            );
+        }
+
+        return Err(err).with_context(|| format!("failed to write to {:?}", path));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_utils;
+    use serial_test::serial;
+
+    #[test]
+    fn test_apply_empty_profile() -> Result<()> {
+        apply_profile("")
+    }
+
+    #[test]
+    #[serial]
+    fn test_apply_loaded_profile() -> Result<()> {
+        if !is_enabled().unwrap_or(false) {
+            return Ok(());
+        }
+
+        // The unconfined profile is always available when apparmor is enabled.
+        test_utils::test_in_child_process(|| {
+            apply_profile("unconfined")?;
+            let exec_attr = exec_attr_path().context("no exec attr found")?;
+            let pending = fs::read_to_string(exec_attr)?;
+            if !pending.contains("unconfined") {
+                bail!("expected unconfined transition, but got {}", pending);
+            }
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_apply_profile_disabled() -> Result<()> {
+        if is_enabled().unwrap_or(false) || Path::new(EXEC_ATTR_PATH).exists() {
+            return Ok(());
+        }
+
+        // the legacy interface of another LSM must not be written to
+        match apply_profile("youki-test-profile") {
+            Ok(_) => bail!("applying a profile without apparmor should fail"),
+            Err(err) if err.to_string().contains("not enabled") => Ok(()),
+            Err(err) => bail!("unexpected error: {:?}", err),
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn test_apply_missing_profile() -> Result<()> {
+        if !is_enabled().unwrap_or(false) {
+            return Ok(());
+        }
+
+        test_utils::test_in_child_process(|| match apply_profile("youki-test-missing-profile") {
+            Ok(_) => bail!("applying a profile that is not loaded should fail"),
+            Err(err) if err.to_string().contains("is not loaded") => Ok(()),
+            Err(err) => bail!("unexpected error: {:?}", err),
+        })
+    }
 }
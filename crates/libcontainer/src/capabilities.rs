@@ -121,6 +121,17 @@ impl CapabilityExt for SpecCapability {
     }
 }
 
+/// Returns the names of all capabilities supported by the running kernel,
+/// e.g. CAP_CHOWN
+pub fn supported_capabilities() -> Vec<String> {
+    let mut supported: Vec<String> = caps::runtime::thread_all_supported()
+        .iter()
+        .map(|cap| cap.to_string())
+        .collect();
+    supported.sort();
+    supported
+}
+
 /// reset capabilities of process calling this to effective capabilities
 /// effective capability set is set of capabilities used by kernel to perform checks
 /// see https://man7.org/linux/man-pages/man7/capabilities.7.html for more information
@@ -130,13 +141,21 @@ pub fn reset_effective<S: Syscall + ?Sized>(syscall: &S) -> Result<()> {
     Ok(())
 }
 
-/// Drop any extra granted capabilities, and reset to defaults which are in oci specification
-pub fn drop_privileges<S: Syscall + ?Sized>(cs: &LinuxCapabilities, syscall: &S) -> Result<()> {
+/// Drop the capabilities from the bounding set, which are not in the oci
+/// specification. Dropping requires CAP_SETPCAP, so this has to happen before
+/// the user is switched, but after all privileged mount operations are done.
+pub fn drop_bounding<S: Syscall + ?Sized>(cs: &LinuxCapabilities, syscall: &S) -> Result<()> {
     log::debug!("dropping bounding capabilities to {:?}", cs.bounding());
     if let Some(bounding) = cs.bounding() {
         syscall.set_capability(CapSet::Bounding, &to_set(bounding))?;
     }
 
+    Ok(())
+}
+
+/// Drop any extra granted capabilities, and reset to defaults which are in oci specification.
+/// The bounding set has to be dropped before with [drop_bounding].
+pub fn drop_privileges<S: Syscall + ?Sized>(cs: &LinuxCapabilities, syscall: &S) -> Result<()> {
     if let Some(effective) = cs.effective() {
         syscall.set_capability(CapSet::Effective, &to_set(effective))?;
     }
@@ -150,8 +169,25 @@ pub fn drop_privileges<S: Syscall + ?Sized>(cs: &LinuxCapabilities, syscall: &S)
     }
 
     if let Some(ambient) = cs.ambient() {
+        // ambient capabilities can only be raised, if they are in the permitted
+        // and inheritable set of the process, so they have to be set last
+        let permitted = cs.permitted().as_ref().map(to_set).unwrap_or_default();
+        let inheritable = cs.inheritable().as_ref().map(to_set).unwrap_or_default();
+        let ambient: CapsHashSet = to_set(ambient)
+            .into_iter()
+            .filter(|c| {
+                let raisable = permitted.contains(c) && inheritable.contains(c);
+                if !raisable {
+                    log::warn!(
+                        "ambient capability {} is not permitted and inheritable, skipping",
+                        c
+                    );
+                }
+                raisable
+            })
+            .collect();
         // check specifically for ambient, as those might not always be available
-        if let Err(e) = syscall.set_capability(CapSet::Ambient, &to_set(ambient)) {
+        if let Err(e) = syscall.set_capability(CapSet::Ambient, &ambient) {
             log::error!("failed to set ambient capabilities: {}", e);
         }
     }
@@ -165,7 +201,9 @@ mod tests {
     use std::collections::HashSet;
 
     use super::*;
-    use crate::syscall::test::TestHelperSyscall;
+    use crate::syscall::{syscall::create_syscall, test::TestHelperSyscall};
+    use crate::utils::test_utils;
+    use anyhow::{bail, Context};
 
     #[test]
     fn test_reset_effective() {
@@ -179,6 +217,121 @@ mod tests {
         assert_eq!(set_capability_args, vec![caps::all()]);
     }
 
+    #[test]
+    fn test_drop_privileges_ambient() -> Result<()> {
+        let test_command = TestHelperSyscall::default();
+        let cs = LinuxCapabilitiesBuilder::default()
+            .permitted(HashSet::from([
+                SpecCapability::NetBindService,
+                SpecCapability::Kill,
+            ]))
+            .inheritable(HashSet::from([
+                SpecCapability::NetBindService,
+                SpecCapability::Chown,
+            ]))
+            .ambient(HashSet::from([
+                SpecCapability::NetBindService,
+                SpecCapability::Kill,
+                SpecCapability::Chown,
+            ]))
+            .build()?;
+        drop_privileges(&cs, &test_command)?;
+
+        let set_capability_args = test_command.get_set_capability_args();
+        let (capset, ambient) = set_capability_args.last().unwrap();
+        assert_eq!(*capset, CapSet::Ambient);
+        assert_eq!(
+            *ambient,
+            CapsHashSet::from([CapsCapability::CAP_NET_BIND_SERVICE])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_drop_bounding() -> Result<()> {
+        let test_command = TestHelperSyscall::default();
+        let cs = LinuxCapabilitiesBuilder::default()
+            .bounding(HashSet::from([SpecCapability::NetBindService]))
+            .effective(HashSet::from([SpecCapability::NetBindService]))
+            .build()?;
+        drop_bounding(&cs, &test_command)?;
+        drop_privileges(&cs, &test_command)?;
+
+        let capsets: Vec<_> = test_command
+            .get_set_capability_args()
+            .into_iter()
+            .map(|(capset, _caps)| capset)
+            .collect();
+        assert_eq!(capsets, vec![CapSet::Bounding, CapSet::Effective]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_bounding_dropped_before_setuid() -> Result<()> {
+        test_utils::test_in_child_process(|| {
+            let syscall = create_syscall();
+            let cap = HashSet::from([SpecCapability::NetBindService]);
+            let cs = LinuxCapabilitiesBuilder::default()
+                .bounding(cap.clone())
+                .effective(cap.clone())
+                .permitted(cap.clone())
+                .inheritable(cap)
+                .build()?;
+
+            // same order as the init process: bounding set, user, remaining sets
+            drop_bounding(&cs, syscall.as_ref())?;
+            syscall.set_id(
+                nix::unistd::Uid::from_raw(65534),
+                nix::unistd::Gid::from_raw(65534),
+            )?;
+            reset_effective(syscall.as_ref())?;
+            drop_privileges(&cs, syscall.as_ref())?;
+
+            let expected = CapsHashSet::from([CapsCapability::CAP_NET_BIND_SERVICE]);
+            for capset in [CapSet::Bounding, CapSet::Effective, CapSet::Permitted] {
+                let got = caps::read(None, capset)?;
+                if got != expected {
+                    bail!("unexpected {:?} capabilities {:?}", capset, got);
+                }
+            }
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_ambient_retained_across_exec() -> Result<()> {
+        test_utils::test_in_child_process(|| {
+            // keep the permitted capabilities when switching to a non-root user
+            if unsafe { libc::prctl(libc::PR_SET_KEEPCAPS, 1, 0, 0, 0) } != 0 {
+                bail!("failed to set keep caps");
+            }
+            nix::unistd::setuid(nix::unistd::Uid::from_raw(65534))?;
+
+            let cap = HashSet::from([SpecCapability::NetBindService]);
+            let cs = LinuxCapabilitiesBuilder::default()
+                .effective(cap.clone())
+                .permitted(cap.clone())
+                .inheritable(cap.clone())
+                .ambient(cap)
+                .build()?;
+            drop_privileges(&cs, create_syscall().as_ref())?;
+
+            let output = std::process::Command::new("cat")
+                .arg("/proc/self/status")
+                .output()?;
+            let status = String::from_utf8(output.stdout)?;
+            let ambient = status
+                .lines()
+                .find_map(|line| line.strip_prefix("CapAmb:"))
+                .context("no CapAmb in status")?;
+            let ambient = u64::from_str_radix(ambient.trim(), 16)?;
+            if ambient != 1 << CapsCapability::CAP_NET_BIND_SERVICE.index() {
+                bail!("unexpected ambient capabilities {:#x}", ambient);
+            }
+            Ok(())
+        })
+    }
+
     #[test]
     fn test_convert_oci_spec_to_caps_type() {
         struct Testcase {
@@ -6,7 +6,7 @@ use std::{
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 
-use oci_spec::runtime::{Hooks, Spec};
+use oci_spec::runtime::{Hooks, LinuxIntelRdt, LinuxResources, Spec};
 
 use crate::utils;
 
@@ -19,20 +19,20 @@ const YOUKI_CONFIG_NAME: &str = "youki_config.json";
 pub struct YoukiConfig {
     pub hooks: Option<Hooks>,
     pub cgroup_path: PathBuf,
+    pub intel_rdt: Option<LinuxIntelRdt>,
+    /// Resources of the cgroup, which are kept up to date by update
+    #[serde(default)]
+    pub resources: Option<LinuxResources>,
 }
 
 impl<'a> YoukiConfig {
     pub fn from_spec(spec: &'a Spec, container_id: &str, rootless: bool) -> Result<Self> {
+        let linux = spec.linux().as_ref().context("no linux in spec")?;
         Ok(YoukiConfig {
             hooks: spec.hooks().clone(),
-            cgroup_path: utils::get_cgroup_path(
-                spec.linux()
-                    .as_ref()
-                    .context("no linux in spec")?
-                    .cgroups_path(),
-                container_id,
-                rootless,
-            ),
+            cgroup_path: utils::get_cgroup_path(linux.cgroups_path(), container_id, rootless),
+            intel_rdt: linux.intel_rdt().clone(),
+            resources: linux.resources().clone(),
         })
     }
 
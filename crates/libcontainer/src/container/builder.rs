@@ -87,7 +87,7 @@ impl<'a> ContainerBuilder<'a> {
     ///
     /// ContainerBuilder::new("74f1a4cb3801".to_owned(), create_syscall().as_ref())
     /// .as_init("/var/run/docker/bundle")
-    /// .with_systemd(false)
+    /// .with_systemd_cgroup(false)
     /// .build();
     /// ```
     #[allow(clippy::wrong_self_convention)]
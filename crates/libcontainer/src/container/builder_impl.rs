@@ -1,13 +1,20 @@
 use super::{Container, ContainerStatus};
 use crate::{
-    hooks,
+    exec_fifo::ExecFifo,
+    intel_rdt,
     notify_socket::NotifyListener,
-    process::{self, args::ContainerArgs},
+    process::{
+        self,
+        args::{ContainerArgs, StartBarrier},
+    },
     rootless::Rootless,
+    seccomp::SeccompOptions,
     syscall::Syscall,
+    time_namespace::TimeNamespace,
     utils,
 };
 use anyhow::{bail, Context, Result};
+use nix::{fcntl, unistd::Pid};
 use oci_spec::runtime::Spec;
 use std::{fs, io::Write, os::unix::prelude::RawFd, path::PathBuf};
 
@@ -29,54 +36,72 @@ pub(super) struct ContainerBuilderImpl<'a> {
     pub pid_file: Option<PathBuf>,
     /// Socket to communicate the file descriptor of the ptty
     pub console_socket: Option<RawFd>,
+    /// Keep the pty master, so that the caller can forward the window size
+    /// of its terminal
+    pub keep_console_master: bool,
     /// Options for rootless containers
     pub rootless: Option<Rootless<'a>>,
-    /// Path to the Unix Domain Socket to communicate container start
+    /// Path to the exec fifo of an init container or the Unix Domain Socket
+    /// of a tenant to communicate container start
     pub notify_path: PathBuf,
     /// Container state
     pub container: Option<Container>,
     /// File descriptos preserved/passed to the container init process.
     pub preserve_fds: i32,
+    /// Use MS_MOVE and chroot instead of pivot_root to enter the rootfs
+    pub no_pivot: bool,
+    /// eBPF LSM program attached to the cgroup of the container
+    pub bpf_audit: Option<RawFd>,
+    /// Time namespace, which is not part of the namespaces of the spec
+    pub time_namespace: Option<TimeNamespace>,
+    /// Options for translating the seccomp profile into a filter
+    pub seccomp_options: SeccompOptions,
 }
 
 impl<'a> ContainerBuilderImpl<'a> {
-    pub(super) fn create(&mut self) -> Result<()> {
-        if let Err(outer) = self.run_container().context("failed to create container") {
-            if let Err(inner) = self.cleanup_container() {
-                return Err(outer.context(inner));
-            }
+    /// Creates the container process and returns its pid, along with the pty
+    /// master if it is kept
+    pub(super) fn create(&mut self) -> Result<(Pid, Option<RawFd>)> {
+        match self.run_container().context("failed to create container") {
+            Ok(created) => Ok(created),
+            Err(outer) => {
+                if let Err(inner) = self.cleanup_container() {
+                    return Err(outer.context(inner));
+                }
 
-            return Err(outer);
+                Err(outer)
+            }
         }
-
-        Ok(())
     }
 
-    fn run_container(&mut self) -> Result<()> {
+    fn run_container(&mut self) -> Result<(Pid, Option<RawFd>)> {
+        // The preserved fds are inherited by the container process, so they
+        // have to be open already.
+        validate_preserved_fds(self.preserve_fds)?;
+
         let linux = self.spec.linux().as_ref().context("no linux in spec")?;
         let cgroups_path = utils::get_cgroup_path(
             linux.cgroups_path(),
             &self.container_id,
             self.rootless.is_some(),
         );
-        let cmanager = libcgroups::common::create_cgroup_manager(
+        let cmanager = utils::create_cgroup_manager(
             &cgroups_path,
-            self.use_systemd || self.rootless.is_some(),
+            self.use_systemd,
+            self.rootless.is_some(),
             &self.container_id,
         )?;
         let process = self.spec.process().as_ref().context("No process in spec")?;
 
-        if self.init {
-            if let Some(hooks) = self.spec.hooks() {
-                hooks::run_hooks(hooks.create_runtime().as_ref(), self.container.as_ref())?
-            }
-        }
-
-        // Need to create the notify socket before we pivot root, since the unix
-        // domain socket used here is outside of the rootfs of container. During
-        // exec, need to create the socket before we enter into existing mount
+        // Need to create the start barrier before we pivot root, since the exec
+        // fifo used here is outside of the rootfs of container. During exec,
+        // need to create the socket before we enter into existing mount
         // namespace.
-        let notify_socket: NotifyListener = NotifyListener::new(&self.notify_path)?;
+        let start_barrier = if self.init {
+            StartBarrier::ExecFifo(ExecFifo::create(&self.notify_path)?)
+        } else {
+            StartBarrier::NotifySocket(NotifyListener::new(&self.notify_path)?)
+        };
 
         // If Out-of-memory score adjustment is set in specification.  set the score
         // value for the current process check
@@ -114,18 +139,24 @@ impl<'a> ContainerBuilderImpl<'a> {
             spec: self.spec,
             rootfs: &self.rootfs,
             console_socket: self.console_socket,
-            notify_socket,
+            keep_console_master: self.keep_console_master,
+            start_barrier,
             preserve_fds: self.preserve_fds,
+            no_pivot: self.no_pivot,
             container: &self.container,
             rootless: &self.rootless,
             cgroup_manager: cmanager,
+            bpf_audit: self.bpf_audit,
+            time_namespace: &self.time_namespace,
+            seccomp_options: &self.seccomp_options,
         };
 
-        let init_pid = process::container_main_process::container_main_process(&container_args)?;
+        let (init_pid, console_master) =
+            process::container_main_process::container_main_process(&container_args)?;
 
         // if file to write the pid to is specified, write pid of the child
         if let Some(pid_file) = &self.pid_file {
-            fs::write(&pid_file, format!("{}", init_pid)).context("failed to write pid file")?;
+            utils::write_pid_file(pid_file, init_pid.as_raw())?;
         }
 
         if let Some(container) = &mut self.container {
@@ -138,7 +169,7 @@ impl<'a> ContainerBuilderImpl<'a> {
                 .context("Failed to save container state")?;
         }
 
-        Ok(())
+        Ok((init_pid, console_master))
     }
 
     fn cleanup_container(&self) -> Result<()> {
@@ -148,17 +179,28 @@ impl<'a> ContainerBuilderImpl<'a> {
             &self.container_id,
             self.rootless.is_some(),
         );
-        let cmanager = libcgroups::common::create_cgroup_manager(
+        let cmanager = utils::create_cgroup_manager(
             &cgroups_path,
-            self.use_systemd || self.rootless.is_some(),
+            self.use_systemd,
+            self.rootless.is_some(),
             &self.container_id,
         )?;
 
         let mut errors = Vec::new();
-        if let Err(e) = cmanager.remove().context("failed to remove cgroup") {
+        // the processes in the cgroup belong to the failed create, so they
+        // are killed
+        if let Err(e) = cmanager.remove(true).context("failed to remove cgroup") {
             errors.push(e.to_string());
         }
 
+        if let Some(intel_rdt) = linux.intel_rdt() {
+            if let Err(e) = intel_rdt::delete_intel_rdt(&self.container_id, intel_rdt)
+                .context("failed to remove intel RDT group")
+            {
+                errors.push(e.to_string());
+            }
+        }
+
         if let Some(container) = &self.container {
             if container.root.exists() {
                 if let Err(e) = fs::remove_dir_all(&container.root)
@@ -176,3 +218,41 @@ impl<'a> ContainerBuilderImpl<'a> {
         Ok(())
     }
 }
+
+/// Checks that the fds following stdio, which should be passed to the
+/// container process, are open
+fn validate_preserved_fds(preserve_fds: i32) -> Result<()> {
+    if preserve_fds < 0 {
+        bail!(
+            "invalid number of preserved file descriptors: {}",
+            preserve_fds
+        );
+    }
+
+    // the preserved fds follow stdio
+    let end = preserve_fds.checked_add(3).with_context(|| {
+        format!(
+            "invalid number of preserved file descriptors: {}",
+            preserve_fds
+        )
+    })?;
+    for fd in 3..end {
+        fcntl::fcntl(fd, fcntl::F_GETFD)
+            .with_context(|| format!("preserved file descriptor {} is not open", fd))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_preserved_fds() {
+        assert!(validate_preserved_fds(0).is_ok());
+        assert!(validate_preserved_fds(-1).is_err());
+        // not that many fds are open by the test process
+        assert!(validate_preserved_fds(100_000).is_err());
+        assert!(validate_preserved_fds(i32::MAX).is_err());
+    }
+}
@@ -1,19 +1,26 @@
 use std::collections::HashMap;
 use std::ffi::OsString;
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::os::unix::prelude::RawFd;
+use std::path::{Component, Path, PathBuf};
 
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use chrono::DateTime;
+use libcgroups::common::{
+    get_cgroup_setup, validate_cgroups_path, CgroupSetup, FreezerState, DEFAULT_CGROUP_ROOT,
+};
 use nix::unistd::Pid;
 
 use chrono::Utc;
 use procfs::process::Process;
+use procfs::ProcError;
 
 use crate::config::YoukiConfig;
 use crate::syscall::syscall::create_syscall;
 
-use crate::container::{ContainerStatus, State};
+use crate::container::{ContainerOperation, ContainerStatus, State};
+
+pub(crate) const SPEC_FILE: &str = "config.json";
 
 /// Structure representing the container data
 #[derive(Debug, Clone)]
@@ -22,6 +29,9 @@ pub struct Container {
     pub state: State,
     // indicated the directory for the root path in the container
     pub root: PathBuf,
+    // pty master of a container created in the foreground with a terminal,
+    // which is only known to the process that has created the container
+    pub(crate) console_master: Option<RawFd>,
 }
 
 impl Default for Container {
@@ -29,6 +39,7 @@ impl Default for Container {
         Self {
             state: State::default(),
             root: PathBuf::from("/run/youki"),
+            console_master: None,
         }
     }
 }
@@ -46,6 +57,7 @@ impl Container {
         Ok(Self {
             state,
             root: container_root,
+            console_master: None,
         })
     }
 
@@ -66,7 +78,7 @@ impl Container {
     }
 
     pub fn can_exec(&self) -> bool {
-        self.state.status == ContainerStatus::Running
+        self.state.status.allows(ContainerOperation::Exec)
     }
 
     pub fn can_pause(&self) -> bool {
@@ -81,6 +93,17 @@ impl Container {
         &self.state.bundle
     }
 
+    /// Returns the path of the runtime spec. If the spec has not been read
+    /// from the bundle during creation, a copy is kept in the container root.
+    pub fn spec_path(&self) -> PathBuf {
+        let spec_path = self.root.join(SPEC_FILE);
+        if spec_path.exists() {
+            spec_path
+        } else {
+            self.bundle().join(SPEC_FILE)
+        }
+    }
+
     pub fn set_annotations(&mut self, annotations: Option<HashMap<String, String>>) -> &mut Self {
         self.state.annotations = annotations;
         self
@@ -90,8 +113,18 @@ impl Container {
         self.state.pid.map(Pid::from_raw)
     }
 
+    /// Returns the pty master of the container, if it has been created in the
+    /// foreground with a console socket. The caller forwards the window size
+    /// of its terminal to it with [crate::tty::forward_winsize].
+    pub fn console_master(&self) -> Option<RawFd> {
+        self.console_master
+    }
+
     pub fn set_pid(&mut self, pid: i32) -> &mut Self {
         self.state.pid = Some(pid);
+        // the start time allows to detect later on, if the pid has been
+        // reused by another process in the meantime
+        self.state.pid_start_time = Process::new(pid).ok().map(|p| p.stat.starttime);
         self
     }
 
@@ -125,6 +158,41 @@ impl Container {
         self
     }
 
+    /// Returns the path of a sub-cgroup of the container, which has to be
+    /// relative to the cgroup of the container. Sub-cgroups are only supported
+    /// for the cgroup v2 filesystem driver.
+    pub fn sub_cgroup_path(&self, sub_cgroup: &Path) -> Result<PathBuf> {
+        let is_nested = !sub_cgroup.as_os_str().is_empty()
+            && sub_cgroup
+                .components()
+                .all(|c| matches!(c, Component::Normal(_)));
+        if !is_nested {
+            bail!(
+                "sub-cgroup {:?} has to be a relative path within the cgroup of the container",
+                sub_cgroup
+            );
+        }
+
+        if self.systemd() == Some(true) {
+            bail!("sub-cgroups are not supported by the systemd cgroup driver");
+        }
+        if !matches!(get_cgroup_setup()?, CgroupSetup::Unified) {
+            bail!("sub-cgroups are only supported on cgroup v2");
+        }
+
+        let config = YoukiConfig::load(&self.root)
+            .with_context(|| format!("failed to load runtime spec for container {}", self.id()))?;
+        // the cgroups path has to be a filesystem path without systemd
+        validate_cgroups_path(&config.cgroup_path, false)?;
+        let cgroup_path = config
+            .cgroup_path
+            .strip_prefix("/")
+            .unwrap_or(&config.cgroup_path);
+        Ok(Path::new(DEFAULT_CGROUP_ROOT)
+            .join(cgroup_path)
+            .join(sub_cgroup))
+    }
+
     pub fn status(&self) -> ContainerStatus {
         self.state.status
     }
@@ -147,20 +215,36 @@ impl Container {
                 // Note that Process::new does not spawn a new process
                 // but instead creates a new Process structure, and fill
                 // it with information about the process with given pid
-                if let Ok(proc) = Process::new(pid.as_raw()) {
-                    use procfs::process::ProcState;
-
-                    match proc.stat.state()? {
-                        ProcState::Zombie | ProcState::Dead => ContainerStatus::Stopped,
-                        _ => match self.status() {
-                            ContainerStatus::Creating
-                            | ContainerStatus::Created
-                            | ContainerStatus::Paused => self.status(),
-                            _ => ContainerStatus::Running,
-                        },
+                match Process::new(pid.as_raw()) {
+                    Ok(proc) => {
+                        use procfs::process::ProcState;
+
+                        // A process with another start time has reused the
+                        // pid after the init process has been reaped. Any
+                        // state but zombie or dead is alive, even if the
+                        // process is currently stopped or not scheduled.
+                        let reused = matches!(self.state.pid_start_time,
+                            Some(start_time) if start_time != proc.stat.starttime);
+                        if reused {
+                            ContainerStatus::Stopped
+                        } else {
+                            match proc.stat.state()? {
+                                ProcState::Zombie | ProcState::Dead => ContainerStatus::Stopped,
+                                _ => match self.status() {
+                                    ContainerStatus::Creating | ContainerStatus::Created => {
+                                        self.status()
+                                    }
+                                    _ => self.started_status(),
+                                },
+                            }
+                        }
+                    }
+                    Err(ProcError::NotFound(_)) => ContainerStatus::Stopped,
+                    Err(e) => {
+                        return Err(e).with_context(|| {
+                            format!("failed to get the status of container process {}", pid)
+                        })
                     }
-                } else {
-                    ContainerStatus::Stopped
                 }
             }
             None => ContainerStatus::Stopped,
@@ -170,6 +254,41 @@ impl Container {
         Ok(())
     }
 
+    /// Returns the status of a started container with a live process. It is
+    /// paused while its cgroup is frozen, regardless of whether it has been
+    /// frozen by the runtime or another tool.
+    fn started_status(&self) -> ContainerStatus {
+        let persisted = match self.status() {
+            ContainerStatus::Paused => ContainerStatus::Paused,
+            _ => ContainerStatus::Running,
+        };
+
+        match self.freezer_state() {
+            Ok(FreezerState::Frozen) => ContainerStatus::Paused,
+            Ok(FreezerState::Thawed) => ContainerStatus::Running,
+            Ok(FreezerState::Undefined) => persisted,
+            Err(e) => {
+                log::warn!(
+                    "could not read the freezer state of container {}: {:?}",
+                    self.id(),
+                    e
+                );
+                persisted
+            }
+        }
+    }
+
+    /// Reads the freezer state from the cgroup of the container directly, as
+    /// creating a cgroup manager may set up the cgroup, e.g. start a unit.
+    fn freezer_state(&self) -> Result<FreezerState> {
+        let cgroups_path = self.spec()?.cgroup_path;
+        let use_systemd = self
+            .systemd()
+            .context("container state does not contain cgroup manager")?;
+        let pid = self.pid().context("container has no pid")?;
+        libcgroups::common::get_freezer_state(&cgroups_path, use_systemd, pid)
+    }
+
     pub fn refresh_state(&mut self) -> Result<&mut Self> {
         let state = State::load(&self.root)?;
         self.state = state;
@@ -182,11 +301,61 @@ impl Container {
         let mut container = Self {
             state,
             root: container_root,
+            console_master: None,
         };
         container.refresh_status()?;
         Ok(container)
     }
 
+    /// Loads the container with the given id from the root path the
+    /// container states are stored in
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use libcontainer::container::Container;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let container = Container::load_by_id("/run/youki", "74f1a4cb3801")?;
+    /// println!("{}", container.status());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn load_by_id<P: AsRef<Path>>(root_path: P, container_id: &str) -> Result<Self> {
+        let container_root = Self::container_root(root_path, container_id)?;
+        if !container_root.exists() {
+            bail!("container {} does not exist.", container_id)
+        }
+
+        Self::load(container_root)
+            .with_context(|| format!("could not load state for container {}", container_id))
+    }
+
+    /// Checks if a container with the given id exists in the root path
+    pub fn exists<P: AsRef<Path>>(root_path: P, container_id: &str) -> Result<bool> {
+        Ok(Self::container_root(root_path, container_id)?.exists())
+    }
+
+    /// Returns the directory the state of the container with the given id is
+    /// stored in
+    pub fn container_root<P: AsRef<Path>>(root_path: P, container_id: &str) -> Result<PathBuf> {
+        // resolves relative paths, symbolic links etc. and get complete path
+        let root_path = fs::canonicalize(&root_path).with_context(|| {
+            format!(
+                "failed to canonicalize {} for container {}",
+                root_path.as_ref().display(),
+                container_id
+            )
+        })?;
+        // the state of the container is stored in a directory named after the container id
+        Ok(root_path.join(container_id))
+    }
+
+    /// Returns the state of the container as defined by the runtime spec
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
     pub fn save(&self) -> Result<()> {
         log::debug!("Save container status: {:?} in {:?}", self, self.root);
         self.state.save(&self.root)
@@ -207,6 +376,44 @@ pub struct CheckpointOptions {
     pub shell_job: bool,
     pub tcp_established: bool,
     pub work_path: Option<PathBuf>,
+    /// Path to the CRIU binary, which is looked up in PATH if not given
+    pub criu_path: Option<PathBuf>,
+    /// Only dump the memory, which changed since the parent images, and
+    /// leave the container running, so that the final checkpoint is fast
+    pub pre_dump: bool,
+    /// Images of the previous pre-dump, relative to the image path
+    pub parent_path: Option<PathBuf>,
+}
+
+/// Restore parameter structure
+pub struct RestoreOptions {
+    pub ext_unix_sk: bool,
+    pub file_locks: bool,
+    pub image_path: PathBuf,
+    pub shell_job: bool,
+    pub tcp_established: bool,
+    pub work_path: Option<PathBuf>,
+    /// Path to the CRIU binary, which is looked up in PATH if not given
+    pub criu_path: Option<PathBuf>,
+}
+
+/// Exec parameter structure
+#[derive(Debug, Default)]
+pub struct ExecOptions {
+    /// Socket to which the pseudo terminal of the process is sent
+    pub console_socket: Option<PathBuf>,
+    /// File the pid of the process is written to
+    pub pid_file: Option<PathBuf>,
+    /// Number of file descriptors following stdio passed to the process
+    pub preserve_fds: i32,
+    /// Sub-cgroup of the container, relative to its cgroup, the process is
+    /// placed in. It is created if it does not exist yet.
+    pub sub_cgroup: Option<PathBuf>,
+    /// Makes the caller the child subreaper, which is required to wait for
+    /// the process, as it is forked by an intermediate process. This applies
+    /// to all orphaned descendants of the caller for the rest of its
+    /// lifetime, which is why it has to be opted in.
+    pub subreaper: bool,
 }
 
 #[cfg(test)]
@@ -263,6 +470,14 @@ mod tests {
         assert_eq!(container.state.annotations, Some(annotations));
     }
 
+    #[test]
+    fn test_sub_cgroup_path_escape() {
+        let container = Container::default();
+        for sub_cgroup in ["", "/exec", "../exec", "exec/../../other", "./exec"] {
+            assert!(container.sub_cgroup_path(Path::new(sub_cgroup)).is_err());
+        }
+    }
+
     #[test]
     fn test_get_set_systemd() {
         let mut container = Container::default();
@@ -306,6 +521,30 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    #[serial]
+    fn test_load_by_id() -> Result<()> {
+        let tmp_dir = create_temp_dir("test_load_by_id")?;
+        assert!(!Container::exists(tmp_dir.path(), "container_id")?);
+        assert!(Container::load_by_id(tmp_dir.path(), "container_id").is_err());
+
+        let container_root = Container::container_root(tmp_dir.path(), "container_id")?;
+        fs::create_dir(&container_root)?;
+        Container::new(
+            "container_id",
+            ContainerStatus::Created,
+            None,
+            &PathBuf::from("."),
+            &container_root,
+        )?
+        .save()?;
+
+        assert!(Container::exists(tmp_dir.path(), "container_id")?);
+        let container = Container::load_by_id(tmp_dir.path(), "container_id")?;
+        assert_eq!(container.state().id, "container_id");
+        Ok(())
+    }
+
     #[test]
     #[serial]
     fn test_get_spec() -> Result<()> {
@@ -357,6 +596,19 @@ mod tests {
         container.refresh_status()?;
         assert_eq!(container.status(), ContainerStatus::Running);
 
+        // with PID case but PID reused by another process
+        let start_time = Process::new(1)?.stat.starttime;
+        container.state.pid_start_time = Some(start_time + 1);
+        container.refresh_status()?;
+        assert_eq!(container.status(), ContainerStatus::Stopped);
+        // the last known pid is still reported
+        assert_eq!(container.pid(), Some(Pid::from_raw(1)));
+
+        container.state.pid_start_time = Some(start_time);
+        container.set_status(ContainerStatus::Running);
+        container.refresh_status()?;
+        assert_eq!(container.status(), ContainerStatus::Running);
+
         Ok(())
     }
 }
@@ -1,4 +1,4 @@
-use super::{Container, ContainerStatus};
+use super::{criu, Container, ContainerOperation, ContainerStatus};
 use crate::container::container::CheckpointOptions;
 use anyhow::{bail, Context, Result};
 
@@ -6,117 +6,132 @@ use libcgroups::common::{
     CgroupSetup::{Hybrid, Legacy},
     DEFAULT_CGROUP_ROOT,
 };
+use nix::unistd::Pid;
 use oci_spec::runtime::Spec;
-use std::os::unix::io::AsRawFd;
+use std::process::Command;
 
 const CRIU_CHECKPOINT_LOG_FILE: &str = "dump.log";
+const CRIU_PRE_DUMP_LOG_FILE: &str = "pre-dump.log";
+/// File in the image directory, which tells restore where the stdio file
+/// descriptors of the init process pointed to at the time of checkpointing.
+pub(crate) const CRIU_DESCRIPTORS_FILE: &str = "descriptors.json";
 
 impl Container {
     pub fn checkpoint(&mut self, opts: &CheckpointOptions) -> Result<()> {
         self.refresh_status()
             .context("failed to refresh container status")?;
 
-        // can_pause() checks if the container is running. That also works for
-        // checkpoitning. is_running() would make more sense here, but let's
-        // just reuse existing functions.
-        if !self.can_pause() {
-            bail!(
-                "{} could not be checkpointed because it was {:?}",
-                self.id(),
-                self.status()
-            );
+        self.state.check_operation(ContainerOperation::Checkpoint)?;
+
+        let pid = self
+            .pid()
+            .with_context(|| format!("could not find the init pid of {}", self.id()))?;
+
+        let criu_path = criu::criu_path(opts.criu_path.as_ref());
+        let mut features = vec![criu::CHECKPOINT];
+        if opts.pre_dump {
+            features.push(criu::PRE_DUMP);
         }
+        criu::check_version(&criu_path, &features)?;
 
-        let mut criu = rust_criu::Criu::new().unwrap();
+        let spec = Spec::load(&self.spec_path())?;
+        let log_file = if opts.pre_dump {
+            CRIU_PRE_DUMP_LOG_FILE
+        } else {
+            CRIU_CHECKPOINT_LOG_FILE
+        };
+        let log_dir = opts.work_path.as_ref().unwrap_or(&opts.image_path);
+        let mut criu = Command::new(&criu_path);
+        criu.arg(if opts.pre_dump { "pre-dump" } else { "dump" })
+            .arg("--tree")
+            .arg(pid.to_string())
+            .arg("--images-dir")
+            .arg(&opts.image_path)
+            .arg("--work-dir")
+            .arg(log_dir)
+            .arg("--root")
+            .arg(self.bundle())
+            .arg("--log-file")
+            .arg(log_file)
+            .arg("-v4")
+            .arg("--manage-cgroups");
+        if criu::has_terminal(&spec) {
+            criu.arg("--orphan-pts-master");
+        }
+        if opts.pre_dump {
+            // only the memory changed since the previous pre-dump is dumped
+            criu.arg("--track-mem");
+        } else if opts.leave_running {
+            criu.arg("--leave-running");
+        }
+        if let Some(parent_path) = &opts.parent_path {
+            // CRIU links the images to their parent, so restore finds the
+            // memory dumped by the pre-dumps
+            if !opts.image_path.join(parent_path).is_dir() {
+                bail!(
+                    "parent images {:?} do not exist in {:?}",
+                    parent_path,
+                    opts.image_path
+                );
+            }
+            criu.arg("--prev-images-dir").arg(parent_path);
+            if !opts.pre_dump {
+                criu.arg("--track-mem");
+            }
+        }
+        if opts.ext_unix_sk {
+            criu.arg("--ext-unix-sk");
+        }
+        if opts.shell_job {
+            criu.arg("--shell-job");
+        }
+        if opts.tcp_established {
+            criu.arg("--tcp-established");
+        }
+        if opts.file_locks {
+            criu.arg("--file-locks");
+        }
 
         // We need to tell CRIU that all bind mounts are external. CRIU will fail checkpointing
         // if it does not know that these bind mounts are coming from the outside of the container.
         // This information is needed during restore again. The external location of the bind
         // mounts can change and CRIU will just mount whatever we tell it to mount based on
         // information found in 'config.json'.
-        let source_spec_path = self.bundle().join("config.json");
-        let spec = Spec::load(&source_spec_path)?;
-        let mounts = spec.mounts().clone();
-        for m in mounts.unwrap() {
-            match m.typ().as_deref() {
-                Some("bind") => {
-                    let dest = m
-                        .destination()
-                        .clone()
-                        .into_os_string()
-                        .into_string()
-                        .expect("failed to convert mount destination");
-                    criu.set_external_mount(dest.clone(), dest);
-                }
-                Some("cgroup") => {
-                    match libcgroups::common::get_cgroup_setup()
-                        .context("failed to determine cgroup setup")?
-                    {
-                        // For v1 it is necessary to list all cgroup mounts as external mounts
-                        Legacy | Hybrid => {
-                            for mp in libcgroups::v1::util::list_subsystem_mount_points()
-                                .context("failed to get subsystem mount points")?
-                            {
-                                let cgroup_mount = mp
-                                    .clone()
-                                    .into_os_string()
-                                    .into_string()
-                                    .expect("failed to convert mount point");
-                                if cgroup_mount.starts_with(DEFAULT_CGROUP_ROOT) {
-                                    criu.set_external_mount(cgroup_mount.clone(), cgroup_mount);
-                                }
-                            }
-                        }
-                        _ => (),
-                    }
-                }
-                _ => (),
-            }
+        for external in external_mounts(&spec)? {
+            criu.arg("--external").arg(external);
         }
 
-        let directory = std::fs::File::open(&opts.image_path)
-            .with_context(|| format!("failed to open {:?}", opts.image_path))?;
-        criu.set_images_dir_fd(directory.as_raw_fd());
-
-        // It seems to be necessary to be defined outside of 'if' to
-        // keep the FD open until CRIU uses it.
-        let work_dir: std::fs::File;
-        if let Some(wp) = &opts.work_path {
-            work_dir = std::fs::File::open(wp)?;
-            criu.set_work_dir_fd(work_dir.as_raw_fd());
+        if !opts.pre_dump {
+            // CRIU does not know where the stdio of the init process is coming
+            // from, so restore has to reconnect them. Record the targets they
+            // point to.
+            let descriptors = stdio_descriptors(pid)?;
+            std::fs::write(
+                opts.image_path.join(CRIU_DESCRIPTORS_FILE),
+                serde_json::to_string(&descriptors)?,
+            )
+            .with_context(|| format!("failed to write {}", CRIU_DESCRIPTORS_FILE))?;
         }
 
-        criu.set_log_file(CRIU_CHECKPOINT_LOG_FILE.to_string());
-        criu.set_log_level(4);
-        criu.set_pid(self.pid().unwrap().into());
-        criu.set_leave_running(opts.leave_running);
-        criu.set_ext_unix_sk(opts.ext_unix_sk);
-        criu.set_shell_job(opts.shell_job);
-        criu.set_tcp_established(opts.tcp_established);
-        criu.set_file_locks(opts.file_locks);
-        criu.set_orphan_pts_master(true);
-        criu.set_manage_cgroups(true);
-        criu.set_root(
-            self.bundle()
-                .clone()
-                .into_os_string()
-                .into_string()
-                .unwrap(),
-        );
-        if let Err(e) = criu.dump() {
+        log::debug!("checkpoint container {} with {:?}", self.id(), criu);
+        let status = criu
+            .status()
+            .with_context(|| format!("failed to execute {:?}", criu_path))?;
+        if !status.success() {
+            let log_file = log_dir.join(log_file);
+            let log = std::fs::read_to_string(&log_file)
+                .unwrap_or_else(|e| format!("failed to read {}: {}", log_file.display(), e));
             bail!(
-                "checkpointing container {} failed with {:?}. Please check CRIU logfile {:}/{}",
+                "checkpointing container {} failed with {}. CRIU log {}:\n{}",
                 self.id(),
-                e,
-                opts.work_path
-                    .as_ref()
-                    .unwrap_or(&opts.image_path)
-                    .display(),
-                CRIU_CHECKPOINT_LOG_FILE
+                status,
+                log_file.display(),
+                log
             );
         }
 
-        if !opts.leave_running {
+        // a pre-dump always leaves the container running
+        if !opts.pre_dump && !opts.leave_running {
             self.set_status(ContainerStatus::Stopped).save()?;
         }
 
@@ -124,3 +139,90 @@ impl Container {
         Ok(())
     }
 }
+
+/// Returns the bind mounts and, on cgroup v1, the cgroup mounts, which CRIU
+/// has to treat as external mounts. They are keyed by their destination.
+fn external_mounts(spec: &Spec) -> Result<Vec<String>> {
+    let mut externals = Vec::new();
+    for m in spec.mounts().iter().flatten() {
+        match m.typ().as_deref() {
+            Some("bind") => {
+                externals.push(format!("mnt[{0}]:{0}", m.destination().display()));
+            }
+            Some("cgroup") => {
+                match libcgroups::common::get_cgroup_setup()
+                    .context("failed to determine cgroup setup")?
+                {
+                    // For v1 it is necessary to list all cgroup mounts as external mounts
+                    Legacy | Hybrid => {
+                        for mp in libcgroups::v1::util::list_subsystem_mount_points()
+                            .context("failed to get subsystem mount points")?
+                        {
+                            if mp.starts_with(DEFAULT_CGROUP_ROOT) {
+                                externals.push(format!("mnt[{0}]:{0}", mp.display()));
+                            }
+                        }
+                    }
+                    _ => (),
+                }
+            }
+            _ => (),
+        }
+    }
+
+    Ok(externals)
+}
+
+/// Returns the targets of the stdio file descriptors of the process. These are
+/// the same as reported by the corresponding links in /proc/<pid>/fd.
+fn stdio_descriptors(pid: Pid) -> Result<Vec<String>> {
+    (0..3)
+        .map(|fd| {
+            let link = format!("/proc/{}/fd/{}", pid, fd);
+            std::fs::read_link(&link)
+                .map(|target| target.to_string_lossy().into_owned())
+                .with_context(|| format!("failed to read {}", link))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oci_spec::runtime::{MountBuilder, SpecBuilder};
+
+    #[test]
+    fn test_stdio_descriptors() -> Result<()> {
+        let pid = nix::unistd::getpid();
+        let descriptors = stdio_descriptors(pid)?;
+        assert_eq!(descriptors.len(), 3);
+        for (fd, descriptor) in descriptors.iter().enumerate() {
+            let want = std::fs::read_link(format!("/proc/self/fd/{}", fd))?;
+            assert_eq!(descriptor, &want.to_string_lossy());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_external_mounts() -> Result<()> {
+        let spec = SpecBuilder::default()
+            .mounts(vec![
+                MountBuilder::default()
+                    .destination("/proc")
+                    .typ("proc")
+                    .source("proc")
+                    .build()?,
+                MountBuilder::default()
+                    .destination("/data")
+                    .typ("bind")
+                    .source("/srv/data")
+                    .build()?,
+            ])
+            .build()?;
+        assert_eq!(
+            external_mounts(&spec)?,
+            vec!["mnt[/data]:/data".to_string()]
+        );
+        Ok(())
+    }
+}
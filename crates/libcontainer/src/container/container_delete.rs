@@ -1,13 +1,30 @@
-use super::{Container, ContainerStatus};
+use super::{Container, ContainerOperation, ContainerStatus};
 use crate::config::YoukiConfig;
 use crate::hooks;
+use crate::intel_rdt;
 use anyhow::{bail, Context, Result};
 use libcgroups;
+use libcgroups::common::FreezerState;
+use nix::errno::Errno;
+use nix::mount::{mount, umount2, MntFlags, MsFlags};
 use nix::sys::signal;
 use std::fs;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Time to wait for the init process to exit after it has been killed
+const KILL_TIMEOUT: Duration = Duration::from_secs(10);
+/// Namespaces which can be preserved on delete. The pid namespace is not
+/// among them, no process can join it anymore once its init has exited.
+pub const PRESERVABLE_NAMESPACES: &[&str] = &["net", "ipc", "uts", "mnt", "cgroup", "user"];
+/// Directory in the container root to which the namespaces are bind mounted
+const PRESERVED_NAMESPACES_DIR: &str = "namespaces";
 
 impl Container {
-    /// Deletes the container
+    /// Deletes the container. If it is forced, a running container is killed
+    /// and so are processes left behind in its cgroup, otherwise such
+    /// processes fail the removal of the cgroup.
     ///
     /// # Example
     ///
@@ -25,56 +42,379 @@ impl Container {
     /// # }
     /// ```
     pub fn delete(&mut self, force: bool) -> Result<()> {
+        self.delete_keep_namespaces(force, &[])
+    }
+
+    /// Deletes the container, but keeps the given namespaces of the init
+    /// process alive for inspection by bind mounting them to
+    /// `<container root>/namespaces/<namespace>`. The namespaces can only be
+    /// preserved while the init process is still running, so the container
+    /// has to be deleted forcefully. The state directory is kept until the
+    /// container is deleted again, which releases the namespaces.
+    ///
+    /// See [`PRESERVABLE_NAMESPACES`] for the namespaces that can be preserved.
+    /// Note that the mount namespace can only be bind mounted, if the state
+    /// directory is not on a shared mount.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use libcontainer::container::Container;
+    /// use std::path::PathBuf;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let mut container = Container::load(PathBuf::from("/run/youki/74f1a4cb3801"))?;
+    /// container.delete_keep_namespaces(true, &["net".to_owned()])?;
+    ///
+    /// // inspect /run/youki/74f1a4cb3801/namespaces/net, then release it
+    /// container.delete(false)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn delete_keep_namespaces(&mut self, force: bool, namespaces: &[String]) -> Result<()> {
         self.refresh_status()
             .context("failed to refresh container status")?;
+
+        if self.root.join(PRESERVED_NAMESPACES_DIR).exists() {
+            // the resources of the container have been released already by
+            // the delete which preserved the namespaces
+            if !namespaces.is_empty() {
+                bail!(
+                    "namespaces of container {} are preserved already",
+                    self.id()
+                );
+            }
+            self.state.check_operation(ContainerOperation::Delete)?;
+            self.release_namespaces()?;
+            return fs::remove_dir_all(&self.root).with_context(|| {
+                format!("failed to remove container dir {}", self.root.display())
+            });
+        }
+
+        if !namespaces.is_empty() {
+            if !(self.can_kill() && force) {
+                bail!(
+                    "namespaces of container {} can only be preserved when it is deleted forcefully while running",
+                    self.id()
+                );
+            }
+            self.preserve_namespaces(namespaces)?;
+        }
+
         if self.can_kill() && force {
-            let sig = signal::Signal::SIGKILL;
-            log::debug!("kill signal {} to {}", sig, self.pid().unwrap());
-            signal::kill(self.pid().unwrap(), sig)?;
-            self.set_status(ContainerStatus::Stopped).save()?;
+            self.kill_and_wait(KILL_TIMEOUT)?;
         }
         log::debug!("container status: {:?}", self.status());
-        if self.can_delete() {
-            if self.root.exists() {
-                let config = YoukiConfig::load(&self.root).with_context(|| {
-                    format!("failed to load runtime spec for container {}", self.id())
-                })?;
-                log::debug!("config: {:?}", config);
-
-                // remove the directory storing container state
-                log::debug!("remove dir {:?}", self.root);
-                fs::remove_dir_all(&self.root).with_context(|| {
-                    format!("failed to remove container dir {}", self.root.display())
-                })?;
-
-                // remove the cgroup created for the container
-                // check https://man7.org/linux/man-pages/man7/cgroups.7.html
-                // creating and removing cgroups section for more information on cgroups
-                let use_systemd = self
-                    .systemd()
-                    .context("container state does not contain cgroup manager")?;
-                let cmanager = libcgroups::common::create_cgroup_manager(
-                    &config.cgroup_path,
-                    use_systemd,
-                    self.id(),
-                )
-                .context("failed to create cgroup manager")?;
-                cmanager.remove().with_context(|| {
-                    format!("failed to remove cgroup {}", config.cgroup_path.display())
-                })?;
+        self.state.check_operation(ContainerOperation::Delete)?;
+        if !self.root.exists() {
+            return Ok(());
+        }
+
+        // The cleanup continues if a step fails, so that the poststop hooks
+        // run regardless of how far the container got, even if its start
+        // has failed midway.
+        let mut errors = Vec::new();
+        let config = YoukiConfig::load(&self.root)
+            .with_context(|| format!("failed to load runtime spec for container {}", self.id()));
+        log::debug!("config: {:?}", config);
+
+        // remove the directory storing container state, unless it holds the
+        // preserved namespaces
+        if namespaces.is_empty() {
+            log::debug!("remove dir {:?}", self.root);
+            if let Err(e) = fs::remove_dir_all(&self.root) {
+                errors.push(anyhow::Error::from(e).context(format!(
+                    "failed to remove container dir {}",
+                    self.root.display()
+                )));
+            }
+        }
+
+        match config {
+            Ok(config) => {
+                if let Err(e) = self.delete_resources(&config, force) {
+                    errors.push(e);
+                }
 
                 if let Some(hooks) = config.hooks.as_ref() {
-                    hooks::run_hooks(hooks.poststop().as_ref(), Some(self))
-                        .with_context(|| "failed to run post stop hooks")?;
+                    if let Err(e) = hooks::run_hooks(hooks.poststop().as_ref(), Some(self)) {
+                        errors.push(e.context("failed to run post stop hooks"));
+                    }
                 }
             }
-            std::process::exit(0)
-        } else {
+            Err(e) => errors.push(e),
+        }
+
+        if !errors.is_empty() {
+            let errors: Vec<String> = errors.iter().map(|e| format!("{:?}", e)).collect();
             bail!(
-                "{} could not be deleted because it was {:?}",
+                "failed to delete container {}: {}",
+                self.id(),
+                errors.join("; ")
+            );
+        }
+        Ok(())
+    }
+
+    /// Kills the init process and waits until it has exited. A paused
+    /// container is thawed, so that the processes can handle the signal.
+    fn kill_and_wait(&mut self, timeout: Duration) -> Result<()> {
+        let pid = self.pid().context("container has no init process")?;
+        let sig = signal::Signal::SIGKILL;
+        log::debug!("kill signal {} to {}", sig, pid);
+        if let Err(e) = self.send_signal(sig) {
+            // the init process may have exited in the meantime
+            self.refresh_status()
+                .context("failed to refresh container status")?;
+            if self.status() != ContainerStatus::Stopped {
+                return Err(e);
+            }
+        }
+
+        if self.status() == ContainerStatus::Paused {
+            let use_systemd = self
+                .systemd()
+                .context("container state does not contain cgroup manager")?;
+            let cmanager = libcgroups::common::create_cgroup_manager(
+                self.spec()?.cgroup_path,
+                use_systemd,
                 self.id(),
-                self.status()
-            )
+            )?;
+            cmanager
+                .freeze(FreezerState::Thawed)
+                .context("failed to thaw the paused container")?;
         }
+
+        let start = Instant::now();
+        loop {
+            self.refresh_status()
+                .context("failed to refresh container status")?;
+            if self.status() == ContainerStatus::Stopped {
+                break;
+            }
+            if start.elapsed() >= timeout {
+                bail!(
+                    "init process {} of container {} has not exited within {:?} after {}",
+                    pid,
+                    self.id(),
+                    timeout,
+                    sig
+                );
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+
+        self.save()?;
+        Ok(())
+    }
+
+    /// Bind mounts the namespaces of the init process into the container root,
+    /// so that they outlive the init process
+    fn preserve_namespaces(&self, namespaces: &[String]) -> Result<()> {
+        for ns in namespaces {
+            if !PRESERVABLE_NAMESPACES.contains(&ns.as_str()) {
+                bail!(
+                    "namespace {} cannot be preserved, supported are {}",
+                    ns,
+                    PRESERVABLE_NAMESPACES.join(", ")
+                );
+            }
+        }
+
+        let pid = self.pid().context("container has no init process")?;
+        let dir = self.root.join(PRESERVED_NAMESPACES_DIR);
+        fs::create_dir_all(&dir).with_context(|| format!("failed to create {:?}", dir))?;
+        for ns in namespaces {
+            let source = PathBuf::from(format!("/proc/{}/ns/{}", pid, ns));
+            let target = dir.join(ns);
+            if target.exists() {
+                continue;
+            }
+
+            let result = fs::File::create(&target)
+                .with_context(|| format!("failed to create {:?}", target))
+                .and_then(|_| {
+                    mount(
+                        Some(&source),
+                        &target,
+                        None::<&str>,
+                        MsFlags::MS_BIND,
+                        None::<&str>,
+                    )
+                    .with_context(|| format!("failed to bind mount {:?} to {:?}", source, target))
+                });
+            if let Err(err) = result {
+                if let Err(e) = self.release_namespaces() {
+                    log::warn!("failed to release preserved namespaces: {:?}", e);
+                }
+                return Err(err);
+            }
+            log::debug!("preserved {} namespace of {} at {:?}", ns, pid, target);
+        }
+
+        Ok(())
+    }
+
+    /// Unmounts the preserved namespaces and removes their directory
+    fn release_namespaces(&self) -> Result<()> {
+        let dir = self.root.join(PRESERVED_NAMESPACES_DIR);
+        if !dir.exists() {
+            return Ok(());
+        }
+
+        for entry in fs::read_dir(&dir).with_context(|| format!("failed to read {:?}", dir))? {
+            let path = entry?.path();
+            match umount2(&path, MntFlags::MNT_DETACH) {
+                // the namespace has not been bind mounted
+                Ok(_) | Err(Errno::EINVAL) => {}
+                Err(e) => {
+                    return Err(e).with_context(|| format!("failed to unmount {:?}", path));
+                }
+            }
+        }
+
+        fs::remove_dir_all(&dir).with_context(|| format!("failed to remove {:?}", dir))
+    }
+
+    /// Removes the cgroup and the intel RDT group of the container
+    fn delete_resources(&self, config: &YoukiConfig, force: bool) -> Result<()> {
+        // remove the cgroup created for the container
+        // check https://man7.org/linux/man-pages/man7/cgroups.7.html
+        // creating and removing cgroups section for more information on cgroups
+        let use_systemd = self
+            .systemd()
+            .context("container state does not contain cgroup manager")?;
+        let cmanager =
+            libcgroups::common::create_cgroup_manager(&config.cgroup_path, use_systemd, self.id())
+                .context("failed to create cgroup manager")?;
+        cmanager
+            .remove(force)
+            .with_context(|| format!("failed to remove cgroup {}", config.cgroup_path.display()))?;
+
+        if let Some(intel_rdt) = config.intel_rdt.as_ref() {
+            intel_rdt::delete_intel_rdt(self.id(), intel_rdt)
+                .context("failed to remove intel RDT group")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::container::State;
+    use crate::utils::create_temp_dir;
+    use oci_spec::runtime::{HookBuilder, HooksBuilder, Spec};
+    use serial_test::serial;
+    use std::os::unix::fs::MetadataExt;
+
+    #[test]
+    #[serial]
+    fn test_delete_runs_poststop_hooks() -> Result<()> {
+        let tmp = create_temp_dir("test_delete_runs_poststop_hooks")?;
+        let root = tmp.join("container");
+        fs::create_dir_all(&root)?;
+        let marker = tmp.join("poststop");
+
+        // the container has been created, but never started
+        let mut container = Container::new(
+            "never_started",
+            ContainerStatus::Stopped,
+            None,
+            &PathBuf::from("."),
+            &root,
+        )?;
+        container.set_systemd(false).save()?;
+
+        let mut config = YoukiConfig::from_spec(&Spec::default(), container.id(), false)?;
+        config.hooks = Some(
+            HooksBuilder::default()
+                .poststop(vec![HookBuilder::default()
+                    .path("bash")
+                    .args(vec![
+                        "bash".to_owned(),
+                        "-c".to_owned(),
+                        format!("touch {}", marker.display()),
+                    ])
+                    .build()?])
+                .build()?,
+        );
+        config.save(&root)?;
+
+        // removing the cgroup may fail here, the hooks are run regardless
+        let _ = container.delete(false);
+        assert!(marker.exists());
+        assert!(!root.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_preserve_unsupported_namespace() -> Result<()> {
+        let tmp = create_temp_dir("test_preserve_unsupported_namespace")?;
+        let container = Container::new(
+            "running",
+            ContainerStatus::Running,
+            Some(std::process::id() as i32),
+            &PathBuf::from("."),
+            &tmp,
+        )?;
+
+        assert!(container.preserve_namespaces(&["pid".to_owned()]).is_err());
+        assert!(container.preserve_namespaces(&["foo".to_owned()]).is_err());
+        assert!(!tmp.join(PRESERVED_NAMESPACES_DIR).exists());
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_preserve_namespaces() -> Result<()> {
+        if !nix::unistd::geteuid().is_root() {
+            // bind mounting the namespaces requires root
+            return Ok(());
+        }
+
+        let tmp = create_temp_dir("test_preserve_namespaces")?;
+        let mut child = std::process::Command::new("sleep").arg("100").spawn()?;
+        let container = Container::new(
+            "running",
+            ContainerStatus::Running,
+            Some(child.id() as i32),
+            &PathBuf::from("."),
+            &tmp,
+        )?;
+
+        container.preserve_namespaces(&["net".to_owned(), "uts".to_owned()])?;
+        child.kill()?;
+        child.wait()?;
+        for ns in ["net", "uts"] {
+            let preserved = tmp.join(PRESERVED_NAMESPACES_DIR).join(ns);
+            let own = PathBuf::from(format!("/proc/self/ns/{}", ns));
+            // the child shares the namespaces of the test process
+            assert_eq!(fs::metadata(&preserved)?.ino(), fs::metadata(own)?.ino());
+        }
+
+        container.release_namespaces()?;
+        assert!(!tmp.join(PRESERVED_NAMESPACES_DIR).exists());
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_kill_and_wait() -> Result<()> {
+        let tmp = create_temp_dir("test_kill_and_wait")?;
+        let mut child = std::process::Command::new("sleep").arg("100").spawn()?;
+        let mut container = Container::new(
+            "running",
+            ContainerStatus::Running,
+            Some(child.id() as i32),
+            &PathBuf::from("."),
+            &tmp,
+        )?;
+
+        container.kill_and_wait(Duration::from_secs(5))?;
+        assert_eq!(container.status(), ContainerStatus::Stopped);
+        assert!(!child.wait()?.success());
+        assert_eq!(State::load(&tmp)?.status, ContainerStatus::Stopped);
+        Ok(())
     }
 }
@@ -1,7 +1,24 @@
-use std::{thread, time::Duration};
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
 
 use super::{Container, ContainerStatus};
 use anyhow::{bail, Context, Result};
+use crossbeam_channel::RecvTimeoutError;
+use libcgroups::common::CgroupManager;
+use libcgroups::stats::{network_stats, Stats};
+use serde::Serialize;
+
+/// Event emitted by the events command, following the format used by runc
+#[derive(Debug, Serialize)]
+struct Event<'a> {
+    #[serde(rename = "type")]
+    typ: &'a str,
+    id: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<&'a Stats>,
+}
 
 impl Container {
     /// Displays container events
@@ -33,20 +50,105 @@ impl Container {
             .systemd()
             .context("could not determine cgroup manager")?;
 
-        let cgroup_manager =
-            libcgroups::common::create_cgroup_manager(cgroups_path, use_systemd, self.id())?;
-        match stats {
-            true => {
-                let stats = cgroup_manager.stats()?;
-                println!("{}", serde_json::to_string_pretty(&stats)?);
+        let cgroup_manager = libcgroups::common::create_cgroup_manager(
+            cgroups_path.clone(),
+            use_systemd,
+            self.id(),
+        )?;
+        if stats {
+            let stats = self.collect_stats(cgroup_manager.as_ref())?;
+            // the OOM killer has been invoked for the container before
+            if stats.memory.oom_kill > 0 {
+                self.print_oom_event()?;
             }
-            false => loop {
-                let stats = cgroup_manager.stats()?;
-                println!("{}", serde_json::to_string_pretty(&stats)?);
-                thread::sleep(Duration::from_secs(interval as u64));
-            },
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&self.stats_event(&stats))?
+            );
+            return Ok(());
+        }
+
+        let (oom_sender, oom_receiver) = crossbeam_channel::unbounded();
+        if let Some(pid) = self.pid() {
+            // The kernel notifies about OOM kills, so they are reported as
+            // they happen instead of with the next sample.
+            thread::spawn(move || {
+                let result =
+                    libcgroups::common::watch_oom_events(&cgroups_path, use_systemd, pid, || {
+                        let _ = oom_sender.send(());
+                    });
+                if let Err(e) = result {
+                    log::warn!("failed to watch for OOM events: {:?}", e);
+                }
+            });
         }
 
+        loop {
+            let stats = match self.collect_stats(cgroup_manager.as_ref()) {
+                Ok(stats) => stats,
+                // The cgroup may already be gone, if the container has stopped
+                // in the meantime.
+                Err(_) if self.is_stopped()? => break,
+                Err(e) => return Err(e),
+            };
+            println!("{}", serde_json::to_string(&self.stats_event(&stats))?);
+
+            // Check once a second if the container is still alive, so streaming
+            // ends shortly after the init process has exited.
+            let next_sample = Instant::now() + Duration::from_secs(interval.max(1).into());
+            while let Some(remaining) = next_sample.checked_duration_since(Instant::now()) {
+                let timeout = remaining.min(Duration::from_secs(1));
+                match oom_receiver.recv_timeout(timeout) {
+                    Ok(()) => self.print_oom_event()?,
+                    Err(RecvTimeoutError::Timeout) => {}
+                    // OOM events are not watched for (anymore)
+                    Err(RecvTimeoutError::Disconnected) => thread::sleep(timeout),
+                }
+                if self.is_stopped()? {
+                    return Ok(());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn print_oom_event(&self) -> Result<()> {
+        let event = Event {
+            typ: "oom",
+            id: self.id(),
+            data: None,
+        };
+        println!("{}", serde_json::to_string(&event)?);
         Ok(())
     }
+
+    /// Combines the cgroup statistics with the ones of the network namespace
+    pub(super) fn collect_stats(&self, cgroup_manager: &dyn CgroupManager) -> Result<Stats> {
+        let mut stats = cgroup_manager.stats()?;
+        if let Some(pid) = self.pid() {
+            stats.network = match network_stats(pid) {
+                Ok(network) => network,
+                Err(e) => {
+                    log::warn!("failed to get network statistics of {}: {}", self.id(), e);
+                    Vec::new()
+                }
+            };
+        }
+        Ok(stats)
+    }
+
+    fn stats_event<'a>(&'a self, stats: &'a Stats) -> Event<'a> {
+        Event {
+            typ: "stats",
+            id: self.id(),
+            data: Some(stats),
+        }
+    }
+
+    fn is_stopped(&mut self) -> Result<bool> {
+        self.refresh_status()
+            .context("failed to refresh container status")?;
+        Ok(self.status() == ContainerStatus::Stopped)
+    }
 }
@@ -0,0 +1,108 @@
+use super::builder::ContainerBuilder;
+use super::container_wait::{wait_for_process, ExitStatus};
+use super::{tenant_builder, Container, ExecOptions};
+use crate::syscall::syscall::create_syscall;
+use crate::utils;
+use anyhow::{Context, Result};
+use nix::unistd::Pid;
+use oci_spec::runtime::{Process, Spec, User};
+
+/// Process which has been executed in a running container
+#[derive(Debug)]
+pub struct ExecProcess {
+    pid: Pid,
+}
+
+impl ExecProcess {
+    /// Returns the pid of the process
+    pub fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    /// Blocks until the process has exited and returns its exit status. The
+    /// exit code is only known, if the process has been executed with
+    /// [`ExecOptions::subreaper`].
+    pub fn wait(&self) -> Result<ExitStatus> {
+        wait_for_process(self.pid, None, || Ok(()))?
+            .context("wait returned before the process has exited")
+    }
+}
+
+impl Container {
+    /// Executes the process in the namespaces and the cgroup of the running
+    /// container, the same way the exec command does. The working directory
+    /// of the caller is left untouched.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use libcontainer::container::{Container, ExecOptions};
+    /// use oci_spec::runtime::ProcessBuilder;
+    /// use std::path::PathBuf;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let container = Container::load(PathBuf::from("/run/youki/74f1a4cb3801"))?;
+    /// let process = ProcessBuilder::default()
+    ///     .args(vec!["ls".to_owned(), "/".to_owned()])
+    ///     .build()?;
+    /// let opts = ExecOptions {
+    ///     subreaper: true,
+    ///     ..Default::default()
+    /// };
+    /// let exec = container.exec(process, opts)?;
+    /// let status = exec.wait()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn exec(&self, process: Process, opts: ExecOptions) -> Result<ExecProcess> {
+        let root_path = self
+            .root
+            .parent()
+            .context("container directory has no parent")?;
+        if opts.subreaper {
+            utils::set_child_subreaper()?;
+        }
+
+        let syscall = create_syscall();
+        let pid = ContainerBuilder::new(self.id().to_owned(), syscall.as_ref())
+            .with_root_path(root_path)?
+            .with_console_socket(opts.console_socket.as_ref())
+            .with_pid_file(opts.pid_file.as_ref())?
+            .with_preserved_fds(opts.preserve_fds)
+            .as_tenant()
+            .with_process_spec(process)
+            .with_sub_cgroup(opts.sub_cgroup.as_ref())
+            .build()?;
+
+        Ok(ExecProcess { pid })
+    }
+
+    /// Resolves `user[:group]` against /etc/passwd and /etc/group of the
+    /// container, the way the user of a process passed to
+    /// [`exec`](Container::exec) is expected
+    pub fn resolve_user(&self, user: &str) -> Result<User> {
+        let mut spec = Spec::load(self.spec_path())
+            .with_context(|| format!("failed to load spec of container {}", self.id()))?;
+        spec.canonicalize_rootfs(self.bundle())
+            .context("failed to canonicalize rootfs")?;
+        let rootfs = spec.root().as_ref().context("no root in spec")?.path();
+        tenant_builder::resolve_user(rootfs, user)
+            .with_context(|| format!("failed to resolve user {:?}", user))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn test_exec_process_wait() -> Result<()> {
+        let child = Command::new("sh").args(["-c", "exit 5"]).spawn()?;
+        let exec = ExecProcess {
+            pid: Pid::from_raw(child.id() as i32),
+        };
+        assert_eq!(exec.wait()?, ExitStatus::Exited(5));
+        Ok(())
+    }
+}
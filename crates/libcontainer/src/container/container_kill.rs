@@ -1,7 +1,13 @@
-use super::{Container, ContainerStatus};
+use super::{Container, ContainerOperation, ContainerStatus};
 use crate::signal::Signal;
 use anyhow::{bail, Context, Result};
-use nix::sys::signal::{self};
+use nix::{
+    errno::Errno,
+    sys::signal::{self, Signal as NixSignal},
+    unistd::{self, Pid},
+};
+use procfs::process::Process;
+use std::{os::unix::io::RawFd, ptr};
 
 impl Container {
     /// Sends the specified signal to the container init process
@@ -26,17 +32,106 @@ impl Container {
         let signal = signal.into().into_raw();
         self.refresh_status()
             .context("failed to refresh container status")?;
-        if self.can_kill() {
-            log::debug!("kill signal {} to {}", signal, self.pid().unwrap());
-            signal::kill(self.pid().unwrap(), signal)?;
-            self.set_status(ContainerStatus::Stopped).save()?;
-            std::process::exit(0)
-        } else {
-            bail!(
-                "{} could not be killed because it was {:?}",
-                self.id(),
-                self.status()
-            )
+        self.state.check_operation(ContainerOperation::Kill)?;
+        log::debug!("kill signal {} to {}", signal, self.pid().unwrap());
+        self.send_signal(signal)?;
+        self.set_status(ContainerStatus::Stopped).save()?;
+        std::process::exit(0)
+    }
+
+    /// Sends the signal to the container init process through a pidfd, so it
+    /// cannot hit another process which reused the pid. Falls back to kill(2)
+    /// on kernels without pidfd support.
+    pub(crate) fn send_signal(&self, signal: NixSignal) -> Result<()> {
+        let pid = self.pid().context("container has no pid")?;
+        match pidfd_open(pid) {
+            Ok(pidfd) => {
+                // The pidfd refers to the process, which had the pid at the
+                // time it was opened. Validating it afterwards rules out that
+                // the process exits and the pid is reused before signaling.
+                let result = self
+                    .validate_pid(pid)
+                    .and_then(|_| pidfd_send_signal(pidfd, signal).map_err(Into::into));
+                let _ = unistd::close(pidfd);
+                result
+            }
+            Err(Errno::ENOSYS) => {
+                log::debug!("pidfd is not supported, fall back to kill");
+                self.validate_pid(pid)?;
+                signal::kill(pid, signal)?;
+                Ok(())
+            }
+            Err(Errno::ESRCH) => bail!("container process {} does not exist anymore", pid),
+            Err(e) => Err(e).with_context(|| format!("failed to open pidfd of {}", pid)),
+        }
+    }
+
+    /// Checks that the pid still belongs to the container process by comparing
+    /// the start time recorded at creation.
+    pub(super) fn validate_pid(&self, pid: Pid) -> Result<()> {
+        if let Some(pid_start_time) = self.state.pid_start_time {
+            let start_time = Process::new(pid.as_raw())
+                .with_context(|| format!("container process {} does not exist anymore", pid))?
+                .stat
+                .starttime;
+            if start_time != pid_start_time {
+                bail!(
+                    "pid {} of container {} has been reused by another process",
+                    pid,
+                    self.id()
+                );
+            }
         }
+
+        Ok(())
+    }
+}
+
+pub(super) fn pidfd_open(pid: Pid) -> nix::Result<RawFd> {
+    let res = unsafe { libc::syscall(libc::SYS_pidfd_open, pid.as_raw(), 0) };
+    Errno::result(res).map(|fd| fd as RawFd)
+}
+
+fn pidfd_send_signal(pidfd: RawFd, signal: NixSignal) -> nix::Result<()> {
+    let res = unsafe {
+        libc::syscall(
+            libc::SYS_pidfd_send_signal,
+            pidfd,
+            signal as libc::c_int,
+            ptr::null::<libc::siginfo_t>(),
+            0,
+        )
+    };
+    Errno::result(res).map(drop)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn test_send_signal() -> Result<()> {
+        let mut child = Command::new("sleep").arg("10").spawn()?;
+        let mut container = Container::default();
+        container.set_pid(child.id() as i32);
+        assert!(container.state.pid_start_time.is_some());
+
+        // a different start time means that the pid has been reused
+        let start_time = container.state.pid_start_time;
+        container.state.pid_start_time = start_time.map(|t| t + 1);
+        assert!(container.send_signal(NixSignal::SIGKILL).is_err());
+
+        container.state.pid_start_time = start_time;
+        container.send_signal(NixSignal::SIGKILL)?;
+        let status = child.wait()?;
+        assert_eq!(
+            std::os::unix::process::ExitStatusExt::signal(&status),
+            Some(NixSignal::SIGKILL as i32)
+        );
+
+        // the process is gone now
+        assert!(container.send_signal(NixSignal::SIGKILL).is_err());
+        Ok(())
     }
 }
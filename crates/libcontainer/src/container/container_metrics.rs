@@ -0,0 +1,334 @@
+use std::fmt::Display;
+
+use super::{Container, ContainerStatus};
+use anyhow::{bail, Context, Result};
+use libcgroups::common::CgroupSetup;
+use libcgroups::stats::{BlkioDeviceStat, Pressure, Stats};
+use nix::unistd::{sysconf, SysconfVar};
+
+impl Container {
+    /// Renders the cgroup statistics of the container in the Prometheus text
+    /// exposition format, every sample is labeled with the container id.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use libcontainer::container::Container;
+    /// use std::path::PathBuf;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let mut container = Container::load(PathBuf::from("/run/youki/74f1a4cb3801"))?;
+    /// print!("{}", container.metrics_prometheus()?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn metrics_prometheus(&mut self) -> Result<String> {
+        self.refresh_status()
+            .context("failed to refresh container status")?;
+        if !matches!(
+            self.status(),
+            ContainerStatus::Running | ContainerStatus::Paused
+        ) {
+            bail!("{} is not in running or paused state", self.id());
+        }
+
+        let cgroups_path = self.spec()?.cgroup_path;
+        let use_systemd = self
+            .systemd()
+            .context("could not determine cgroup manager")?;
+        let cgroup_manager =
+            libcgroups::common::create_cgroup_manager(cgroups_path, use_systemd, self.id())?;
+        let stats = self.collect_stats(cgroup_manager.as_ref())?;
+
+        let cpu_time_units = match libcgroups::common::get_cgroup_setup()? {
+            CgroupSetup::Unified => CpuTimeUnits::V2,
+            CgroupSetup::Legacy | CgroupSetup::Hybrid => CpuTimeUnits::v1()?,
+        };
+
+        Ok(render_prometheus(self.id(), &stats, cpu_time_units))
+    }
+}
+
+/// Units per second of the cpu times reported by the cgroup. cgroup v2
+/// reports all of them in microseconds. cgroup v1 reports the usage and the
+/// throttled time in nanoseconds, but the user and kernel mode times of
+/// cpuacct.stat in clock ticks (USER_HZ).
+#[derive(Debug, Clone, Copy)]
+struct CpuTimeUnits {
+    usage: f64,
+    user_kernel: f64,
+}
+
+impl CpuTimeUnits {
+    const V2: Self = Self {
+        usage: 1e6,
+        user_kernel: 1e6,
+    };
+
+    fn v1() -> Result<Self> {
+        let clock_ticks = sysconf(SysconfVar::CLK_TCK)
+            .context("failed to get the clock ticks per second")?
+            .context("clock ticks per second are not known")?;
+        Ok(Self {
+            usage: 1e9,
+            user_kernel: clock_ticks as f64,
+        })
+    }
+}
+
+/// Writes the samples of the metric families, each of them is preceded by
+/// its HELP and TYPE line.
+struct Metrics<'a> {
+    id: &'a str,
+    out: String,
+}
+
+impl Metrics<'_> {
+    fn family(&mut self, name: &str, typ: &str, help: &str) -> &mut Self {
+        self.out.push_str(&format!(
+            "# HELP {} {}\n# TYPE {} {}\n",
+            name, help, name, typ
+        ));
+        self
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: impl Display) -> &mut Self {
+        let labels: Vec<String> = [("id", self.id)]
+            .iter()
+            .chain(labels)
+            .map(|(key, value)| format!("{}=\"{}\"", key, escape_label(value)))
+            .collect();
+        self.out
+            .push_str(&format!("{}{{{}}} {}\n", name, labels.join(","), value));
+        self
+    }
+
+    fn single(&mut self, name: &str, typ: &str, help: &str, value: impl Display) {
+        self.family(name, typ, help).sample(name, &[], value);
+    }
+
+    fn blkio(&mut self, name: &str, help: &str, device_stats: &[BlkioDeviceStat]) {
+        self.family(name, "counter", help);
+        for stat in device_stats {
+            let major = stat.major.to_string();
+            let minor = stat.minor.to_string();
+            let mut labels = vec![("major", major.as_str()), ("minor", minor.as_str())];
+            if let Some(op_type) = &stat.op_type {
+                labels.push(("operation", op_type.as_str()));
+            }
+            self.sample(name, &labels, stat.value);
+        }
+    }
+
+    fn pressure(&mut self, resource: &str, pressure: &Pressure) {
+        let waiting = format!("container_pressure_{}_waiting_seconds_total", resource);
+        self.single(
+            &waiting,
+            "counter",
+            &format!("Total time some tasks have been stalled on {}", resource),
+            pressure.some.total as f64 / 1e6,
+        );
+        let stalled = format!("container_pressure_{}_stalled_seconds_total", resource);
+        self.single(
+            &stalled,
+            "counter",
+            &format!("Total time all tasks have been stalled on {}", resource),
+            pressure.full.total as f64 / 1e6,
+        );
+    }
+}
+
+/// Escapes a label value as required by the text exposition format
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn render_prometheus(id: &str, stats: &Stats, cpu_time_units: CpuTimeUnits) -> String {
+    let mut metrics = Metrics {
+        id,
+        out: String::new(),
+    };
+
+    let cpu = &stats.cpu;
+    metrics.single(
+        "container_cpu_usage_seconds_total",
+        "counter",
+        "Cpu time consumed in total",
+        cpu.usage.usage_total as f64 / cpu_time_units.usage,
+    );
+    metrics.single(
+        "container_cpu_user_seconds_total",
+        "counter",
+        "Cpu time consumed in user mode",
+        cpu.usage.usage_user as f64 / cpu_time_units.user_kernel,
+    );
+    metrics.single(
+        "container_cpu_system_seconds_total",
+        "counter",
+        "Cpu time consumed in kernel mode",
+        cpu.usage.usage_kernel as f64 / cpu_time_units.user_kernel,
+    );
+    metrics.single(
+        "container_cpu_cfs_periods_total",
+        "counter",
+        "Number of elapsed enforcement periods",
+        cpu.throttling.periods,
+    );
+    metrics.single(
+        "container_cpu_cfs_throttled_periods_total",
+        "counter",
+        "Number of throttled enforcement periods",
+        cpu.throttling.throttled_periods,
+    );
+    metrics.single(
+        "container_cpu_cfs_throttled_seconds_total",
+        "counter",
+        "Total time the tasks have been throttled",
+        cpu.throttling.throttled_time as f64 / cpu_time_units.usage,
+    );
+
+    let memory = &stats.memory;
+    metrics.single(
+        "container_memory_usage_bytes",
+        "gauge",
+        "Current memory usage",
+        memory.memory.usage,
+    );
+    metrics.single(
+        "container_memory_max_usage_bytes",
+        "gauge",
+        "Maximum recorded memory usage",
+        memory.memory.max_usage,
+    );
+    metrics.single(
+        "container_memory_failures_total",
+        "counter",
+        "Number of times the memory usage hit the limit",
+        memory.memory.fail_count,
+    );
+    metrics.single(
+        "container_memory_cache_bytes",
+        "gauge",
+        "Page cache usage",
+        memory.cache,
+    );
+    metrics.single(
+        "container_oom_events_total",
+        "counter",
+        "Number of processes killed by the OOM killer",
+        memory.oom_kill,
+    );
+
+    metrics.single(
+        "container_pids_current",
+        "gauge",
+        "Number of active pids",
+        stats.pids.current,
+    );
+    metrics.single(
+        "container_pids_limit",
+        "gauge",
+        "Allowed number of active pids",
+        stats
+            .pids
+            .limit
+            .map_or_else(|| "+Inf".to_owned(), |limit| limit.to_string()),
+    );
+
+    metrics.blkio(
+        "container_blkio_io_service_bytes_total",
+        "Number of bytes transferred to and from the device",
+        &stats.blkio.service_bytes,
+    );
+    metrics.blkio(
+        "container_blkio_io_serviced_total",
+        "Number of io operations performed on the device",
+        &stats.blkio.serviced,
+    );
+
+    metrics.pressure("cpu", &stats.psi.cpu);
+    metrics.pressure("memory", &stats.psi.memory);
+    metrics.pressure("io", &stats.psi.io);
+
+    metrics.out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_prometheus() {
+        let mut stats = Stats::default();
+        stats.cpu.usage.usage_total = 1_500_000;
+        stats.memory.memory.usage = 4096;
+        stats.pids.current = 3;
+        stats.blkio.service_bytes = vec![BlkioDeviceStat {
+            major: 8,
+            minor: 0,
+            op_type: Some("Read".to_owned()),
+            value: 512,
+        }];
+        stats.psi.memory.some.total = 250_000;
+
+        let text = render_prometheus("ab\"c", &stats, CpuTimeUnits::V2);
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(
+            lines.contains(&"# HELP container_cpu_usage_seconds_total Cpu time consumed in total")
+        );
+        assert!(lines.contains(&"# TYPE container_cpu_usage_seconds_total counter"));
+        assert!(lines.contains(&"container_cpu_usage_seconds_total{id=\"ab\\\"c\"} 1.5"));
+        assert!(lines.contains(&"# TYPE container_memory_usage_bytes gauge"));
+        assert!(lines.contains(&"container_memory_usage_bytes{id=\"ab\\\"c\"} 4096"));
+        assert!(lines.contains(&"container_pids_current{id=\"ab\\\"c\"} 3"));
+        assert!(lines.contains(&"container_pids_limit{id=\"ab\\\"c\"} +Inf"));
+        assert!(lines.contains(
+            &"container_blkio_io_service_bytes_total{id=\"ab\\\"c\",major=\"8\",minor=\"0\",operation=\"Read\"} 512"
+        ));
+        assert!(
+            lines.contains(&"container_pressure_memory_waiting_seconds_total{id=\"ab\\\"c\"} 0.25")
+        );
+
+        // every sample belongs to a family with HELP and TYPE lines
+        for line in lines.iter().filter(|line| !line.starts_with('#')) {
+            let name = line.split('{').next().unwrap();
+            assert!(
+                lines.contains(&format!("# TYPE {} counter", name).as_str())
+                    || lines.contains(&format!("# TYPE {} gauge", name).as_str())
+            );
+            assert!(lines
+                .iter()
+                .any(|l| l.starts_with(&format!("# HELP {} ", name))));
+        }
+    }
+
+    #[test]
+    fn test_render_prometheus_v1() {
+        // as read from cpuacct.usage and cpuacct.stat
+        let mut stats = Stats::default();
+        stats.cpu.usage.usage_total = 18_198_092_369_681;
+        stats.cpu.usage.usage_user = 1_300_888;
+        stats.cpu.usage.usage_kernel = 364_592;
+        stats.cpu.throttling.throttled_time = 500_000_000;
+
+        let units = CpuTimeUnits {
+            usage: 1e9,
+            user_kernel: 100.0,
+        };
+        let text = render_prometheus("abc", &stats, units);
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines.contains(&"container_cpu_usage_seconds_total{id=\"abc\"} 18198.092369681"));
+        assert!(lines.contains(&"container_cpu_user_seconds_total{id=\"abc\"} 13008.88"));
+        assert!(lines.contains(&"container_cpu_system_seconds_total{id=\"abc\"} 3645.92"));
+        assert!(lines.contains(&"container_cpu_cfs_throttled_seconds_total{id=\"abc\"} 0.5"));
+    }
+
+    #[test]
+    fn test_escape_label() {
+        assert_eq!(escape_label("plain"), "plain");
+        assert_eq!(escape_label("a\\b\"c\nd"), "a\\\\b\\\"c\\nd");
+    }
+}
@@ -1,5 +1,5 @@
-use super::{Container, ContainerStatus};
-use anyhow::{bail, Context, Result};
+use super::{Container, ContainerOperation, ContainerStatus};
+use anyhow::{Context, Result};
 use libcgroups::common::FreezerState;
 
 impl Container {
@@ -24,13 +24,7 @@ impl Container {
         self.refresh_status()
             .context("failed to refresh container status")?;
 
-        if !self.can_pause() {
-            bail!(
-                "{} could not be paused because it was {:?}",
-                self.id(),
-                self.status()
-            );
-        }
+        self.state.check_operation(ContainerOperation::Pause)?;
 
         let cgroups_path = self.spec()?.cgroup_path;
         let use_systemd = self
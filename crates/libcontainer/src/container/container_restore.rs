@@ -0,0 +1,219 @@
+use super::{
+    container::SPEC_FILE, criu, init_builder::read_spec_file, Container, ContainerOperation,
+    ContainerStatus,
+};
+use crate::config::YoukiConfig;
+use crate::container::container::RestoreOptions;
+use crate::container::container_checkpoint::CRIU_DESCRIPTORS_FILE;
+use anyhow::{bail, Context, Result};
+
+use libcgroups::common::{
+    CgroupSetup::{Hybrid, Legacy},
+    ControllerOpt, DEFAULT_CGROUP_ROOT,
+};
+use nix::unistd::Pid;
+use oci_spec::runtime::Spec;
+use std::{fs, path::Path, process::Command};
+
+const CRIU_RESTORE_LOG_FILE: &str = "restore.log";
+const CRIU_RESTORE_PID_FILE: &str = "restore.pid";
+
+impl Container {
+    /// Restores the container from the CRIU images created by checkpoint. The
+    /// container state has to be created before, but the container must not
+    /// be running. CRIU re-creates the namespaces of the container and moves
+    /// the restored processes back into their cgroups.
+    pub fn restore(&mut self, opts: &RestoreOptions) -> Result<Pid> {
+        self.state.check_operation(ContainerOperation::Restore)?;
+        // the restored process is moved into the cgroup of the manager it has
+        // been created with, so it has to be known before restoring
+        let use_systemd = self
+            .systemd()
+            .context("container state does not contain cgroup manager")?;
+
+        let (mut spec, _, _) = read_spec_file(&self.spec_path(), self.bundle())?;
+        spec.canonicalize_rootfs(self.bundle())
+            .context("failed to canonicalize rootfs")?;
+        // the restored container has not been created by the builder, so the
+        // spec is kept like the builder does for the other commands
+        spec.save(self.root.join(SPEC_FILE))
+            .context("failed to save spec")?;
+        let config = YoukiConfig::from_spec(&spec, self.id(), false)?;
+        config.save(&self.root).context("failed to save config")?;
+
+        // The restored init process becomes a child of the caller, so it can
+        // be waited for when not running detached.
+        let pid_file = self.root.join(CRIU_RESTORE_PID_FILE);
+        let log_dir = opts.work_path.as_ref().unwrap_or(&opts.image_path);
+        let criu_path = criu::criu_path(opts.criu_path.as_ref());
+        criu::check_version(&criu_path, &[criu::RESTORE])?;
+        let mut criu = Command::new(&criu_path);
+        criu.arg("restore")
+            .arg("--images-dir")
+            .arg(&opts.image_path)
+            .arg("--work-dir")
+            .arg(log_dir)
+            .arg("--root")
+            .arg(spec.root().as_ref().context("no root in spec")?.path())
+            .arg("--pidfile")
+            .arg(&pid_file)
+            .arg("--log-file")
+            .arg(CRIU_RESTORE_LOG_FILE)
+            .arg("-v4")
+            .args([
+                "--restore-detached",
+                "--restore-sibling",
+                "--manage-cgroups",
+            ]);
+        if criu::has_terminal(&spec) {
+            criu.arg("--orphan-pts-master");
+        }
+        if opts.ext_unix_sk {
+            criu.arg("--ext-unix-sk");
+        }
+        if opts.shell_job {
+            criu.arg("--shell-job");
+        }
+        if opts.tcp_established {
+            criu.arg("--tcp-established");
+        }
+        if opts.file_locks {
+            criu.arg("--file-locks");
+        }
+
+        for external in external_mounts(&spec)? {
+            criu.arg("--external").arg(external);
+        }
+
+        for inherit_fd in inherit_fds(&opts.image_path)? {
+            criu.arg("--inherit-fd").arg(inherit_fd);
+        }
+
+        log::debug!("restore container {} with {:?}", self.id(), criu);
+        let status = criu
+            .status()
+            .with_context(|| format!("failed to execute {:?}", criu_path))?;
+        if !status.success() {
+            let log_file = log_dir.join(CRIU_RESTORE_LOG_FILE);
+            let log = fs::read_to_string(&log_file)
+                .unwrap_or_else(|e| format!("failed to read {}: {}", log_file.display(), e));
+            bail!(
+                "restoring container {} failed with {}. CRIU log {}:\n{}",
+                self.id(),
+                status,
+                log_file.display(),
+                log
+            );
+        }
+
+        let pid = fs::read_to_string(&pid_file)
+            .with_context(|| format!("failed to read {:?}", pid_file))?
+            .trim()
+            .parse::<i32>()
+            .context("failed to parse restored pid")?;
+        let _ = fs::remove_file(&pid_file);
+        let pid = Pid::from_raw(pid);
+
+        // Make sure the restored process ends up in the container cgroup with
+        // the configured resource restrictions, the same way create does.
+        let cmanager = libcgroups::common::create_cgroup_manager(
+            &config.cgroup_path,
+            use_systemd,
+            self.id(),
+        )?;
+        cmanager.add_task(pid)?;
+        if let Some(resources) = spec.linux().as_ref().and_then(|l| l.resources().as_ref()) {
+            cmanager.apply(&ControllerOpt {
+                resources,
+                freezer_state: None,
+                oom_score_adj: None,
+                disable_oom_killer: false,
+            })?;
+        }
+
+        self.set_status(ContainerStatus::Running)
+            .set_creator(nix::unistd::geteuid().as_raw())
+            .set_pid(pid.as_raw())
+            .save()
+            .context("failed to save container state")?;
+
+        log::debug!("container {} restored with pid {}", self.id(), pid);
+        Ok(pid)
+    }
+}
+
+/// Returns the external mounts, which have been declared as external during
+/// checkpoint, mapped to their (possibly changed) source.
+fn external_mounts(spec: &Spec) -> Result<Vec<String>> {
+    let mut externals = Vec::new();
+    for m in spec.mounts().iter().flatten() {
+        match m.typ().as_deref() {
+            Some("bind") => {
+                let dest = m.destination().display();
+                let source = m
+                    .source()
+                    .as_ref()
+                    .with_context(|| format!("bind mount {} has no source", dest))?;
+                externals.push(format!("mnt[{}]:{}", dest, source.display()));
+            }
+            Some("cgroup") => {
+                match libcgroups::common::get_cgroup_setup()
+                    .context("failed to determine cgroup setup")?
+                {
+                    Legacy | Hybrid => {
+                        for mp in libcgroups::v1::util::list_subsystem_mount_points()
+                            .context("failed to get subsystem mount points")?
+                        {
+                            if mp.starts_with(DEFAULT_CGROUP_ROOT) {
+                                externals.push(format!("mnt[{0}]:{0}", mp.display()));
+                            }
+                        }
+                    }
+                    _ => (),
+                }
+            }
+            _ => (),
+        }
+    }
+
+    Ok(externals)
+}
+
+/// Reads the stdio descriptors recorded during checkpoint and returns the
+/// inherit-fd options for the ones which are neither files nor terminals,
+/// e.g. pipes, so that CRIU connects them to the stdio of the caller.
+fn inherit_fds(image_path: &Path) -> Result<Vec<String>> {
+    let descriptors_path = image_path.join(CRIU_DESCRIPTORS_FILE);
+    let descriptors: Vec<String> = serde_json::from_str(
+        &fs::read_to_string(&descriptors_path)
+            .with_context(|| format!("failed to read {:?}", descriptors_path))?,
+    )
+    .with_context(|| format!("failed to parse {:?}", descriptors_path))?;
+
+    Ok(descriptors
+        .iter()
+        .enumerate()
+        .filter(|(_, descriptor)| !descriptor.starts_with('/'))
+        .map(|(fd, descriptor)| format!("fd[{}]:{}", fd, descriptor))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::create_temp_dir;
+
+    #[test]
+    fn test_inherit_fds() -> Result<()> {
+        let tmp = create_temp_dir("test_inherit_fds")?;
+        fs::write(
+            tmp.join(CRIU_DESCRIPTORS_FILE),
+            r#"["/dev/null", "pipe:[1234]", "/dev/pts/0"]"#,
+        )?;
+        assert_eq!(inherit_fds(&tmp)?, vec!["fd[1]:pipe:[1234]".to_string()]);
+
+        fs::remove_file(tmp.join(CRIU_DESCRIPTORS_FILE))?;
+        assert!(inherit_fds(&tmp).is_err());
+        Ok(())
+    }
+}
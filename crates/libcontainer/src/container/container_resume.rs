@@ -1,6 +1,6 @@
-use super::{Container, ContainerStatus};
+use super::{Container, ContainerOperation, ContainerStatus};
 
-use anyhow::{bail, Context, Result};
+use anyhow::{Context, Result};
 use libcgroups::common::FreezerState;
 
 impl Container {
@@ -26,13 +26,7 @@ impl Container {
             .context("failed to refresh container status")?;
         // check if container can be resumed :
         // for example, a running process cannot be resumed
-        if !self.can_resume() {
-            bail!(
-                "{} could not be resumed because it was {:?}",
-                self.id(),
-                self.status()
-            );
-        }
+        self.state.check_operation(ContainerOperation::Resume)?;
 
         let cgroups_path = self.spec()?.cgroup_path;
         let use_systemd = self
@@ -1,12 +1,11 @@
 use crate::{
     config::YoukiConfig,
+    exec_fifo::{self, EXEC_FIFO_FILE},
     hooks,
-    notify_socket::{NotifySocket, NOTIFY_FILE},
 };
 
-use super::{Container, ContainerStatus};
-use anyhow::{bail, Context, Result};
-use nix::unistd;
+use super::{Container, ContainerOperation, ContainerStatus};
+use anyhow::{Context, Result};
 
 impl Container {
     /// Starts a previously created container
@@ -30,15 +29,7 @@ impl Container {
         self.refresh_status()
             .context("failed to refresh container status")?;
 
-        if !self.can_start() {
-            let err_msg = format!(
-                "{} could not be started because it was {:?}",
-                self.id(),
-                self.status()
-            );
-            log::error!("{}", err_msg);
-            bail!(err_msg);
-        }
+        self.state.check_operation(ContainerOperation::Start)?;
 
         let config = YoukiConfig::load(&self.root)
             .with_context(|| format!("failed to load runtime spec for container {}", self.id()))?;
@@ -50,10 +41,8 @@ impl Container {
                 .with_context(|| "failed to run pre start hooks")?;
         }
 
-        unistd::chdir(self.root.as_os_str())?;
-
-        let mut notify_socket = NotifySocket::new(&self.root.join(NOTIFY_FILE));
-        notify_socket.notify_container_start()?;
+        let init_pid = self.pid().context("container has no init process")?;
+        exec_fifo::notify_container_start(&self.root.join(EXEC_FIFO_FILE), init_pid)?;
         self.set_status(ContainerStatus::Running)
             .save()
             .with_context(|| format!("could not save state for container {}", self.id()))?;
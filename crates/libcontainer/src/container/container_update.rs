@@ -0,0 +1,147 @@
+use super::{Container, ContainerOperation};
+use anyhow::{Context, Result};
+use libcgroups::common::ControllerOpt;
+use oci_spec::runtime::LinuxResources;
+use serde_json::{Map, Value};
+
+impl Container {
+    /// Updates the resources of the container. Only the controllers the
+    /// resources contain are applied, and fields that are omitted keep the
+    /// value they had before, like runc does for a partial `LinuxResources`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use libcontainer::container::builder::ContainerBuilder;
+    /// use libcontainer::syscall::syscall::create_syscall;
+    /// use oci_spec::runtime::{LinuxPidsBuilder, LinuxResourcesBuilder};
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let mut container = ContainerBuilder::new("74f1a4cb3801".to_owned(), create_syscall().as_ref())
+    /// .as_init("/var/run/docker/bundle")
+    /// .build()?;
+    ///
+    /// let resources = LinuxResourcesBuilder::default()
+    ///     .pids(LinuxPidsBuilder::default().limit(100).build()?)
+    ///     .build()?;
+    /// container.update(&resources)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn update(&mut self, resources: &LinuxResources) -> Result<()> {
+        self.refresh_status()
+            .context("failed to refresh container status")?;
+        self.state.check_operation(ContainerOperation::Update)?;
+
+        let mut config = self.spec()?;
+        let (merged, changed) = merge_resources(config.resources.as_ref(), resources)?;
+
+        let use_systemd = self
+            .systemd()
+            .context("container state does not contain cgroup manager")?;
+        let cmanager =
+            libcgroups::common::create_cgroup_manager(&config.cgroup_path, use_systemd, self.id())?;
+        cmanager.apply(&ControllerOpt {
+            resources: &changed,
+            disable_oom_killer: false,
+            oom_score_adj: None,
+            freezer_state: None,
+        })?;
+
+        config.resources = Some(merged);
+        config
+            .save(&self.root)
+            .context("failed to save updated resources")?;
+        Ok(())
+    }
+}
+
+/// Merges the update over the saved resources. Returns the merged resources
+/// and the ones of the controllers contained in the update, which are the
+/// merged ones, so that an omitted field does not reset the current value.
+fn merge_resources(
+    saved: Option<&LinuxResources>,
+    update: &LinuxResources,
+) -> Result<(LinuxResources, LinuxResources)> {
+    let mut merged = match saved {
+        Some(saved) => serde_json::to_value(saved)?,
+        None => Value::Object(Map::new()),
+    };
+    let update = serde_json::to_value(update)?;
+    merge_value(&mut merged, &update);
+
+    let changed: Map<String, Value> = match (&merged, &update) {
+        (Value::Object(merged), Value::Object(update)) => update
+            .keys()
+            .filter_map(|key| merged.get(key).map(|value| (key.clone(), value.clone())))
+            .collect(),
+        _ => Map::new(),
+    };
+
+    Ok((
+        serde_json::from_value(merged).context("failed to merge resources")?,
+        serde_json::from_value(Value::Object(changed)).context("failed to merge resources")?,
+    ))
+}
+
+/// Merges objects recursively, any other value of the update replaces the
+/// current one. Lists like the device rules are replaced as a whole.
+fn merge_value(current: &mut Value, update: &Value) {
+    match (current, update) {
+        (Value::Object(current), Value::Object(update)) => {
+            for (key, value) in update {
+                match current.get_mut(key) {
+                    Some(current) => merge_value(current, value),
+                    None => {
+                        current.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        (current, update) => *current = update.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oci_spec::runtime::{LinuxMemoryBuilder, LinuxPidsBuilder, LinuxResourcesBuilder};
+
+    #[test]
+    fn test_merge_resources() -> Result<()> {
+        let saved = LinuxResourcesBuilder::default()
+            .memory(
+                LinuxMemoryBuilder::default()
+                    .limit(1024 * 1024 * 1024)
+                    .reservation(512 * 1024 * 1024)
+                    .build()?,
+            )
+            .pids(LinuxPidsBuilder::default().limit(100).build()?)
+            .build()?;
+        let update: LinuxResources = serde_json::from_str(r#"{"memory": {"limit": 268435456}}"#)?;
+
+        let (merged, changed) = merge_resources(Some(&saved), &update)?;
+        let memory = merged.memory().as_ref().unwrap();
+        assert_eq!(memory.limit(), Some(268435456));
+        assert_eq!(memory.reservation(), Some(512 * 1024 * 1024));
+        assert_eq!(merged.pids(), saved.pids());
+
+        // only the memory controller is applied, with the saved reservation
+        assert_eq!(changed.memory(), merged.memory());
+        assert!(changed.pids().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_resources_without_saved() -> Result<()> {
+        let update = LinuxResourcesBuilder::default()
+            .pids(LinuxPidsBuilder::default().limit(10).build()?)
+            .build()?;
+
+        let (merged, changed) = merge_resources(None, &update)?;
+        assert_eq!(merged.pids(), update.pids());
+        assert_eq!(changed.pids(), update.pids());
+        assert!(changed.memory().is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,244 @@
+use super::container_kill::pidfd_open;
+use super::{Container, ContainerStatus};
+use anyhow::{bail, Context, Result};
+use nix::errno::Errno;
+use nix::poll::{poll, PollFd, PollFlags};
+use nix::unistd::{self, Pid};
+use procfs::process::{ProcState, Process};
+use std::os::unix::io::RawFd;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// waitid(2) id type to wait for the process a pidfd refers to
+const P_PIDFD: libc::idtype_t = 3;
+
+/// Exit status of the container init process or of an executed process
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitStatus {
+    /// The process has exited with the code
+    Exited(i32),
+    /// The process has been terminated by the signal
+    Signaled(i32),
+    /// The process has exited, but it has been reaped by its parent before
+    /// the status could be read
+    Unknown,
+}
+
+impl ExitStatus {
+    /// Decodes the status as returned by waitpid(2)
+    fn from_raw(status: i32) -> Self {
+        match status & 0x7f {
+            0 => Self::Exited((status >> 8) & 0xff),
+            // stopped and continued processes have not exited
+            0x7f => Self::Unknown,
+            signal => Self::Signaled(signal),
+        }
+    }
+}
+
+impl Container {
+    /// Blocks until the init process of the container has exited and returns
+    /// its exit status. It does not have to be called from the parent of the
+    /// init process, e.g. when the container has been created detached.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use libcontainer::container::builder::ContainerBuilder;
+    /// use libcontainer::syscall::syscall::create_syscall;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let mut container = ContainerBuilder::new("74f1a4cb3801".to_owned(), create_syscall().as_ref())
+    /// .as_init("/var/run/docker/bundle")
+    /// .build()?;
+    ///
+    /// container.start()?;
+    /// let status = container.wait()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn wait(&self) -> Result<ExitStatus> {
+        self.wait_for_exit(None)?
+            .context("wait returned before the init process has exited")
+    }
+
+    /// Same as wait, but gives up after the timeout has elapsed, in which case
+    /// None is returned.
+    pub fn wait_timeout(&self, timeout: Duration) -> Result<Option<ExitStatus>> {
+        self.wait_for_exit(Some(timeout))
+    }
+
+    fn wait_for_exit(&self, timeout: Option<Duration>) -> Result<Option<ExitStatus>> {
+        if matches!(
+            self.status(),
+            ContainerStatus::Creating | ContainerStatus::Created
+        ) {
+            bail!("container {} has never been started", self.id());
+        }
+        let pid = self.pid().context("container has no init process")?;
+        wait_for_process(pid, timeout, || self.validate_pid(pid))
+    }
+}
+
+/// Waits for the process, which does not have to be a child of the caller.
+/// The validation runs once the pid has been pinned by a pidfd, so it can not
+/// be reused by another process in between.
+pub(super) fn wait_for_process(
+    pid: Pid,
+    timeout: Option<Duration>,
+    validate: impl Fn() -> Result<()>,
+) -> Result<Option<ExitStatus>> {
+    match pidfd_open(pid) {
+        Ok(pidfd) => {
+            let result = validate().and_then(|_| wait_pidfd(pidfd, pid, timeout));
+            let _ = unistd::close(pidfd);
+            result
+        }
+        // the process has already exited and been reaped
+        Err(Errno::ESRCH) => Ok(Some(ExitStatus::Unknown)),
+        Err(Errno::ENOSYS) => {
+            log::debug!("pidfd is not supported, fall back to polling");
+            validate()?;
+            wait_polling(pid, timeout)
+        }
+        Err(e) => Err(e).context("failed to open pidfd"),
+    }
+}
+
+/// The pidfd becomes readable, once the process has exited
+fn wait_pidfd(pidfd: RawFd, pid: Pid, timeout: Option<Duration>) -> Result<Option<ExitStatus>> {
+    let start = Instant::now();
+    loop {
+        let remaining = match timeout {
+            Some(timeout) => match timeout.checked_sub(start.elapsed()) {
+                Some(remaining) => remaining.as_millis().min(i32::MAX as u128) as i32,
+                None => return Ok(None),
+            },
+            None => -1,
+        };
+
+        let mut fds = [PollFd::new(pidfd, PollFlags::POLLIN)];
+        match poll(&mut fds, remaining) {
+            Ok(0) => return Ok(None),
+            Ok(_) => break,
+            Err(Errno::EINTR) => continue,
+            Err(e) => return Err(e).context("failed to poll pidfd"),
+        }
+    }
+
+    exit_status(pidfd, pid).map(Some)
+}
+
+/// Reads the exit status of the exited process. Only the parent can reap it,
+/// everyone else reads the status of the zombie from procfs.
+fn exit_status(pidfd: RawFd, pid: Pid) -> Result<ExitStatus> {
+    let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+    let res = unsafe {
+        libc::waitid(
+            P_PIDFD,
+            pidfd as libc::id_t,
+            &mut info,
+            libc::WEXITED | libc::WNOHANG,
+        )
+    };
+    match Errno::result(res) {
+        Ok(_) => {
+            let status = unsafe { info.si_status() };
+            return Ok(match info.si_code {
+                libc::CLD_EXITED => ExitStatus::Exited(status),
+                libc::CLD_KILLED | libc::CLD_DUMPED => ExitStatus::Signaled(status),
+                _ => ExitStatus::Unknown,
+            });
+        }
+        Err(Errno::ECHILD) => {}
+        Err(e) => return Err(e).context("failed to wait for process"),
+    }
+
+    Ok(Process::new(pid.as_raw())
+        .ok()
+        .and_then(|process| process.stat.exit_code)
+        .map_or(ExitStatus::Unknown, ExitStatus::from_raw))
+}
+
+fn wait_polling(pid: Pid, timeout: Option<Duration>) -> Result<Option<ExitStatus>> {
+    let start = Instant::now();
+    loop {
+        match Process::new(pid.as_raw()) {
+            Ok(process) => {
+                if matches!(process.stat.state()?, ProcState::Zombie | ProcState::Dead) {
+                    return Ok(Some(
+                        process
+                            .stat
+                            .exit_code
+                            .map_or(ExitStatus::Unknown, ExitStatus::from_raw),
+                    ));
+                }
+            }
+            Err(_) => return Ok(Some(ExitStatus::Unknown)),
+        }
+
+        if matches!(timeout, Some(timeout) if start.elapsed() >= timeout) {
+            return Ok(None);
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::create_temp_dir;
+    use std::path::PathBuf;
+    use std::process::Command;
+
+    fn running_container(name: &str, pid: u32) -> Result<(Container, crate::utils::TempDir)> {
+        let tmp = create_temp_dir(name)?;
+        let container = Container::new(
+            "container",
+            ContainerStatus::Running,
+            Some(pid as i32),
+            &PathBuf::from("."),
+            &tmp,
+        )?;
+        Ok((container, tmp))
+    }
+
+    #[test]
+    fn test_wait_exit_code() -> Result<()> {
+        let child = Command::new("sh").args(["-c", "exit 3"]).spawn()?;
+        let (container, _tmp) = running_container("test_wait_exit_code", child.id())?;
+        assert_eq!(container.wait()?, ExitStatus::Exited(3));
+        Ok(())
+    }
+
+    #[test]
+    fn test_wait_timeout() -> Result<()> {
+        let child = Command::new("sleep").arg("100").spawn()?;
+        let (container, _tmp) = running_container("test_wait_timeout", child.id())?;
+        assert_eq!(container.wait_timeout(Duration::from_millis(100))?, None);
+
+        nix::sys::signal::kill(
+            Pid::from_raw(child.id() as i32),
+            nix::sys::signal::Signal::SIGTERM,
+        )?;
+        assert_eq!(
+            container.wait_timeout(Duration::from_secs(5))?,
+            Some(ExitStatus::Signaled(libc::SIGTERM))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_wait_never_started() -> Result<()> {
+        let (mut container, _tmp) = running_container("test_wait_never_started", 1)?;
+        container.set_status(ContainerStatus::Created);
+        assert!(container.wait().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_exit_status_from_raw() {
+        assert_eq!(ExitStatus::from_raw(3 << 8), ExitStatus::Exited(3));
+        assert_eq!(ExitStatus::from_raw(libc::SIGKILL), ExitStatus::Signaled(9));
+    }
+}
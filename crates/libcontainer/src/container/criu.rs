@@ -0,0 +1,163 @@
+//! Checks shared by checkpoint and restore, that the CRIU binary provides the
+//! features the requested options require.
+//!
+//! Checkpoint and restore both run the criu binary. The rust-criu bindings
+//! can neither restore nor pre-dump with parent images, so they are only
+//! used to query the version over RPC, which the binary does not offer in a
+//! parseable form.
+use anyhow::{anyhow, bail, Result};
+use oci_spec::runtime::Spec;
+use std::path::{Path, PathBuf};
+
+/// Binary used if no path to CRIU is given, looked up in PATH
+const DEFAULT_CRIU_BINARY: &str = "criu";
+
+/// The version RPC, through which the requirements are checked, has been
+/// added in CRIU 3.0
+const VERSION_RPC: u32 = 30000;
+
+/// Feature of CRIU, which checkpoint, restore or one of their options require
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct CriuFeature {
+    /// Name of the option or operation requiring the feature
+    pub name: &'static str,
+    /// Version providing the feature, encoded as major * 10000 + minor * 100
+    /// + sublevel, the same way the version RPC of CRIU reports it
+    pub version: u32,
+}
+
+pub(super) const CHECKPOINT: CriuFeature = CriuFeature {
+    name: "checkpoint",
+    version: VERSION_RPC,
+};
+pub(super) const RESTORE: CriuFeature = CriuFeature {
+    name: "restore",
+    version: VERSION_RPC,
+};
+/// Pre-dumps with memory tracking predate the version RPC, so any CRIU which
+/// reports its version supports them
+pub(super) const PRE_DUMP: CriuFeature = CriuFeature {
+    name: "--pre-dump",
+    version: VERSION_RPC,
+};
+
+/// Returns the CRIU binary to use
+pub(super) fn criu_path(path: Option<&PathBuf>) -> PathBuf {
+    path.cloned()
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_CRIU_BINARY))
+}
+
+/// Returns whether the init process of the container has a pseudo terminal.
+/// CRIU only has to take over its master, i.e. be passed
+/// `--orphan-pts-master`, if it does.
+pub(super) fn has_terminal(spec: &Spec) -> bool {
+    spec.process()
+        .as_ref()
+        .and_then(|process| process.terminal())
+        .unwrap_or(false)
+}
+
+/// Queries the version of CRIU over RPC and fails, if it is older than any
+/// of the features require
+pub(super) fn check_version(criu_path: &Path, features: &[CriuFeature]) -> Result<()> {
+    let mut criu = rust_criu::Criu::new_with_criu_path(criu_path.to_string_lossy().into_owned())
+        .map_err(|e| anyhow!("failed to set up CRIU {:?}: {}", criu_path, e))?;
+    let version = criu.get_criu_version().map_err(|e| {
+        anyhow!(
+            "failed to query the version of CRIU {:?}, CRIU {} or newer is required: {}",
+            criu_path,
+            format_version(VERSION_RPC),
+            e
+        )
+    })?;
+    log::debug!(
+        "CRIU {:?} has version {}",
+        criu_path,
+        format_version(version)
+    );
+    validate_version(version, features)
+}
+
+fn validate_version(version: u32, features: &[CriuFeature]) -> Result<()> {
+    let missing: Vec<String> = features
+        .iter()
+        .filter(|feature| feature.version > version)
+        .map(|feature| {
+            format!(
+                "{} requires CRIU {}",
+                feature.name,
+                format_version(feature.version)
+            )
+        })
+        .collect();
+    if !missing.is_empty() {
+        bail!(
+            "CRIU {} is too old: {}",
+            format_version(version),
+            missing.join(", ")
+        );
+    }
+    Ok(())
+}
+
+fn format_version(version: u32) -> String {
+    format!(
+        "{}.{}.{}",
+        version / 10000,
+        version / 100 % 100,
+        version % 100
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_has_terminal() -> anyhow::Result<()> {
+        use oci_spec::runtime::{ProcessBuilder, SpecBuilder};
+
+        let spec = SpecBuilder::default()
+            .process(ProcessBuilder::default().terminal(true).build()?)
+            .build()?;
+        assert!(has_terminal(&spec));
+        let spec = SpecBuilder::default()
+            .process(ProcessBuilder::default().terminal(false).build()?)
+            .build()?;
+        assert!(!has_terminal(&spec));
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_version() {
+        assert_eq!(format_version(30000), "3.0.0");
+        assert_eq!(format_version(31502), "3.15.2");
+    }
+
+    #[test]
+    fn test_validate_version() {
+        let pre_dump = CriuFeature {
+            name: "--pre-dump",
+            version: 31100,
+        };
+        assert!(validate_version(31100, &[CHECKPOINT, pre_dump]).is_ok());
+        assert!(validate_version(31500, &[CHECKPOINT]).is_ok());
+
+        let err = validate_version(30500, &[CHECKPOINT, pre_dump])
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            err,
+            "CRIU 3.5.0 is too old: --pre-dump requires CRIU 3.11.0"
+        );
+        let err = validate_version(20800, &[RESTORE]).unwrap_err().to_string();
+        assert_eq!(err, "CRIU 2.8.0 is too old: restore requires CRIU 3.0.0");
+    }
+
+    #[test]
+    fn test_criu_path() {
+        assert_eq!(criu_path(None), PathBuf::from("criu"));
+        let path = PathBuf::from("/usr/local/sbin/criu");
+        assert_eq!(criu_path(Some(&path)), path);
+    }
+}
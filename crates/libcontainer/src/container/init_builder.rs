@@ -1,23 +1,75 @@
-use anyhow::{bail, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
+use nix::mount::MsFlags;
 use nix::unistd;
-use oci_spec::runtime::Spec;
+use oci_spec::runtime::{LinuxNamespaceType, LinuxSeccomp, Mount as SpecMount, PosixRlimit, Spec};
 use rootless::Rootless;
 use std::{
+    collections::HashMap,
     fs,
+    os::unix::io::RawFd,
     path::{Path, PathBuf},
 };
 
-use crate::{apparmor, config::YoukiConfig, notify_socket::NOTIFY_FILE, rootless, tty, utils};
+use crate::{
+    apparmor,
+    config::YoukiConfig,
+    exec_fifo::EXEC_FIFO_FILE,
+    rootfs::{
+        rootfs::parse_rootfs_propagation,
+        utils::{open_userns, parse_idmap, parse_mount},
+    },
+    rootless,
+    sd_notify::NotifyProxy,
+    seccomp::{self, SeccompOptions},
+    sysctl,
+    time_namespace::TimeNamespace,
+    tty, utils,
+};
 
 use super::{
-    builder::ContainerBuilder, builder_impl::ContainerBuilderImpl, Container, ContainerStatus,
+    builder::ContainerBuilder, builder_impl::ContainerBuilderImpl, container::SPEC_FILE, Container,
+    ContainerStatus,
 };
 
-// Builder that can be used to configure the properties of a new container
+/// Builder that can be used to configure the properties of a new container
+///
+/// # Example
+///
+/// ```no_run
+/// use libcontainer::container::builder::ContainerBuilder;
+/// use libcontainer::syscall::syscall::create_syscall;
+/// use nix::sys::signal::Signal;
+///
+/// # fn main() -> anyhow::Result<()> {
+/// let syscall = create_syscall();
+/// let mut container = ContainerBuilder::new("74f1a4cb3801".to_owned(), syscall.as_ref())
+///     .with_pid_file(Some("/var/run/docker.pid"))?
+///     .with_console_socket(Some("/var/run/docker/sock.tty"))
+///     .as_init("/var/run/docker/bundle")
+///     .with_systemd_cgroup(false)
+///     .with_detach(true)
+///     .create()?;
+///
+/// container.start()?;
+/// println!("{:?}", container.state());
+/// container.kill(Signal::SIGTERM)?;
+/// container.delete(true)?;
+/// # Ok(())
+/// # }
+/// ```
 pub struct InitContainerBuilder<'a> {
     base: ContainerBuilder<'a>,
     bundle: PathBuf,
+    spec_path: Option<PathBuf>,
+    spec: Option<Spec>,
     use_systemd: bool,
+    no_pivot: bool,
+    env: HashMap<String, String>,
+    bpf_audit: Option<RawFd>,
+    notify_socket: Option<PathBuf>,
+    allow_existing_cgroup: bool,
+    seccomp_options: SeccompOptions,
+    detach: bool,
 }
 
 impl<'a> InitContainerBuilder<'a> {
@@ -27,32 +79,146 @@ impl<'a> InitContainerBuilder<'a> {
         Self {
             base: builder,
             bundle,
+            spec_path: None,
+            spec: None,
             use_systemd: true,
+            no_pivot: false,
+            env: HashMap::new(),
+            bpf_audit: None,
+            notify_socket: None,
+            allow_existing_cgroup: false,
+            seccomp_options: SeccompOptions::default(),
+            detach: true,
         }
     }
 
     /// Sets if systemd should be used for managing cgroups
-    pub fn with_systemd(mut self, should_use: bool) -> Self {
+    pub fn with_systemd_cgroup(mut self, should_use: bool) -> Self {
         self.use_systemd = should_use;
         self
     }
 
-    /// Creates a new container
-    pub fn build(self) -> Result<Container> {
-        let spec = self.load_spec().context("failed to load spec")?;
+    /// Sets if systemd should be used for managing cgroups
+    #[deprecated(note = "use with_systemd_cgroup instead")]
+    pub fn with_systemd(self, should_use: bool) -> Self {
+        self.with_systemd_cgroup(should_use)
+    }
+
+    /// Sets if the container is detached from the caller, which is the
+    /// default. Otherwise the caller becomes the child subreaper before the
+    /// container is created, so it can wait for the init process, which is
+    /// forked by the intermediate process and therefore not its own child.
+    pub fn with_detach(mut self, detach: bool) -> Self {
+        self.detach = detach;
+        self
+    }
+
+    /// Sets the path the runtime spec is read from instead of the config.json
+    /// of the bundle, e.g. /dev/stdin. The rootfs is still taken from the bundle.
+    pub fn with_spec_path<P: Into<PathBuf>>(mut self, path: Option<P>) -> Self {
+        self.spec_path = path.map(|p| p.into());
+        self
+    }
+
+    /// Sets the runtime spec of the container, which is used instead of
+    /// reading it from a file. The rootfs is still taken from the bundle.
+    pub fn with_spec(mut self, spec: Spec) -> Self {
+        self.spec = Some(spec);
+        self
+    }
+
+    /// Sets if the rootfs should be moved to / and chrooted into instead of
+    /// using pivot_root, which is not possible on e.g. a ramdisk
+    pub fn with_no_pivot(mut self, no_pivot: bool) -> Self {
+        self.no_pivot = no_pivot;
+        self
+    }
+
+    /// Sets environment variables of the container process, which take
+    /// precedence over the ones of the runtime spec
+    pub fn with_env(mut self, env: HashMap<String, String>) -> Self {
+        self.env = env;
+        self
+    }
+
+    /// Sets an eBPF LSM program, e.g. one auditing device opens and mounts,
+    /// which is attached to the cgroup of the container (cgroup v2 only).
+    /// The program is loaded and owned by the caller, the fd has to stay
+    /// open until the container has been created.
+    pub fn with_bpf_audit(mut self, prog_fd: RawFd) -> Self {
+        self.bpf_audit = Some(prog_fd);
+        self
+    }
+
+    /// Sets the notify socket of the service manager, to which the readiness
+    /// notifications of the container process are relayed once it is started
+    pub fn with_notify_socket<P: Into<PathBuf>>(mut self, path: Option<P>) -> Self {
+        self.notify_socket = path.map(|p| p.into());
+        self
+    }
+
+    /// Sets if the container may use a cgroup, which already contains
+    /// processes or is used by another container
+    pub fn with_allow_existing_cgroup(mut self, allow: bool) -> Self {
+        self.allow_existing_cgroup = allow;
+        self
+    }
+
+    /// Sets the options for translating the seccomp profile of the spec into
+    /// a filter, which are used by validate as well
+    pub fn with_seccomp_options(mut self, options: SeccompOptions) -> Self {
+        self.seccomp_options = options;
+        self
+    }
+
+    /// Creates a new container, which is started with [Container::start]
+    pub fn create(self) -> Result<Container> {
+        if !self.detach {
+            utils::set_child_subreaper()?;
+        }
+
+        let (mut spec, time_namespace, spec_modified) =
+            self.load_spec().context("failed to load spec")?;
+        if self.no_pivot {
+            validate_no_pivot(&spec)?;
+        }
+        let cgroups_path_resolved = resolve_cgroups_path(&mut spec)?
+            || set_default_rootless_cgroups_path(
+                &mut spec,
+                &self.base.container_id,
+                self.use_systemd,
+            )?;
+        if !self.allow_existing_cgroup {
+            self.validate_cgroup_unused(&spec)?;
+        }
         let container_dir = self
             .create_container_dir()
             .context("failed to create container dir")?;
+        if let Some(notify_socket) = &self.notify_socket {
+            NotifyProxy::new(notify_socket, &container_dir)
+                .setup_spec(&mut spec)
+                .context("failed to set up notify socket")?;
+        }
+        if self.spec.is_some()
+            || self.spec_path.is_some()
+            || !self.env.is_empty()
+            || self.notify_socket.is_some()
+            || cgroups_path_resolved
+            || spec_modified
+        {
+            // other commands read the spec later on, but not from the bundle,
+            // as it has been read from elsewhere or has been modified
+            spec.save(container_dir.join(SPEC_FILE))
+                .context("failed to save spec")?;
+        }
 
         let mut container = self
-            .create_container_state(&container_dir)
+            .create_container_state(&container_dir, &spec)
             .context("failed to create container state")?;
-        container
-            .set_systemd(self.use_systemd)
-            .set_annotations(spec.annotations().clone());
+        container.set_systemd(self.use_systemd);
 
         unistd::chdir(&container_dir)?;
-        let notify_path = container_dir.join(NOTIFY_FILE);
+        let notify_path = container_dir.join(EXEC_FIFO_FILE);
         // convert path of root file system of the container to absolute path
         let rootfs = fs::canonicalize(&spec.root().as_ref().context("no root in spec")?.path())?;
 
@@ -80,6 +246,9 @@ impl<'a> InitContainerBuilder<'a> {
             container_id: self.base.container_id,
             pid_file: self.base.pid_file,
             console_socket: csocketfd,
+            // in the foreground, the caller forwards the window size of its
+            // terminal to the container
+            keep_console_master: !self.detach,
             use_systemd: self.use_systemd,
             spec: &spec,
             rootfs,
@@ -87,14 +256,194 @@ impl<'a> InitContainerBuilder<'a> {
             notify_path,
             container: Some(container.clone()),
             preserve_fds: self.base.preserve_fds,
+            no_pivot: self.no_pivot,
+            bpf_audit: self.bpf_audit,
+            time_namespace,
+            seccomp_options: self.seccomp_options,
         };
 
-        builder_impl.create()?;
+        let (_, console_master) = builder_impl.create()?;
         container.refresh_state()?;
+        container.console_master = console_master;
 
         Ok(container)
     }
 
+    /// Creates a new container, the same as [create](Self::create)
+    pub fn build(self) -> Result<Container> {
+        self.create()
+    }
+
+    /// Validates the bundle the same way build does, but without creating the
+    /// container state, its namespaces or cgroups. Instead of stopping at the
+    /// first problem, every problem found is returned.
+    pub fn validate(&self) -> Vec<anyhow::Error> {
+        let mut spec = match self.read_spec() {
+            Ok((spec, _, _)) => spec,
+            Err(e) => return vec![e],
+        };
+
+        let mut problems = Vec::new();
+        if self.base.root_path.join(&self.base.container_id).exists() {
+            problems.push(anyhow!(
+                "container {} already exists",
+                self.base.container_id
+            ));
+        }
+        if let Err(e) = Self::validate_spec(&spec) {
+            problems.push(e);
+        }
+        if let Err(e) = self.merge_env(&mut spec) {
+            problems.push(e);
+        }
+        if let Err(e) = spec.canonicalize_rootfs(&self.bundle) {
+            problems.push(anyhow::Error::from(e).context("failed to canonicalize rootfs"));
+        }
+        if let Err(e) =
+            set_default_rootless_cgroups_path(&mut spec, &self.base.container_id, self.use_systemd)
+        {
+            problems.push(e);
+        }
+
+        let linux = match spec.linux() {
+            Some(linux) => linux,
+            None => {
+                problems.push(anyhow!("no linux in spec"));
+                return problems;
+            }
+        };
+
+        if let Some(seccomp) = linux.seccomp() {
+            if let Err(e) = seccomp::validate_seccomp_with_options(seccomp, &self.seccomp_options) {
+                problems.push(e.context("invalid seccomp profile"));
+            }
+            if let Err(e) = validate_seccomp_notify(seccomp) {
+                problems.push(e);
+            }
+        }
+
+        if let Err(e) = parse_rootfs_propagation(linux.rootfs_propagation().as_deref()) {
+            problems.push(e);
+        }
+
+        if self.no_pivot {
+            if let Err(e) = validate_no_pivot(&spec) {
+                problems.push(e);
+            }
+        }
+
+        match Rootless::new(&spec) {
+            Ok(rootless) => {
+                let cgroup_path = utils::get_cgroup_path(
+                    linux.cgroups_path(),
+                    &self.base.container_id,
+                    rootless.is_some(),
+                );
+                if let Err(e) = utils::create_cgroup_manager(
+                    &cgroup_path,
+                    self.use_systemd,
+                    rootless.is_some(),
+                    &self.base.container_id,
+                ) {
+                    problems.push(e.context(format!("invalid cgroups path {:?}", cgroup_path)));
+                }
+            }
+            Err(e) => problems.push(e),
+        }
+
+        if !self.allow_existing_cgroup {
+            if let Err(e) = self.validate_cgroup_unused(&spec) {
+                problems.push(e);
+            }
+        }
+
+        for mount in spec.mounts().iter().flatten() {
+            if let Err(e) = self.validate_mount(mount) {
+                problems.push(e);
+            }
+        }
+
+        if let Some(rlimits) = spec.process().as_ref().and_then(|p| p.rlimits().as_ref()) {
+            problems.extend(validate_rlimits(rlimits));
+        }
+
+        problems
+    }
+
+    fn validate_mount(&self, mount: &SpecMount) -> Result<()> {
+        let destination = mount.destination();
+        if !destination.is_absolute() {
+            bail!("mount destination {:?} is not absolute", destination);
+        }
+
+        let (flags, _) = parse_mount(mount);
+        let is_bind = mount.typ().as_deref() == Some("bind") || flags.contains(MsFlags::MS_BIND);
+        if let Some(userns) = parse_idmap(mount)? {
+            if !is_bind {
+                bail!("idmapped mount {:?} is not a bind mount", destination);
+            }
+            open_userns(&userns)?;
+        }
+        if is_bind {
+            let source = mount
+                .source()
+                .as_ref()
+                .with_context(|| format!("bind mount {:?} has no source", destination))?;
+            if !self.bundle.join(source).exists() {
+                bail!(
+                    "source {:?} of bind mount {:?} does not exist",
+                    source,
+                    destination
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the cgroup of the container is neither used by another
+    /// container nor contains processes, which would end up being managed
+    /// together with the container, e.g. killed when it is deleted
+    fn validate_cgroup_unused(&self, spec: &Spec) -> Result<()> {
+        let linux = spec.linux().as_ref().context("no linux in spec")?;
+        let rootless = Rootless::new(spec)?.is_some();
+        let cgroups_path =
+            utils::get_cgroup_path(linux.cgroups_path(), &self.base.container_id, rootless);
+
+        if let Some(owner) =
+            find_cgroup_owner(&self.base.root_path, &self.base.container_id, &cgroups_path)
+        {
+            bail!(
+                "cgroup {:?} is already used by container {}",
+                cgroups_path,
+                owner
+            );
+        }
+
+        let cmanager = utils::create_cgroup_manager(
+            &cgroups_path,
+            self.use_systemd,
+            rootless,
+            &self.base.container_id,
+        )?;
+        match cmanager.get_all_pids() {
+            Ok(pids) if !pids.is_empty() => bail!(
+                "cgroup {:?} already contains the processes {:?}",
+                cgroups_path,
+                pids
+            ),
+            Ok(_) => {}
+            // the cgroup is created along with the container
+            Err(e) if is_not_found(&e) => {}
+            Err(e) => log::warn!(
+                "could not check if cgroup {:?} is in use: {:?}",
+                cgroups_path,
+                e
+            ),
+        }
+        Ok(())
+    }
+
     fn create_container_dir(&self) -> Result<PathBuf> {
         let container_dir = self.base.root_path.join(&self.base.container_id);
         log::debug!("container directory will be {:?}", container_dir);
@@ -108,14 +457,49 @@ impl<'a> InitContainerBuilder<'a> {
         Ok(container_dir)
     }
 
-    fn load_spec(&self) -> Result<Spec> {
-        let source_spec_path = self.bundle.join("config.json");
-        let mut spec = Spec::load(&source_spec_path)?;
+    fn source_spec_path(&self) -> PathBuf {
+        self.spec_path
+            .clone()
+            .unwrap_or_else(|| self.bundle.join(SPEC_FILE))
+    }
+
+    /// Reads the spec along with its time namespace and reports whether it
+    /// differs from the spec file, in which case other commands cannot read it
+    /// from the file anymore
+    fn read_spec(&self) -> Result<(Spec, Option<TimeNamespace>, bool)> {
+        if let Some(spec) = &self.spec {
+            return Ok((spec.clone(), None, false));
+        }
+
+        read_spec_file(&self.source_spec_path(), &self.bundle)
+    }
+
+    fn load_spec(&self) -> Result<(Spec, Option<TimeNamespace>, bool)> {
+        let (mut spec, time_namespace, modified) = self.read_spec()?;
         Self::validate_spec(&spec).context("failed to validate runtime spec")?;
+        self.merge_env(&mut spec)?;
 
         spec.canonicalize_rootfs(&self.bundle)
             .context("failed to canonicalize rootfs")?;
-        Ok(spec)
+        Ok((spec, time_namespace, modified))
+    }
+
+    /// Sets the environment variables passed to the builder in the process of
+    /// the spec
+    fn merge_env(&self, spec: &mut Spec) -> Result<()> {
+        if self.env.is_empty() {
+            return Ok(());
+        }
+
+        let mut process = spec
+            .process()
+            .clone()
+            .context("environment variables are set, but the spec has no process")?;
+        let mut env = process.env().clone().unwrap_or_default();
+        utils::merge_env(&mut env, &self.env);
+        process.set_env(Some(env));
+        spec.set_process(Some(process));
+        Ok(())
     }
 
     fn validate_spec(spec: &Spec) -> Result<()> {
@@ -138,18 +522,489 @@ impl<'a> InitContainerBuilder<'a> {
             }
         }
 
+        if let Some(linux) = spec.linux() {
+            if let Some(kernel_params) = linux.sysctl() {
+                sysctl::validate_sysctl(
+                    kernel_params,
+                    linux.namespaces().as_deref().unwrap_or_default(),
+                )
+                .context("invalid sysctl")?;
+            }
+        }
+
+        validate_domainname(spec)?;
+
         Ok(())
     }
 
-    fn create_container_state(&self, container_dir: &Path) -> Result<Container> {
-        let container = Container::new(
+    fn create_container_state(&self, container_dir: &Path, spec: &Spec) -> Result<Container> {
+        let mut container = Container::new(
             &self.base.container_id,
             ContainerStatus::Creating,
             None,
             &self.bundle,
             container_dir,
         )?;
+        // the annotations are part of the state from the beginning, so they
+        // can be queried without reading the spec
+        container.set_annotations(spec.annotations().clone());
         container.save()?;
         Ok(container)
     }
 }
+
+/// A chroot does not contain a process, which is root in its user namespace,
+/// as it could simply chroot out of it.
+fn validate_no_pivot(spec: &Spec) -> Result<()> {
+    let has_user_ns = spec
+        .linux()
+        .as_ref()
+        .and_then(|linux| linux.namespaces().as_ref())
+        .map_or(false, |namespaces| {
+            namespaces
+                .iter()
+                .any(|ns| ns.typ() == LinuxNamespaceType::User)
+        });
+    if has_user_ns {
+        bail!("--no-pivot can not be used together with a user namespace");
+    }
+    Ok(())
+}
+
+/// The domainname is only set in a new uts namespace, it would change the one
+/// of the host or of the joined namespace otherwise.
+fn validate_domainname(spec: &Spec) -> Result<()> {
+    let domainname = match spec.domainname() {
+        Some(domainname) => domainname,
+        None => return Ok(()),
+    };
+    let has_new_uts_ns = spec
+        .linux()
+        .as_ref()
+        .and_then(|linux| linux.namespaces().as_ref())
+        .map_or(false, |namespaces| {
+            namespaces
+                .iter()
+                .any(|ns| ns.typ() == LinuxNamespaceType::Uts && ns.path().is_none())
+        });
+    if !has_new_uts_ns {
+        bail!(
+            "domainname {} can only be set in a new uts namespace",
+            domainname
+        );
+    }
+    Ok(())
+}
+
+/// Returns the id of the container, other than the one with the given id,
+/// whose state refers to the cgroup
+fn find_cgroup_owner(root_path: &Path, container_id: &str, cgroups_path: &Path) -> Option<String> {
+    fs::read_dir(root_path)
+        .ok()?
+        .flatten()
+        .filter(|entry| entry.file_name() != container_id)
+        .filter_map(|entry| Container::load(entry.path()).ok())
+        .find(|container| {
+            container
+                .spec()
+                .map_or(false, |config| config.cgroup_path == cgroups_path)
+        })
+        .map(|container| container.id().to_owned())
+}
+
+fn is_not_found(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        cause
+            .downcast_ref::<std::io::Error>()
+            .map_or(false, |e| e.kind() == std::io::ErrorKind::NotFound)
+    })
+}
+
+/// Replaces a relative cgroups path by the absolute one under the cgroup of
+/// youki, so that later commands, e.g. exec or delete, use the same cgroup
+/// regardless of the cgroup they are run in. Returns true if the path changed.
+fn resolve_cgroups_path(spec: &mut Spec) -> Result<bool> {
+    let mut linux = match spec.linux() {
+        Some(linux) => linux.clone(),
+        None => return Ok(false),
+    };
+    let cgroups_path = match linux.cgroups_path() {
+        Some(cgroups_path) => cgroups_path,
+        None => return Ok(false),
+    };
+
+    let resolved = libcgroups::common::resolve_cgroups_path(cgroups_path)
+        .with_context(|| format!("failed to resolve cgroups path {:?}", cgroups_path))?;
+    if &resolved == cgroups_path {
+        return Ok(false);
+    }
+
+    log::debug!("resolved cgroups path {:?} to {:?}", cgroups_path, resolved);
+    linux.set_cgroups_path(Some(resolved));
+    spec.set_linux(Some(linux));
+    Ok(true)
+}
+
+/// Places a rootless container without a cgroups path in the cgroup subtree
+/// delegated to the user, unless its cgroup is managed by systemd. Returns true
+/// if the path has been set.
+fn set_default_rootless_cgroups_path(
+    spec: &mut Spec,
+    container_id: &str,
+    use_systemd: bool,
+) -> Result<bool> {
+    let mut linux = match spec.linux() {
+        Some(linux) if linux.cgroups_path().is_none() => linux.clone(),
+        _ => return Ok(false),
+    };
+    if use_systemd
+        || Rootless::new(spec)?.is_none()
+        || !matches!(
+            libcgroups::common::get_cgroup_setup()?,
+            libcgroups::common::CgroupSetup::Unified
+        )
+    {
+        return Ok(false);
+    }
+
+    let cgroups_path = libcgroups::common::delegation_boundary()
+        .context("failed to find the cgroup delegated to the user")?
+        .join("youki")
+        .join(container_id);
+    log::debug!("placing rootless container in cgroup {:?}", cgroups_path);
+    linux.set_cgroups_path(Some(cgroups_path));
+    spec.set_linux(Some(linux));
+    Ok(true)
+}
+
+/// The notify fd is only available in the init process while the container is
+/// created. It is handed over to the seccomp agent at the listener path right
+/// away, so a container, which is started later, already has a connected agent.
+fn validate_seccomp_notify(seccomp: &LinuxSeccomp) -> Result<()> {
+    if seccomp::is_notify(seccomp) && seccomp.listener_path().is_none() {
+        bail!("seccomp notify requires a listener path to send the notify fd to");
+    }
+    Ok(())
+}
+
+/// Reads the spec file of a bundle. Limits of -1 are normalized, a referenced
+/// seccomp profile is resolved and the time namespace is taken out of the
+/// spec, in which case the spec is reported as modified.
+pub(super) fn read_spec_file(
+    spec_path: &Path,
+    bundle: &Path,
+) -> Result<(Spec, Option<TimeNamespace>, bool)> {
+    let load = || -> Result<(Spec, Option<TimeNamespace>, bool)> {
+        let content = fs::read_to_string(spec_path)?;
+        let mut value: serde_json::Value = serde_json::from_str(&content)?;
+        let normalized = normalize_rlimits(&mut value);
+        let resolved = resolve_seccomp_ref(&mut value, bundle)?;
+        let time_namespace = TimeNamespace::take_from_spec(&mut value)?;
+        let modified = normalized || resolved || time_namespace.is_some();
+        Ok((serde_json::from_value(value)?, time_namespace, modified))
+    };
+    load().with_context(|| format!("failed to load spec from {:?}", spec_path))
+}
+
+/// Replaces soft and hard limits of -1, which the runtime spec uses for
+/// unlimited like runc does, with the maximum value of u64 as it cannot be
+/// deserialized otherwise
+fn normalize_rlimits(spec: &mut serde_json::Value) -> bool {
+    let mut normalized = false;
+    let rlimits = spec
+        .pointer_mut("/process/rlimits")
+        .and_then(|rlimits| rlimits.as_array_mut());
+    for rlimit in rlimits.into_iter().flatten() {
+        for key in ["soft", "hard"] {
+            if let Some(limit) = rlimit.get_mut(key) {
+                if limit.as_i64() == Some(-1) {
+                    *limit = u64::MAX.into();
+                    normalized = true;
+                }
+            }
+        }
+    }
+    normalized
+}
+
+/// As an extension of the runtime spec, the seccomp profile can be kept in a
+/// separate file, which is referenced by `"seccomp": {"$ref": "<path>"}`. The
+/// path is relative to the bundle and must not escape it. Returns true if the
+/// reference has been replaced by the profile.
+fn resolve_seccomp_ref(spec: &mut serde_json::Value, bundle: &Path) -> Result<bool> {
+    let seccomp = match spec.pointer_mut("/linux/seccomp") {
+        Some(seccomp) => seccomp,
+        None => return Ok(false),
+    };
+    let reference = match seccomp.get("$ref") {
+        Some(reference) => reference
+            .as_str()
+            .context("seccomp $ref has to be a path")?,
+        None => return Ok(false),
+    };
+    if seccomp.as_object().map_or(0, |seccomp| seccomp.len()) > 1 {
+        bail!("seccomp $ref cannot be combined with an inline profile");
+    }
+
+    let reference = Path::new(reference);
+    if reference.is_absolute() {
+        bail!(
+            "seccomp profile {:?} has to be relative to the bundle",
+            reference
+        );
+    }
+    let bundle = fs::canonicalize(bundle)
+        .with_context(|| format!("failed to canonicalize bundle {:?}", bundle))?;
+    let profile_path = fs::canonicalize(bundle.join(reference))
+        .with_context(|| format!("failed to resolve seccomp profile {:?}", reference))?;
+    if !profile_path.starts_with(&bundle) {
+        bail!(
+            "seccomp profile {:?} resolves to {:?}, which is outside of the bundle {:?}",
+            reference,
+            profile_path,
+            bundle
+        );
+    }
+
+    let content = fs::read_to_string(&profile_path)
+        .with_context(|| format!("failed to read seccomp profile {:?}", profile_path))?;
+    let profile: LinuxSeccomp = serde_json::from_str(&content)
+        .with_context(|| format!("failed to parse seccomp profile {:?}", profile_path))?;
+    *seccomp = serde_json::to_value(profile)?;
+    Ok(true)
+}
+
+fn validate_rlimits(rlimits: &[PosixRlimit]) -> Vec<anyhow::Error> {
+    let mut problems = Vec::new();
+    for (i, rlimit) in rlimits.iter().enumerate() {
+        if rlimits[..i].iter().any(|r| r.typ() == rlimit.typ()) {
+            problems.push(anyhow!(
+                "rlimit {:?} is specified more than once",
+                rlimit.typ()
+            ));
+        }
+        if rlimit.soft() > rlimit.hard() {
+            problems.push(anyhow!(
+                "soft limit {} of rlimit {:?} exceeds the hard limit {}",
+                rlimit.soft(),
+                rlimit.typ(),
+                rlimit.hard()
+            ));
+        }
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::create_temp_dir;
+    use oci_spec::runtime::{
+        LinuxBuilder, LinuxNamespaceBuilder, LinuxSeccompAction, LinuxSeccompBuilder,
+        LinuxSyscallBuilder, PosixRlimitBuilder, PosixRlimitType,
+    };
+
+    #[test]
+    fn test_find_cgroup_owner() -> Result<()> {
+        let root = create_temp_dir("test_find_cgroup_owner")?;
+        let other_root = root.join("other");
+        fs::create_dir(&other_root)?;
+        let other = Container::new(
+            "other",
+            ContainerStatus::Stopped,
+            None,
+            Path::new("."),
+            &other_root,
+        )?;
+        other.save()?;
+        let mut spec = Spec::default();
+        spec.set_linux(Some(
+            LinuxBuilder::default()
+                .cgroups_path(PathBuf::from("/shared"))
+                .build()?,
+        ));
+        YoukiConfig::from_spec(&spec, other.id(), false)?.save(&other_root)?;
+        // entries, which are not containers, are skipped
+        fs::create_dir(root.join("not-a-container"))?;
+
+        assert_eq!(
+            find_cgroup_owner(&root, "new", Path::new("/shared")),
+            Some("other".to_owned())
+        );
+        assert_eq!(find_cgroup_owner(&root, "new", Path::new("/new")), None);
+        assert_eq!(
+            find_cgroup_owner(&root, "other", Path::new("/shared")),
+            None
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_not_found() {
+        let err = anyhow::Error::from(std::io::Error::from(std::io::ErrorKind::NotFound))
+            .context("failed to read cgroup");
+        assert!(is_not_found(&err));
+        assert!(!is_not_found(&anyhow!("subsystem does not exist")));
+    }
+
+    #[test]
+    fn test_validate_rlimits() -> Result<()> {
+        let nofile = PosixRlimitBuilder::default()
+            .typ(PosixRlimitType::RlimitNofile)
+            .soft(1024u64)
+            .hard(4096u64)
+            .build()?;
+        let nproc = PosixRlimitBuilder::default()
+            .typ(PosixRlimitType::RlimitNproc)
+            .soft(2048u64)
+            .hard(1024u64)
+            .build()?;
+
+        assert!(validate_rlimits(&[nofile.clone()]).is_empty());
+        assert_eq!(validate_rlimits(&[nofile.clone(), nofile, nproc]).len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_normalize_rlimits() -> Result<()> {
+        let mut value = serde_json::json!({
+            "process": {
+                "rlimits": [
+                    {"type": "RLIMIT_CORE", "soft": -1, "hard": -1},
+                    {"type": "RLIMIT_NOFILE", "soft": 1024, "hard": 4096}
+                ]
+            }
+        });
+        assert!(normalize_rlimits(&mut value));
+
+        let rlimits: Vec<PosixRlimit> = serde_json::from_value(value["process"]["rlimits"].take())?;
+        assert_eq!(rlimits[0].soft(), u64::MAX);
+        assert_eq!(rlimits[0].hard(), u64::MAX);
+        assert_eq!(rlimits[1].soft(), 1024);
+        assert_eq!(rlimits[1].hard(), 4096);
+
+        // a spec without rlimits is kept as it is
+        let mut value = serde_json::json!({"process": {"cwd": "/"}});
+        assert!(!normalize_rlimits(&mut value));
+        assert_eq!(value, serde_json::json!({"process": {"cwd": "/"}}));
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_seccomp_ref() -> Result<()> {
+        let tmp = create_temp_dir("test_resolve_seccomp_ref")?;
+        let bundle = tmp.join("bundle");
+        fs::create_dir_all(bundle.join("profiles"))?;
+        fs::write(
+            bundle.join("profiles/seccomp.json"),
+            r#"{"defaultAction": "SCMP_ACT_ERRNO", "syscalls": [{"names": ["read"], "action": "SCMP_ACT_ALLOW"}]}"#,
+        )?;
+        fs::write(
+            tmp.join("outside.json"),
+            r#"{"defaultAction": "SCMP_ACT_ALLOW"}"#,
+        )?;
+
+        let mut value =
+            serde_json::json!({"linux": {"seccomp": {"$ref": "profiles/seccomp.json"}}});
+        assert!(resolve_seccomp_ref(&mut value, &bundle)?);
+        let seccomp: LinuxSeccomp = serde_json::from_value(value["linux"]["seccomp"].take())?;
+        assert_eq!(seccomp.default_action(), LinuxSeccompAction::ScmpActErrno);
+        assert_eq!(seccomp.syscalls().as_ref().map(|s| s.len()), Some(1));
+
+        // the inline form is kept as it is
+        let inline = serde_json::json!({"linux": {"seccomp": {"defaultAction": "SCMP_ACT_ALLOW"}}});
+        let mut value = inline.clone();
+        assert!(!resolve_seccomp_ref(&mut value, &bundle)?);
+        assert_eq!(value, inline);
+
+        for reference in ["../outside.json", "/etc/passwd", "profiles/missing.json"] {
+            let mut value = serde_json::json!({"linux": {"seccomp": {"$ref": reference}}});
+            assert!(resolve_seccomp_ref(&mut value, &bundle).is_err());
+        }
+
+        // a symlink must not lead out of the bundle either
+        std::os::unix::fs::symlink(tmp.join("outside.json"), bundle.join("link.json"))?;
+        let mut value = serde_json::json!({"linux": {"seccomp": {"$ref": "link.json"}}});
+        assert!(resolve_seccomp_ref(&mut value, &bundle).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_env() -> Result<()> {
+        let syscall = crate::syscall::syscall::create_syscall();
+        let builder = ContainerBuilder::new("test_merge_env".to_owned(), syscall.as_ref())
+            .as_init("/tmp/bundle")
+            .with_env(HashMap::from([("FOO".to_owned(), "bar".to_owned())]));
+
+        let mut spec = Spec::default();
+        builder.merge_env(&mut spec)?;
+        let env = spec
+            .process()
+            .as_ref()
+            .and_then(|process| process.env().clone())
+            .unwrap_or_default();
+        assert!(env.contains(&"FOO=bar".to_owned()), "{:?}", env);
+
+        // the variables would be silently dropped otherwise
+        spec.set_process(None);
+        assert!(builder.merge_env(&mut spec).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_no_pivot() -> Result<()> {
+        let mut spec = Spec::default();
+        assert!(validate_no_pivot(&spec).is_ok());
+
+        let namespaces = vec![LinuxNamespaceBuilder::default()
+            .typ(LinuxNamespaceType::User)
+            .build()?];
+        spec.set_linux(Some(
+            LinuxBuilder::default().namespaces(namespaces).build()?,
+        ));
+        assert!(validate_no_pivot(&spec).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_domainname() -> Result<()> {
+        let mut spec = Spec::default();
+        assert!(validate_domainname(&spec).is_ok());
+
+        spec.set_domainname(Some("youki.test".to_owned()));
+        spec.set_linux(Some(LinuxBuilder::default().namespaces(vec![]).build()?));
+        assert!(validate_domainname(&spec).is_err());
+
+        let joined = LinuxNamespaceBuilder::default()
+            .typ(LinuxNamespaceType::Uts)
+            .path("/proc/1/ns/uts")
+            .build()?;
+        spec.set_linux(Some(
+            LinuxBuilder::default().namespaces(vec![joined]).build()?,
+        ));
+        assert!(validate_domainname(&spec).is_err());
+
+        let new = LinuxNamespaceBuilder::default()
+            .typ(LinuxNamespaceType::Uts)
+            .build()?;
+        spec.set_linux(Some(LinuxBuilder::default().namespaces(vec![new]).build()?));
+        assert!(validate_domainname(&spec).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_seccomp_notify() -> Result<()> {
+        let mut seccomp = LinuxSeccompBuilder::default()
+            .default_action(LinuxSeccompAction::ScmpActAllow)
+            .syscalls(vec![LinuxSyscallBuilder::default()
+                .names(vec![String::from("mount")])
+                .action(LinuxSeccompAction::ScmpActNotify)
+                .build()?])
+            .build()?;
+        assert!(validate_seccomp_notify(&seccomp).is_err());
+
+        seccomp.set_listener_path(Some(PathBuf::from("/run/seccomp-agent.sock")));
+        assert!(validate_seccomp_notify(&seccomp).is_ok());
+        Ok(())
+    }
+}
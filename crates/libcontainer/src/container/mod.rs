@@ -11,13 +11,24 @@ mod container;
 mod container_checkpoint;
 mod container_delete;
 mod container_events;
+mod container_exec;
 mod container_kill;
+mod container_metrics;
 mod container_pause;
+mod container_restore;
 mod container_resume;
 mod container_start;
+mod container_update;
+mod container_wait;
+mod criu;
 pub mod init_builder;
 pub mod state;
 pub mod tenant_builder;
 pub use container::CheckpointOptions;
 pub use container::Container;
-pub use state::{ContainerProcessState, ContainerStatus, State};
+pub use container::ExecOptions;
+pub use container::RestoreOptions;
+pub use container_delete::PRESERVABLE_NAMESPACES;
+pub use container_exec::ExecProcess;
+pub use container_wait::ExitStatus;
+pub use state::{ContainerOperation, ContainerProcessState, ContainerStatus, State};
@@ -6,7 +6,7 @@ use std::io::BufReader;
 use std::path::PathBuf;
 use std::{fs::File, path::Path};
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
@@ -58,6 +58,65 @@ impl ContainerStatus {
     }
 }
 
+/// Operations of the container lifecycle, which are only valid in some of
+/// the statuses of the container
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ContainerOperation {
+    Start,
+    Kill,
+    Delete,
+    Exec,
+    Pause,
+    Resume,
+    Checkpoint,
+    Restore,
+    Update,
+}
+
+impl Display for ContainerOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let print = match *self {
+            Self::Start => "start",
+            Self::Kill => "kill",
+            Self::Delete => "delete",
+            Self::Exec => "exec into",
+            Self::Pause => "pause",
+            Self::Resume => "resume",
+            Self::Checkpoint => "checkpoint",
+            Self::Restore => "restore",
+            Self::Update => "update",
+        };
+
+        write!(f, "{}", print)
+    }
+}
+
+impl ContainerStatus {
+    /// Checks if the operation is a valid transition from this status
+    pub fn allows(&self, operation: ContainerOperation) -> bool {
+        match operation {
+            ContainerOperation::Start => self.can_start(),
+            ContainerOperation::Kill => self.can_kill(),
+            ContainerOperation::Delete => self.can_delete(),
+            ContainerOperation::Exec => *self == ContainerStatus::Running,
+            ContainerOperation::Pause | ContainerOperation::Checkpoint => self.can_pause(),
+            ContainerOperation::Resume => self.can_resume(),
+            ContainerOperation::Restore => *self == ContainerStatus::Creating,
+            ContainerOperation::Update => *self != ContainerStatus::Stopped,
+        }
+    }
+
+    fn describe(&self) -> &'static str {
+        match self {
+            ContainerStatus::Creating => "still being created",
+            ContainerStatus::Created => "created, but not started",
+            ContainerStatus::Running => "running",
+            ContainerStatus::Stopped => "stopped",
+            ContainerStatus::Paused => "paused",
+        }
+    }
+}
+
 impl Display for ContainerStatus {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let print = match *self {
@@ -98,6 +157,10 @@ pub struct State {
     pub creator: Option<u32>,
     // Specifies if systemd should be used to manage cgroups
     pub use_systemd: Option<bool>,
+    // Start time of the container process in clock ticks after boot, which
+    // identifies the process together with the pid, even if the pid is reused
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pid_start_time: Option<u64>,
 }
 
 impl State {
@@ -119,6 +182,7 @@ impl State {
             created: None,
             creator: None,
             use_systemd: None,
+            pid_start_time: None,
         }
     }
 
@@ -146,6 +210,30 @@ impl State {
         Ok(state)
     }
 
+    /// Checks that the operation is a valid transition from the current status
+    /// of the container, otherwise the error explains why it is not.
+    pub fn check_operation(&self, operation: ContainerOperation) -> Result<()> {
+        if self.status.allows(operation) {
+            return Ok(());
+        }
+
+        let hint = match (operation, self.status) {
+            (ContainerOperation::Delete, ContainerStatus::Created)
+            | (ContainerOperation::Delete, ContainerStatus::Running)
+            | (ContainerOperation::Delete, ContainerStatus::Paused) => {
+                ", use force to kill it first"
+            }
+            _ => "",
+        };
+        bail!(
+            "cannot {} container {} because it is {}{}",
+            operation,
+            self.id,
+            self.status.describe(),
+            hint
+        )
+    }
+
     /// Returns the path to the state JSON file for the provided `container_root`.
     ///
     /// ```
@@ -181,6 +269,7 @@ pub struct ContainerProcessState {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::utils::create_temp_dir;
 
     #[test]
     fn test_creating_status() {
@@ -231,4 +320,88 @@ mod tests {
         assert!(!cstatus.can_pause());
         assert!(cstatus.can_resume());
     }
+
+    #[test]
+    fn test_check_operation() {
+        let state = |status| State::new("youki", status, None, PathBuf::from("."));
+        let error = |status, operation| {
+            state(status)
+                .check_operation(operation)
+                .unwrap_err()
+                .to_string()
+        };
+
+        assert!(state(ContainerStatus::Created)
+            .check_operation(ContainerOperation::Start)
+            .is_ok());
+        assert_eq!(
+            error(ContainerStatus::Running, ContainerOperation::Start),
+            "cannot start container youki because it is running"
+        );
+        assert_eq!(
+            error(ContainerStatus::Stopped, ContainerOperation::Start),
+            "cannot start container youki because it is stopped"
+        );
+        assert_eq!(
+            error(ContainerStatus::Stopped, ContainerOperation::Kill),
+            "cannot kill container youki because it is stopped"
+        );
+        assert_eq!(
+            error(ContainerStatus::Creating, ContainerOperation::Kill),
+            "cannot kill container youki because it is still being created"
+        );
+        assert_eq!(
+            error(ContainerStatus::Running, ContainerOperation::Delete),
+            "cannot delete container youki because it is running, use force to kill it first"
+        );
+        assert_eq!(
+            error(ContainerStatus::Created, ContainerOperation::Exec),
+            "cannot exec into container youki because it is created, but not started"
+        );
+        assert_eq!(
+            error(ContainerStatus::Paused, ContainerOperation::Pause),
+            "cannot pause container youki because it is paused"
+        );
+        assert_eq!(
+            error(ContainerStatus::Running, ContainerOperation::Resume),
+            "cannot resume container youki because it is running"
+        );
+        assert_eq!(
+            error(ContainerStatus::Stopped, ContainerOperation::Checkpoint),
+            "cannot checkpoint container youki because it is stopped"
+        );
+        assert_eq!(
+            error(ContainerStatus::Created, ContainerOperation::Restore),
+            "cannot restore container youki because it is created, but not started"
+        );
+        assert_eq!(
+            error(ContainerStatus::Stopped, ContainerOperation::Update),
+            "cannot update container youki because it is stopped"
+        );
+    }
+
+    #[test]
+    fn test_save_load_annotations() -> Result<()> {
+        let tmp = create_temp_dir("test_save_load_annotations")?;
+        let mut state = State::new(
+            "container",
+            ContainerStatus::Created,
+            None,
+            PathBuf::from("."),
+        );
+        state.annotations = Some(HashMap::from([(
+            "org.opencontainers.image.title".to_owned(),
+            "youki".to_owned(),
+        )]));
+        state.save(&tmp)?;
+
+        let json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(State::file_path(&tmp))?)?;
+        assert_eq!(
+            json["annotations"]["org.opencontainers.image.title"],
+            "youki"
+        );
+        assert_eq!(State::load(&tmp)?.annotations, state.annotations);
+        Ok(())
+    }
 }
@@ -1,10 +1,11 @@
 use anyhow::{bail, Context, Result};
 use caps::Capability;
-use nix::unistd;
+use libcgroups::common::CGROUP_PROCS;
+use nix::unistd::Pid;
 use oci_spec::runtime::{
     Capabilities as SpecCapabilities, Capability as SpecCapability, LinuxBuilder,
     LinuxCapabilities, LinuxCapabilitiesBuilder, LinuxNamespace, LinuxNamespaceBuilder,
-    LinuxNamespaceType, Process, ProcessBuilder, Spec,
+    LinuxNamespaceType, Process, ProcessBuilder, Spec, User, UserBuilder,
 };
 use procfs::process::Namespace;
 
@@ -18,9 +19,9 @@ use std::{
 };
 
 use crate::{capabilities::CapabilityExt, container::builder_impl::ContainerBuilderImpl};
-use crate::{notify_socket::NotifySocket, rootless::Rootless, tty, utils};
+use crate::{notify_socket::NotifySocket, rootless::Rootless, seccomp::SeccompOptions, tty, utils};
 
-use super::{builder::ContainerBuilder, Container};
+use super::{builder::ContainerBuilder, Container, ContainerOperation};
 
 const NAMESPACE_TYPES: &[&str] = &["ipc", "uts", "net", "pid", "mnt", "cgroup"];
 const TENANT_NOTIFY: &str = "tenant-notify-";
@@ -36,6 +37,9 @@ pub struct TenantContainerBuilder<'a> {
     no_new_privs: Option<bool>,
     capabilities: Vec<String>,
     process: Option<PathBuf>,
+    process_spec: Option<Process>,
+    sub_cgroup: Option<PathBuf>,
+    user: Option<String>,
 }
 
 impl<'a> TenantContainerBuilder<'a> {
@@ -51,6 +55,9 @@ impl<'a> TenantContainerBuilder<'a> {
             no_new_privs: None,
             capabilities: Vec::new(),
             process: None,
+            process_spec: None,
+            sub_cgroup: None,
+            user: None,
         }
     }
 
@@ -87,8 +94,29 @@ impl<'a> TenantContainerBuilder<'a> {
         self
     }
 
-    /// Joins an existing container
-    pub fn build(self) -> Result<()> {
+    /// Sets the complete process which should be executed, the same way a
+    /// process file does
+    pub fn with_process_spec(mut self, process: Process) -> Self {
+        self.process_spec = Some(process);
+        self
+    }
+
+    /// Places the process into a sub-cgroup of the container, which is
+    /// created if it does not exist yet
+    pub fn with_sub_cgroup<P: Into<PathBuf>>(mut self, path: Option<P>) -> Self {
+        self.sub_cgroup = path.map(|p| p.into());
+        self
+    }
+
+    /// Sets the user the process is executed as, in the form of
+    /// `user[:group]`, where both can either be a name or a numeric id
+    pub fn with_user(mut self, user: Option<String>) -> Self {
+        self.user = user;
+        self
+    }
+
+    /// Joins an existing container and returns the pid of the new process
+    pub fn build(self) -> Result<Pid> {
         let container_dir = self
             .lookup_container_dir()
             .context("failed to look up container dir")?;
@@ -100,10 +128,17 @@ impl<'a> TenantContainerBuilder<'a> {
             .context("failed to load init spec")?;
         self.adapt_spec_for_tenant(&mut spec, &container)
             .context("failed to adapt spec for tenant")?;
+        let sub_cgroup = match &self.sub_cgroup {
+            Some(sub_cgroup) => Some(
+                container
+                    .sub_cgroup_path(sub_cgroup)
+                    .context("invalid sub-cgroup")?,
+            ),
+            None => None,
+        };
 
         log::debug!("{:#?}", spec);
 
-        unistd::chdir(&container_dir)?;
         let notify_path = Self::setup_notify_listener(&container_dir)?;
         // convert path of root file system of the container to absolute path
         let rootfs = fs::canonicalize(&spec.root().as_ref().context("no root in spec")?.path())?;
@@ -121,6 +156,7 @@ impl<'a> TenantContainerBuilder<'a> {
             container_id: self.base.container_id,
             pid_file: self.base.pid_file,
             console_socket: csocketfd,
+            keep_console_master: false,
             use_systemd,
             spec: &spec,
             rootfs,
@@ -128,13 +164,29 @@ impl<'a> TenantContainerBuilder<'a> {
             notify_path: notify_path.clone(),
             container: None,
             preserve_fds: self.base.preserve_fds,
+            no_pivot: false,
+            bpf_audit: None,
+            time_namespace: None,
+            seccomp_options: SeccompOptions::default(),
         };
 
-        builder_impl.create()?;
+        let (pid, _) = builder_impl.create()?;
+
+        // The process waits for the start notification before it executes
+        // the command, so it can still be moved into the sub-cgroup.
+        if let Some(sub_cgroup) = sub_cgroup {
+            Self::join_sub_cgroup(&sub_cgroup, pid)?;
+        }
 
         let mut notify_socket = NotifySocket::new(notify_path);
         notify_socket.notify_container_start()?;
-        Ok(())
+        Ok(pid)
+    }
+
+    fn join_sub_cgroup(sub_cgroup: &Path, pid: Pid) -> Result<()> {
+        utils::create_dir_all(sub_cgroup)?;
+        fs::write(sub_cgroup.join(CGROUP_PROCS), pid.to_string())
+            .with_context(|| format!("failed to move {} into sub-cgroup {:?}", pid, sub_cgroup))
     }
 
     fn lookup_container_dir(&self) -> Result<PathBuf> {
@@ -147,7 +199,7 @@ impl<'a> TenantContainerBuilder<'a> {
     }
 
     fn load_init_spec(&self, container: &Container) -> Result<Spec> {
-        let spec_path = container.bundle().join("config.json");
+        let spec_path = container.spec_path();
 
         let mut spec = Spec::load(&spec_path)
             .with_context(|| format!("failed to load spec from {:?}", spec_path))?;
@@ -159,18 +211,26 @@ impl<'a> TenantContainerBuilder<'a> {
 
     fn load_container_state(&self, container_dir: PathBuf) -> Result<Container> {
         let container = Container::load(container_dir)?;
-        if !container.can_exec() {
-            bail!(
-                "Cannot exec as container is in state {}",
-                container.status()
-            );
-        }
+        container.state.check_operation(ContainerOperation::Exec)?;
 
         Ok(container)
     }
 
     fn adapt_spec_for_tenant(&self, spec: &mut Spec, container: &Container) -> Result<()> {
-        let process = if let Some(process) = &self.process {
+        let mut process = if let Some(process) = &self.process_spec {
+            if !self.args.is_empty() || self.process.is_some() {
+                bail!(
+                    "a command or a process file can not be specified together with a process spec"
+                );
+            }
+            validate_process(process).context("invalid process spec")?;
+            process.clone()
+        } else if let Some(process) = &self.process {
+            // The process file describes the complete process, a command
+            // would be silently ignored.
+            if !self.args.is_empty() {
+                bail!("a command can not be specified together with a process file");
+            }
             self.get_process(process)?
         } else {
             let mut process_builder = ProcessBuilder::default()
@@ -188,9 +248,26 @@ impl<'a> TenantContainerBuilder<'a> {
                 process_builder = process_builder.capabilities(caps);
             }
 
+            if let Some(user) = &self.user {
+                let rootfs = spec.root().as_ref().context("no root in spec")?.path();
+                process_builder = process_builder.user(
+                    resolve_user(rootfs, user)
+                        .with_context(|| format!("failed to resolve user {:?}", user))?,
+                );
+            }
+
             process_builder.build()?
         };
 
+        // the process runs with the label of the container, unless it has its own
+        if process.selinux_label().is_none() {
+            let label = spec
+                .process()
+                .as_ref()
+                .and_then(|p| p.selinux_label().clone());
+            process.set_selinux_label(label);
+        }
+
         if container.pid().is_none() {
             bail!("could not retrieve container init pid");
         }
@@ -211,8 +288,11 @@ impl<'a> TenantContainerBuilder<'a> {
             )
         }
 
-        let process = utils::open(process)?;
-        let process_spec = serde_json::from_reader(process)?;
+        let file = utils::open(process)?;
+        let process_spec: Process = serde_json::from_reader(file)
+            .with_context(|| format!("failed to parse process spec {:?}", process))?;
+        validate_process(&process_spec)
+            .with_context(|| format!("invalid process spec {:?}", process))?;
         Ok(process_spec)
     }
 
@@ -379,3 +459,203 @@ impl<'a> TenantContainerBuilder<'a> {
         }
     }
 }
+
+/// Checks that a complete process, which is not assembled from the single
+/// options, can be executed
+fn validate_process(process: &Process) -> Result<()> {
+    if process.args().as_ref().map_or(true, |args| args.is_empty()) {
+        bail!("process does not contain args");
+    }
+    if process.cwd().is_relative() {
+        bail!(
+            "current working directory must be an absolute path, but is {:?}",
+            process.cwd()
+        );
+    }
+    Ok(())
+}
+
+/// Entry of /etc/passwd or /etc/group, which consist of colon separated fields
+/// starting with the name, the password and the id
+struct Entry<'a> {
+    name: &'a str,
+    id: u32,
+    fields: Vec<&'a str>,
+}
+
+fn parse_entries(content: &str) -> Vec<Entry> {
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(':').collect();
+            let id = fields.get(2)?.parse().ok()?;
+            Some(Entry {
+                name: fields[0],
+                id,
+                fields,
+            })
+        })
+        .collect()
+}
+
+/// Reads a file of the container, which may not exist in minimal images
+fn read_container_file(rootfs: &Path, path: &str) -> Result<String> {
+    let path = utils::secure_join(rootfs, Path::new(path))?;
+    match fs::read_to_string(&path) {
+        Ok(content) => Ok(content),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(String::new()),
+        Err(e) => Err(e).with_context(|| format!("failed to read {:?}", path)),
+    }
+}
+
+/// Resolves `user[:group]` against /etc/passwd and /etc/group of the
+/// container. Names take precedence over numeric ids, a numeric user that
+/// is not part of /etc/passwd belongs to the root group unless specified.
+pub(super) fn resolve_user(rootfs: &Path, user: &str) -> Result<User> {
+    let passwd = read_container_file(rootfs, "/etc/passwd")?;
+    let group = read_container_file(rootfs, "/etc/group")?;
+    resolve_user_from(&passwd, &group, user)
+}
+
+fn resolve_user_from(passwd: &str, group: &str, user: &str) -> Result<User> {
+    let (user, group_name) = match user.split_once(':') {
+        Some((user, group)) => (user, Some(group)),
+        None => (user, None),
+    };
+    if user.is_empty() {
+        bail!("user must not be empty");
+    }
+
+    let users = parse_entries(passwd);
+    let entry = users.iter().find(|e| e.name == user).or_else(|| {
+        let uid: u32 = user.parse().ok()?;
+        users.iter().find(|e| e.id == uid)
+    });
+    let (uid, mut gid, name) = match entry {
+        Some(entry) => {
+            let gid = entry
+                .fields
+                .get(3)
+                .and_then(|gid| gid.parse().ok())
+                .with_context(|| format!("invalid /etc/passwd entry of user {}", entry.name))?;
+            (entry.id, gid, Some(entry.name))
+        }
+        None => match user.parse() {
+            Ok(uid) => (uid, 0, None),
+            Err(_) => bail!("user {} does not exist in the container", user),
+        },
+    };
+
+    let groups = parse_entries(group);
+    if let Some(group_name) = group_name {
+        gid = match groups.iter().find(|e| e.name == group_name) {
+            Some(entry) => entry.id,
+            None => group_name
+                .parse()
+                .with_context(|| format!("group {} does not exist in the container", group_name))?,
+        };
+    }
+
+    // the groups the user is a member of, besides the primary one
+    let additional_gids: Vec<u32> = match name {
+        Some(name) => groups
+            .iter()
+            .filter(|e| e.id != gid)
+            .filter(|e| {
+                e.fields.get(3).map_or(false, |members| {
+                    members.split(',').any(|m| m.trim() == name)
+                })
+            })
+            .map(|e| e.id)
+            .collect(),
+        None => Vec::new(),
+    };
+
+    let mut builder = UserBuilder::default().uid(uid).gid(gid);
+    if !additional_gids.is_empty() {
+        builder = builder.additional_gids(additional_gids);
+    }
+    Ok(builder.build()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PASSWD: &str = "root:x:0:0:root:/root:/bin/sh\n\
+                          daemon:x:1:1:daemon:/usr/sbin:/usr/sbin/nologin\n\
+                          app:x:1000:1000::/home/app:/bin/sh\n";
+    const GROUP: &str = "root:x:0:\n\
+                         daemon:x:1:\n\
+                         wheel:x:10:root,app\n\
+                         docker:x:999:app\n\
+                         app:x:1000:\n";
+
+    #[test]
+    fn test_validate_process() -> Result<()> {
+        let process = ProcessBuilder::default()
+            .args(vec!["sleep".to_owned(), "10".to_owned()])
+            .cwd("/")
+            .build()?;
+        assert!(validate_process(&process).is_ok());
+
+        let no_args = ProcessBuilder::default().args(vec![]).cwd("/").build()?;
+        assert!(validate_process(&no_args).is_err());
+
+        let relative_cwd = ProcessBuilder::default()
+            .args(vec!["sleep".to_owned()])
+            .cwd("tmp")
+            .build()?;
+        assert!(validate_process(&relative_cwd).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_user_by_name() -> Result<()> {
+        let user = resolve_user_from(PASSWD, GROUP, "app")?;
+        assert_eq!(user.uid(), 1000);
+        assert_eq!(user.gid(), 1000);
+        assert_eq!(user.additional_gids(), &Some(vec![10, 999]));
+
+        let user = resolve_user_from(PASSWD, GROUP, "app:docker")?;
+        assert_eq!(user.gid(), 999);
+        assert_eq!(user.additional_gids(), &Some(vec![10]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_user_numeric() -> Result<()> {
+        // a known uid resolves to its entry, including the groups of the user
+        let user = resolve_user_from(PASSWD, GROUP, "0")?;
+        assert_eq!((user.uid(), user.gid()), (0, 0));
+        assert_eq!(user.additional_gids(), &Some(vec![10]));
+
+        let user = resolve_user_from(PASSWD, GROUP, "2000:3000")?;
+        assert_eq!((user.uid(), user.gid()), (2000, 3000));
+        assert_eq!(user.additional_gids(), &None);
+
+        let user = resolve_user_from("", "", "2000")?;
+        assert_eq!((user.uid(), user.gid()), (2000, 0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_user_unknown() {
+        assert!(resolve_user_from(PASSWD, GROUP, "nobody").is_err());
+        assert!(resolve_user_from(PASSWD, GROUP, "app:nogroup").is_err());
+        assert!(resolve_user_from(PASSWD, GROUP, "").is_err());
+    }
+
+    #[test]
+    fn test_resolve_user_from_rootfs() -> Result<()> {
+        let rootfs = utils::create_temp_dir("test_resolve_user_from_rootfs")?;
+        fs::create_dir_all(rootfs.join("etc"))?;
+        fs::write(rootfs.join("etc/passwd"), PASSWD)?;
+        fs::write(rootfs.join("etc/group"), GROUP)?;
+
+        let user = resolve_user(&rootfs, "daemon:wheel")?;
+        assert_eq!((user.uid(), user.gid()), (1, 10));
+        Ok(())
+    }
+}
@@ -0,0 +1,130 @@
+//! The exec fifo is the barrier between create and start. The init process
+//! blocks on reading from it after the container has been set up, until start
+//! writes to it.
+use anyhow::{bail, Context, Result};
+use nix::{
+    errno::Errno,
+    fcntl::{self, OFlag},
+    sys::{signal, stat::Mode},
+    unistd::{self, Pid},
+};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{Read, Write},
+    os::unix::{
+        fs::{OpenOptionsExt, PermissionsExt},
+        io::RawFd,
+    },
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+pub const EXEC_FIFO_FILE: &str = "exec.fifo";
+
+/// The exec fifo as seen by the init process. It is opened with O_PATH before
+/// the init process enters the rootfs, where the fifo is not reachable anymore.
+pub struct ExecFifo {
+    fd: RawFd,
+}
+
+impl ExecFifo {
+    /// Creates the exec fifo, which can be read by the container process
+    /// regardless of the user it runs as
+    pub fn create(path: &Path) -> Result<Self> {
+        unistd::mkfifo(path, Mode::from_bits_truncate(0o644))
+            .with_context(|| format!("failed to create exec fifo {:?}", path))?;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o644))?;
+        let fd = fcntl::open(path, OFlag::O_PATH | OFlag::O_CLOEXEC, Mode::empty())
+            .with_context(|| format!("failed to open exec fifo {:?}", path))?;
+        Ok(Self { fd })
+    }
+
+    /// Blocks until the container is started
+    pub fn wait_for_container_start(&self) -> Result<()> {
+        // opening the fifo for reading blocks until start opens it for writing
+        let mut fifo = File::open(PathBuf::from(format!("/proc/self/fd/{}", self.fd)))
+            .context("failed to open exec fifo")?;
+        let mut buf = [0; 1];
+        if fifo.read(&mut buf).context("failed to read exec fifo")? == 0 {
+            bail!("exec fifo has been closed before the container was started");
+        }
+        Ok(())
+    }
+}
+
+impl Drop for ExecFifo {
+    fn drop(&mut self) {
+        let _ = unistd::close(self.fd);
+    }
+}
+
+/// Unblocks the init process waiting on the exec fifo and removes the fifo,
+/// so the container can only be started once.
+pub fn notify_container_start(path: &Path, init_pid: Pid) -> Result<()> {
+    let mut fifo = loop {
+        // without O_NONBLOCK opening would hang forever if the init process
+        // has exited, with it the open fails until the fifo is opened for reading
+        match OpenOptions::new()
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(path)
+        {
+            Ok(fifo) => break fifo,
+            Err(e) if e.raw_os_error() == Some(libc::ENXIO) => {
+                if let Err(Errno::ESRCH) = signal::kill(init_pid, None) {
+                    bail!("init process {} has exited before start", init_pid);
+                }
+                thread::sleep(Duration::from_millis(10));
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("failed to open exec fifo {:?}", path))
+            }
+        }
+    };
+
+    fifo.write_all(b"0")
+        .context("failed to write to exec fifo")?;
+    fs::remove_file(path).with_context(|| format!("failed to remove exec fifo {:?}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::create_temp_dir;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_exec_fifo_blocks_until_start() -> Result<()> {
+        let tmp = create_temp_dir("test_exec_fifo_blocks_until_start")?;
+        let path = tmp.join(EXEC_FIFO_FILE);
+        let fifo = ExecFifo::create(&path)?;
+
+        let (sender, receiver) = mpsc::channel();
+        let waiter = thread::spawn(move || {
+            let result = fifo.wait_for_container_start();
+            sender.send(()).unwrap();
+            result
+        });
+
+        // the created but not started container stays blocked
+        assert!(receiver.recv_timeout(Duration::from_millis(200)).is_err());
+
+        notify_container_start(&path, Pid::this())?;
+        receiver.recv_timeout(Duration::from_secs(5))?;
+        waiter.join().unwrap()?;
+        assert!(!path.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_notify_exited_init() -> Result<()> {
+        let tmp = create_temp_dir("test_notify_exited_init")?;
+        let path = tmp.join(EXEC_FIFO_FILE);
+        let _fifo = ExecFifo::create(&path)?;
+
+        // nobody reads from the fifo and the pid does not exist
+        assert!(notify_container_start(&path, Pid::from_raw(i32::MAX)).is_err());
+        Ok(())
+    }
+}
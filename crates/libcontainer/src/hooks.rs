@@ -1,23 +1,104 @@
 use anyhow::{bail, Context, Result};
-use nix::{sys::signal, unistd::Pid};
+use nix::{
+    sys::signal,
+    unistd::{self, Pid},
+};
 use oci_spec::runtime::Hook;
 use std::{
-    collections::HashMap, fmt, io::ErrorKind, io::Write, os::unix::prelude::CommandExt, process,
+    collections::HashMap,
+    env, fmt,
+    io::ErrorKind,
+    io::Read,
+    io::Write,
+    os::unix::prelude::CommandExt,
+    path::PathBuf,
+    process,
+    sync::{Arc, Mutex},
     thread, time,
 };
 
 use crate::{container::Container, utils};
+
+/// PATH used for hooks that do not declare one in their env.
+const DEFAULT_HOOK_PATH: &str = "/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin";
+
+/// How long to wait for the output of a hook after it has been killed or, if
+/// it has no timeout, after it has exited.
+const OUTPUT_TIMEOUT: time::Duration = time::Duration::from_secs(1);
+
 // A special error used to signal a timeout. We want to differentiate between a
 // timeout vs. other error.
 #[derive(Debug)]
-pub struct HookTimeoutError;
+pub struct HookTimeoutError {
+    pub path: PathBuf,
+    pub timeout: i64,
+    pub stdout: String,
+    pub stderr: String,
+}
 impl std::error::Error for HookTimeoutError {}
 impl fmt::Display for HookTimeoutError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        "hook command timeout".fmt(f)
+        write!(
+            f,
+            "hook command {:?} timeout after {}s, stdout: {}, stderr: {}",
+            self.path,
+            self.timeout,
+            self.stdout.trim(),
+            self.stderr.trim()
+        )
+    }
+}
+
+// Reads the pipe in a separate thread, so a hook writing a lot of output can
+// not block on a full pipe. A process the hook left behind can keep the pipe
+// open, so the output is collected with a timeout and the thread is abandoned
+// if the pipe is not closed by then.
+struct OutputReader {
+    output: Arc<Mutex<Vec<u8>>>,
+    done: crossbeam_channel::Receiver<()>,
+}
+
+impl OutputReader {
+    fn new<R: Read + Send + 'static>(pipe: Option<R>) -> Self {
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let (s, done) = crossbeam_channel::bounded(1);
+        let buffer = Arc::clone(&output);
+        thread::spawn(move || {
+            if let Some(mut pipe) = pipe {
+                let mut chunk = [0; 4096];
+                while let Ok(n) = pipe.read(&mut chunk) {
+                    if n == 0 {
+                        break;
+                    }
+                    buffer.lock().unwrap().extend_from_slice(&chunk[..n]);
+                }
+            }
+            let _ = s.send(());
+        });
+        Self { output, done }
+    }
+
+    /// Returns the output read until the pipe has been closed or the timeout
+    /// has passed, whichever happens first
+    fn collect(self, timeout: time::Duration) -> String {
+        if self.done.recv_timeout(timeout).is_err() {
+            log::warn!(
+                "the output pipe of a hook is still open, the hook may have left a process behind"
+            );
+        }
+        let output = self.output.lock().unwrap();
+        String::from_utf8_lossy(&output).into_owned()
     }
 }
 
+// Hooks run with only their declared env and a default PATH, so that secrets
+// or an unexpected PATH from the runtime's own environment don't leak into
+// them. Setting YOUKI_HOOKS_INHERIT_ENV=true lets hooks inherit the runtime
+// environment instead, for setups that rely on the old behaviour.
+fn inherit_runtime_env() -> bool {
+    matches!(env::var("YOUKI_HOOKS_INHERIT_ENV").as_deref(), Ok("true"))
+}
+
 pub fn run_hooks(hooks: Option<&Vec<Hook>>, container: Option<&Container>) -> Result<()> {
     if container.is_none() {
         bail!("container state is required to run hook");
@@ -41,20 +122,45 @@ pub fn run_hooks(hooks: Option<&Vec<Hook>>, container: Option<&Container>) -> Re
                 hook_command.arg0(&hook.path().display().to_string())
             };
 
-            let envs: HashMap<String, String> = if let Some(env) = hook.env() {
+            let mut envs: HashMap<String, String> = if let Some(env) = hook.env() {
                 utils::parse_env(env)
             } else {
                 HashMap::new()
             };
+            if !inherit_runtime_env() {
+                hook_command.env_clear();
+                envs.entry("PATH".to_string())
+                    .or_insert_with(|| DEFAULT_HOOK_PATH.to_string());
+            }
             log::debug!("run_hooks envs: {:?}", envs);
 
+            // The hook is placed into its own process group, so that on
+            // timeout the hook and everything it spawned can be killed. The
+            // signals youki blocks to forward them are not blocked for it.
+            unsafe {
+                hook_command.pre_exec(|| {
+                    signal::SigSet::all()
+                        .thread_unblock()
+                        .and_then(|_| unistd::setpgid(Pid::from_raw(0), Pid::from_raw(0)))
+                        .map_err(|e| std::io::Error::from_raw_os_error(e as i32))
+                })
+            };
+
             let mut hook_process = hook_command
-                .env_clear()
                 .envs(envs)
                 .stdin(process::Stdio::piped())
+                .stdout(process::Stdio::piped())
+                .stderr(process::Stdio::piped())
                 .spawn()
                 .with_context(|| "Failed to execute hook")?;
             let hook_process_pid = Pid::from_raw(hook_process.id() as i32);
+            let deadline = hook.timeout().map(|timeout_sec| {
+                time::Instant::now() + time::Duration::from_secs(timeout_sec as u64)
+            });
+            // The output of the hook is captured, so it can be reported if the
+            // hook fails.
+            let stdout_reader = OutputReader::new(hook_process.stdout.take());
+            let stderr_reader = OutputReader::new(hook_process.stderr.take());
             // Based on the OCI spec, we need to pipe the container state into
             // the hook command through stdin.
             if let Some(stdin) = &mut hook_process.stdin {
@@ -72,7 +178,7 @@ pub fn run_hooks(hooks: Option<&Vec<Hook>>, container: Option<&Container>) -> Re
                     if e.kind() != ErrorKind::BrokenPipe {
                         // Not a broken pipe. The hook command may be waiting
                         // for us.
-                        let _ = signal::kill(hook_process_pid, signal::Signal::SIGKILL);
+                        let _ = signal::killpg(hook_process_pid, signal::Signal::SIGKILL);
                         bail!("failed to write container state to stdin: {:?}", e);
                     }
                 }
@@ -97,10 +203,19 @@ pub fn run_hooks(hooks: Option<&Vec<Hook>>, container: Option<&Container>) -> Re
                 match r.recv_timeout(time::Duration::from_secs(timeout_sec as u64)) {
                     Ok(res) => res,
                     Err(crossbeam_channel::RecvTimeoutError::Timeout) => {
-                        // Kill the process. There is no need to further clean
-                        // up because we will be error out.
-                        let _ = signal::kill(hook_process_pid, signal::Signal::SIGKILL);
-                        return Err(HookTimeoutError.into());
+                        // Kill the whole process group of the hook and reap
+                        // it. The output is collected on a best effort basis,
+                        // since a process which left the process group could
+                        // still hold on to the pipes.
+                        let _ = signal::killpg(hook_process_pid, signal::Signal::SIGKILL);
+                        let _ = r.recv_timeout(OUTPUT_TIMEOUT);
+                        return Err(HookTimeoutError {
+                            path: hook.path().clone(),
+                            timeout: timeout_sec,
+                            stdout: stdout_reader.collect(OUTPUT_TIMEOUT),
+                            stderr: stderr_reader.collect(OUTPUT_TIMEOUT),
+                        }
+                        .into());
                     }
                     Err(_) => {
                         unreachable!();
@@ -110,21 +225,44 @@ pub fn run_hooks(hooks: Option<&Vec<Hook>>, container: Option<&Container>) -> Re
                 hook_process.wait()
             };
 
+            // The output is not waited for beyond the timeout of the hook
+            let output_timeout = deadline.map_or(OUTPUT_TIMEOUT, |deadline| {
+                deadline.saturating_duration_since(time::Instant::now())
+            });
+            let stdout = stdout_reader.collect(output_timeout);
+            let stdout = stdout.trim();
+            if !stdout.is_empty() {
+                log::debug!("hook {:?} stdout: {}", hook.path(), stdout);
+            }
+            let stderr = stderr_reader.collect(output_timeout);
+            let stderr = stderr.trim();
+            if !stderr.is_empty() {
+                log::debug!("hook {:?} stderr: {}", hook.path(), stderr);
+            }
+
             match res {
                 Ok(exit_status) => match exit_status.code() {
                     Some(0) => {}
                     Some(exit_code) => {
                         bail!(
-                            "Failed to execute hook command. Non-zero return code. {:?}",
-                            exit_code
+                            "Failed to execute hook command {:?}. Non-zero return code. {:?}, stdout: {}, stderr: {}",
+                            hook.path(),
+                            exit_code,
+                            stdout,
+                            stderr
                         );
                     }
                     None => {
-                        bail!("Process is killed by signal");
+                        bail!(
+                            "Hook command {:?} is killed by signal, stdout: {}, stderr: {}",
+                            hook.path(),
+                            stdout,
+                            stderr
+                        );
                     }
                 },
                 Err(e) => {
-                    bail!("Failed to execute hook command: {:?}", e);
+                    bail!("Failed to execute hook command {:?}: {:?}", hook.path(), e);
                 }
             }
         }
@@ -200,6 +338,78 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    #[serial]
+    fn test_run_hook_restricted_env() -> Result<()> {
+        let default_container: Container = Default::default();
+        env::set_var("YOUKI_HOOK_TEST_SENTINEL", "leaked");
+
+        // The hook should only see its declared env plus the default PATH.
+        let hook = HookBuilder::default()
+            .path("bash")
+            .args(vec![
+                String::from("bash"),
+                String::from("-c"),
+                format!(
+                    "[ -z \"$YOUKI_HOOK_TEST_SENTINEL\" ] && [ \"$PATH\" = \"{}\" ] && [ \"$key\" = \"value\" ]",
+                    DEFAULT_HOOK_PATH
+                ),
+            ])
+            .env(vec![String::from("key=value")])
+            .build()?;
+        let hooks = Some(vec![hook]);
+        let restricted = run_hooks(hooks.as_ref(), Some(&default_container));
+
+        // With the opt-out, the runtime environment is passed through.
+        env::set_var("YOUKI_HOOKS_INHERIT_ENV", "true");
+        let hook = HookBuilder::default()
+            .path("bash")
+            .args(vec![
+                String::from("bash"),
+                String::from("-c"),
+                String::from("[ \"$YOUKI_HOOK_TEST_SENTINEL\" = \"leaked\" ]"),
+            ])
+            .build()?;
+        let hooks = Some(vec![hook]);
+        let inherited = run_hooks(hooks.as_ref(), Some(&default_container));
+
+        env::remove_var("YOUKI_HOOKS_INHERIT_ENV");
+        env::remove_var("YOUKI_HOOK_TEST_SENTINEL");
+        restricted.context("Failed restricted env test")?;
+        inherited.context("Failed inherited env test")?;
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_run_hook_failure_stderr() -> Result<()> {
+        let default_container: Container = Default::default();
+        let hook = HookBuilder::default()
+            .path("bash")
+            .args(vec![
+                String::from("bash"),
+                String::from("-c"),
+                String::from("echo 'checking state'; echo 'hook went wrong' >&2; exit 1"),
+            ])
+            .build()?;
+        let hooks = Some(vec![hook]);
+        match run_hooks(hooks.as_ref(), Some(&default_container)) {
+            Ok(_) => bail!("The test expects the hook to fail"),
+            Err(err) => {
+                let msg = err.to_string();
+                if !msg.contains("hook went wrong") {
+                    bail!("expected the stderr of the hook in the error: {}", msg);
+                }
+                if !msg.contains("checking state") {
+                    bail!("expected the stdout of the hook in the error: {}", msg);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     #[test]
     #[serial]
     // This will test executing hook with a timeout. Since the timeout is set in
@@ -232,4 +442,61 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    #[serial]
+    fn test_run_hook_timeout_kills_process_group() -> Result<()> {
+        let default_container: Container = Default::default();
+        // The sleep is a child of bash, so it only goes away if the whole
+        // process group of the hook is killed.
+        let hook = HookBuilder::default()
+            .path("bash")
+            .args(vec![
+                String::from("bash"),
+                String::from("-c"),
+                String::from("echo started; sleep 30; true"),
+            ])
+            .timeout(1)
+            .build()?;
+        let hooks = Some(vec![hook]);
+        let start = time::Instant::now();
+        let err = match run_hooks(hooks.as_ref(), Some(&default_container)) {
+            Ok(_) => bail!("The test expects the hook to error out with timeout"),
+            Err(err) => err,
+        };
+
+        let timeout_err = err
+            .downcast_ref::<HookTimeoutError>()
+            .with_context(|| format!("expected a timeout error: {:?}", err))?;
+        assert_eq!(timeout_err.path, PathBuf::from("bash"));
+        assert_eq!(timeout_err.stdout.trim(), "started");
+        assert!(start.elapsed() < time::Duration::from_secs(10));
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_run_hook_leaked_output() -> Result<()> {
+        let default_container: Container = Default::default();
+        // The background sleep inherits the stdout and stderr of the hook, so
+        // the pipes stay open after the hook has exited.
+        for timeout in [None, Some(1)] {
+            let mut hook = HookBuilder::default()
+                .path("bash")
+                .args(vec![
+                    String::from("bash"),
+                    String::from("-c"),
+                    String::from("sleep 10 & echo done"),
+                ])
+                .build()?;
+            hook.set_timeout(timeout);
+            let hooks = Some(vec![hook]);
+            let start = time::Instant::now();
+            run_hooks(hooks.as_ref(), Some(&default_container))?;
+            assert!(start.elapsed() < time::Duration::from_secs(5));
+        }
+
+        Ok(())
+    }
 }
@@ -0,0 +1,329 @@
+//! Intel Resource Director Technology, which allows to restrict the L3 cache
+//! and memory bandwidth of the container as specified by `linux.intelRdt`.
+//! See https://www.kernel.org/doc/html/latest/x86/resctrl.html for more information
+use anyhow::{bail, Context, Result};
+use nix::unistd::Pid;
+use oci_spec::runtime::LinuxIntelRdt;
+use procfs::process::Process;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Directories in the resctrl root, which are not resource groups
+const RESCTRL_RESERVED_DIRS: &[&str] = &["info", "mon_groups", "mon_data"];
+
+/// Returns the mount point of the resctrl filesystem
+pub fn find_resctrl_mount() -> Result<PathBuf> {
+    let mount_infos = Process::myself()?
+        .mountinfo()
+        .context("failed to get mountinfo")?;
+    mount_infos
+        .into_iter()
+        .find(|m| m.fs_type == "resctrl")
+        .map(|m| m.mount_point)
+        .context("resctrl is not mounted, intel RDT is not available on this system")
+}
+
+/// Name of the resource group, the closID if shared or the container id
+fn group_name<'a>(container_id: &'a str, intel_rdt: &'a LinuxIntelRdt) -> &'a str {
+    intel_rdt.clos_id().as_deref().unwrap_or(container_id)
+}
+
+/// Creates or joins the resource group of the container and moves the init
+/// process into it.
+pub fn setup_intel_rdt(container_id: &str, init_pid: Pid, intel_rdt: &LinuxIntelRdt) -> Result<()> {
+    let root = find_resctrl_mount()?;
+    setup_group(&root, container_id, init_pid, intel_rdt)
+}
+
+fn setup_group(
+    root: &Path,
+    container_id: &str,
+    init_pid: Pid,
+    intel_rdt: &LinuxIntelRdt,
+) -> Result<()> {
+    // validate the schemata before any resource group is created
+    let mut schemata = Vec::new();
+    if let Some(l3_cache_schema) = intel_rdt.l3_cache_schema() {
+        let cache = CacheInfo::load(&root.join("info/L3"))?;
+        schemata.extend(validate_schema(l3_cache_schema, |line| {
+            validate_l3_line(line, &cache)
+        })?);
+    }
+    if let Some(mem_bw_schema) = intel_rdt.mem_bw_schema() {
+        let min_bandwidth = read_info(&root.join("info/MB"), "min_bandwidth")
+            .context("memory bandwidth allocation is not supported")?;
+        schemata.extend(validate_schema(mem_bw_schema, |line| {
+            validate_mb_line(line, min_bandwidth)
+        })?);
+    }
+
+    let group = root.join(group_name(container_id, intel_rdt));
+    if !group.exists() {
+        // every resource group occupies a CLOS, including the default group
+        let num_closids = num_closids(root)?;
+        let used = fs::read_dir(root)?
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().is_dir())
+            .filter(|e| !RESCTRL_RESERVED_DIRS.contains(&e.file_name().to_string_lossy().as_ref()))
+            .count()
+            + 1;
+        if used >= num_closids {
+            bail!(
+                "no free CLOS available, {} of {} are in use",
+                used,
+                num_closids
+            );
+        }
+
+        fs::create_dir(&group)
+            .with_context(|| format!("failed to create resctrl group {:?}", group))?;
+    }
+
+    for line in schemata {
+        fs::write(group.join("schemata"), line)
+            .with_context(|| format!("failed to write schema {:?} to {:?}", line, group))?;
+    }
+
+    fs::write(group.join("tasks"), init_pid.to_string())
+        .with_context(|| format!("failed to add {} to resctrl group {:?}", init_pid, group))?;
+    Ok(())
+}
+
+/// Removes the resource group of the container. Groups named after the closID
+/// may be shared with other containers and are left untouched.
+pub fn delete_intel_rdt(container_id: &str, intel_rdt: &LinuxIntelRdt) -> Result<()> {
+    if intel_rdt.clos_id().is_some() {
+        return Ok(());
+    }
+
+    let group = find_resctrl_mount()?.join(container_id);
+    if group.exists() {
+        fs::remove_dir(&group)
+            .with_context(|| format!("failed to remove resctrl group {:?}", group))?;
+    }
+    Ok(())
+}
+
+fn read_info(info: &Path, name: &str) -> Result<u64> {
+    let path = info.join(name);
+    let content =
+        fs::read_to_string(&path).with_context(|| format!("failed to read {:?}", path))?;
+    let content = content.trim();
+    let value = if name == "cbm_mask" {
+        u64::from_str_radix(content, 16)
+    } else {
+        content.parse()
+    };
+    value.with_context(|| format!("failed to parse {:?} from {:?}", content, path))
+}
+
+fn num_closids(root: &Path) -> Result<usize> {
+    ["info/L3", "info/MB"]
+        .iter()
+        .map(|info| root.join(info))
+        .find(|info| info.exists())
+        .context("neither cache nor memory bandwidth allocation is supported")
+        .and_then(|info| read_info(&info, "num_closids"))
+        .map(|n| n as usize)
+}
+
+/// Capacity bitmask limits of the L3 cache
+struct CacheInfo {
+    cbm_mask: u64,
+    min_cbm_bits: u32,
+}
+
+impl CacheInfo {
+    fn load(info: &Path) -> Result<Self> {
+        if !info.exists() {
+            bail!("L3 cache allocation is not supported");
+        }
+
+        Ok(Self {
+            cbm_mask: read_info(info, "cbm_mask")?,
+            min_cbm_bits: read_info(info, "min_cbm_bits")? as u32,
+        })
+    }
+}
+
+/// Validates each line of a schema, e.g. `L3:0=ff;1=f0`
+fn validate_schema<'a, F>(schema: &'a str, validate_line: F) -> Result<Vec<&'a str>>
+where
+    F: Fn(&str) -> Result<()>,
+{
+    let lines: Vec<&str> = schema
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect();
+    for line in &lines {
+        validate_line(line).with_context(|| format!("invalid schema {:?}", line))?;
+    }
+    Ok(lines)
+}
+
+/// Splits a schema line into its resource and the values per cache domain
+fn parse_line(line: &str) -> Result<(&str, Vec<&str>)> {
+    let (resource, domains) = line.split_once(':').context("missing resource")?;
+    let values = domains
+        .split(';')
+        .map(|domain| {
+            let (id, value) = domain
+                .split_once('=')
+                .with_context(|| format!("invalid domain {:?}", domain))?;
+            id.trim()
+                .parse::<u32>()
+                .with_context(|| format!("invalid domain id {:?}", id))?;
+            Ok(value.trim())
+        })
+        .collect::<Result<_>>()?;
+    Ok((resource.trim(), values))
+}
+
+fn validate_l3_line(line: &str, cache: &CacheInfo) -> Result<()> {
+    let (resource, values) = parse_line(line)?;
+    if !matches!(resource, "L3" | "L3CODE" | "L3DATA") {
+        bail!("expected L3 resource, got {:?}", resource);
+    }
+
+    for value in values {
+        let cbm = u64::from_str_radix(value, 16)
+            .with_context(|| format!("invalid capacity bitmask {:?}", value))?;
+        if cbm == 0 || cbm & !cache.cbm_mask != 0 {
+            bail!(
+                "capacity bitmask {:x} is not within {:x}",
+                cbm,
+                cache.cbm_mask
+            );
+        }
+        // the set bits of a capacity bitmask have to be contiguous
+        if ((cbm >> cbm.trailing_zeros()) + 1).count_ones() != 1 {
+            bail!("capacity bitmask {:x} is not contiguous", cbm);
+        }
+        if cbm.count_ones() < cache.min_cbm_bits {
+            bail!(
+                "capacity bitmask {:x} has less than {} bits",
+                cbm,
+                cache.min_cbm_bits
+            );
+        }
+    }
+    Ok(())
+}
+
+fn validate_mb_line(line: &str, min_bandwidth: u64) -> Result<()> {
+    let (resource, values) = parse_line(line)?;
+    if resource != "MB" {
+        bail!("expected MB resource, got {:?}", resource);
+    }
+
+    for value in values {
+        let bandwidth = value
+            .parse::<u64>()
+            .with_context(|| format!("invalid bandwidth {:?}", value))?;
+        if !(min_bandwidth..=100).contains(&bandwidth) {
+            bail!(
+                "bandwidth {} is out of range [{}, 100]",
+                bandwidth,
+                min_bandwidth
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::create_temp_dir;
+    use oci_spec::runtime::LinuxIntelRdtBuilder;
+
+    fn setup_resctrl(root: &Path) -> Result<()> {
+        fs::create_dir_all(root.join("info/L3"))?;
+        fs::create_dir_all(root.join("info/MB"))?;
+        fs::write(root.join("info/L3/cbm_mask"), "fff\n")?;
+        fs::write(root.join("info/L3/min_cbm_bits"), "2\n")?;
+        fs::write(root.join("info/L3/num_closids"), "2\n")?;
+        fs::write(root.join("info/MB/min_bandwidth"), "10\n")?;
+        fs::write(root.join("info/MB/num_closids"), "2\n")?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_l3_line() {
+        let cache = CacheInfo {
+            cbm_mask: 0xfff,
+            min_cbm_bits: 2,
+        };
+        assert!(validate_l3_line("L3:0=ff0;1=3", &cache).is_ok());
+        assert!(validate_l3_line("L3CODE:0=fff", &cache).is_ok());
+        assert!(validate_l3_line("L3:0=ff0f", &cache).is_err());
+        assert!(validate_l3_line("L3:0=f0f", &cache).is_err());
+        assert!(validate_l3_line("L3:0=1", &cache).is_err());
+        assert!(validate_l3_line("L3:0=0", &cache).is_err());
+        assert!(validate_l3_line("L3:a=ff", &cache).is_err());
+        assert!(validate_l3_line("MB:0=ff", &cache).is_err());
+        assert!(validate_l3_line("L3", &cache).is_err());
+    }
+
+    #[test]
+    fn test_validate_mb_line() {
+        assert!(validate_mb_line("MB:0=70;1=10", 10).is_ok());
+        assert!(validate_mb_line("MB:0=5", 10).is_err());
+        assert!(validate_mb_line("MB:0=101", 10).is_err());
+        assert!(validate_mb_line("MB:0=ff", 10).is_err());
+        assert!(validate_mb_line("L3:0=70", 10).is_err());
+    }
+
+    #[test]
+    fn test_setup_group() -> Result<()> {
+        let tmp = create_temp_dir("test_setup_intel_rdt_group")?;
+        setup_resctrl(&tmp)?;
+        let intel_rdt = LinuxIntelRdtBuilder::default()
+            .l3_cache_schema("L3:0=ff0")
+            .mem_bw_schema("MB:0=70")
+            .build()?;
+
+        setup_group(&tmp, "container", Pid::from_raw(1000), &intel_rdt)?;
+        assert_eq!(fs::read_to_string(tmp.join("container/tasks"))?, "1000");
+        // the schemata is written line by line, the last one remains in a regular file
+        assert_eq!(
+            fs::read_to_string(tmp.join("container/schemata"))?,
+            "MB:0=70"
+        );
+
+        // the default and the container group occupy all CLOS
+        assert!(setup_group(&tmp, "other", Pid::from_raw(1001), &intel_rdt).is_err());
+        assert!(!tmp.join("other").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_setup_group_shared_clos_id() -> Result<()> {
+        let tmp = create_temp_dir("test_setup_intel_rdt_shared")?;
+        setup_resctrl(&tmp)?;
+        let intel_rdt = LinuxIntelRdtBuilder::default().clos_id("shared").build()?;
+
+        setup_group(&tmp, "first", Pid::from_raw(1000), &intel_rdt)?;
+        setup_group(&tmp, "second", Pid::from_raw(1001), &intel_rdt)?;
+        assert!(tmp.join("shared").exists());
+        assert!(!tmp.join("first").exists());
+        assert_eq!(fs::read_to_string(tmp.join("shared/tasks"))?, "1001");
+        Ok(())
+    }
+
+    #[test]
+    fn test_setup_group_invalid_schema() -> Result<()> {
+        let tmp = create_temp_dir("test_setup_intel_rdt_invalid")?;
+        setup_resctrl(&tmp)?;
+        let intel_rdt = LinuxIntelRdtBuilder::default()
+            .l3_cache_schema("L3:0=f000")
+            .build()?;
+
+        assert!(setup_group(&tmp, "container", Pid::from_raw(1000), &intel_rdt).is_err());
+        assert!(!tmp.join("container").exists());
+        Ok(())
+    }
+}
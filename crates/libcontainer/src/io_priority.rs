@@ -0,0 +1,106 @@
+//! Applies the I/O scheduling class and priority of the container process as
+//! specified by `process.ioPriority`.
+//! See https://man7.org/linux/man-pages/man2/ioprio_set.2.html for more information
+use anyhow::{bail, Result};
+use nix::errno::Errno;
+use oci_spec::runtime::{IOPriorityClass, LinuxIOPriority};
+
+// The ioprio constants are not exported by libc. Ref: include/uapi/linux/ioprio.h
+const IOPRIO_CLASS_SHIFT: i64 = 13;
+const IOPRIO_CLASS_RT: i64 = 1;
+const IOPRIO_CLASS_BE: i64 = 2;
+const IOPRIO_CLASS_IDLE: i64 = 3;
+const IOPRIO_WHO_PROCESS: i64 = 1;
+
+const IOPRIO_MAX_LEVEL: i64 = 7;
+
+fn ioprio_value(class: i64, level: i64) -> i64 {
+    (class << IOPRIO_CLASS_SHIFT) | level
+}
+
+fn to_ioprio(io_priority: &LinuxIOPriority) -> Result<i64> {
+    let level = io_priority.priority();
+    let class = match io_priority.class() {
+        IOPriorityClass::IoprioClassRt => IOPRIO_CLASS_RT,
+        IOPriorityClass::IoprioClassBe => IOPRIO_CLASS_BE,
+        // The idle class has no priority levels, so the level is ignored.
+        IOPriorityClass::IoprioClassIdle => return Ok(ioprio_value(IOPRIO_CLASS_IDLE, 0)),
+    };
+
+    if !(0..=IOPRIO_MAX_LEVEL).contains(&level) {
+        bail!(
+            "io priority {} is out of range [0, {}] for class {:?}",
+            level,
+            IOPRIO_MAX_LEVEL,
+            io_priority.class()
+        );
+    }
+
+    Ok(ioprio_value(class, level))
+}
+
+/// Sets the I/O scheduling class and priority of the calling process.
+pub fn set_io_priority(io_priority: &LinuxIOPriority) -> Result<()> {
+    let ioprio = to_ioprio(io_priority)?;
+    log::debug!("set io priority {:?}", io_priority);
+
+    // who 0 refers to the calling process, which is the container init process.
+    let ret = unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, ioprio) };
+    match Errno::result(ret) {
+        Ok(_) => Ok(()),
+        Err(Errno::EPERM) => bail!(
+            "not permitted to set io priority class {:?}, the realtime class requires CAP_SYS_ADMIN",
+            io_priority.class()
+        ),
+        Err(e) => bail!("failed to set io priority {:?}: {}", io_priority, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_utils;
+    use oci_spec::runtime::LinuxIOPriorityBuilder;
+
+    #[test]
+    fn test_to_ioprio() -> Result<()> {
+        let io_priority = LinuxIOPriorityBuilder::default()
+            .class(IOPriorityClass::IoprioClassBe)
+            .priority(4)
+            .build()?;
+        assert_eq!(to_ioprio(&io_priority)?, (2 << 13) | 4);
+
+        let io_priority = LinuxIOPriorityBuilder::default()
+            .class(IOPriorityClass::IoprioClassIdle)
+            .priority(100)
+            .build()?;
+        assert_eq!(to_ioprio(&io_priority)?, 3 << 13);
+
+        for level in [-1, 8] {
+            let io_priority = LinuxIOPriorityBuilder::default()
+                .class(IOPriorityClass::IoprioClassRt)
+                .priority(level)
+                .build()?;
+            assert!(to_ioprio(&io_priority).is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_io_priority() -> Result<()> {
+        test_utils::test_in_child_process(|| {
+            let io_priority = LinuxIOPriorityBuilder::default()
+                .class(IOPriorityClass::IoprioClassBe)
+                .priority(6)
+                .build()?;
+            set_io_priority(&io_priority)?;
+
+            let ret = unsafe { libc::syscall(libc::SYS_ioprio_get, IOPRIO_WHO_PROCESS, 0) };
+            let got = Errno::result(ret)?;
+            if got != ioprio_value(IOPRIO_CLASS_BE, 6) {
+                bail!("expected io priority {:?}, got {:#x}", io_priority, got);
+            }
+            Ok(())
+        })
+    }
+}
@@ -3,15 +3,24 @@ pub mod apparmor;
 pub mod capabilities;
 pub mod config;
 pub mod container;
+pub mod exec_fifo;
 pub mod hooks;
+pub mod intel_rdt;
+pub mod io_priority;
 pub mod namespaces;
 pub mod notify_socket;
+pub mod personality;
 pub mod process;
 pub mod rootfs;
 pub mod rootless;
+pub mod scheduler;
+pub mod sd_notify;
 pub mod seccomp;
+pub mod selinux;
 pub mod signal;
 pub mod syscall;
+pub mod sysctl;
+pub mod time_namespace;
 pub mod tty;
 pub mod utils;
 pub mod workload;
@@ -0,0 +1,120 @@
+//! Sets the execution domain of the container process as specified by
+//! `linux.personality`.
+//! See https://man7.org/linux/man-pages/man2/personality.2.html for more information
+use anyhow::{bail, Result};
+use nix::errno::Errno;
+use oci_spec::runtime::{LinuxPersonality, LinuxPersonalityDomain};
+
+// Ref: include/uapi/linux/personality.h
+const PER_LINUX: libc::c_ulong = 0x0000;
+const PER_LINUX32: libc::c_ulong = 0x0008;
+
+const UNAME26: libc::c_ulong = 0x0020000;
+const ADDR_NO_RANDOMIZE: libc::c_ulong = 0x0040000;
+const FDPIC_FUNCPTRS: libc::c_ulong = 0x0080000;
+const MMAP_PAGE_ZERO: libc::c_ulong = 0x0100000;
+const ADDR_COMPAT_LAYOUT: libc::c_ulong = 0x0200000;
+const READ_IMPLIES_EXEC: libc::c_ulong = 0x0400000;
+const ADDR_LIMIT_32BIT: libc::c_ulong = 0x0800000;
+const SHORT_INODE: libc::c_ulong = 0x1000000;
+const WHOLE_SECONDS: libc::c_ulong = 0x2000000;
+const STICKY_TIMEOUTS: libc::c_ulong = 0x4000000;
+const ADDR_LIMIT_3GB: libc::c_ulong = 0x8000000;
+
+fn translate_flag(flag: &str) -> Result<libc::c_ulong> {
+    let value = match flag {
+        "UNAME26" => UNAME26,
+        "ADDR_NO_RANDOMIZE" => ADDR_NO_RANDOMIZE,
+        "FDPIC_FUNCPTRS" => FDPIC_FUNCPTRS,
+        "MMAP_PAGE_ZERO" => MMAP_PAGE_ZERO,
+        "ADDR_COMPAT_LAYOUT" => ADDR_COMPAT_LAYOUT,
+        "READ_IMPLIES_EXEC" => READ_IMPLIES_EXEC,
+        "ADDR_LIMIT_32BIT" => ADDR_LIMIT_32BIT,
+        "SHORT_INODE" => SHORT_INODE,
+        "WHOLE_SECONDS" => WHOLE_SECONDS,
+        "STICKY_TIMEOUTS" => STICKY_TIMEOUTS,
+        "ADDR_LIMIT_3GB" => ADDR_LIMIT_3GB,
+        _ => bail!("unknown personality flag {}", flag),
+    };
+
+    Ok(value)
+}
+
+fn to_persona(personality: &LinuxPersonality) -> Result<libc::c_ulong> {
+    let domain = match personality.domain() {
+        LinuxPersonalityDomain::PerLinux => PER_LINUX,
+        LinuxPersonalityDomain::PerLinux32 => PER_LINUX32,
+    };
+
+    let mut persona = domain;
+    for flag in personality.flags().iter().flatten() {
+        persona |= translate_flag(flag)?;
+    }
+
+    Ok(persona)
+}
+
+/// Sets the execution domain of the calling process. The personality is
+/// inherited across execve, so it applies to the container workload.
+pub fn set_personality(personality: &LinuxPersonality) -> Result<()> {
+    let persona = to_persona(personality)?;
+    log::debug!("set personality {:?} ({:#x})", personality, persona);
+
+    let ret = unsafe { libc::personality(persona) };
+    if let Err(e) = Errno::result(ret) {
+        bail!("failed to set personality {:?}: {}", personality, e);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_utils;
+    use oci_spec::runtime::LinuxPersonalityBuilder;
+
+    #[test]
+    fn test_to_persona() -> Result<()> {
+        let personality = LinuxPersonalityBuilder::default()
+            .domain(LinuxPersonalityDomain::PerLinux)
+            .build()?;
+        assert_eq!(to_persona(&personality)?, PER_LINUX);
+
+        let personality = LinuxPersonalityBuilder::default()
+            .domain(LinuxPersonalityDomain::PerLinux32)
+            .flags(vec!["ADDR_LIMIT_32BIT".to_string()])
+            .build()?;
+        assert_eq!(to_persona(&personality)?, PER_LINUX32 | ADDR_LIMIT_32BIT);
+
+        let personality = LinuxPersonalityBuilder::default()
+            .domain(LinuxPersonalityDomain::PerLinux)
+            .flags(vec!["NOT_A_FLAG".to_string()])
+            .build()?;
+        assert!(to_persona(&personality).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_domain() {
+        let personality = serde_json::from_str::<LinuxPersonality>(r#"{"domain": "LINUX64"}"#);
+        assert!(personality.is_err());
+    }
+
+    #[test]
+    fn test_set_personality() -> Result<()> {
+        test_utils::test_in_child_process(|| {
+            let personality = LinuxPersonalityBuilder::default()
+                .domain(LinuxPersonalityDomain::PerLinux32)
+                .build()?;
+            set_personality(&personality)?;
+
+            // 0xffffffff queries the current persona without changing it.
+            let current = unsafe { libc::personality(0xffffffff) };
+            if current as libc::c_ulong & 0xff != PER_LINUX32 {
+                bail!("expected personality LINUX32, got {:#x}", current);
+            }
+            Ok(())
+        })
+    }
+}
@@ -1,10 +1,32 @@
+use anyhow::Result;
 use libcgroups::common::CgroupManager;
 use oci_spec::runtime::Spec;
 use std::os::unix::prelude::RawFd;
 use std::path::PathBuf;
 
 use crate::rootless::Rootless;
-use crate::{container::Container, notify_socket::NotifyListener, syscall::Syscall};
+use crate::seccomp::SeccompOptions;
+use crate::time_namespace::TimeNamespace;
+use crate::{
+    container::Container, exec_fifo::ExecFifo, notify_socket::NotifyListener, syscall::Syscall,
+};
+
+/// Barrier the container process waits on, before it executes the payload
+pub enum StartBarrier {
+    /// Exec fifo of an init container, which is written to by start
+    ExecFifo(ExecFifo),
+    /// Notify socket of a tenant, which is notified once it has been set up
+    NotifySocket(NotifyListener),
+}
+
+impl StartBarrier {
+    pub fn wait_for_container_start(&self) -> Result<()> {
+        match self {
+            Self::ExecFifo(fifo) => fifo.wait_for_container_start(),
+            Self::NotifySocket(socket) => socket.wait_for_container_start(),
+        }
+    }
+}
 
 pub struct ContainerArgs<'a> {
     /// Flag indicating if an init or a tenant container should be created
@@ -17,14 +39,25 @@ pub struct ContainerArgs<'a> {
     pub rootfs: &'a PathBuf,
     /// Socket to communicate the file descriptor of the ptty
     pub console_socket: Option<RawFd>,
-    /// The Unix Domain Socket to communicate container start
-    pub notify_socket: NotifyListener,
+    /// Pass the pty master to the main process as well, which forwards the
+    /// window size of its terminal to it
+    pub keep_console_master: bool,
+    /// Barrier to communicate container start
+    pub start_barrier: StartBarrier,
     /// File descriptos preserved/passed to the container init process.
     pub preserve_fds: i32,
+    /// Use MS_MOVE and chroot instead of pivot_root to enter the rootfs
+    pub no_pivot: bool,
     /// Container state
     pub container: &'a Option<Container>,
     /// Options for rootless containers
     pub rootless: &'a Option<Rootless<'a>>,
     /// Cgroup Manager
     pub cgroup_manager: Box<dyn CgroupManager>,
+    /// eBPF LSM program attached to the cgroup of the container
+    pub bpf_audit: Option<RawFd>,
+    /// Time namespace, which is not part of the namespaces of the spec
+    pub time_namespace: &'a Option<TimeNamespace>,
+    /// Options for translating the seccomp profile into a filter
+    pub seccomp_options: &'a SeccompOptions,
 }
@@ -1,6 +1,8 @@
 use crate::process::message::Message;
 use anyhow::{bail, Context, Result};
 use nix::{
+    errno::Errno,
+    poll::{self, PollFd, PollFlags},
     sys::{socket, uio},
     unistd::{self, Pid},
 };
@@ -8,8 +10,14 @@ use serde::{Deserialize, Serialize};
 use std::{
     marker::PhantomData,
     os::unix::prelude::{AsRawFd, RawFd},
+    time::{Duration, Instant},
 };
 
+/// How long the main process waits for the init process to become ready, so
+/// that a stuck init process does not block the caller forever. The time the
+/// createContainer hooks may take is not included.
+pub const INIT_READY_TIMEOUT: Duration = Duration::from_secs(60);
+
 /// Channel Design
 ///
 /// Each of the main, intermediate, and init process will have a uni-directional
@@ -23,11 +31,18 @@ use std::{
 
 pub fn main_channel() -> Result<(MainSender, MainReceiver)> {
     let (sender, receiver) = channel::<Message>()?;
-    Ok((MainSender { sender }, MainReceiver { receiver }))
+    Ok((
+        MainSender {
+            sender,
+            closed: false,
+        },
+        MainReceiver { receiver },
+    ))
 }
 
 pub struct MainSender {
     sender: Sender<Message>,
+    closed: bool,
 }
 
 impl MainSender {
@@ -55,13 +70,41 @@ impl MainSender {
         Ok(())
     }
 
-    pub fn init_ready(&mut self) -> Result<()> {
-        self.sender.send(Message::InitReady)?;
+    // requests the Main to run the createRuntime hooks, which have to run in
+    // the runtime namespace once the container namespaces have been created
+    pub fn create_runtime_hooks_request(&mut self) -> Result<()> {
+        log::debug!("send create runtime hooks request");
+        self.sender.send(Message::CreateRuntimeHooks)?;
 
         Ok(())
     }
 
-    pub fn close(&self) -> Result<()> {
+    // reports that the init process is ready. The pty master is passed along,
+    // if the main process keeps it to forward the window size of the terminal.
+    pub fn init_ready(&mut self, console_master: Option<RawFd>) -> Result<()> {
+        match console_master {
+            Some(fd) => self.sender.send_fds(Message::InitReady, &[fd])?,
+            None => self.sender.send(Message::InitReady)?,
+        }
+
+        Ok(())
+    }
+
+    // reports an error to the Main, which is waiting for the process to become
+    // ready. Once the sender has been closed, the Main is not waiting anymore.
+    pub fn send_error(&mut self, error: &anyhow::Error) -> Result<()> {
+        if self.closed {
+            return Ok(());
+        }
+
+        self.sender
+            .send(Message::OtherError(format!("{:?}", error)))?;
+
+        Ok(())
+    }
+
+    pub fn close(&mut self) -> Result<()> {
+        self.closed = true;
         self.sender.close()
     }
 }
@@ -81,6 +124,7 @@ impl MainReceiver {
 
         match msg {
             Message::IntermediateReady(pid) => Ok(Pid::from_raw(pid)),
+            Message::OtherError(err) => bail!("container process failed: {}", err),
             _ => bail!(
                 "receive unexpected message {:?} waiting for intermediate ready",
                 msg
@@ -95,6 +139,7 @@ impl MainReceiver {
             .context("failed to wait for mapping request")?;
         match msg {
             Message::WriteMapping => Ok(()),
+            Message::OtherError(err) => bail!("intermediate process failed: {}", err),
             msg => bail!(
                 "receive unexpected message {:?} waiting for mapping request",
                 msg
@@ -116,6 +161,7 @@ impl MainReceiver {
                 };
                 Ok(fd)
             }
+            Message::OtherError(err) => bail!("init process failed: {}", err),
             msg => bail!(
                 "receive unexpected message {:?} waiting for seccomp request",
                 msg
@@ -123,15 +169,45 @@ impl MainReceiver {
         }
     }
 
-    /// Waits for associated init process to send ready message
-    /// and return the pid of init process which is forked by init process
-    pub fn wait_for_init_ready(&mut self) -> Result<()> {
+    pub fn wait_for_create_runtime_hooks_request(&mut self) -> Result<()> {
         let msg = self
             .receiver
             .recv()
+            .context("failed to wait for create runtime hooks request")?;
+        match msg {
+            Message::CreateRuntimeHooks => Ok(()),
+            Message::OtherError(err) => bail!("init process failed: {}", err),
+            msg => bail!(
+                "receive unexpected message {:?} waiting for create runtime hooks request",
+                msg
+            ),
+        }
+    }
+
+    /// Waits for associated init process to send ready message
+    /// and returns the pty master, if the init process has passed it along
+    pub fn wait_for_init_ready(&mut self) -> Result<Option<RawFd>> {
+        self.wait_for_init_ready_timeout(Some(INIT_READY_TIMEOUT))
+    }
+
+    /// Same as wait_for_init_ready, but gives up once the timeout has elapsed
+    /// or waits indefinitely without a timeout
+    pub fn wait_for_init_ready_timeout(
+        &mut self,
+        timeout: Option<Duration>,
+    ) -> Result<Option<RawFd>> {
+        if let Some(timeout) = timeout {
+            self.receiver
+                .wait_readable(timeout)
+                .context("failed to wait for init ready")?;
+        }
+        let (msg, fds) = self
+            .receiver
+            .recv_with_fds::<[RawFd; 1]>()
             .context("failed to wait for init ready")?;
         match msg {
-            Message::InitReady => Ok(()),
+            Message::InitReady => Ok(fds.map(|fds| fds[0])),
+            Message::OtherError(err) => bail!("init process failed: {}", err),
             msg => bail!(
                 "receive unexpected message {:?} waiting for init ready",
                 msg
@@ -211,6 +287,12 @@ impl InitSender {
         Ok(())
     }
 
+    pub fn create_runtime_hooks_done(&mut self) -> Result<()> {
+        self.sender.send(Message::CreateRuntimeHooksDone)?;
+
+        Ok(())
+    }
+
     pub fn close(&self) -> Result<()> {
         self.sender.close()
     }
@@ -236,6 +318,21 @@ impl InitReceiver {
         }
     }
 
+    pub fn wait_for_create_runtime_hooks_done(&mut self) -> Result<()> {
+        let msg = self
+            .receiver
+            .recv()
+            .context("failed to wait for create runtime hooks")?;
+
+        match msg {
+            Message::CreateRuntimeHooksDone => Ok(()),
+            msg => bail!(
+                "receive unexpected message {:?} waiting for create runtime hooks done",
+                msg
+            ),
+        }
+    }
+
     pub fn close(&self) -> Result<()> {
         self.receiver.close()
     }
@@ -380,6 +477,22 @@ where
         }
     }
 
+    // Blocks until a message can be received, or bails once the timeout has
+    // elapsed. A broken connection is reported by the following recv.
+    pub fn wait_readable(&self, timeout: Duration) -> Result<()> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let mut fds = [PollFd::new(self.receiver, PollFlags::POLLIN)];
+            match poll::poll(&mut fds, remaining.as_millis() as i32) {
+                Ok(0) => bail!("timed out after {:?}", timeout),
+                Ok(_) => return Ok(()),
+                Err(Errno::EINTR) => continue,
+                Err(e) => bail!("failed to poll channel: {}", e),
+            }
+        }
+    }
+
     // Recv the next message of type T.
     pub fn recv(&mut self) -> Result<T> {
         let (buf, _) = self.recv_into_buf_with_len::<[RawFd; 0]>()?;
@@ -467,6 +580,33 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    #[serial]
+    fn test_channel_create_runtime_hooks() -> Result<()> {
+        let (main_sender, main_receiver) = &mut main_channel()?;
+        let (init_sender, init_receiver) = &mut init_channel()?;
+        match unsafe { unistd::fork()? } {
+            unistd::ForkResult::Parent { child } => {
+                main_receiver.wait_for_create_runtime_hooks_request()?;
+                init_sender.create_runtime_hooks_done()?;
+                wait::waitpid(child, None)?;
+                main_receiver.close()?;
+                init_sender.close()?;
+            }
+            unistd::ForkResult::Child => {
+                main_sender
+                    .create_runtime_hooks_request()
+                    .with_context(|| "Failed to send create runtime hooks request")?;
+                init_receiver.wait_for_create_runtime_hooks_done()?;
+                main_sender.close()?;
+                init_receiver.close()?;
+                std::process::exit(0);
+            }
+        };
+
+        Ok(())
+    }
+
     #[test]
     #[serial]
     fn test_channel_id_mapping_request() -> Result<()> {
@@ -516,12 +656,40 @@ mod tests {
         match unsafe { unistd::fork()? } {
             unistd::ForkResult::Parent { child } => {
                 wait::waitpid(child, None)?;
-                receiver.wait_for_init_ready()?;
+                assert_eq!(receiver.wait_for_init_ready()?, None);
+                receiver.close()?;
+            }
+            unistd::ForkResult::Child => {
+                sender
+                    .init_ready(None)
+                    .with_context(|| "Failed to send init ready")?;
+                sender.close()?;
+                std::process::exit(0);
+            }
+        };
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_channel_init_ready_console_master() -> Result<()> {
+        let (sender, receiver) = &mut main_channel()?;
+        match unsafe { unistd::fork()? } {
+            unistd::ForkResult::Parent { child } => {
+                wait::waitpid(child, None)?;
+                let master = receiver
+                    .wait_for_init_ready()?
+                    .expect("pty master is passed along");
+                // the master refers to the same pty as the one of the child
+                assert!(unistd::isatty(master)?);
+                unistd::close(master)?;
                 receiver.close()?;
             }
             unistd::ForkResult::Child => {
+                let pty = nix::pty::openpty(None, None)?;
                 sender
-                    .init_ready()
+                    .init_ready(Some(pty.master))
                     .with_context(|| "Failed to send init ready")?;
                 sender.close()?;
                 std::process::exit(0);
@@ -531,6 +699,44 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    #[serial]
+    fn test_channel_init_error() -> Result<()> {
+        let (sender, receiver) = &mut main_channel()?;
+        match unsafe { unistd::fork()? } {
+            unistd::ForkResult::Parent { child } => {
+                wait::waitpid(child, None)?;
+                let err = receiver
+                    .wait_for_init_ready()
+                    .expect_err("init error is reported");
+                assert!(format!("{:?}", err).contains("no such executable"));
+                receiver.close()?;
+            }
+            unistd::ForkResult::Child => {
+                sender.send_error(&anyhow::anyhow!("no such executable"))?;
+                sender.close()?;
+                // errors after the sender is closed are not sent anymore
+                sender.send_error(&anyhow::anyhow!("ignored"))?;
+                std::process::exit(0);
+            }
+        };
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_channel_init_ready_timeout() -> Result<()> {
+        let (sender, receiver) = &mut main_channel()?;
+        // the sender is kept open, but nothing is sent
+        let ret = receiver.wait_for_init_ready_timeout(Some(Duration::from_millis(100)));
+        assert!(ret.is_err());
+        sender.close()?;
+        receiver.close()?;
+
+        Ok(())
+    }
+
     #[test]
     #[serial]
     fn test_channel_main_graceful_exit() -> Result<()> {
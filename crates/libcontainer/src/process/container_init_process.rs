@@ -1,10 +1,12 @@
 use super::args::ContainerArgs;
-use crate::apparmor;
+use super::container_main_process;
+use crate::rootfs::mount::unified_process_cgroup;
 use crate::syscall::Syscall;
 use crate::workload::ExecutorManager;
+use crate::{apparmor, selinux};
 use crate::{
-    capabilities, hooks, namespaces::Namespaces, process::channel, rootfs::RootFS,
-    rootless::Rootless, seccomp, tty, utils,
+    capabilities, hooks, io_priority, namespaces::Namespaces, personality, process::channel,
+    rootfs::RootFS, rootless::Rootless, scheduler, seccomp, sysctl, tty, utils,
 };
 use anyhow::{bail, Context, Result};
 use nix::mount::MsFlags;
@@ -17,12 +19,8 @@ use nix::{
     unistd::{self, Gid, Uid},
 };
 use oci_spec::runtime::{LinuxNamespaceType, Spec, User};
-use std::collections::HashMap;
 use std::os::unix::io::AsRawFd;
-use std::{
-    env, fs,
-    path::{Path, PathBuf},
-};
+use std::{env, fs, path::Path};
 
 // Get a list of open fds for the calling process.
 fn get_open_fds() -> Result<Vec<i32>> {
@@ -54,14 +52,24 @@ fn get_open_fds() -> Result<Vec<i32>> {
 // Cleanup any extra file descriptors, so the new container process will not
 // leak a file descriptor from before execve gets executed. The first 3 fd will
 // stay open: stdio, stdout, and stderr. We would further preserve the next
-// "preserve_fds" number of fds. Set the rest of fd with CLOEXEC flag, so they
-// will be closed after execve into the container payload. We can't close the
-// fds immediately since we at least still need it for the pipe used to wait on
-// starting the container.
+// "preserve_fds" number of fds and clear their CLOEXEC flag. Set the rest of
+// fd with CLOEXEC flag, so they will be closed after execve into the
+// container payload. We can't close the fds immediately since we at least
+// still need it for the pipe used to wait on starting the container.
 fn cleanup_file_descriptors(preserve_fds: i32) -> Result<()> {
     let open_fds = get_open_fds().with_context(|| "Failed to obtain opened fds")?;
     // Include stdin, stdout, and stderr for fd 0, 1, and 2 respectively.
-    let min_fd = preserve_fds + 3;
+    let min_fd = preserve_fds
+        .checked_add(3)
+        .with_context(|| format!("too many preserved file descriptors: {}", preserve_fds))?;
+
+    // The preserved fds have to stay open across execve, even if they were
+    // opened with CLOEXEC. Whether they are open has been validated on
+    // creation already.
+    for fd in 3..min_fd {
+        let _ = fcntl::fcntl(fd, fcntl::F_SETFD(fcntl::FdFlag::empty()));
+    }
+
     let to_be_cleaned_up_fds: Vec<i32> = open_fds
         .iter()
         .filter_map(|&fd| if fd >= min_fd { Some(fd) } else { None })
@@ -76,20 +84,42 @@ fn cleanup_file_descriptors(preserve_fds: i32) -> Result<()> {
     Ok(())
 }
 
-fn sysctl(kernel_params: &HashMap<String, String>) -> Result<()> {
-    let sys = PathBuf::from("/proc/sys");
-    for (kernel_param, value) in kernel_params {
-        let path = sys.join(kernel_param.replace('.', "/"));
-        log::debug!(
-            "apply value {} to kernel parameter {}.",
-            value,
-            kernel_param
-        );
-        fs::write(path, value.as_bytes())
-            .with_context(|| format!("failed to set sysctl {}={}", kernel_param, value))?;
-    }
+// Remounts the root read only. A bind remount replaces the per mount flags,
+// so the current ones are kept, as they may be locked in a user namespace.
+fn readonly_root(syscall: &dyn Syscall) -> Result<()> {
+    let mount_infos = procfs::process::Process::myself()?
+        .mountinfo()
+        .context("failed to get mountinfo")?;
+    let current = mount_infos
+        .iter()
+        .rev()
+        .find(|m| m.mount_point == Path::new("/"))
+        .map(|m| mount_flags(m.mount_options.keys().map(String::as_str)))
+        .unwrap_or_else(MsFlags::empty);
 
-    Ok(())
+    syscall.mount(
+        None,
+        Path::new("/"),
+        None,
+        current | MsFlags::MS_RDONLY | MsFlags::MS_REMOUNT | MsFlags::MS_BIND,
+        None,
+    )
+}
+
+// Converts the per mount options listed in the mountinfo into mount flags
+fn mount_flags<'a>(options: impl Iterator<Item = &'a str>) -> MsFlags {
+    options.fold(MsFlags::empty(), |flags, option| {
+        flags
+            | match option {
+                "nosuid" => MsFlags::MS_NOSUID,
+                "nodev" => MsFlags::MS_NODEV,
+                "noexec" => MsFlags::MS_NOEXEC,
+                "noatime" => MsFlags::MS_NOATIME,
+                "nodiratime" => MsFlags::MS_NODIRATIME,
+                "relatime" => MsFlags::MS_RELATIME,
+                _ => MsFlags::empty(),
+            }
+    })
 }
 
 // make a read only path
@@ -132,34 +162,35 @@ fn readonly_path(path: &Path, syscall: &dyn Syscall) -> Result<()> {
 
 // For files, bind mounts /dev/null over the top of the specified path.
 // For directories, mounts read-only tmpfs over the top of the specified path.
-fn masked_path(path: &Path, mount_label: &Option<String>, syscall: &dyn Syscall) -> Result<()> {
-    if let Err(e) = syscall.mount(
-        Some(Path::new("/dev/null")),
-        path,
-        None,
-        MsFlags::MS_BIND,
-        None,
-    ) {
-        if let Some(errno) = e.downcast_ref() {
-            if matches!(errno, nix::errno::Errno::ENOENT) {
-                log::warn!("masked path {:?} not exist", path);
-            } else if matches!(errno, nix::errno::Errno::ENOTDIR) {
-                let label = match mount_label {
-                    Some(l) => format!("context=\"{}\"", l),
-                    None => "".to_string(),
-                };
-                syscall.mount(
-                    Some(Path::new("tmpfs")),
-                    path,
-                    Some("tmpfs"),
-                    MsFlags::MS_RDONLY,
-                    Some(label.as_str()),
-                )?;
-            }
-        } else {
-            bail!(e)
+fn masked_path(path: &Path, mount_label: Option<&str>, syscall: &dyn Syscall) -> Result<()> {
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            log::debug!("masked path {:?} does not exist, skipping", path);
+            return Ok(());
         }
+        Err(e) => return Err(e).with_context(|| format!("failed to stat masked path {:?}", path)),
     };
+
+    if metadata.is_dir() {
+        // directories are shadowed by an empty read only tmpfs
+        let label = selinux::format_mount_data("", Some("tmpfs"), mount_label);
+        syscall.mount(
+            Some(Path::new("tmpfs")),
+            path,
+            Some("tmpfs"),
+            MsFlags::MS_RDONLY,
+            Some(label.as_str()),
+        )?;
+    } else {
+        syscall.mount(
+            Some(Path::new("/dev/null")),
+            path,
+            None,
+            MsFlags::MS_BIND,
+            None,
+        )?;
+    }
     Ok(())
 }
 
@@ -178,11 +209,22 @@ fn apply_rest_namespaces(
         })
         .with_context(|| "failed to apply namespaces")?;
 
-    // Only set the host name if entering into a new uts namespace
-    if let Some(uts_namespace) = namespaces.get(LinuxNamespaceType::Uts) {
-        if uts_namespace.path().is_none() {
-            if let Some(hostname) = spec.hostname() {
-                syscall.set_hostname(hostname)?;
+    // Only set the host name and the domain name if entering into a new uts
+    // namespace
+    match namespaces.get(LinuxNamespaceType::Uts) {
+        Some(uts_namespace) => {
+            if uts_namespace.path().is_none() {
+                if let Some(hostname) = spec.hostname() {
+                    syscall.set_hostname(hostname)?;
+                }
+                if let Some(domainname) = spec.domainname() {
+                    syscall.set_domainname(domainname)?;
+                }
+            }
+        }
+        None => {
+            if spec.domainname().is_some() {
+                bail!("domainname requires a uts namespace");
             }
         }
     }
@@ -227,10 +269,22 @@ pub fn container_init_process(
 
     setsid().context("failed to create session")?;
     // set up tty if specified
-    if let Some(csocketfd) = args.console_socket {
-        tty::setup_console(&csocketfd).with_context(|| "failed to set up tty")?;
-    }
+    let console_master = match args.console_socket {
+        Some(csocketfd) => tty::setup_console(
+            &csocketfd,
+            proc.console_size().as_ref(),
+            args.keep_console_master,
+        )
+        .with_context(|| "failed to set up tty")?,
+        None => None,
+    };
 
+    // The cgroup of the process becomes the root of a new cgroup namespace,
+    // so it is recorded before in case cgroup2 has to be bind mounted.
+    let cgroup2_path = match namespaces.get(LinuxNamespaceType::Cgroup) {
+        Some(cgroup_ns) if cgroup_ns.path().is_none() => unified_process_cgroup().ok(),
+        _ => None,
+    };
     apply_rest_namespaces(&namespaces, spec, syscall)?;
 
     if let Some(true) = proc.no_new_privileges() {
@@ -238,6 +292,15 @@ pub fn container_init_process(
     }
 
     if args.init {
+        // create_runtime hooks are run by the main process in the runtime
+        // namespace. Wait for them to finish before setting up the rootfs.
+        if container_main_process::has_create_runtime_hooks(spec) {
+            main_sender.create_runtime_hooks_request()?;
+            init_receiver
+                .wait_for_create_runtime_hooks_done()
+                .context("failed to wait for create runtime hooks")?;
+        }
+
         // create_container hook needs to be called after the namespace setup, but
         // before pivot_root is called. This runs in the container namespaces.
         if let Some(hooks) = hooks {
@@ -253,6 +316,7 @@ pub fn container_init_process(
                 rootfs_path,
                 bind_service,
                 namespaces.get(LinuxNamespaceType::Cgroup).is_some(),
+                cgroup2_path.as_deref(),
             )
             .with_context(|| "Failed to prepare rootfs")?;
 
@@ -260,7 +324,11 @@ pub fn container_init_process(
         // we use pivot_root, but if we are on the host mount namespace, we will
         // use simple chroot. Scary things will happen if you try to pivot_root
         // in the host mount namespace...
-        if namespaces.get(LinuxNamespaceType::Mount).is_some() {
+        if namespaces.get(LinuxNamespaceType::Mount).is_some() && args.no_pivot {
+            syscall
+                .move_rootfs(rootfs_path)
+                .with_context(|| format!("failed to move root to {:?}", rootfs_path))?;
+        } else if namespaces.get(LinuxNamespaceType::Mount).is_some() {
             // change the root of filesystem of the process to the rootfs
             syscall
                 .pivot_rootfs(rootfs_path)
@@ -278,8 +346,11 @@ pub fn container_init_process(
         reopen_dev_null()?;
 
         if let Some(kernel_params) = linux.sysctl() {
-            sysctl(kernel_params)
-                .with_context(|| format!("failed to sysctl: {:?}", kernel_params))?;
+            sysctl::set_sysctl(
+                kernel_params,
+                linux.namespaces().as_deref().unwrap_or_default(),
+            )
+            .with_context(|| format!("failed to sysctl: {:?}", kernel_params))?;
         }
     }
 
@@ -288,24 +359,30 @@ pub fn container_init_process(
             .with_context(|| format!("failed to apply apparmor profile {}", profile))?;
     }
 
-    if let Some(true) = spec.root().as_ref().map(|r| r.readonly().unwrap_or(false)) {
-        syscall.mount(
-            None,
-            Path::new("/"),
-            None,
-            MsFlags::MS_RDONLY | MsFlags::MS_REMOUNT | MsFlags::MS_BIND,
-            None,
-        )?
+    let labels = selinux::Labels::from_spec(spec);
+    if let Some(label) = &labels.process {
+        selinux::set_exec_label(label)
+            .with_context(|| format!("failed to apply selinux label {}", label))?;
     }
 
-    if let Some(umask) = proc.user().umask() {
-        if let Some(mode) = Mode::from_bits(umask) {
-            nix::sys::stat::umask(mode);
-        } else {
-            bail!("invalid umask {}", umask);
-        }
+    // Setting a real-time scheduler policy requires CAP_SYS_NICE, so this has
+    // to happen before the capabilities are dropped.
+    if let Some(sched) = proc.scheduler() {
+        scheduler::set_scheduler(sched).context("failed to set scheduler")?;
     }
 
+    if let Some(io_priority) = proc.io_priority() {
+        io_priority::set_io_priority(io_priority).context("failed to set io priority")?;
+    }
+
+    if let Some(persona) = linux.personality() {
+        personality::set_personality(persona).context("failed to set personality")?;
+    }
+
+    // Validate the umask early, but only apply it right before the payload is
+    // executed, so that files created by the runtime are not affected.
+    let umask = proc.user().umask().map(umask_mode).transpose()?;
+
     if let Some(paths) = linux.readonly_paths() {
         // mount readonly path
         for path in paths {
@@ -317,11 +394,17 @@ pub fn container_init_process(
     if let Some(paths) = linux.masked_paths() {
         // mount masked path
         for path in paths {
-            masked_path(Path::new(path), linux.mount_label(), syscall)
+            masked_path(Path::new(path), labels.mount.as_deref(), syscall)
                 .with_context(|| format!("failed to set masked path {:?}", path))?;
         }
     }
 
+    // The root is made read only after everything else has been mounted, so
+    // the mount points can still be created and writable mounts stay writable.
+    if let Some(true) = spec.root().as_ref().map(|r| r.readonly().unwrap_or(false)) {
+        readonly_root(syscall).context("failed to make root read only")?;
+    }
+
     let cwd = format!("{}", proc.cwd().display());
     let do_chdir = if cwd.is_empty() {
         false
@@ -336,6 +419,13 @@ pub fn container_init_process(
         }
     };
 
+    // Dropping capabilities from the bounding set requires CAP_SETPCAP, which
+    // may be gone after switching the user. All mounts are done at this point.
+    if let Some(caps) = proc.capabilities() {
+        capabilities::drop_bounding(caps, syscall)
+            .context("failed to drop bounding capabilities")?;
+    }
+
     set_supplementary_gids(proc.user(), args.rootless, syscall)
         .context("failed to set supplementary gids")?;
 
@@ -351,8 +441,8 @@ pub fn container_init_process(
     // as close to exec as possible.
     if let Some(seccomp) = linux.seccomp() {
         if proc.no_new_privileges().is_none() {
-            let notify_fd =
-                seccomp::initialize_seccomp(seccomp).context("failed to execute seccomp")?;
+            let notify_fd = seccomp::initialize_seccomp_with_options(seccomp, args.seccomp_options)
+                .context("failed to execute seccomp")?;
             sync_seccomp(notify_fd, main_sender, init_receiver)
                 .context("failed to sync seccomp")?;
         }
@@ -391,7 +481,12 @@ pub fn container_init_process(
                 ]);
             }
 
-            args.preserve_fds + listen_fds
+            args.preserve_fds.checked_add(listen_fds).with_context(|| {
+                format!(
+                    "too many preserved file descriptors with LISTEN_FDS={}",
+                    listen_fds
+                )
+            })?
         }
         Err(env::VarError::NotPresent) => args.preserve_fds,
         Err(env::VarError::NotUnicode(value)) => {
@@ -422,17 +517,17 @@ pub fn container_init_process(
 
     // Reset the process env based on oci spec.
     env::vars().for_each(|(key, _value)| env::remove_var(key));
-    utils::parse_env(&envs)
-        .iter()
+    utils::normalize_env(&envs)
+        .into_iter()
         .for_each(|(key, value)| env::set_var(key, value));
 
     // Initialize seccomp profile right before we are ready to execute the
     // payload so as few syscalls will happen between here and payload exec. The
-    // notify socket will still need network related syscalls.
+    // start barrier will still need file or network related syscalls.
     if let Some(seccomp) = linux.seccomp() {
         if proc.no_new_privileges().is_some() {
-            let notify_fd =
-                seccomp::initialize_seccomp(seccomp).context("failed to execute seccomp")?;
+            let notify_fd = seccomp::initialize_seccomp_with_options(seccomp, args.seccomp_options)
+                .context("failed to execute seccomp")?;
             sync_seccomp(notify_fd, main_sender, init_receiver)
                 .context("failed to sync seccomp")?;
         }
@@ -442,13 +537,17 @@ pub fn container_init_process(
     // payload.  Note, because we are already inside the pid namespace, the pid
     // outside the pid namespace should be recorded by the intermediate process
     // already.
-    main_sender.init_ready()?;
+    main_sender.init_ready(console_master)?;
     main_sender
         .close()
         .context("failed to close down main sender in init process")?;
+    // the pty master has been duplicated to the main process
+    if let Some(master) = console_master {
+        unistd::close(master).context("failed to close pty master")?;
+    }
 
-    // listing on the notify socket for container start command
-    args.notify_socket.wait_for_container_start()?;
+    // wait for the container start command
+    args.start_barrier.wait_for_container_start()?;
 
     // create_container hook needs to be called after the namespace setup, but
     // before pivot_root is called. This runs in the container namespaces.
@@ -458,6 +557,10 @@ pub fn container_init_process(
         }
     }
 
+    if let Some(mode) = umask {
+        nix::sys::stat::umask(mode);
+    }
+
     if proc.args().is_some() {
         ExecutorManager::exec(spec)
     } else {
@@ -489,48 +592,71 @@ pub fn container_init_process(
 //
 // Privileged user starting a normal container: Just add the supplementary groups.
 //
+// If no additional gids are specified, the supplementary groups inherited from the
+// runtime are cleared, unless setgroups has been disabled for the user namespace, in
+// which case there is nothing we could do about them.
+//
 fn set_supplementary_gids(
     user: &User,
     rootless: &Option<Rootless>,
     syscall: &dyn Syscall,
 ) -> Result<()> {
-    if let Some(additional_gids) = user.additional_gids() {
-        if additional_gids.is_empty() {
-            return Ok(());
-        }
-
+    let additional_gids = user.additional_gids().as_deref().unwrap_or_default();
+    let setgroups_denied = || -> Result<bool> {
         let setgroups =
             fs::read_to_string("/proc/self/setgroups").context("failed to read setgroups")?;
-        if setgroups.trim() == "deny" {
-            bail!("cannot set supplementary gids, setgroup is disabled");
+        Ok(setgroups.trim() == "deny")
+    };
+
+    if additional_gids.is_empty() {
+        if matches!(rootless, Some(r) if !r.privileged) || setgroups_denied()? {
+            log::debug!("setgroups is disabled, keeping inherited supplementary gids");
+            return Ok(());
         }
 
-        let gids: Vec<Gid> = additional_gids
-            .iter()
-            .map(|gid| Gid::from_raw(*gid))
-            .collect();
-
-        match rootless {
-            Some(r) if r.privileged => {
-                syscall.set_groups(&gids).with_context(|| {
-                    format!("failed to set privileged supplementary gids: {:?}", gids)
-                })?;
-            }
-            None => {
-                syscall.set_groups(&gids).with_context(|| {
-                    format!("failed to set unprivileged supplementary gids: {:?}", gids)
-                })?;
-            }
-            // this should have been detected during validation
-            _ => unreachable!(
-                "unprivileged users cannot set supplementary gids in rootless container"
-            ),
+        syscall
+            .set_groups(&[])
+            .context("failed to clear supplementary gids")?;
+        return Ok(());
+    }
+
+    if setgroups_denied()? {
+        bail!("cannot set supplementary gids, setgroup is disabled");
+    }
+
+    let gids: Vec<Gid> = additional_gids
+        .iter()
+        .map(|gid| Gid::from_raw(*gid))
+        .collect();
+
+    match rootless {
+        Some(r) if r.privileged => {
+            syscall.set_groups(&gids).with_context(|| {
+                format!("failed to set privileged supplementary gids: {:?}", gids)
+            })?;
+        }
+        None => {
+            syscall.set_groups(&gids).with_context(|| {
+                format!("failed to set unprivileged supplementary gids: {:?}", gids)
+            })?;
         }
+        // this should have been detected during validation
+        _ => unreachable!("unprivileged users cannot set supplementary gids in rootless container"),
     }
 
     Ok(())
 }
 
+/// Converts the umask of the spec into a mode, which must fit into the 12
+/// permission bits.
+fn umask_mode(umask: u32) -> Result<Mode> {
+    if umask > 0o7777 {
+        bail!("invalid umask {:#o}, must not be larger than 0o7777", umask);
+    }
+
+    Mode::from_bits(umask).with_context(|| format!("invalid umask {:#o}", umask))
+}
+
 fn sync_seccomp(
     fd: Option<i32>,
     main_sender: &mut channel::MainSender,
@@ -559,7 +685,7 @@ mod tests {
     use nix::{fcntl, sys, unistd};
     use oci_spec::runtime::{LinuxNamespaceBuilder, SpecBuilder, UserBuilder};
     use serial_test::serial;
-    use std::{fs, os::unix::prelude::AsRawFd};
+    use std::{fs, os::unix::prelude::AsRawFd, path::PathBuf};
 
     // Note: We have to run these tests here as serial. The main issue is that
     // these tests has a dependency on the system state. The
@@ -607,6 +733,25 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    #[serial]
+    fn test_cleanup_file_descriptors_preserved() -> Result<()> {
+        let fd = fcntl::open(
+            "/dev/null",
+            fcntl::OFlag::O_RDWR | fcntl::OFlag::O_CLOEXEC,
+            sys::stat::Mode::empty(),
+        )?;
+        // the last preserved fd loses CLOEXEC
+        cleanup_file_descriptors(fd - 2).with_context(|| "Failed to clean up the fds")?;
+        let fd_flag = fcntl::fcntl(fd, fcntl::F_GETFD)?;
+        if (fd_flag & fcntl::FdFlag::FD_CLOEXEC.bits()) != 0 {
+            bail!("CLOEXEC flag of the preserved fd is not cleared");
+        }
+
+        unistd::close(fd)?;
+        Ok(())
+    }
+
     #[test]
     fn test_readonly_path() -> Result<()> {
         let syscall = create_syscall();
@@ -644,6 +789,34 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_mount_flags() {
+        let flags = mount_flags(["rw", "nosuid", "nodev", "relatime"].into_iter());
+        assert_eq!(
+            flags,
+            MsFlags::MS_NOSUID | MsFlags::MS_NODEV | MsFlags::MS_RELATIME
+        );
+        assert_eq!(mount_flags(["rw"].into_iter()), MsFlags::empty());
+    }
+
+    #[test]
+    fn test_readonly_root() -> Result<()> {
+        let syscall = create_syscall();
+        readonly_root(syscall.as_ref())?;
+
+        let got = syscall
+            .as_any()
+            .downcast_ref::<TestHelperSyscall>()
+            .unwrap()
+            .get_mount_args();
+        assert_eq!(got.len(), 1);
+        assert_eq!(got[0].target, PathBuf::from("/"));
+        assert!(got[0]
+            .flags
+            .contains(MsFlags::MS_RDONLY | MsFlags::MS_REMOUNT | MsFlags::MS_BIND));
+        Ok(())
+    }
+
     #[test]
     fn test_apply_rest_namespaces() -> Result<()> {
         let syscall = create_syscall();
@@ -671,6 +844,41 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_apply_rest_namespaces_domainname() -> Result<()> {
+        let syscall = create_syscall();
+        let mut spec = SpecBuilder::default().build()?;
+        spec.set_domainname(Some("youki.test".to_owned()));
+        let linux_spaces = vec![LinuxNamespaceBuilder::default()
+            .typ(LinuxNamespaceType::Uts)
+            .build()?];
+
+        apply_rest_namespaces(
+            &Namespaces::from(Some(&linux_spaces)),
+            &spec,
+            syscall.as_ref(),
+        )?;
+        let got_domainnames = syscall
+            .as_ref()
+            .as_any()
+            .downcast_ref::<TestHelperSyscall>()
+            .unwrap()
+            .get_domainname_args();
+        assert_eq!(vec!["youki.test".to_string()], got_domainnames);
+
+        // the domainname cannot be set without a uts namespace
+        assert!(apply_rest_namespaces(&Namespaces::from(None), &spec, syscall.as_ref()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_umask_mode() -> Result<()> {
+        assert_eq!(umask_mode(0o022)?, Mode::S_IWGRP | Mode::S_IWOTH);
+        assert_eq!(umask_mode(0o7777)?.bits(), 0o7777);
+        assert!(umask_mode(0o10000).is_err());
+        Ok(())
+    }
+
     #[test]
     fn test_set_supplementary_gids() -> Result<()> {
         // gids additional gids is empty case
@@ -678,6 +886,12 @@ mod tests {
         assert!(set_supplementary_gids(&user, &None, create_syscall().as_ref()).is_ok());
 
         let tests = vec![
+            // empty additional gids clear the inherited supplementary gids
+            (
+                UserBuilder::default().additional_gids(vec![]).build()?,
+                None::<Rootless>,
+                vec![vec![]],
+            ),
             (
                 UserBuilder::default()
                     .additional_gids(vec![33, 34])
@@ -711,7 +925,8 @@ mod tests {
             let result = set_supplementary_gids(&user, &rootless, syscall.as_ref());
             match fs::read_to_string("/proc/self/setgroups")?.trim() {
                 "deny" => {
-                    assert!(result.is_err());
+                    let gids = user.additional_gids().as_deref().unwrap_or_default();
+                    assert_eq!(result.is_err(), !gids.is_empty());
                 }
                 "allow" => {
                     assert!(result.is_ok());
@@ -766,23 +981,47 @@ mod tests {
             .as_any()
             .downcast_ref::<TestHelperSyscall>()
             .unwrap();
-        mocks.set_ret_err(ArgName::Mount, || bail!(nix::errno::Errno::ENOENT));
 
-        assert!(masked_path(Path::new("/proc/self"), &None, syscall.as_ref()).is_ok());
+        assert!(masked_path(Path::new("/proc/does_not_exist"), None, syscall.as_ref()).is_ok());
         let got = mocks.get_mount_args();
         assert_eq!(0, got.len());
     }
 
     #[test]
-    fn test_masked_path_is_file_with_no_label() {
+    fn test_masked_path_is_file() -> Result<()> {
+        let tmp = utils::create_temp_dir("test_masked_path_is_file")?;
+        let file = tmp.join("kcore");
+        fs::write(&file, "secret")?;
+        let syscall = create_syscall();
+        let mocks = syscall
+            .as_any()
+            .downcast_ref::<TestHelperSyscall>()
+            .unwrap();
+
+        masked_path(&file, Some("default"), syscall.as_ref())?;
+
+        let got = mocks.get_mount_args();
+        let want = MountArgs {
+            source: Some(PathBuf::from("/dev/null")),
+            target: file,
+            fstype: None,
+            flags: MsFlags::MS_BIND,
+            data: None,
+        };
+        assert_eq!(1, got.len());
+        assert_eq!(want, got[0]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_masked_path_is_dir_with_no_label() {
         let syscall = create_syscall();
         let mocks = syscall
             .as_any()
             .downcast_ref::<TestHelperSyscall>()
             .unwrap();
-        mocks.set_ret_err(ArgName::Mount, || bail!(nix::errno::Errno::ENOTDIR));
 
-        assert!(masked_path(Path::new("/proc/self"), &None, syscall.as_ref()).is_ok());
+        assert!(masked_path(Path::new("/proc/self"), None, syscall.as_ref()).is_ok());
 
         let got = mocks.get_mount_args();
         let want = MountArgs {
@@ -797,20 +1036,14 @@ mod tests {
     }
 
     #[test]
-    fn test_masked_path_is_file_with_label() {
+    fn test_masked_path_is_dir_with_label() {
         let syscall = create_syscall();
         let mocks = syscall
             .as_any()
             .downcast_ref::<TestHelperSyscall>()
             .unwrap();
-        mocks.set_ret_err(ArgName::Mount, || bail!(nix::errno::Errno::ENOTDIR));
 
-        assert!(masked_path(
-            Path::new("/proc/self"),
-            &Some("default".to_string()),
-            syscall.as_ref()
-        )
-        .is_ok());
+        assert!(masked_path(Path::new("/proc/self"), Some("default"), syscall.as_ref()).is_ok());
 
         let got = mocks.get_mount_args();
         let want = MountArgs {
@@ -833,7 +1066,7 @@ mod tests {
             .unwrap();
         mocks.set_ret_err(ArgName::Mount, || bail!("unknown error"));
 
-        assert!(masked_path(Path::new("/proc/self"), &None, syscall.as_ref()).is_err());
+        assert!(masked_path(Path::new("/proc/self"), None, syscall.as_ref()).is_err());
         let got = mocks.get_mount_args();
         assert_eq!(0, got.len());
     }
@@ -38,6 +38,11 @@ pub fn container_intermediate_process(
         args.init,
     )
     .context("failed to apply cgroups")?;
+    if let Some(prog_fd) = args.bpf_audit {
+        args.cgroup_manager
+            .attach_bpf_audit(prog_fd)
+            .context("failed to attach eBPF audit program")?;
+    }
 
     // if new user is specified in specification, this will be true and new
     // namespace will be created, check
@@ -83,6 +88,14 @@ pub fn container_intermediate_process(
             .with_context(|| format!("failed to enter pid namespace: {:?}", pid_namespace))?;
     }
 
+    // Same goes for the time namespace, whose offsets can only be set until
+    // the init process has entered it.
+    if let Some(time_namespace) = args.time_namespace {
+        time_namespace
+            .apply(command)
+            .context("failed to enter time namespace")?;
+    }
+
     // We have to record the pid of the child (container init process), since
     // the child will be inside the pid namespace. We can't rely on child_ready
     // to send us the correct pid.
@@ -95,7 +108,13 @@ pub fn container_intermediate_process(
         intermediate_sender
             .close()
             .context("failed to close sender in the intermediate process")?;
-        container_init_process(args, main_sender, init_receiver)
+        let ret = container_init_process(args, main_sender, init_receiver);
+        // Report the error to the main process, which waits for init ready,
+        // so it fails with the actual cause instead of a broken channel.
+        if let Err(err) = &ret {
+            let _ = main_sender.send_error(err);
+        }
+        ret
     })?;
     // Once we fork the container init process, the job for intermediate process
     // is done. We notify the container main process about the pid we just
@@ -1,18 +1,25 @@
 use crate::{
     container::ContainerProcessState,
+    hooks, intel_rdt,
     process::{args::ContainerArgs, channel, container_intermediate_process, fork},
     rootless::Rootless,
     seccomp, utils,
 };
 use anyhow::{Context, Result};
 use nix::{
-    sys::{socket, uio},
+    sys::{
+        signal::{self, Signal},
+        socket, uio,
+        wait::{self, WaitStatus},
+    },
     unistd::{self, Pid},
 };
 use oci_spec::runtime;
-use std::path::Path;
+use std::{os::unix::prelude::RawFd, path::Path, time::Duration};
 
-pub fn container_main_process(container_args: &ContainerArgs) -> Result<Pid> {
+/// Creates the container process and returns its pid, along with the pty
+/// master if it has been requested to be kept
+pub fn container_main_process(container_args: &ContainerArgs) -> Result<(Pid, Option<RawFd>)> {
     // We use a set of channels to communicate between parent and child process.
     // Each channel is uni-directional. Because we will pass these channel to
     // forked process, we have to be deligent about closing any unused channel.
@@ -23,14 +30,18 @@ pub fn container_main_process(container_args: &ContainerArgs) -> Result<Pid> {
     let (init_sender, init_receiver) = &mut channel::init_channel()?;
 
     let intermediate_pid = fork::container_fork(|| {
-        container_intermediate_process::container_intermediate_process(
+        let ret = container_intermediate_process::container_intermediate_process(
             container_args,
             intermediate_sender,
             intermediate_receiver,
             init_sender,
             init_receiver,
             main_sender,
-        )
+        );
+        if let Err(err) = &ret {
+            let _ = main_sender.send_error(err);
+        }
+        ret
     })?;
     // Close down unused fds. The corresponding fds are duplicated to the
     // child process during fork.
@@ -57,20 +68,71 @@ pub fn container_main_process(container_args: &ContainerArgs) -> Result<Pid> {
     // process.  The intermediate process should exit after this point.
     let init_pid = main_receiver.wait_for_intermediate_ready()?;
 
+    // The init process is reparented to the host init, or to the nearest
+    // subreaper like youki running a container in the foreground, once the
+    // intermediate process has exited. Reaping it here makes sure that a
+    // detached container does not depend on the youki process anymore.
+    match wait::waitpid(intermediate_pid, None) {
+        Ok(WaitStatus::Exited(_, 0)) => {}
+        Ok(status) => log::warn!("intermediate process exited unexpectedly: {:?}", status),
+        Err(e) => log::warn!("failed to wait for intermediate process: {}", e),
+    }
+
+    // The init process is moved into its resctrl group by the main process,
+    // before it is released to set up and execute the container process.
+    if container_args.init {
+        if let Some(intel_rdt) = container_args
+            .spec
+            .linux()
+            .as_ref()
+            .and_then(|linux| linux.intel_rdt().as_ref())
+        {
+            let container = container_args
+                .container
+                .as_ref()
+                .context("container state is required to set up intel RDT")?;
+            intel_rdt::setup_intel_rdt(container.id(), init_pid, intel_rdt)
+                .context("failed to set up intel RDT")?;
+        }
+    }
+
+    // The createRuntime hooks run in the runtime namespace, after the container
+    // namespaces have been created, but before the init process pivots into the
+    // rootfs. The init process asks for them and waits until they are done.
+    if container_args.init && has_create_runtime_hooks(container_args.spec) {
+        main_receiver.wait_for_create_runtime_hooks_request()?;
+        let mut container = container_args
+            .container
+            .clone()
+            .context("container state is required to run hook")?;
+        container.set_pid(init_pid.as_raw());
+        let hooks = container_args.spec.hooks().as_ref();
+        hooks::run_hooks(
+            hooks.and_then(|hooks| hooks.create_runtime().as_ref()),
+            Some(&container),
+        )
+        .context("failed to run create runtime hooks")?;
+        init_sender.create_runtime_hooks_done()?;
+    }
+
+    // The init process loads the seccomp profile before it reports to be
+    // ready, so the notify fd is received and handed to the seccomp agent while
+    // the container is created. Nothing has to be kept until start, which may
+    // happen from another youki process.
     if let Some(linux) = container_args.spec.linux() {
         if let Some(seccomp) = linux.seccomp() {
+            let mut container = container_args
+                .container
+                .clone()
+                .context("container state is required")?;
+            container.set_pid(init_pid.as_raw());
             let state = ContainerProcessState {
                 oci_version: container_args.spec.version().to_string(),
                 // runc hardcode the `seccompFd` name for fds.
                 fds: vec![String::from("seccompFd")],
                 pid: init_pid.as_raw(),
                 metadata: seccomp.listener_metadata().to_owned().unwrap_or_default(),
-                state: container_args
-                    .container
-                    .as_ref()
-                    .context("container state is required")?
-                    .state
-                    .clone(),
+                state: container.state,
             };
             sync_seccomp(seccomp, &state, init_sender, main_receiver)
                 .context("failed to sync seccomp with init")?;
@@ -83,13 +145,45 @@ pub fn container_main_process(container_args: &ContainerArgs) -> Result<Pid> {
         .close()
         .context("failed to close unused init sender")?;
 
-    main_receiver
-        .wait_for_init_ready()
-        .context("failed to wait for init ready")?;
+    // Only return once the init process is set up, so that a following start
+    // does not race with it. A stuck init process is killed after the timeout.
+    let timeout = init_ready_timeout(container_args.spec);
+    let console_master = match main_receiver.wait_for_init_ready_timeout(timeout) {
+        Ok(console_master) => console_master,
+        Err(err) => {
+            let _ = signal::kill(init_pid, Signal::SIGKILL);
+            return Err(err.context("failed to wait for init ready"));
+        }
+    };
 
     log::debug!("init pid is {:?}", init_pid);
 
-    Ok(init_pid)
+    Ok((init_pid, console_master))
+}
+
+/// Returns true if the spec declares createRuntime hooks, in which case the
+/// init process synchronizes with the main process to run them.
+pub fn has_create_runtime_hooks(spec: &runtime::Spec) -> bool {
+    spec.hooks()
+        .as_ref()
+        .and_then(|hooks| hooks.create_runtime().as_ref())
+        .map_or(false, |hooks| !hooks.is_empty())
+}
+
+/// Returns how long to wait for the init process to become ready. The
+/// createContainer hooks run in the init process before, so their timeouts
+/// are added to the timeout. A hook without a timeout may run indefinitely, in
+/// which case there is no timeout at all.
+pub fn init_ready_timeout(spec: &runtime::Spec) -> Option<Duration> {
+    spec.hooks()
+        .as_ref()
+        .and_then(|hooks| hooks.create_container().as_ref())
+        .into_iter()
+        .flatten()
+        .try_fold(channel::INIT_READY_TIMEOUT, |timeout, hook| {
+            hook.timeout()
+                .map(|secs| timeout + Duration::from_secs(secs.max(0) as u64))
+        })
 }
 
 fn sync_seccomp(
@@ -155,9 +249,10 @@ fn sync_seccomp_send_msg(listener_path: &Path, msg: &[u8], fd: i32) -> Result<()
 
 fn setup_mapping(rootless: &Rootless, pid: Pid) -> Result<()> {
     log::debug!("write mapping for pid {:?}", pid);
-    if !rootless.privileged {
+    if !rootless.privileged && rootless.newgidmap.is_none() {
         // The main process is running as an unprivileged user and cannot write the mapping
-        // until "deny" has been written to setgroups. See CVE-2014-8989.
+        // until "deny" has been written to setgroups. See CVE-2014-8989. The
+        // setuid newgidmap binary is allowed to write the mapping either way.
         utils::write_file(format!("/proc/{}/setgroups", pid), "deny")?;
     }
 
@@ -180,13 +275,44 @@ mod tests {
         unistd::{self, getgid, getuid},
     };
     use oci_spec::runtime::{
-        LinuxIdMappingBuilder, LinuxSeccompAction, LinuxSeccompBuilder, LinuxSyscallBuilder,
+        Hook, HookBuilder, HooksBuilder, LinuxIdMappingBuilder, LinuxSeccompAction,
+        LinuxSeccompBuilder, LinuxSyscallBuilder, SpecBuilder,
     };
     use serial_test::serial;
     use std::fs;
 
     use crate::utils::TempDir;
 
+    #[test]
+    fn test_init_ready_timeout() -> Result<()> {
+        let hook = |timeout: Option<i64>| {
+            let mut hook = HookBuilder::default().path("/bin/true");
+            if let Some(timeout) = timeout {
+                hook = hook.timeout(timeout);
+            }
+            hook.build()
+        };
+        let spec = |hooks: Vec<Hook>| -> Result<runtime::Spec> {
+            Ok(SpecBuilder::default()
+                .hooks(HooksBuilder::default().create_container(hooks).build()?)
+                .build()?)
+        };
+
+        assert_eq!(
+            init_ready_timeout(&SpecBuilder::default().build()?),
+            Some(channel::INIT_READY_TIMEOUT)
+        );
+        assert_eq!(
+            init_ready_timeout(&spec(vec![hook(Some(5))?, hook(Some(10))?])?),
+            Some(channel::INIT_READY_TIMEOUT + Duration::from_secs(15))
+        );
+        assert_eq!(
+            init_ready_timeout(&spec(vec![hook(Some(5))?, hook(None)?])?),
+            None
+        );
+        Ok(())
+    }
+
     #[test]
     #[serial]
     fn setup_uid_mapping_should_succeed() -> Result<()> {
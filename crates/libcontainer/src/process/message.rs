@@ -9,4 +9,7 @@ pub enum Message {
     MappingWritten,
     SeccompNotify,
     SeccompNotifyDone,
+    CreateRuntimeHooks,
+    CreateRuntimeHooksDone,
+    OtherError(String),
 }
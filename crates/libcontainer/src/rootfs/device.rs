@@ -3,6 +3,7 @@ use crate::syscall::{syscall::create_syscall, Syscall};
 use crate::utils::{self, PathBufExt};
 use anyhow::{bail, Context, Result};
 use nix::{
+    errno::Errno,
     fcntl::{open, OFlag},
     mount::MsFlags,
     sys::stat::{umask, Mode},
@@ -43,7 +44,7 @@ impl Device {
                 if bind {
                     self.bind_dev(rootfs, dev)
                 } else {
-                    self.mknod_dev(rootfs, dev)
+                    self.create_dev(rootfs, dev)
                 }
             })
             .collect::<Result<Vec<_>>>()?;
@@ -52,19 +53,39 @@ impl Device {
         Ok(())
     }
 
+    /// Creates the device node, or bind mounts the one of the host if mknod
+    /// is not permitted, e.g. by the device cgroup or inside a user namespace
+    fn create_dev(&self, rootfs: &Path, dev: &LinuxDevice) -> Result<()> {
+        match self.mknod_dev(rootfs, dev) {
+            Err(e) => match e.downcast_ref::<Errno>() {
+                // the node exists already, e.g. if /dev is not a tmpfs
+                Some(Errno::EEXIST) => Ok(()),
+                Some(Errno::EPERM) => {
+                    log::debug!("mknod of {:?} not permitted, bind mounting it", dev.path());
+                    self.bind_dev(rootfs, dev)
+                }
+                _ => Err(e),
+            },
+            ok => ok,
+        }
+    }
+
     fn bind_dev(&self, rootfs: &Path, dev: &LinuxDevice) -> Result<()> {
         let full_container_path = create_container_dev_path(rootfs, dev)
             .with_context(|| format!("could not create container path for device {:?}", dev))?;
 
+        // the node must not be a symlink, which could point to the host
         let fd = open(
             &full_container_path,
-            OFlag::O_RDWR | OFlag::O_CREAT,
+            OFlag::O_RDWR | OFlag::O_CREAT | OFlag::O_NOFOLLOW,
             Mode::from_bits_truncate(0o644),
         )?;
         close(fd)?;
+        let dest_file = utils::open_in_rootfs(rootfs, &full_container_path)
+            .with_context(|| format!("unsafe device path {:?}", dev.path()))?;
         self.syscall.mount(
             Some(dev.path()),
-            &full_container_path,
+            &utils::proc_fd_path(&dest_file),
             Some("bind"),
             MsFlags::MS_BIND,
             None,
@@ -108,11 +129,12 @@ fn create_container_dev_path(rootfs: &Path, dev: &LinuxDevice) -> Result<PathBuf
     let full_container_path = utils::secure_join(rootfs, relative_dev_path)
         .with_context(|| format!("could not join {:?} with {:?}", rootfs, dev.path()))?;
 
-    crate::utils::create_dir_all(
-        &full_container_path
-            .parent()
-            .unwrap_or_else(|| Path::new("")),
-    )?;
+    let parent = full_container_path
+        .parent()
+        .unwrap_or_else(|| Path::new(""));
+    crate::utils::create_dir_all(parent)?;
+    utils::open_in_rootfs(rootfs, parent)
+        .with_context(|| format!("unsafe device path {:?}", dev.path()))?;
 
     Ok(full_container_path)
 }
@@ -120,7 +142,7 @@ fn create_container_dev_path(rootfs: &Path, dev: &LinuxDevice) -> Result<PathBuf
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::syscall::test::{ChownArgs, MknodArgs, MountArgs, TestHelperSyscall};
+    use crate::syscall::test::{ArgName, ChownArgs, MknodArgs, MountArgs, TestHelperSyscall};
     use crate::utils::TempDir;
     use nix::{
         sys::stat::SFlag,
@@ -207,6 +229,43 @@ mod tests {
         assert_eq!(want_chown, *got_chown);
     }
 
+    #[test]
+    fn test_create_dev_falls_back_to_bind() {
+        let tmp_dir = TempDir::new("/tmp/test_create_dev_falls_back_to_bind").unwrap();
+        let device = Device::new();
+        let mocks = device
+            .syscall
+            .as_any()
+            .downcast_ref::<TestHelperSyscall>()
+            .unwrap();
+        mocks.set_ret_err(ArgName::Mknod, || Err(Errno::EPERM.into()));
+        mocks.set_ret_err_times(ArgName::Mknod, 1);
+
+        let dev = LinuxDeviceBuilder::default()
+            .path(PathBuf::from("/null"))
+            .major(1)
+            .minor(3)
+            .typ(LinuxDeviceType::C)
+            .build()
+            .unwrap();
+        assert!(device.create_dev(tmp_dir.path(), &dev).is_ok());
+        assert!(mocks.get_mknod_args().is_empty());
+        assert_eq!(
+            mocks.get_mount_args()[0].target,
+            tmp_dir.path().join("null")
+        );
+
+        // an existing node is kept
+        mocks.set_ret_err(ArgName::Mknod, || Err(Errno::EEXIST.into()));
+        mocks.set_ret_err_times(ArgName::Mknod, 1);
+        assert!(device.create_dev(tmp_dir.path(), &dev).is_ok());
+        assert_eq!(mocks.get_mount_args().len(), 1);
+
+        mocks.set_ret_err(ArgName::Mknod, || Err(Errno::EINVAL.into()));
+        mocks.set_ret_err_times(ArgName::Mknod, 1);
+        assert!(device.create_dev(tmp_dir.path(), &dev).is_err());
+    }
+
     #[test]
     fn test_create_devices() {
         let tmp_dir = TempDir::new("/tmp/test_create_devices").unwrap();
@@ -9,3 +9,4 @@ pub(super) mod device;
 pub(super) mod mount;
 pub(super) mod symlink;
 pub(super) mod utils;
+pub use utils::{idmapped_mounts_supported, MOUNT_OPTIONS};
@@ -1,9 +1,10 @@
 use super::{
     symlink::Symlink,
-    utils::{find_parent_mount, parse_mount},
+    utils::{find_parent_mount, open_userns, parse_idmap, parse_mount},
 };
 use crate::utils::PathBufExt;
 use crate::{
+    selinux,
     syscall::{syscall::create_syscall, Syscall},
     utils,
 };
@@ -16,17 +17,36 @@ use nix::{errno::Errno, mount::MsFlags};
 use oci_spec::runtime::{Mount as SpecMount, MountBuilder as SpecMountBuilder};
 use procfs::process::{MountInfo, MountOptFields, Process};
 use std::borrow::Cow;
+use std::os::unix::io::AsRawFd;
 use std::path::{Path, PathBuf};
 use std::{
     collections::HashMap,
-    fs::{canonicalize, create_dir_all, OpenOptions},
+    fs::{self, canonicalize, create_dir_all, OpenOptions},
 };
 
+/// Group owning the pseudoterminals, if the rootfs has no tty group
+const DEFAULT_TTY_GID: u32 = 5;
+
+/// Returns the unified cgroup of the calling process, relative to the root
+/// of the cgroup hierarchy
+pub(crate) fn unified_process_cgroup() -> Result<PathBuf> {
+    Process::myself()?
+        .cgroups()
+        .context("failed to get process cgroups")?
+        .into_iter()
+        .find(|c| c.hierarchy == 0)
+        .map(|c| PathBuf::from(c.pathname))
+        .ok_or_else(|| anyhow!("failed to find unified process cgroup"))
+}
+
 #[derive(Debug)]
 pub struct MountOptions<'a> {
     pub root: &'a Path,
     pub label: Option<&'a str>,
     pub cgroup_ns: bool,
+    /// Unified cgroup of the process on the host, recorded before it entered
+    /// the cgroup namespace, of which it is the root
+    pub cgroup2_path: Option<&'a Path>,
 }
 
 pub struct Mount {
@@ -63,6 +83,11 @@ impl Mount {
                         .context("failed to mount cgroup v2")?,
                 }
             }
+            Some("devpts") => {
+                let data = devpts_data(&data, options.root);
+                self.mount_into_container(mount, options.root, flags, &data, options.label)
+                    .with_context(|| format!("failed to mount devpts: {:?}", mount))?;
+            }
             _ => {
                 if *mount.destination() == PathBuf::from("/dev") {
                     self.mount_into_container(
@@ -153,6 +178,25 @@ impl Mount {
             }
         }
 
+        // The tmpfs holding the subsystem mount points can only be made
+        // read-only once they are mounted, if the cgroup mount is read-only.
+        let (flags, _) = parse_mount(cgroup_mount);
+        if flags.contains(MsFlags::MS_RDONLY) {
+            self.syscall
+                .mount(
+                    None,
+                    &cgroup_root,
+                    None,
+                    MsFlags::MS_REMOUNT
+                        | MsFlags::MS_RDONLY
+                        | MsFlags::MS_NOEXEC
+                        | MsFlags::MS_NOSUID
+                        | MsFlags::MS_NODEV,
+                    Some("mode=755"),
+                )
+                .context("failed to remount cgroup tmpfs read-only")?;
+        }
+
         Ok(())
     }
 
@@ -273,21 +317,27 @@ impl Mount {
             .build()?;
         log::debug!("{:?}", cgroup_mount);
 
-        if self
-            .mount_into_container(&cgroup_mount, options.root, flags, data, options.label)
-            .context("failed to mount into container")
-            .is_err()
+        if let Err(err) =
+            self.mount_into_container(&cgroup_mount, options.root, flags, data, options.label)
         {
+            log::debug!(
+                "failed to mount cgroup2, falling back to a bind mount: {:?}",
+                err
+            );
             let host_mount = libcgroups::v2::util::get_unified_mount_point()
                 .context("failed to get unified mount point")?;
 
-            let process_cgroup = Process::myself()?
-                .cgroups()
-                .context("failed to get process cgroups")?
-                .into_iter()
-                .find(|c| c.hierarchy == 0)
-                .map(|c| PathBuf::from(c.pathname))
-                .ok_or_else(|| anyhow!("failed to find unified process cgroup"))?;
+            // Within a cgroup namespace the own cgroup is reported as the root,
+            // so the cgroup has to be known from before entering it. Binding
+            // the reported one would expose the cgroups of the host.
+            let process_cgroup = if options.cgroup_ns {
+                options
+                    .cgroup2_path
+                    .map(Path::to_path_buf)
+                    .context("cgroup of the process outside of the cgroup namespace is unknown")?
+            } else {
+                unified_process_cgroup()?
+            };
 
             let bind_mount = SpecMountBuilder::default()
                 .typ("bind")
@@ -345,16 +395,7 @@ impl Mount {
         label: Option<&str>,
     ) -> Result<()> {
         let typ = m.typ().as_deref();
-        let mut d = data.to_string();
-
-        if let Some(l) = label {
-            if typ != Some("proc") && typ != Some("sysfs") {
-                match data.is_empty() {
-                    true => d = format!("context=\"{}\"", l),
-                    false => d = format!("{},context=\"{}\"", data, l),
-                }
-            }
-        }
+        let d = selinux::format_mount_data(data, typ, label);
 
         let dest_for_host = utils::secure_join(rootfs, m.destination())
             .with_context(|| format!("failed to join {:?} with {:?}", rootfs, m.destination()))?;
@@ -364,26 +405,18 @@ impl Mount {
             .source()
             .as_ref()
             .with_context(|| "no source in mount spec".to_string())?;
-        let src = if typ == Some("bind") {
+        let is_bind = typ == Some("bind") || flags.contains(MsFlags::MS_BIND);
+        let idmap = parse_idmap(m)?;
+        if idmap.is_some() && !is_bind {
+            bail!(
+                "idmapped mounts are only supported for bind mounts, but {:?} is not one",
+                m.destination()
+            );
+        }
+        let src = if is_bind {
             let src = canonicalize(source)
                 .with_context(|| format!("failed to canonicalize: {:?}", source))?;
-            let dir = if src.is_file() {
-                Path::new(&dest).parent().unwrap()
-            } else {
-                Path::new(&dest)
-            };
-
-            create_dir_all(&dir)
-                .with_context(|| format!("failed to create dir for bind mount: {:?}", dir))?;
-
-            if src.is_file() {
-                OpenOptions::new()
-                    .create(true)
-                    .write(true)
-                    .open(&dest)
-                    .with_context(|| format!("failed to create file for bind mount: {:?}", src))?;
-            }
-
+            create_bind_target(&src, dest)?;
             src
         } else {
             create_dir_all(&dest)
@@ -391,8 +424,26 @@ impl Mount {
 
             PathBuf::from(source)
         };
-
-        if let Err(err) = self.syscall.mount(Some(&*src), dest, typ, flags, Some(&*d)) {
+        // the destination is mounted onto through its fd, so it can't be
+        // swapped out for a symlink once it has been verified
+        let dest_file = utils::open_in_rootfs(rootfs, dest)
+            .with_context(|| format!("unsafe mount destination {:?}", m.destination()))?;
+        let target = utils::proc_fd_path(&dest_file);
+
+        if let Some(userns) = idmap {
+            let userns = open_userns(&userns)?;
+            self.syscall
+                .mount_idmapped(
+                    &src,
+                    &target,
+                    userns.as_raw_fd(),
+                    flags.contains(MsFlags::MS_REC),
+                )
+                .with_context(|| format!("failed to mount {:?} idmapped to {:?}", src, dest))?;
+        } else if let Err(err) = self
+            .syscall
+            .mount(Some(&*src), &target, typ, flags, Some(&*d))
+        {
             if let Some(errno) = err.downcast_ref() {
                 if !matches!(errno, Errno::EINVAL) {
                     bail!("mount of {:?} failed. {}", m.destination(), errno);
@@ -400,11 +451,11 @@ impl Mount {
             }
 
             self.syscall
-                .mount(Some(&*src), dest, typ, flags, Some(data))
+                .mount(Some(&*src), &target, typ, flags, Some(data))
                 .with_context(|| format!("failed to mount {:?} to {:?}", src, dest))?;
         }
 
-        if typ == Some("bind")
+        if is_bind
             && flags.intersects(
                 !(MsFlags::MS_REC
                     | MsFlags::MS_REMOUNT
@@ -414,8 +465,13 @@ impl Mount {
                     | MsFlags::MS_SLAVE),
             )
         {
+            // the fd refers to the directory below the new mount, so the
+            // destination is opened again to remount the mount itself
+            let dest_file = utils::open_in_rootfs(rootfs, dest)
+                .with_context(|| format!("unsafe mount destination {:?}", m.destination()))?;
+            let target = utils::proc_fd_path(&dest_file);
             self.syscall
-                .mount(Some(dest), dest, None, flags | MsFlags::MS_REMOUNT, None)
+                .mount(Some(dest), &target, None, flags | MsFlags::MS_REMOUNT, None)
                 .with_context(|| format!("Failed to remount: {:?}", dest))?;
         }
 
@@ -423,12 +479,108 @@ impl Mount {
     }
 }
 
+/// Creates the target of a bind mount, if it does not exist yet. Any source,
+/// which is not a directory, e.g. a regular file, a device or a socket, needs
+/// a file as target, as mounting it onto a directory fails with ENOTDIR. This
+/// happens before the root is remounted read-only.
+fn create_bind_target(src: &Path, dest: &Path) -> Result<()> {
+    let metadata =
+        fs::metadata(src).with_context(|| format!("failed to stat bind mount source {:?}", src))?;
+    if metadata.is_dir() {
+        return create_dir_all(dest)
+            .with_context(|| format!("failed to create dir for bind mount: {:?}", dest));
+    }
+
+    if let Some(parent) = dest.parent() {
+        create_dir_all(parent)
+            .with_context(|| format!("failed to create dir for bind mount: {:?}", parent))?;
+    }
+
+    if fs::symlink_metadata(dest).is_err() {
+        OpenOptions::new()
+            .create(true)
+            .write(true)
+            .open(dest)
+            .with_context(|| format!("failed to create file for bind mount: {:?}", dest))?;
+    }
+
+    Ok(())
+}
+
+/// Completes the options of a devpts mount, so that pseudoterminals can be
+/// allocated in the container. Options of the spec take precedence.
+fn devpts_data(data: &str, rootfs: &Path) -> String {
+    let mut options: Vec<String> = data
+        .split(',')
+        .filter(|o| !o.is_empty())
+        .map(String::from)
+        .collect();
+    let has_option = |options: &[String], key: &str| {
+        options
+            .iter()
+            .any(|o| o == key || o.starts_with(&format!("{}=", key)))
+    };
+
+    for (key, default) in [
+        ("newinstance", "newinstance"),
+        ("ptmxmode", "ptmxmode=0666"),
+        ("mode", "mode=0620"),
+    ] {
+        if !has_option(&options, key) {
+            options.push(default.to_owned());
+        }
+    }
+
+    // The gid has to be mapped in the user namespace of the container,
+    // otherwise the mount fails.
+    if !has_option(&options, "gid") {
+        let gid = tty_gid(rootfs);
+        let gid_map = fs::read_to_string("/proc/self/gid_map").unwrap_or_default();
+        if is_gid_mapped(&gid_map, gid) {
+            options.push(format!("gid={}", gid));
+        }
+    }
+
+    options.join(",")
+}
+
+/// Returns the gid of the tty group of the rootfs
+fn tty_gid(rootfs: &Path) -> u32 {
+    utils::secure_join(rootfs, Path::new("/etc/group"))
+        .ok()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|groups| {
+            groups.lines().find_map(|line| {
+                let mut fields = line.split(':');
+                if fields.next() != Some("tty") {
+                    return None;
+                }
+                // skip the password
+                fields.nth(1)?.parse().ok()
+            })
+        })
+        .unwrap_or(DEFAULT_TTY_GID)
+}
+
+/// Checks if the gid is part of one of the ranges of the gid_map, whose
+/// lines consist of the first id inside, the first id outside and the size
+fn is_gid_mapped(gid_map: &str, gid: u32) -> bool {
+    gid_map.lines().any(|line| {
+        let fields: Vec<u64> = line
+            .split_whitespace()
+            .filter_map(|f| f.parse().ok())
+            .collect();
+        let gid = gid as u64;
+        matches!(fields[..], [inside, _, size] if inside <= gid && gid < inside + size)
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use std::fs;
 
     use super::*;
-    use crate::syscall::test::{MountArgs, TestHelperSyscall};
+    use crate::syscall::test::{IdmappedMountArgs, MountArgs, TestHelperSyscall};
     use crate::utils::create_temp_dir;
     use anyhow::Result;
 
@@ -523,6 +675,183 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_mount_idmapped() -> Result<()> {
+        let tmp = create_temp_dir("test_mount_idmapped")?;
+        let source = tmp.join("data");
+        fs::create_dir(&source)?;
+        let m = Mount::new();
+        let mount = &SpecMountBuilder::default()
+            .destination(PathBuf::from("/data"))
+            .typ("bind")
+            .source(&source)
+            .options(
+                ["rbind", "ro", "idmap=/proc/self/ns/user"]
+                    .iter()
+                    .map(|o| o.to_string())
+                    .collect::<Vec<String>>(),
+            )
+            .build()?;
+        let (flags, data) = parse_mount(mount);
+        let rootfs = tmp.join("rootfs");
+        m.mount_into_container(mount, &rootfs, flags, &data, None)?;
+
+        let syscall = m
+            .syscall
+            .as_any()
+            .downcast_ref::<TestHelperSyscall>()
+            .unwrap();
+        let got = syscall.get_idmapped_mount_args();
+        assert_eq!(got.len(), 1);
+        assert_eq!(
+            got[0],
+            IdmappedMountArgs {
+                source: source.canonicalize()?,
+                target: rootfs.join("data"),
+                userns_fd: got[0].userns_fd,
+                recursive: true,
+            }
+        );
+        // only the remount applying the read-only flag is left
+        assert_eq!(
+            syscall.get_mount_args(),
+            vec![MountArgs {
+                source: Some(rootfs.join("data")),
+                target: rootfs.join("data"),
+                fstype: None,
+                flags: flags | MsFlags::MS_REMOUNT,
+                data: None,
+            }]
+        );
+
+        let tmpfs = &SpecMountBuilder::default()
+            .destination(PathBuf::from("/tmp"))
+            .typ("tmpfs")
+            .source("tmpfs")
+            .options(vec!["idmap=/proc/self/ns/user".to_string()])
+            .build()?;
+        let (flags, data) = parse_mount(tmpfs);
+        assert!(m
+            .mount_into_container(tmpfs, &rootfs, flags, &data, None)
+            .is_err());
+
+        let not_userns = &SpecMountBuilder::default()
+            .destination(PathBuf::from("/data"))
+            .typ("bind")
+            .source(&source)
+            .options(vec!["idmap=/proc/self/ns/net".to_string()])
+            .build()?;
+        let (flags, data) = parse_mount(not_userns);
+        assert!(m
+            .mount_into_container(not_userns, &rootfs, flags, &data, None)
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_bind_target() -> Result<()> {
+        let tmp = create_temp_dir("test_create_bind_target")?;
+        let file = tmp.join("source_file");
+        fs::write(&file, "data")?;
+        let dir = tmp.join("source_dir");
+        fs::create_dir(&dir)?;
+
+        let rootfs = tmp.join("rootfs");
+        create_bind_target(&file, &rootfs.join("etc/resolv.conf"))?;
+        assert!(rootfs.join("etc/resolv.conf").is_file());
+        create_bind_target(&dir, &rootfs.join("mnt/data"))?;
+        assert!(rootfs.join("mnt/data").is_dir());
+
+        // an existing target is kept as it is
+        fs::write(rootfs.join("etc/resolv.conf"), "nameserver")?;
+        create_bind_target(&file, &rootfs.join("etc/resolv.conf"))?;
+        assert_eq!(
+            fs::read_to_string(rootfs.join("etc/resolv.conf"))?,
+            "nameserver"
+        );
+
+        // a device is mounted onto a file as well
+        create_bind_target(Path::new("/dev/null"), &rootfs.join("dev/null"))?;
+        assert!(rootfs.join("dev/null").is_file());
+        Ok(())
+    }
+
+    #[test]
+    fn test_mount_through_malicious_symlink() -> Result<()> {
+        use std::os::unix::fs::symlink;
+
+        let tmp = create_temp_dir("test_mount_through_malicious_symlink")?;
+        let rootfs = tmp.join("rootfs");
+        fs::create_dir(&rootfs)?;
+        let outside = tmp.join("outside");
+        fs::create_dir(&outside)?;
+        let source = tmp.join("source");
+        fs::create_dir(&source)?;
+        symlink(&outside, rootfs.join("evil"))?;
+
+        let m = Mount::new();
+        let mount = &SpecMountBuilder::default()
+            .destination(PathBuf::from("/evil/target"))
+            .typ("bind")
+            .source(&source)
+            .options(vec!["bind".to_string()])
+            .build()?;
+        let (flags, data) = parse_mount(mount);
+        m.mount_into_container(mount, &rootfs, flags, &data, None)?;
+
+        // the symlink is resolved inside of the rootfs
+        let got = m
+            .syscall
+            .as_any()
+            .downcast_ref::<TestHelperSyscall>()
+            .unwrap()
+            .get_mount_args();
+        assert_eq!(
+            got[0].target,
+            utils::secure_join(&rootfs, &outside.join("target"))?
+        );
+        assert!(got[0].target.starts_with(&rootfs));
+        assert!(!outside.join("target").exists());
+
+        // a symlink swapped into the created destination is refused
+        let dest = got[0].target.clone();
+        fs::remove_dir(&dest)?;
+        symlink(&outside, &dest)?;
+        assert!(utils::open_in_rootfs(&rootfs, &dest).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_devpts_data() -> Result<()> {
+        let tmp = create_temp_dir("test_devpts_data")?;
+        fs::create_dir_all(tmp.join("etc"))?;
+        fs::write(tmp.join("etc/group"), "root:x:0:\ntty:x:7:\n")?;
+        assert_eq!(tty_gid(&tmp), 7);
+
+        // the gid of the test process is mapped in its own user namespace
+        let data = devpts_data("", &tmp);
+        assert!(data.starts_with("newinstance,ptmxmode=0666,mode=0620"));
+
+        assert_eq!(
+            devpts_data("newinstance,mode=0600,gid=5", &tmp),
+            "newinstance,mode=0600,gid=5,ptmxmode=0666"
+        );
+
+        let tmp = create_temp_dir("test_devpts_data_no_group")?;
+        assert_eq!(tty_gid(&tmp), DEFAULT_TTY_GID);
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_gid_mapped() {
+        let gid_map = "         0       1000          1\n         1     100000      65536\n";
+        assert!(is_gid_mapped(gid_map, 0));
+        assert!(is_gid_mapped(gid_map, 5));
+        assert!(is_gid_mapped(gid_map, 65536));
+        assert!(!is_gid_mapped(gid_map, 65537));
+        assert!(!is_gid_mapped("         0       1000          1\n", 5));
+    }
+
     #[test]
     fn test_make_parent_mount_private() {
         let tmp_dir = create_temp_dir("test_make_parent_mount_private").unwrap();
@@ -570,6 +899,7 @@ mod tests {
             root: tmp.path(),
             label: None,
             cgroup_ns: true,
+            cgroup2_path: None,
         };
 
         let subsystem_name = "cpu";
@@ -621,6 +951,7 @@ mod tests {
             root: tmp.path(),
             label: None,
             cgroup_ns: false,
+            cgroup2_path: None,
         };
 
         let subsystem_name = "cpu";
@@ -671,6 +1002,7 @@ mod tests {
             .destination(&container_cgroup)
             .source("cgroup")
             .typ("cgroup")
+            .options(vec!["ro".to_owned()])
             .build()
             .context("failed to build cgroup mount")?;
 
@@ -678,6 +1010,7 @@ mod tests {
             root: tmp.path(),
             label: None,
             cgroup_ns: true,
+            cgroup2_path: None,
         };
 
         let mounter = Mount::new();
@@ -697,7 +1030,7 @@ mod tests {
             .into_iter();
 
         let host_mounts = libcgroups::v1::util::list_subsystem_mount_points()?;
-        assert_eq!(got.len(), host_mounts.len() + 1);
+        assert_eq!(got.len(), host_mounts.len() + 2);
 
         let expected = MountArgs {
             source: Some(PathBuf::from("tmpfs".to_owned())),
@@ -708,7 +1041,7 @@ mod tests {
         };
         assert_eq!(expected, got.next().unwrap());
 
-        for (host_mount, act) in host_mounts.iter().zip(got) {
+        for (host_mount, act) in host_mounts.iter().zip(got.by_ref()) {
             let subsystem_name = host_mount.file_name().and_then(|f| f.to_str()).unwrap();
             let expected = MountArgs {
                 source: Some(PathBuf::from("cgroup".to_owned())),
@@ -727,6 +1060,55 @@ mod tests {
             assert_eq!(expected, act);
         }
 
+        let expected = MountArgs {
+            source: None,
+            target: tmp.join_safely(&container_cgroup)?,
+            fstype: None,
+            flags: MsFlags::MS_REMOUNT
+                | MsFlags::MS_RDONLY
+                | MsFlags::MS_NOEXEC
+                | MsFlags::MS_NOSUID
+                | MsFlags::MS_NODEV,
+            data: Some("mode=755".to_owned()),
+        };
+        assert_eq!(expected, got.next().unwrap());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mount_cgroup_v1_read_write() -> Result<()> {
+        let tmp = create_temp_dir("test_mount_cgroup_v1_read_write")?;
+        let spec_cgroup_mount = SpecMountBuilder::default()
+            .destination("/sys/fs/cgroup")
+            .source("cgroup")
+            .typ("cgroup")
+            .options(vec!["rw".to_owned()])
+            .build()
+            .context("failed to build cgroup mount")?;
+        let mount_opts = MountOptions {
+            root: tmp.path(),
+            label: None,
+            cgroup_ns: true,
+            cgroup2_path: None,
+        };
+
+        let mounter = Mount::new();
+        mounter
+            .mount_cgroup_v1(&spec_cgroup_mount, &mount_opts)
+            .context("failed to mount cgroup v1")?;
+
+        let got = mounter
+            .syscall
+            .as_any()
+            .downcast_ref::<TestHelperSyscall>()
+            .unwrap()
+            .get_mount_args();
+        assert!(
+            got.iter().all(|m| !m.flags.contains(MsFlags::MS_REMOUNT)),
+            "tmpfs of a read-write cgroup mount is not remounted read-only"
+        );
+
         Ok(())
     }
 
@@ -747,6 +1129,7 @@ mod tests {
             root: tmp.path(),
             label: None,
             cgroup_ns: true,
+            cgroup2_path: None,
         };
 
         let mounter = Mount::new();
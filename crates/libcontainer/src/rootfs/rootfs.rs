@@ -2,12 +2,16 @@ use super::{
     device::Device,
     mount::{Mount, MountOptions},
     symlink::Symlink,
-    utils::default_devices,
+    utils::{default_devices, is_mount_point},
+};
+use crate::{
+    selinux,
+    syscall::{syscall::create_syscall, Syscall},
 };
-use crate::syscall::{syscall::create_syscall, Syscall};
 use anyhow::{bail, Context, Result};
 use nix::mount::MsFlags;
-use oci_spec::runtime::{Linux, Spec};
+use oci_spec::runtime::{Linux, LinuxDevice, Spec};
+use procfs::process::Process;
 use std::path::Path;
 
 /// Holds information about rootfs
@@ -34,17 +38,20 @@ impl RootFS {
         rootfs: &Path,
         bind_devices: bool,
         cgroup_ns: bool,
+        cgroup2_path: Option<&Path>,
     ) -> Result<()> {
         log::debug!("Prepare rootfs: {:?}", rootfs);
-        let mut flags = MsFlags::MS_REC;
         let linux = spec.linux().as_ref().context("no linux in spec")?;
 
-        match linux.rootfs_propagation().as_deref() {
-            Some("shared") => flags |= MsFlags::MS_SHARED,
-            Some("private") => flags |= MsFlags::MS_PRIVATE,
-            Some("slave" | "unbindable") | None => flags |= MsFlags::MS_SLAVE,
-            Some(uknown) => bail!("unknown rootfs_propagation: {}", uknown),
-        }
+        // Shared and unbindable propagation is applied after pivot_root, as it
+        // would affect the mounts of the host before. Until then the mounts
+        // are made slaves, so that nothing propagates back to the host.
+        let propagation = parse_rootfs_propagation(linux.rootfs_propagation().as_deref())?;
+        let flags = if propagation.intersects(MsFlags::MS_SHARED | MsFlags::MS_UNBINDABLE) {
+            MsFlags::MS_SLAVE | MsFlags::MS_REC
+        } else {
+            propagation
+        };
 
         self.syscall
             .mount(None, Path::new("/"), None, flags, None)
@@ -56,19 +63,14 @@ impl RootFS {
             .make_parent_mount_private(rootfs)
             .context("failed to change parent mount of rootfs private")?;
 
-        log::debug!("mount root fs {:?}", rootfs);
-        self.syscall.mount(
-            Some(rootfs),
-            rootfs,
-            None,
-            MsFlags::MS_BIND | MsFlags::MS_REC,
-            None,
-        )?;
+        self.bind_rootfs(rootfs)?;
 
+        let labels = selinux::Labels::from_spec(spec);
         let global_options = MountOptions {
             root: rootfs,
-            label: linux.mount_label().as_deref(),
+            label: labels.mount.as_deref(),
             cgroup_ns,
+            cgroup2_path,
         };
 
         if let Some(mounts) = spec.mounts() {
@@ -88,30 +90,43 @@ impl RootFS {
             .context("failed to setup default symlinks")?;
 
         let devicer = Device::new();
-        if let Some(added_devices) = linux.devices() {
-            devicer.create_devices(
-                rootfs,
-                default_devices().iter().chain(added_devices),
-                bind_devices,
-            )
-        } else {
-            devicer.create_devices(rootfs, &default_devices(), bind_devices)
-        }?;
+        devicer.create_devices(
+            rootfs,
+            &with_default_devices(linux.devices().as_deref().unwrap_or_default()),
+            bind_devices,
+        )?;
 
         symlinker.setup_ptmx(rootfs)?;
         Ok(())
     }
 
+    /// Makes the rootfs a mount point, which pivot_root requires. If it is one
+    /// already, e.g. mounted by an image snapshotter, it is kept as it is, as
+    /// binding it onto itself would stack another mount on top of it, which
+    /// does not have the propagation adjusted before.
+    fn bind_rootfs(&self, rootfs: &Path) -> Result<()> {
+        let mount_infos = Process::myself()?
+            .mountinfo()
+            .context("failed to get mountinfo")?;
+        if is_mount_point(rootfs, &mount_infos) {
+            log::debug!("root fs {:?} is a mount point already", rootfs);
+            return Ok(());
+        }
+
+        log::debug!("mount root fs {:?}", rootfs);
+        self.syscall.mount(
+            Some(rootfs),
+            rootfs,
+            None,
+            MsFlags::MS_BIND | MsFlags::MS_REC,
+            None,
+        )
+    }
+
     /// Change propagation type of rootfs as specified in spec.
     pub fn adjust_root_mount_propagation(&self, linux: &Linux) -> Result<()> {
-        let rootfs_propagation = linux.rootfs_propagation().as_deref();
-        let flags = match rootfs_propagation {
-            Some("shared") => Some(MsFlags::MS_SHARED),
-            Some("unbindable") => Some(MsFlags::MS_UNBINDABLE),
-            _ => None,
-        };
-
-        if let Some(flags) = flags {
+        if let Some(rootfs_propagation) = linux.rootfs_propagation().as_deref() {
+            let flags = parse_rootfs_propagation(Some(rootfs_propagation))?;
             log::debug!("make root mount {:?}", flags);
             self.syscall
                 .mount(None, Path::new("/"), None, flags, None)?;
@@ -120,3 +135,143 @@ impl RootFS {
         Ok(())
     }
 }
+
+/// Returns the devices of the spec together with the default ones, which are
+/// always created, unless the spec lists a device with the same path.
+fn with_default_devices(devices: &[LinuxDevice]) -> Vec<LinuxDevice> {
+    default_devices()
+        .into_iter()
+        .filter(|default| !devices.iter().any(|d| d.path() == default.path()))
+        .chain(devices.iter().cloned())
+        .collect()
+}
+
+/// Translates the rootfs propagation of the spec into mount flags. The
+/// propagation defaults to rslave like in runc.
+pub(crate) fn parse_rootfs_propagation(propagation: Option<&str>) -> Result<MsFlags> {
+    let flags = match propagation {
+        None | Some("") | Some("rslave") => MsFlags::MS_SLAVE | MsFlags::MS_REC,
+        Some("slave") => MsFlags::MS_SLAVE,
+        Some("private") => MsFlags::MS_PRIVATE,
+        Some("rprivate") => MsFlags::MS_PRIVATE | MsFlags::MS_REC,
+        Some("shared") => MsFlags::MS_SHARED,
+        Some("rshared") => MsFlags::MS_SHARED | MsFlags::MS_REC,
+        Some("unbindable") => MsFlags::MS_UNBINDABLE,
+        Some("runbindable") => MsFlags::MS_UNBINDABLE | MsFlags::MS_REC,
+        Some(unknown) => bail!("unknown rootfs propagation {:?}", unknown),
+    };
+
+    Ok(flags)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syscall::test::TestHelperSyscall;
+    use crate::utils::{create_temp_dir, test_utils};
+    use nix::sched::{unshare, CloneFlags};
+    use oci_spec::runtime::{LinuxDeviceBuilder, LinuxDeviceType};
+    use std::fs;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_with_default_devices() -> Result<()> {
+        let null = LinuxDeviceBuilder::default()
+            .path(PathBuf::from("/dev/null"))
+            .typ(LinuxDeviceType::C)
+            .major(1)
+            .minor(3)
+            .file_mode(0o600u32)
+            .build()?;
+        let fuse = LinuxDeviceBuilder::default()
+            .path(PathBuf::from("/dev/fuse"))
+            .typ(LinuxDeviceType::C)
+            .major(10)
+            .minor(229)
+            .build()?;
+
+        let devices = with_default_devices(&[null.clone(), fuse.clone()]);
+        assert_eq!(devices.len(), default_devices().len() + 1);
+        // the device of the spec replaces the default one
+        assert_eq!(
+            devices
+                .iter()
+                .filter(|d| d.path() == null.path())
+                .collect::<Vec<_>>(),
+            vec![&null]
+        );
+        assert!(devices.contains(&fuse));
+        assert!(devices.iter().any(|d| d.path() == Path::new("/dev/tty")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_bind_rootfs() -> Result<()> {
+        let tmp = create_temp_dir("test_bind_rootfs")?;
+        fs::create_dir(tmp.join("plain"))?;
+        fs::create_dir(tmp.join("mounted"))?;
+        // the mount points are listed with their canonical path
+        let plain = fs::canonicalize(tmp.join("plain"))?;
+        let mounted = fs::canonicalize(tmp.join("mounted"))?;
+
+        test_utils::test_in_child_process(|| {
+            if unshare(CloneFlags::CLONE_NEWNS).is_err() {
+                // mounting a tmpfs requires CAP_SYS_ADMIN
+                return Ok(());
+            }
+            nix::mount::mount(
+                None::<&str>,
+                "/",
+                None::<&str>,
+                MsFlags::MS_PRIVATE | MsFlags::MS_REC,
+                None::<&str>,
+            )?;
+            nix::mount::mount(
+                Some("tmpfs"),
+                &mounted,
+                Some("tmpfs"),
+                MsFlags::empty(),
+                None::<&str>,
+            )?;
+
+            let rootfs = RootFS::new();
+            rootfs.bind_rootfs(&mounted)?;
+            rootfs.bind_rootfs(&plain)?;
+
+            // only the plain directory is bound onto itself
+            let got = rootfs
+                .syscall
+                .as_any()
+                .downcast_ref::<TestHelperSyscall>()
+                .unwrap()
+                .get_mount_args();
+            if got.len() != 1 || got[0].target != plain {
+                bail!("unexpected mounts {:?}", got);
+            }
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_parse_rootfs_propagation() -> Result<()> {
+        assert_eq!(
+            parse_rootfs_propagation(None)?,
+            MsFlags::MS_SLAVE | MsFlags::MS_REC
+        );
+        assert_eq!(parse_rootfs_propagation(Some("slave"))?, MsFlags::MS_SLAVE);
+        assert_eq!(
+            parse_rootfs_propagation(Some("rprivate"))?,
+            MsFlags::MS_PRIVATE | MsFlags::MS_REC
+        );
+        assert_eq!(
+            parse_rootfs_propagation(Some("shared"))?,
+            MsFlags::MS_SHARED
+        );
+        assert_eq!(
+            parse_rootfs_propagation(Some("runbindable"))?,
+            MsFlags::MS_UNBINDABLE | MsFlags::MS_REC
+        );
+        assert!(parse_rootfs_propagation(Some("rshare")).is_err());
+        Ok(())
+    }
+}
@@ -1,9 +1,17 @@
-use anyhow::{anyhow, Result};
-use nix::{mount::MsFlags, sys::stat::SFlag, NixPath};
+use anyhow::{anyhow, bail, Context, Result};
+use nix::{errno::Errno, mount::MsFlags, sys::stat::SFlag, NixPath};
 use oci_spec::runtime::{LinuxDevice, LinuxDeviceBuilder, LinuxDeviceType, Mount};
 use procfs::process::MountInfo;
+use std::fs::File;
+use std::os::unix::io::AsRawFd;
 use std::path::{Path, PathBuf};
 
+/// Mount option referring to the user namespace, e.g. /proc/<pid>/ns/user of
+/// another container, through which the ids of a bind mount are mapped
+const IDMAP_OPTION: &str = "idmap";
+/// ioctl(2) request returning the type of a namespace file
+const NS_GET_NSTYPE: libc::c_ulong = 0xb703;
+
 pub fn default_devices() -> Vec<LinuxDevice> {
     vec![
         LinuxDeviceBuilder::default()
@@ -66,47 +74,146 @@ pub fn to_sflag(dev_type: LinuxDeviceType) -> SFlag {
     }
 }
 
+/// Mount options which are translated into mount flags, all other options are
+/// passed to the filesystem as data.
+pub const MOUNT_OPTIONS: &[&str] = &[
+    "defaults",
+    "ro",
+    "rw",
+    "suid",
+    "nosuid",
+    "dev",
+    "nodev",
+    "exec",
+    "noexec",
+    "sync",
+    "async",
+    "dirsync",
+    "remount",
+    "mand",
+    "nomand",
+    "atime",
+    "noatime",
+    "diratime",
+    "nodiratime",
+    "bind",
+    "rbind",
+    "unbindable",
+    "runbindable",
+    "private",
+    "rprivate",
+    "shared",
+    "rshared",
+    "slave",
+    "rslave",
+    "relatime",
+    "norelatime",
+    "strictatime",
+    "nostrictatime",
+];
+
+fn parse_mount_option(option: &str) -> Option<(bool, MsFlags)> {
+    match option {
+        "defaults" => Some((false, MsFlags::empty())),
+        "ro" => Some((false, MsFlags::MS_RDONLY)),
+        "rw" => Some((true, MsFlags::MS_RDONLY)),
+        "suid" => Some((true, MsFlags::MS_NOSUID)),
+        "nosuid" => Some((false, MsFlags::MS_NOSUID)),
+        "dev" => Some((true, MsFlags::MS_NODEV)),
+        "nodev" => Some((false, MsFlags::MS_NODEV)),
+        "exec" => Some((true, MsFlags::MS_NOEXEC)),
+        "noexec" => Some((false, MsFlags::MS_NOEXEC)),
+        "sync" => Some((false, MsFlags::MS_SYNCHRONOUS)),
+        "async" => Some((true, MsFlags::MS_SYNCHRONOUS)),
+        "dirsync" => Some((false, MsFlags::MS_DIRSYNC)),
+        "remount" => Some((false, MsFlags::MS_REMOUNT)),
+        "mand" => Some((false, MsFlags::MS_MANDLOCK)),
+        "nomand" => Some((true, MsFlags::MS_MANDLOCK)),
+        "atime" => Some((true, MsFlags::MS_NOATIME)),
+        "noatime" => Some((false, MsFlags::MS_NOATIME)),
+        "diratime" => Some((true, MsFlags::MS_NODIRATIME)),
+        "nodiratime" => Some((false, MsFlags::MS_NODIRATIME)),
+        "bind" => Some((false, MsFlags::MS_BIND)),
+        "rbind" => Some((false, MsFlags::MS_BIND | MsFlags::MS_REC)),
+        "unbindable" => Some((false, MsFlags::MS_UNBINDABLE)),
+        "runbindable" => Some((false, MsFlags::MS_UNBINDABLE | MsFlags::MS_REC)),
+        "private" => Some((true, MsFlags::MS_PRIVATE)),
+        "rprivate" => Some((true, MsFlags::MS_PRIVATE | MsFlags::MS_REC)),
+        "shared" => Some((true, MsFlags::MS_SHARED)),
+        "rshared" => Some((true, MsFlags::MS_SHARED | MsFlags::MS_REC)),
+        "slave" => Some((true, MsFlags::MS_SLAVE)),
+        "rslave" => Some((true, MsFlags::MS_SLAVE | MsFlags::MS_REC)),
+        "relatime" => Some((true, MsFlags::MS_RELATIME)),
+        "norelatime" => Some((true, MsFlags::MS_RELATIME)),
+        "strictatime" => Some((true, MsFlags::MS_STRICTATIME)),
+        "nostrictatime" => Some((true, MsFlags::MS_STRICTATIME)),
+        _ => None,
+    }
+}
+
+/// Checks if the running kernel supports idmapped mounts, by probing for the
+/// mount_setattr syscall with invalid arguments.
+pub fn idmapped_mounts_supported() -> bool {
+    let res = unsafe {
+        libc::syscall(
+            libc::SYS_mount_setattr,
+            -1,
+            b"\0".as_ptr(),
+            0,
+            std::ptr::null::<libc::c_void>(),
+            0,
+        )
+    };
+    !(res == -1 && nix::errno::Errno::last() == nix::errno::Errno::ENOSYS)
+}
+
+/// Returns the user namespace the mount is idmapped through, if it has an
+/// `idmap=<path>` option
+pub fn parse_idmap(m: &Mount) -> Result<Option<PathBuf>> {
+    let mut userns = None;
+    for option in m.options().iter().flatten() {
+        match option.split_once('=') {
+            Some((IDMAP_OPTION, path)) if Path::new(path).is_absolute() => {
+                userns = Some(PathBuf::from(path))
+            }
+            None if option == IDMAP_OPTION => bail!(
+                "idmap option of mount {:?} requires the path of a user namespace",
+                m.destination()
+            ),
+            Some((IDMAP_OPTION, path)) => bail!(
+                "user namespace {:?} of mount {:?} is not an absolute path",
+                path,
+                m.destination()
+            ),
+            _ => {}
+        }
+    }
+    Ok(userns)
+}
+
+/// Opens the user namespace of an idmapped mount and checks that it is one
+pub fn open_userns(path: &Path) -> Result<File> {
+    let file =
+        File::open(path).with_context(|| format!("failed to open user namespace {:?}", path))?;
+    let nstype = unsafe { libc::ioctl(file.as_raw_fd(), NS_GET_NSTYPE as _) };
+    match Errno::result(nstype) {
+        Ok(libc::CLONE_NEWUSER) => Ok(file),
+        Ok(_) => bail!("{:?} is not a user namespace", path),
+        // not a namespace file at all
+        Err(e) => bail!("{:?} is not a user namespace: {}", path, e),
+    }
+}
+
 pub fn parse_mount(m: &Mount) -> (MsFlags, String) {
     let mut flags = MsFlags::empty();
     let mut data = Vec::new();
     if let Some(options) = &m.options() {
         for s in options {
-            if let Some((is_clear, flag)) = match s.as_str() {
-                "defaults" => Some((false, MsFlags::empty())),
-                "ro" => Some((false, MsFlags::MS_RDONLY)),
-                "rw" => Some((true, MsFlags::MS_RDONLY)),
-                "suid" => Some((true, MsFlags::MS_NOSUID)),
-                "nosuid" => Some((false, MsFlags::MS_NOSUID)),
-                "dev" => Some((true, MsFlags::MS_NODEV)),
-                "nodev" => Some((false, MsFlags::MS_NODEV)),
-                "exec" => Some((true, MsFlags::MS_NOEXEC)),
-                "noexec" => Some((false, MsFlags::MS_NOEXEC)),
-                "sync" => Some((false, MsFlags::MS_SYNCHRONOUS)),
-                "async" => Some((true, MsFlags::MS_SYNCHRONOUS)),
-                "dirsync" => Some((false, MsFlags::MS_DIRSYNC)),
-                "remount" => Some((false, MsFlags::MS_REMOUNT)),
-                "mand" => Some((false, MsFlags::MS_MANDLOCK)),
-                "nomand" => Some((true, MsFlags::MS_MANDLOCK)),
-                "atime" => Some((true, MsFlags::MS_NOATIME)),
-                "noatime" => Some((false, MsFlags::MS_NOATIME)),
-                "diratime" => Some((true, MsFlags::MS_NODIRATIME)),
-                "nodiratime" => Some((false, MsFlags::MS_NODIRATIME)),
-                "bind" => Some((false, MsFlags::MS_BIND)),
-                "rbind" => Some((false, MsFlags::MS_BIND | MsFlags::MS_REC)),
-                "unbindable" => Some((false, MsFlags::MS_UNBINDABLE)),
-                "runbindable" => Some((false, MsFlags::MS_UNBINDABLE | MsFlags::MS_REC)),
-                "private" => Some((true, MsFlags::MS_PRIVATE)),
-                "rprivate" => Some((true, MsFlags::MS_PRIVATE | MsFlags::MS_REC)),
-                "shared" => Some((true, MsFlags::MS_SHARED)),
-                "rshared" => Some((true, MsFlags::MS_SHARED | MsFlags::MS_REC)),
-                "slave" => Some((true, MsFlags::MS_SLAVE)),
-                "rslave" => Some((true, MsFlags::MS_SLAVE | MsFlags::MS_REC)),
-                "relatime" => Some((true, MsFlags::MS_RELATIME)),
-                "norelatime" => Some((true, MsFlags::MS_RELATIME)),
-                "strictatime" => Some((true, MsFlags::MS_STRICTATIME)),
-                "nostrictatime" => Some((true, MsFlags::MS_STRICTATIME)),
-                _ => None,
-            } {
+            // handled by the runtime, not by the filesystem
+            if s == IDMAP_OPTION || s.starts_with("idmap=") {
+                continue;
+            }
+            if let Some((is_clear, flag)) = parse_mount_option(s) {
                 if is_clear {
                     flags &= !flag;
                 } else {
@@ -131,12 +238,65 @@ pub fn find_parent_mount(rootfs: &Path, mount_infos: Vec<MountInfo>) -> Result<M
     Ok(parent_mount_info)
 }
 
+/// Checks if the path is a mount point in given mount infos
+pub fn is_mount_point(path: &Path, mount_infos: &[MountInfo]) -> bool {
+    mount_infos.iter().any(|mi| mi.mount_point == path)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use anyhow::Context;
     use oci_spec::runtime::MountBuilder;
 
+    #[test]
+    fn test_mount_options() {
+        for option in MOUNT_OPTIONS {
+            assert!(parse_mount_option(option).is_some(), "{}", option);
+        }
+        assert!(parse_mount_option("size=65536k").is_none());
+    }
+
+    fn mount_with_options(options: &[&str]) -> Mount {
+        MountBuilder::default()
+            .destination("/data")
+            .typ("bind")
+            .source("/data")
+            .options(options.iter().map(|o| o.to_string()).collect::<Vec<_>>())
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_parse_idmap() -> anyhow::Result<()> {
+        let mount = mount_with_options(&["rbind", "ro", "idmap=/proc/42/ns/user"]);
+        assert_eq!(
+            parse_idmap(&mount)?,
+            Some(PathBuf::from("/proc/42/ns/user"))
+        );
+        // the option is not passed to the filesystem
+        assert_eq!(
+            parse_mount(&mount),
+            (
+                MsFlags::MS_BIND | MsFlags::MS_REC | MsFlags::MS_RDONLY,
+                String::new()
+            )
+        );
+
+        assert_eq!(parse_idmap(&mount_with_options(&["rbind"]))?, None);
+        assert!(parse_idmap(&mount_with_options(&["idmap"])).is_err());
+        assert!(parse_idmap(&mount_with_options(&["idmap=proc/42/ns/user"])).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_open_userns() {
+        assert!(open_userns(Path::new("/proc/self/ns/user")).is_ok());
+        assert!(open_userns(Path::new("/proc/self/ns/net")).is_err());
+        assert!(open_userns(Path::new("/proc/self/status")).is_err());
+        assert!(open_userns(Path::new("/does/not/exist")).is_err());
+    }
+
     #[test]
     fn test_find_parent_mount() -> anyhow::Result<()> {
         let mount_infos = vec![
@@ -172,6 +332,29 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_is_mount_point() {
+        let mount_infos = vec![MountInfo {
+            mnt_id: 11,
+            pid: 10,
+            majmin: "".to_string(),
+            root: "/".to_string(),
+            mount_point: PathBuf::from("/path/to/rootfs"),
+            mount_options: Default::default(),
+            opt_fields: vec![],
+            fs_type: "overlay".to_string(),
+            mount_source: Some("overlay".to_string()),
+            super_options: Default::default(),
+        }];
+
+        assert!(is_mount_point(Path::new("/path/to/rootfs"), &mount_infos));
+        assert!(!is_mount_point(Path::new("/path/to"), &mount_infos));
+        assert!(!is_mount_point(
+            Path::new("/path/to/rootfs/etc"),
+            &mount_infos
+        ));
+    }
+
     #[test]
     fn test_find_parent_mount_with_empty_mount_infos() {
         let mount_infos = vec![];
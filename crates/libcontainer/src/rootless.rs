@@ -41,9 +41,14 @@ impl<'a> Rootless<'a> {
             validate_spec_for_rootless(spec)
                 .context("The spec failed to comply to rootless requirement")?;
             let mut rootless = Rootless::from(linux);
-            if let Some((uid_binary, gid_binary)) = lookup_map_binaries(linux)? {
+            if let Some((uid_binary, gid_binary)) = lookup_map_binaries(linux, rootless.privileged)?
+            {
                 rootless.newuidmap = Some(uid_binary);
                 rootless.newgidmap = Some(gid_binary);
+            } else {
+                log::debug!(
+                    "id mappings can be written directly, newuidmap/newgidmap are not required"
+                );
             }
 
             Ok(Some(rootless))
@@ -61,6 +66,7 @@ impl<'a> Rootless<'a> {
                 get_uid_path(&target_pid).as_path(),
                 uid_mappings,
                 self.newuidmap.as_deref(),
+                self.privileged,
             )
         } else {
             Ok(())
@@ -75,6 +81,7 @@ impl<'a> Rootless<'a> {
                 get_gid_path(&target_pid).as_path(),
                 gid_mappings,
                 self.newgidmap.as_deref(),
+                self.privileged,
             );
         } else {
             Ok(())
@@ -230,23 +237,36 @@ fn is_id_mapped(id: u32, mappings: &[LinuxIdMapping]) -> bool {
         .any(|m| id >= m.container_id() && id <= m.container_id() + m.size())
 }
 
+/// Checks if the id mappings can be written to the map file directly. A
+/// privileged user can write any mapping, but an unprivileged user can only
+/// map its own id.
+fn is_direct_mapping(mappings: &[LinuxIdMapping], own_id: u32, privileged: bool) -> bool {
+    privileged || matches!(mappings, [m] if m.host_id() == own_id && m.size() == 1)
+}
+
 /// Looks up the location of the newuidmap and newgidmap binaries which
-/// are required to write multiple user/group mappings
-pub fn lookup_map_binaries(spec: &Linux) -> Result<Option<(PathBuf, PathBuf)>> {
-    if let Some(uid_mappings) = spec.uid_mappings() {
-        if uid_mappings.len() == 1 && uid_mappings.len() == 1 {
-            return Ok(None);
-        }
+/// are required to write multiple user/group mappings as an unprivileged user
+pub fn lookup_map_binaries(spec: &Linux, privileged: bool) -> Result<Option<(PathBuf, PathBuf)>> {
+    let uid_direct = is_direct_mapping(
+        spec.uid_mappings().as_deref().unwrap_or_default(),
+        nix::unistd::geteuid().as_raw(),
+        privileged,
+    );
+    let gid_direct = is_direct_mapping(
+        spec.gid_mappings().as_deref().unwrap_or_default(),
+        nix::unistd::getegid().as_raw(),
+        privileged,
+    );
+    if uid_direct && gid_direct {
+        return Ok(None);
+    }
 
-        let uidmap = lookup_map_binary("newuidmap")?;
-        let gidmap = lookup_map_binary("newgidmap")?;
+    let uidmap = lookup_map_binary("newuidmap")?;
+    let gidmap = lookup_map_binary("newgidmap")?;
 
-        match (uidmap, gidmap) {
-            (Some(newuidmap), Some(newgidmap)) => Ok(Some((newuidmap, newgidmap))),
-            _ => bail!("newuidmap/newgidmap binaries could not be found in path. This is required if multiple id mappings are specified"),
-        }
-    } else {
-        Ok(None)
+    match (uidmap, gidmap) {
+        (Some(newuidmap), Some(newgidmap)) => Ok(Some((newuidmap, newgidmap))),
+        _ => bail!("newuidmap/newgidmap binaries could not be found in path. They are required to map more than the current user and group, please install the uidmap package"),
     }
 }
 
@@ -263,19 +283,17 @@ fn write_id_mapping(
     map_file: &Path,
     mappings: &[LinuxIdMapping],
     map_binary: Option<&Path>,
+    privileged: bool,
 ) -> Result<()> {
     log::debug!("Write ID mapping: {:?}", mappings);
 
-    match mappings.len() {
-        0 => bail!("at least one id mapping needs to be defined"),
-        1 => {
-            let mapping = mappings
-                .first()
-                .and_then(|m| format!("{} {} {}", m.container_id(), m.host_id(), m.size()).into())
-                .unwrap();
-            utils::write_file(map_file, mapping)?;
-        }
-        _ => {
+    if mappings.is_empty() {
+        bail!("at least one id mapping needs to be defined");
+    }
+
+    match map_binary {
+        // the binary is only looked up, if the mappings cannot be written directly
+        Some(map_binary) if !privileged => {
             let args: Vec<String> = mappings
                 .iter()
                 .flat_map(|m| {
@@ -287,11 +305,27 @@ fn write_id_mapping(
                 })
                 .collect();
 
-            Command::new(map_binary.unwrap())
+            let output = Command::new(map_binary)
                 .arg(pid.to_string())
                 .args(args)
                 .output()
                 .with_context(|| format!("failed to execute {:?}", map_binary))?;
+            if !output.status.success() {
+                bail!(
+                    "{:?} failed with {}: {}",
+                    map_binary,
+                    output.status,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+            }
+        }
+        _ => {
+            let mapping = mappings
+                .iter()
+                .map(|m| format!("{} {} {}", m.container_id(), m.host_id(), m.size()))
+                .collect::<Vec<_>>()
+                .join("\n");
+            utils::write_file(map_file, mapping)?;
         }
     }
 
@@ -308,7 +342,8 @@ mod tests {
     };
     use serial_test::serial;
 
-    use crate::utils::{test_utils::gen_u32, TempDir};
+    use crate::utils::{create_temp_dir, test_utils::gen_u32, TempDir};
+    use std::os::unix::fs::PermissionsExt;
 
     use super::*;
 
@@ -419,6 +454,118 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_is_direct_mapping() -> Result<()> {
+        let mapping = |host_id: u32, size: u32| {
+            LinuxIdMappingBuilder::default()
+                .host_id(host_id)
+                .container_id(0_u32)
+                .size(size)
+                .build()
+        };
+
+        assert!(is_direct_mapping(&[mapping(1000, 1)?], 1000, false));
+        assert!(!is_direct_mapping(&[mapping(1000, 10)?], 1000, false));
+        assert!(!is_direct_mapping(&[mapping(1001, 1)?], 1000, false));
+        assert!(!is_direct_mapping(
+            &[mapping(1000, 1)?, mapping(100000, 65536)?],
+            1000,
+            false
+        ));
+        assert!(is_direct_mapping(
+            &[mapping(1000, 1)?, mapping(100000, 65536)?],
+            1000,
+            true
+        ));
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_write_direct_mapping() -> Result<()> {
+        let userns = LinuxNamespaceBuilder::default()
+            .typ(LinuxNamespaceType::User)
+            .build()?;
+        let own_uid = nix::unistd::geteuid().as_raw();
+        let own_gid = nix::unistd::getegid().as_raw();
+        let uid_mappings = vec![LinuxIdMappingBuilder::default()
+            .host_id(own_uid)
+            .container_id(0_u32)
+            .size(1_u32)
+            .build()?];
+        let gid_mappings = vec![LinuxIdMappingBuilder::default()
+            .host_id(own_gid)
+            .container_id(0_u32)
+            .size(1_u32)
+            .build()?];
+        let linux = LinuxBuilder::default()
+            .namespaces(vec![userns])
+            .uid_mappings(uid_mappings)
+            .gid_mappings(gid_mappings)
+            .build()?;
+        let spec = SpecBuilder::default().linux(linux).build()?;
+        let rootless = Rootless::new(&spec)?.unwrap();
+        assert!(rootless.newuidmap.is_none());
+        assert!(rootless.newgidmap.is_none());
+
+        let pid = getpid();
+        let tempdir = TempDir::new(get_uid_path(&pid).parent().unwrap())?;
+        let uid_map_path = tempdir.join("uid_map");
+        let _ = fs::File::create(&uid_map_path)?;
+        rootless.write_uid_mapping(pid)?;
+        assert_eq!(format!("0 {own_uid} 1"), fs::read_to_string(uid_map_path)?);
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_write_map_binary() -> Result<()> {
+        let tmp = create_temp_dir("test_write_map_binary")?;
+        let args_file = tmp.join("args");
+        let map_binary = tmp.join("newuidmap");
+        fs::write(
+            &map_binary,
+            format!("#!/bin/sh\necho \"$@\" > {}\n", args_file.display()),
+        )?;
+        fs::set_permissions(&map_binary, fs::Permissions::from_mode(0o755))?;
+
+        let mappings = vec![
+            LinuxIdMappingBuilder::default()
+                .host_id(1000_u32)
+                .container_id(0_u32)
+                .size(1_u32)
+                .build()?,
+            LinuxIdMappingBuilder::default()
+                .host_id(100000_u32)
+                .container_id(1_u32)
+                .size(65536_u32)
+                .build()?,
+        ];
+        write_id_mapping(
+            Pid::from_raw(4321),
+            &tmp.join("uid_map"),
+            &mappings,
+            Some(&map_binary),
+            false,
+        )?;
+        assert_eq!(
+            fs::read_to_string(&args_file)?,
+            "4321 0 1000 1 1 100000 65536\n"
+        );
+        assert!(!tmp.join("uid_map").exists());
+
+        fs::write(&map_binary, "#!/bin/sh\nexit 1\n")?;
+        assert!(write_id_mapping(
+            Pid::from_raw(4321),
+            &tmp.join("uid_map"),
+            &mappings,
+            Some(&map_binary),
+            false,
+        )
+        .is_err());
+        Ok(())
+    }
+
     #[test]
     #[serial]
     fn test_write_uid_mapping() -> Result<()> {
@@ -0,0 +1,241 @@
+//! Applies the scheduling policy and attributes of the container process as
+//! specified by `process.scheduler`.
+//! See https://man7.org/linux/man-pages/man7/sched.7.html for more information
+use anyhow::{bail, Result};
+use nix::errno::Errno;
+use oci_spec::runtime::{LinuxSchedulerFlag, LinuxSchedulerPolicy, Scheduler};
+use std::mem;
+
+// Not all of the policies and flags are exported by libc, so they are defined
+// here. Ref: include/uapi/linux/sched.h
+const SCHED_OTHER: u32 = 0;
+const SCHED_FIFO: u32 = 1;
+const SCHED_RR: u32 = 2;
+const SCHED_BATCH: u32 = 3;
+const SCHED_ISO: u32 = 4;
+const SCHED_IDLE: u32 = 5;
+const SCHED_DEADLINE: u32 = 6;
+
+const SCHED_FLAG_RESET_ON_FORK: u64 = 0x01;
+const SCHED_FLAG_RECLAIM: u64 = 0x02;
+const SCHED_FLAG_DL_OVERRUN: u64 = 0x04;
+const SCHED_FLAG_KEEP_POLICY: u64 = 0x08;
+const SCHED_FLAG_KEEP_PARAMS: u64 = 0x10;
+const SCHED_FLAG_UTIL_CLAMP_MIN: u64 = 0x20;
+const SCHED_FLAG_UTIL_CLAMP_MAX: u64 = 0x40;
+
+/// Mirrors `struct sched_attr` of the kernel, which is not provided by libc.
+/// Ref: include/uapi/linux/sched/types.h
+#[repr(C)]
+#[derive(Debug, Default, PartialEq, Eq)]
+struct SchedAttr {
+    size: u32,
+    sched_policy: u32,
+    sched_flags: u64,
+    sched_nice: i32,
+    sched_priority: u32,
+    sched_runtime: u64,
+    sched_deadline: u64,
+    sched_period: u64,
+    sched_util_min: u32,
+    sched_util_max: u32,
+}
+
+fn translate_policy(policy: LinuxSchedulerPolicy) -> u32 {
+    match policy {
+        LinuxSchedulerPolicy::SchedOther => SCHED_OTHER,
+        LinuxSchedulerPolicy::SchedFifo => SCHED_FIFO,
+        LinuxSchedulerPolicy::SchedRr => SCHED_RR,
+        LinuxSchedulerPolicy::SchedBatch => SCHED_BATCH,
+        LinuxSchedulerPolicy::SchedIso => SCHED_ISO,
+        LinuxSchedulerPolicy::SchedIdle => SCHED_IDLE,
+        LinuxSchedulerPolicy::SchedDeadline => SCHED_DEADLINE,
+    }
+}
+
+fn translate_flag(flag: LinuxSchedulerFlag) -> u64 {
+    match flag {
+        LinuxSchedulerFlag::SchedResetOnFork => SCHED_FLAG_RESET_ON_FORK,
+        LinuxSchedulerFlag::SchedFlagReclaim => SCHED_FLAG_RECLAIM,
+        LinuxSchedulerFlag::SchedFlagDLOverrun => SCHED_FLAG_DL_OVERRUN,
+        LinuxSchedulerFlag::SchedFlagKeepPolicy => SCHED_FLAG_KEEP_POLICY,
+        LinuxSchedulerFlag::SchedFlagKeepParams => SCHED_FLAG_KEEP_PARAMS,
+        LinuxSchedulerFlag::SchedFlagUtilClampMin => SCHED_FLAG_UTIL_CLAMP_MIN,
+        LinuxSchedulerFlag::SchedFlagUtilClampMax => SCHED_FLAG_UTIL_CLAMP_MAX,
+    }
+}
+
+fn is_realtime(policy: LinuxSchedulerPolicy) -> bool {
+    matches!(
+        policy,
+        LinuxSchedulerPolicy::SchedFifo | LinuxSchedulerPolicy::SchedRr
+    )
+}
+
+fn to_sched_attr(scheduler: &Scheduler) -> Result<SchedAttr> {
+    let policy = scheduler.policy();
+    let priority = scheduler.priority().unwrap_or(0);
+
+    if is_realtime(policy) {
+        let sched_policy = translate_policy(policy) as i32;
+        let (min, max) = unsafe {
+            (
+                libc::sched_get_priority_min(sched_policy),
+                libc::sched_get_priority_max(sched_policy),
+            )
+        };
+        if priority < min || priority > max {
+            bail!(
+                "scheduler priority {} is out of range [{}, {}] for policy {:?}",
+                priority,
+                min,
+                max,
+                policy
+            );
+        }
+    } else if priority != 0 {
+        bail!(
+            "scheduler priority can only be set for SCHED_FIFO or SCHED_RR, but policy is {:?}",
+            policy
+        );
+    }
+
+    let runtime = scheduler.runtime().unwrap_or(0);
+    let deadline = scheduler.deadline().unwrap_or(0);
+    let period = scheduler.period().unwrap_or(0);
+    if policy == LinuxSchedulerPolicy::SchedDeadline {
+        if runtime == 0 || deadline == 0 {
+            bail!("SCHED_DEADLINE requires runtime and deadline to be set");
+        }
+
+        // A period of 0 means that the period is the same as the deadline.
+        if runtime > deadline || (period != 0 && deadline > period) {
+            bail!(
+                "SCHED_DEADLINE requires runtime ({}) <= deadline ({}) <= period ({})",
+                runtime,
+                deadline,
+                period
+            );
+        }
+    }
+
+    let sched_flags = scheduler
+        .flags()
+        .iter()
+        .flatten()
+        .fold(0, |flags, flag| flags | translate_flag(*flag));
+
+    Ok(SchedAttr {
+        size: mem::size_of::<SchedAttr>() as u32,
+        sched_policy: translate_policy(policy),
+        sched_flags,
+        sched_nice: scheduler.nice().unwrap_or(0),
+        sched_priority: priority as u32,
+        sched_runtime: runtime,
+        sched_deadline: deadline,
+        sched_period: period,
+        ..Default::default()
+    })
+}
+
+/// Sets the scheduling policy and attributes of the calling process.
+pub fn set_scheduler(scheduler: &Scheduler) -> Result<()> {
+    let attr = to_sched_attr(scheduler)?;
+    log::debug!("set scheduler attributes {:?}", attr);
+
+    // pid 0 refers to the calling thread, which is the container init process.
+    let ret = unsafe { libc::syscall(libc::SYS_sched_setattr, 0, &attr as *const SchedAttr, 0) };
+    match Errno::result(ret) {
+        Ok(_) => Ok(()),
+        Err(Errno::EPERM) => bail!(
+            "not permitted to set scheduler policy {:?}, real-time and deadline policies require CAP_SYS_NICE",
+            scheduler.policy()
+        ),
+        Err(e) => bail!("failed to set scheduler policy {:?}: {}", scheduler.policy(), e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oci_spec::runtime::SchedulerBuilder;
+
+    #[test]
+    fn test_to_sched_attr_other() -> Result<()> {
+        let scheduler = SchedulerBuilder::default()
+            .policy(LinuxSchedulerPolicy::SchedOther)
+            .nice(5)
+            .flags(vec![LinuxSchedulerFlag::SchedResetOnFork])
+            .build()?;
+        let attr = to_sched_attr(&scheduler)?;
+        assert_eq!(attr.sched_policy, SCHED_OTHER);
+        assert_eq!(attr.sched_nice, 5);
+        assert_eq!(attr.sched_flags, SCHED_FLAG_RESET_ON_FORK);
+        assert_eq!(attr.size as usize, mem::size_of::<SchedAttr>());
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_sched_attr_realtime_priority() -> Result<()> {
+        let scheduler = SchedulerBuilder::default()
+            .policy(LinuxSchedulerPolicy::SchedFifo)
+            .priority(10)
+            .build()?;
+        assert_eq!(to_sched_attr(&scheduler)?.sched_priority, 10);
+
+        for priority in [0, 100] {
+            let scheduler = SchedulerBuilder::default()
+                .policy(LinuxSchedulerPolicy::SchedRr)
+                .priority(priority)
+                .build()?;
+            assert!(to_sched_attr(&scheduler).is_err());
+        }
+
+        let scheduler = SchedulerBuilder::default()
+            .policy(LinuxSchedulerPolicy::SchedBatch)
+            .priority(10)
+            .build()?;
+        assert!(to_sched_attr(&scheduler).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_sched_attr_deadline() -> Result<()> {
+        let scheduler = SchedulerBuilder::default()
+            .policy(LinuxSchedulerPolicy::SchedDeadline)
+            .runtime(10_000_000u64)
+            .deadline(20_000_000u64)
+            .period(30_000_000u64)
+            .build()?;
+        let attr = to_sched_attr(&scheduler)?;
+        assert_eq!(attr.sched_policy, SCHED_DEADLINE);
+        assert_eq!(attr.sched_runtime, 10_000_000);
+        assert_eq!(attr.sched_deadline, 20_000_000);
+        assert_eq!(attr.sched_period, 30_000_000);
+
+        // runtime > deadline
+        let scheduler = SchedulerBuilder::default()
+            .policy(LinuxSchedulerPolicy::SchedDeadline)
+            .runtime(30_000_000u64)
+            .deadline(20_000_000u64)
+            .build()?;
+        assert!(to_sched_attr(&scheduler).is_err());
+
+        // deadline > period
+        let scheduler = SchedulerBuilder::default()
+            .policy(LinuxSchedulerPolicy::SchedDeadline)
+            .runtime(10_000_000u64)
+            .deadline(30_000_000u64)
+            .period(20_000_000u64)
+            .build()?;
+        assert!(to_sched_attr(&scheduler).is_err());
+
+        // missing deadline
+        let scheduler = SchedulerBuilder::default()
+            .policy(LinuxSchedulerPolicy::SchedDeadline)
+            .runtime(10_000_000u64)
+            .build()?;
+        assert!(to_sched_attr(&scheduler).is_err());
+        Ok(())
+    }
+}
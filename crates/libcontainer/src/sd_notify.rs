@@ -0,0 +1,265 @@
+//! Relays the readiness notifications of a container to the systemd service
+//! manager of youki, see sd_notify(3). The socket of the host can not be
+//! reached from within the container, so youki binds a socket in the state
+//! directory of the container, which is mounted into the container, and
+//! forwards the messages received on it.
+use anyhow::{bail, Context, Result};
+use nix::unistd::Pid;
+use oci_spec::runtime::{MountBuilder, Spec};
+use procfs::process::{ProcState, Process};
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::os::unix::net::UnixDatagram;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::utils;
+
+/// Environment variable pointing to the notify socket of the service manager
+pub const NOTIFY_SOCKET_ENV: &str = "NOTIFY_SOCKET";
+/// Directory in the container state directory containing the relay socket
+const NOTIFY_DIR: &str = "notify";
+const NOTIFY_SOCKET_FILE: &str = "notify.sock";
+/// Directory the relay socket is mounted to in the container
+const CONTAINER_NOTIFY_DIR: &str = "/run/notify";
+/// Interval in which the relay checks, if the container is still alive
+const LIVENESS_INTERVAL: Duration = Duration::from_secs(1);
+
+pub struct NotifyProxy {
+    host_socket: PathBuf,
+    socket_dir: PathBuf,
+}
+
+impl NotifyProxy {
+    pub fn new<P: Into<PathBuf>>(host_socket: P, container_dir: &Path) -> Self {
+        Self {
+            host_socket: host_socket.into(),
+            socket_dir: container_dir.join(NOTIFY_DIR),
+        }
+    }
+
+    /// Returns true, if the container has been created with a notify proxy
+    pub fn is_enabled(container_dir: &Path) -> bool {
+        container_dir.join(NOTIFY_DIR).is_dir()
+    }
+
+    /// Mounts the directory of the relay socket into the container and points
+    /// the container process to it. The socket itself is bound once the
+    /// container is started.
+    pub fn setup_spec(&self, spec: &mut Spec) -> Result<()> {
+        // abstract sockets are not supported by the std unix sockets
+        if self.host_socket.to_string_lossy().starts_with('@') {
+            bail!(
+                "abstract notify socket {:?} is not supported",
+                self.host_socket
+            );
+        }
+
+        utils::create_dir_all(&self.socket_dir)?;
+        let mount = MountBuilder::default()
+            .typ("bind")
+            .source(&self.socket_dir)
+            .destination(CONTAINER_NOTIFY_DIR)
+            .options(
+                ["bind", "nosuid", "noexec", "nodev", "ro"]
+                    .iter()
+                    .map(|o| o.to_string())
+                    .collect::<Vec<String>>(),
+            )
+            .build()
+            .context("failed to build notify socket mount")?;
+        let mut mounts = spec.mounts().clone().unwrap_or_default();
+        mounts.push(mount);
+        spec.set_mounts(Some(mounts));
+
+        if let Some(mut process) = spec.process().clone() {
+            let mut env = process.env().clone().unwrap_or_default();
+            let socket = Path::new(CONTAINER_NOTIFY_DIR).join(NOTIFY_SOCKET_FILE);
+            utils::merge_env(
+                &mut env,
+                &HashMap::from([(
+                    NOTIFY_SOCKET_ENV.to_owned(),
+                    socket.to_string_lossy().into_owned(),
+                )]),
+            );
+            process.set_env(Some(env));
+            spec.set_process(Some(process));
+        }
+        Ok(())
+    }
+
+    /// Binds the relay socket, which has to happen before the container
+    /// process is started, as messages sent before are lost
+    pub fn bind(&self) -> Result<NotifyRelay> {
+        let path = self.socket_dir.join(NOTIFY_SOCKET_FILE);
+        // a socket left over by a previous start can not be bound again
+        if let Err(e) = fs::remove_file(&path) {
+            if e.kind() != ErrorKind::NotFound {
+                return Err(e).with_context(|| format!("failed to remove {:?}", path));
+            }
+        }
+
+        let socket = UnixDatagram::bind(&path)
+            .with_context(|| format!("failed to bind notify socket {:?}", path))?;
+        socket.set_read_timeout(Some(LIVENESS_INTERVAL))?;
+        Ok(NotifyRelay {
+            socket,
+            path,
+            host_socket: self.host_socket.clone(),
+        })
+    }
+}
+
+pub struct NotifyRelay {
+    socket: UnixDatagram,
+    path: PathBuf,
+    host_socket: PathBuf,
+}
+
+impl NotifyRelay {
+    /// Forwards the messages of the container to the host until it signals
+    /// its readiness. Before that, the pid of the container process is sent
+    /// as the main pid of the service, as youki itself may exit afterwards.
+    pub fn run(&self, pid: Pid) -> Result<()> {
+        let host = UnixDatagram::unbound().context("failed to create socket")?;
+        let mut buf = [0; 4096];
+        loop {
+            let len = match self.socket.recv(&mut buf) {
+                Ok(len) => len,
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    if !is_alive(pid) {
+                        bail!("container process {} exited before it was ready", pid);
+                    }
+                    continue;
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e).context("failed to receive notify message"),
+            };
+
+            let message = &buf[..len];
+            let ready = String::from_utf8_lossy(message)
+                .lines()
+                .any(|line| line == "READY=1");
+            if ready {
+                host.send_to(format!("MAINPID={}\n", pid).as_bytes(), &self.host_socket)
+                    .with_context(|| format!("failed to notify {:?}", self.host_socket))?;
+            }
+            host.send_to(message, &self.host_socket)
+                .with_context(|| format!("failed to notify {:?}", self.host_socket))?;
+            if ready {
+                log::debug!("container process {} is ready", pid);
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl Drop for NotifyRelay {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            log::warn!("failed to remove notify socket {:?}: {}", self.path, e);
+        }
+    }
+}
+
+fn is_alive(pid: Pid) -> bool {
+    Process::new(pid.as_raw())
+        .and_then(|process| process.stat.state())
+        .map_or(false, |state| {
+            !matches!(state, ProcState::Zombie | ProcState::Dead)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::create_temp_dir;
+    use oci_spec::runtime::ProcessBuilder;
+    use std::thread;
+
+    #[test]
+    fn test_setup_spec() -> Result<()> {
+        let tmp = create_temp_dir("test_notify_setup_spec")?;
+        let process = ProcessBuilder::default()
+            .env(vec![
+                "PATH=/bin".to_owned(),
+                "NOTIFY_SOCKET=/run/systemd/notify".to_owned(),
+            ])
+            .build()?;
+        let mut spec = Spec::default();
+        spec.set_process(Some(process));
+
+        let proxy = NotifyProxy::new("/run/systemd/notify", &tmp);
+        assert!(!NotifyProxy::is_enabled(&tmp));
+        proxy.setup_spec(&mut spec)?;
+        assert!(NotifyProxy::is_enabled(&tmp));
+
+        let mount = spec.mounts().as_ref().unwrap().last().unwrap().clone();
+        assert_eq!(mount.destination(), Path::new(CONTAINER_NOTIFY_DIR));
+        assert_eq!(mount.source(), &Some(tmp.join(NOTIFY_DIR)));
+        let env = spec.process().as_ref().unwrap().env().clone().unwrap();
+        assert_eq!(
+            env,
+            vec![
+                "PATH=/bin".to_owned(),
+                "NOTIFY_SOCKET=/run/notify/notify.sock".to_owned()
+            ]
+        );
+
+        let abstract_proxy = NotifyProxy::new("@systemd/notify", &tmp);
+        assert!(abstract_proxy.setup_spec(&mut spec).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_relay() -> Result<()> {
+        let tmp = create_temp_dir("test_notify_relay")?;
+        let host_path = tmp.join("host.sock");
+        let host = UnixDatagram::bind(&host_path)?;
+        let proxy = NotifyProxy::new(&host_path, &tmp);
+        fs::create_dir(tmp.join(NOTIFY_DIR))?;
+        let relay = proxy.bind()?;
+
+        let socket = tmp.join(NOTIFY_DIR).join(NOTIFY_SOCKET_FILE);
+        let sender = thread::spawn(move || -> Result<()> {
+            let container = UnixDatagram::unbound()?;
+            container.send_to(b"STATUS=starting", &socket)?;
+            container.send_to(b"READY=1\nSTATUS=running", &socket)?;
+            Ok(())
+        });
+        let pid = Pid::this();
+        relay.run(pid)?;
+        sender.join().unwrap()?;
+        drop(relay);
+        assert!(!tmp.join(NOTIFY_DIR).join(NOTIFY_SOCKET_FILE).exists());
+
+        let mut buf = [0; 4096];
+        let mut messages = Vec::new();
+        for _ in 0..3 {
+            let len = host.recv(&mut buf)?;
+            messages.push(String::from_utf8_lossy(&buf[..len]).into_owned());
+        }
+        assert_eq!(
+            messages,
+            vec![
+                "STATUS=starting".to_owned(),
+                format!("MAINPID={}\n", pid),
+                "READY=1\nSTATUS=running".to_owned()
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_relay_container_exited() -> Result<()> {
+        let tmp = create_temp_dir("test_notify_relay_container_exited")?;
+        fs::create_dir(tmp.join(NOTIFY_DIR))?;
+        let relay = NotifyProxy::new(tmp.join("host.sock"), &tmp).bind()?;
+
+        let mut child = std::process::Command::new("true").spawn()?;
+        child.wait()?;
+        assert!(relay.run(Pid::from_raw(child.id() as i32)).is_err());
+        Ok(())
+    }
+}
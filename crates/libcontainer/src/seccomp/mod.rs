@@ -1,6 +1,8 @@
 use anyhow::bail;
 use anyhow::Context;
 use anyhow::Result;
+use caps::CapSet;
+use caps::Capability;
 use libseccomp::ScmpAction;
 use libseccomp::ScmpArch;
 use libseccomp::ScmpArgCompare;
@@ -11,6 +13,7 @@ use oci_spec::runtime::Arch;
 use oci_spec::runtime::LinuxSeccomp;
 use oci_spec::runtime::LinuxSeccompAction;
 use oci_spec::runtime::LinuxSeccompOperator;
+use std::collections::BTreeSet;
 use std::os::unix::io;
 
 fn translate_arch(arch: Arch) -> ScmpArch {
@@ -51,15 +54,25 @@ fn translate_action(action: LinuxSeccompAction, errno: Option<u32>) -> Result<Sc
     Ok(action)
 }
 
-fn translate_op(op: LinuxSeccompOperator, datum_b: Option<u64>) -> ScmpCompareOp {
+/// Translates the operator of an argument comparison and returns the datum the
+/// argument is compared with. Like in libseccomp, masked equal uses the value
+/// as the mask and compares the masked argument with the second value, which
+/// defaults to zero.
+fn translate_op(
+    op: LinuxSeccompOperator,
+    value: u64,
+    value_two: Option<u64>,
+) -> (ScmpCompareOp, u64) {
     match op {
-        LinuxSeccompOperator::ScmpCmpNe => ScmpCompareOp::NotEqual,
-        LinuxSeccompOperator::ScmpCmpLt => ScmpCompareOp::Less,
-        LinuxSeccompOperator::ScmpCmpLe => ScmpCompareOp::LessOrEqual,
-        LinuxSeccompOperator::ScmpCmpEq => ScmpCompareOp::Equal,
-        LinuxSeccompOperator::ScmpCmpGe => ScmpCompareOp::GreaterEqual,
-        LinuxSeccompOperator::ScmpCmpGt => ScmpCompareOp::Greater,
-        LinuxSeccompOperator::ScmpCmpMaskedEq => ScmpCompareOp::MaskedEqual(datum_b.unwrap_or(0)),
+        LinuxSeccompOperator::ScmpCmpNe => (ScmpCompareOp::NotEqual, value),
+        LinuxSeccompOperator::ScmpCmpLt => (ScmpCompareOp::Less, value),
+        LinuxSeccompOperator::ScmpCmpLe => (ScmpCompareOp::LessOrEqual, value),
+        LinuxSeccompOperator::ScmpCmpEq => (ScmpCompareOp::Equal, value),
+        LinuxSeccompOperator::ScmpCmpGe => (ScmpCompareOp::GreaterEqual, value),
+        LinuxSeccompOperator::ScmpCmpGt => (ScmpCompareOp::Greater, value),
+        LinuxSeccompOperator::ScmpCmpMaskedEq => {
+            (ScmpCompareOp::MaskedEqual(value), value_two.unwrap_or(0))
+        }
     }
 }
 
@@ -69,23 +82,58 @@ fn check_seccomp(seccomp: &LinuxSeccomp) -> Result<()> {
     // returned fd to another process. Therefore, we need the write syscall or
     // otherwise, the write syscall will be block by the seccomp filter causing
     // the container process to hang. `runc` also disallow notify as default
-    // action.
-    // Note: read and close syscall are also used, because if we can
-    // successfully write fd to another process, the other process can choose to
-    // handle read/close syscall and allow read and close to proceed as
-    // expected.
+    // action. The same holds for the other syscalls used to deliver the fd.
     if seccomp.default_action() == LinuxSeccompAction::ScmpActNotify {
         bail!("SCMP_ACT_NOTIFY cannot be used as default action");
     }
 
+    let forwarding = seccomp.listener_path().is_some();
     if let Some(syscalls) = seccomp.syscalls() {
         for syscall in syscalls {
             if syscall.action() == LinuxSeccompAction::ScmpActNotify {
                 for name in syscall.names() {
-                    if name == "write" {
-                        bail!("SCMP_ACT_NOTIFY cannot be used for the write syscall");
+                    if is_notify_fd_syscall(name, forwarding) {
+                        bail!("SCMP_ACT_NOTIFY cannot be used for the {} syscall", name);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks the second value of the argument comparisons, which only masked
+/// equal compares the masked argument with. Elsewhere it is ignored, which
+/// usually indicates a mistake in the profile.
+fn check_args(seccomp: &LinuxSeccomp, strict: bool) -> Result<()> {
+    for syscall in seccomp.syscalls().iter().flatten() {
+        for arg in syscall.args().iter().flatten() {
+            match (arg.op(), arg.value_two()) {
+                (LinuxSeccompOperator::ScmpCmpMaskedEq, _) => {
+                    if arg.value() == 0 {
+                        log::warn!(
+                            "masked equal comparison of argument {} of {:?} has a mask of zero, so the masked argument is always zero",
+                            arg.index(),
+                            syscall.names()
+                        );
                     }
                 }
+                (_, None) => {}
+                (op, Some(value_two)) if strict => bail!(
+                    "second value {} of argument {} of {:?} is not used by {:?}",
+                    value_two,
+                    arg.index(),
+                    syscall.names(),
+                    op
+                ),
+                (op, Some(value_two)) => log::warn!(
+                    "ignoring second value {} of argument {} of {:?}, which is not used by {:?}",
+                    value_two,
+                    arg.index(),
+                    syscall.names(),
+                    op
+                ),
             }
         }
     }
@@ -93,6 +141,18 @@ fn check_seccomp(seccomp: &LinuxSeccomp) -> Result<()> {
     Ok(())
 }
 
+/// Returns true if the syscall is needed to deliver the notify fd. The fd is
+/// sent to the main process with sendmsg over the channel, which is read and
+/// closed afterwards. If the fd is forwarded to a seccomp agent, the profile
+/// must not notify on socket either.
+fn is_notify_fd_syscall(name: &str, forwarding: bool) -> bool {
+    match name {
+        "write" | "read" | "close" | "sendmsg" | "recvmsg" => true,
+        "socket" => forwarding,
+        _ => false,
+    }
+}
+
 /// All filter return actions except SECCOMP_RET_ALLOW should be logged. An administrator may
 /// override this filter flag by preventing specific actions from being logged via the
 /// /proc/sys/kernel/seccomp/actions_logged file. (since Linux 4.14)
@@ -112,8 +172,89 @@ const SECCOMP_FILTER_FLAG_TSYNC: &str = "SECCOMP_FILTER_FLAG_TSYNC";
 /// Disable Speculative Store Bypass mitigation. (since Linux 4.17)
 const SECCOMP_FILTER_FLAG_SPEC_ALLOW: &str = "SECCOMP_FILTER_FLAG_SPEC_ALLOW";
 
+/// Options controlling how a seccomp profile is translated into a filter
+#[derive(Debug, Clone, Default)]
+pub struct SeccompOptions {
+    /// Rejects a second value of argument comparisons for operators other
+    /// than masked equal, which is otherwise ignored with a warning.
+    pub strict: bool,
+    /// Skips architectures of the profile, which can not be added to the
+    /// filter, as long as one architecture remains. By default, any of them
+    /// failing fails the filter.
+    pub skip_unsupported_archs: bool,
+    /// Optimization level of the filter evaluation. 0 keeps the default of
+    /// libseccomp, 1 sorts the rules into a binary tree, which is faster for
+    /// profiles with many syscalls.
+    pub optimize_level: u32,
+}
+
+/// Value of SCMP_FLTATR_CTL_OPTIMIZE for a binary tree of the rules. libseccomp
+/// numbers its levels from 1, which is its default of ordering the rules by
+/// priority, so optimize level 1 of the options maps to 2 here.
+const SCMP_OPTIMIZE_BINARY_TREE: u32 = 2;
+
 pub fn initialize_seccomp(seccomp: &LinuxSeccomp) -> Result<Option<io::RawFd>> {
+    initialize_seccomp_with_options(seccomp, &SeccompOptions::default())
+}
+
+pub fn initialize_seccomp_with_options(
+    seccomp: &LinuxSeccomp,
+    options: &SeccompOptions,
+) -> Result<Option<io::RawFd>> {
+    let ctx = build_filter(seccomp, options)?;
+
+    // In order to use the SECCOMP_SET_MODE_FILTER operation, either the calling
+    // thread must have the CAP_SYS_ADMIN capability in its user namespace, or
+    // the thread must already have the no_new_privs bit set.
+    // Ref: https://man7.org/linux/man-pages/man2/seccomp.2.html
+    check_load_privileges()?;
+    ctx.load().context("failed to load seccomp context")?;
+
+    let fd = if is_notify(seccomp) {
+        Some(
+            ctx.get_notify_fd()
+                .context("failed to get seccomp notify fd")?,
+        )
+    } else {
+        None
+    };
+
+    Ok(fd)
+}
+
+/// Checks that the calling thread is allowed to load a seccomp filter, which
+/// otherwise fails with a bare EACCES.
+fn check_load_privileges() -> Result<()> {
+    let no_new_privs = unsafe { libc::prctl(libc::PR_GET_NO_NEW_PRIVS, 0, 0, 0, 0) };
+    if no_new_privs == 1 {
+        return Ok(());
+    }
+
+    let sys_admin = caps::has_cap(None, CapSet::Effective, Capability::CAP_SYS_ADMIN)
+        .context("failed to check for CAP_SYS_ADMIN")?;
+    if !sys_admin {
+        bail!("loading the seccomp profile requires either noNewPrivileges to be true in the process of the spec or CAP_SYS_ADMIN");
+    }
+
+    Ok(())
+}
+
+/// Translates the seccomp profile into a filter without loading it, which
+/// reports the same errors as initialize_seccomp would.
+pub fn validate_seccomp(seccomp: &LinuxSeccomp) -> Result<()> {
+    validate_seccomp_with_options(seccomp, &SeccompOptions::default())
+}
+
+pub fn validate_seccomp_with_options(
+    seccomp: &LinuxSeccomp,
+    options: &SeccompOptions,
+) -> Result<()> {
+    build_filter(seccomp, options).map(|_| ())
+}
+
+fn build_filter(seccomp: &LinuxSeccomp, options: &SeccompOptions) -> Result<ScmpFilterContext> {
     check_seccomp(seccomp)?;
+    check_args(seccomp, options.strict)?;
 
     let default_action = translate_action(seccomp.default_action(), seccomp.default_errno_ret())?;
     let mut ctx = ScmpFilterContext::new_filter(translate_action(
@@ -132,11 +273,12 @@ pub fn initialize_seccomp(seccomp: &LinuxSeccomp) -> Result<Option<io::RawFd>> {
         }
     }
 
+    // All architectures have to be added before any rule. Rules are only
+    // translated for the architectures in the filter at the time they are
+    // added, e.g. socket to socketcall on x86. This applies to notify as well,
+    // since initialize_seccomp builds the filter the same way.
     if let Some(architectures) = seccomp.architectures() {
-        for &arch in architectures {
-            ctx.add_arch(translate_arch(arch))
-                .context("failed to add arch to seccomp")?;
-        }
+        add_archs(&mut ctx, architectures, options.skip_unsupported_archs)?;
     }
 
     // The SCMP_FLTATR_CTL_NNP controls if the seccomp load function will set
@@ -148,6 +290,22 @@ pub fn initialize_seccomp(seccomp: &LinuxSeccomp) -> Result<Option<io::RawFd>> {
     // value here.
     ctx.set_ctl_nnp(false)?;
 
+    match options.optimize_level {
+        0 => {}
+        1 => {
+            // Level 0 leaves the attribute alone, so the only other level is
+            // the binary tree. SCMP_FLTATR_CTL_OPTIMIZE is only available
+            // since libseccomp 2.5.
+            if let Err(e) = ctx.set_ctl_optimize(SCMP_OPTIMIZE_BINARY_TREE) {
+                log::warn!(
+                    "failed to set seccomp optimization level, using the default: {}",
+                    e
+                );
+            }
+        }
+        level => bail!("seccomp optimization level {} is not supported", level),
+    }
+
     if let Some(syscalls) = seccomp.syscalls() {
         for syscall in syscalls {
             let action = translate_action(syscall.action(), syscall.errno_ret())?;
@@ -180,11 +338,8 @@ pub fn initialize_seccomp(seccomp: &LinuxSeccomp) -> Result<Option<io::RawFd>> {
                 match syscall.args() {
                     Some(args) => {
                         for arg in args {
-                            let cmp = ScmpArgCompare::new(
-                                arg.index() as u32,
-                                translate_op(arg.op(), arg.value_two()),
-                                arg.value(),
-                            );
+                            let (op, datum) = translate_op(arg.op(), arg.value(), arg.value_two());
+                            let cmp = ScmpArgCompare::new(arg.index() as u32, op, datum);
                             ctx.add_rule_conditional(action, sc, &[cmp])
                                 .with_context(|| {
                                     format!(
@@ -204,22 +359,109 @@ pub fn initialize_seccomp(seccomp: &LinuxSeccomp) -> Result<Option<io::RawFd>> {
         }
     }
 
-    // In order to use the SECCOMP_SET_MODE_FILTER operation, either the calling
-    // thread must have the CAP_SYS_ADMIN capability in its user namespace, or
-    // the thread must already have the no_new_privs bit set.
-    // Ref: https://man7.org/linux/man-pages/man2/seccomp.2.html
-    ctx.load().context("failed to load seccomp context")?;
+    Ok(ctx)
+}
 
-    let fd = if is_notify(seccomp) {
-        Some(
-            ctx.get_notify_fd()
-                .context("failed to get seccomp notify fd")?,
-        )
-    } else {
-        None
-    };
+fn add_archs(
+    ctx: &mut ScmpFilterContext,
+    architectures: &[Arch],
+    skip_unsupported: bool,
+) -> Result<()> {
+    let mut added = false;
+    for &arch in architectures {
+        match ctx.add_arch(translate_arch(arch)) {
+            Ok(_) => added = true,
+            Err(e) if !skip_unsupported => {
+                return Err(e).with_context(|| format!("failed to add arch {:?} to seccomp", arch))
+            }
+            Err(e) => {
+                // Similar to syscalls, which can not be resolved, an
+                // architecture the host can't represent is skipped.
+                log::warn!("failed to add arch {:?} to seccomp, skipping: {}", arch, e);
+            }
+        }
+    }
 
-    Ok(fd)
+    if !architectures.is_empty() && !added {
+        bail!(
+            "failed to add any of the architectures {:?} to seccomp",
+            architectures
+        );
+    }
+
+    Ok(())
+}
+
+/// Syscalls, which the rules of a profile do not allow, with the same action
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockedSyscalls {
+    pub action: LinuxSeccompAction,
+    /// Errno returned by the errno and trace actions, EPERM if the rules do
+    /// not specify one
+    pub errno_ret: Option<u32>,
+    /// Syscalls blocked regardless of their arguments
+    pub syscalls: BTreeSet<String>,
+    /// Syscalls only blocked if their arguments match the conditions of a rule
+    pub conditional: BTreeSet<String>,
+}
+
+/// Returns the syscalls, which are not allowed by the rules of the profile,
+/// grouped by their action and errno. Syscalls that can't be resolved on this
+/// system are skipped, like they are when the filter is built. Rules with the
+/// default action are redundant and therefore not listed.
+pub fn blocked_syscalls(seccomp: &LinuxSeccomp) -> Result<Vec<BlockedSyscalls>> {
+    let default_action = translate_action(seccomp.default_action(), seccomp.default_errno_ret())?;
+    let mut blocked: Vec<BlockedSyscalls> = Vec::new();
+    for syscall in seccomp.syscalls().iter().flatten() {
+        let action = translate_action(syscall.action(), syscall.errno_ret())?;
+        if action == ScmpAction::Allow || action == default_action {
+            continue;
+        }
+
+        let errno_ret = match action {
+            ScmpAction::Errno(_) | ScmpAction::Trace(_) => {
+                Some(syscall.errno_ret().unwrap_or(libc::EPERM as u32))
+            }
+            _ => None,
+        };
+        let index = match blocked
+            .iter()
+            .position(|b| b.action == syscall.action() && b.errno_ret == errno_ret)
+        {
+            Some(index) => index,
+            None => {
+                blocked.push(BlockedSyscalls {
+                    action: syscall.action(),
+                    errno_ret,
+                    syscalls: BTreeSet::new(),
+                    conditional: BTreeSet::new(),
+                });
+                blocked.len() - 1
+            }
+        };
+
+        let group = &mut blocked[index];
+        let is_conditional = syscall
+            .args()
+            .as_ref()
+            .map_or(false, |args| !args.is_empty());
+        for name in syscall
+            .names()
+            .iter()
+            .filter(|name| ScmpSyscall::from_name(name).is_ok())
+        {
+            if is_conditional {
+                if !group.syscalls.contains(name) {
+                    group.conditional.insert(name.clone());
+                }
+            } else {
+                group.conditional.remove(name);
+                group.syscalls.insert(name.clone());
+            }
+        }
+    }
+
+    Ok(blocked)
 }
 
 pub fn is_notify(seccomp: &LinuxSeccomp) -> bool {
@@ -230,13 +472,95 @@ pub fn is_notify(seccomp: &LinuxSeccomp) -> bool {
         .any(|syscall| syscall.action() == LinuxSeccompAction::ScmpActNotify)
 }
 
+/// Returns the version of the linked libseccomp library.
+pub fn library_version() -> Result<String> {
+    let version = libseccomp::ScmpVersion::current().context("failed to get libseccomp version")?;
+    Ok(format!(
+        "{}.{}.{}",
+        version.major, version.minor, version.micro
+    ))
+}
+
+/// Returns the seccomp actions, which are supported by the linked libseccomp
+/// and the running kernel, as reported by the libseccomp API level.
+pub fn supported_actions() -> Vec<LinuxSeccompAction> {
+    let api = libseccomp::get_api();
+    let mut actions = vec![
+        LinuxSeccompAction::ScmpActKill,
+        LinuxSeccompAction::ScmpActTrap,
+        LinuxSeccompAction::ScmpActErrno,
+        LinuxSeccompAction::ScmpActTrace,
+        LinuxSeccompAction::ScmpActAllow,
+    ];
+    if api >= 3 {
+        actions.push(LinuxSeccompAction::ScmpActKillProcess);
+        actions.push(LinuxSeccompAction::ScmpActLog);
+    }
+    if api >= 5 {
+        actions.push(LinuxSeccompAction::ScmpActNotify);
+    }
+
+    actions
+}
+
+/// Returns the supported seccomp argument comparison operators.
+pub fn supported_operators() -> Vec<LinuxSeccompOperator> {
+    vec![
+        LinuxSeccompOperator::ScmpCmpNe,
+        LinuxSeccompOperator::ScmpCmpLt,
+        LinuxSeccompOperator::ScmpCmpLe,
+        LinuxSeccompOperator::ScmpCmpEq,
+        LinuxSeccompOperator::ScmpCmpGe,
+        LinuxSeccompOperator::ScmpCmpGt,
+        LinuxSeccompOperator::ScmpCmpMaskedEq,
+    ]
+}
+
+/// Returns the architectures the linked libseccomp can build filters for.
+pub fn supported_archs() -> Result<Vec<Arch>> {
+    let archs = [
+        Arch::ScmpArchX86,
+        Arch::ScmpArchX86_64,
+        Arch::ScmpArchX32,
+        Arch::ScmpArchArm,
+        Arch::ScmpArchAarch64,
+        Arch::ScmpArchMips,
+        Arch::ScmpArchMips64,
+        Arch::ScmpArchMips64n32,
+        Arch::ScmpArchMipsel,
+        Arch::ScmpArchMipsel64,
+        Arch::ScmpArchMipsel64n32,
+        Arch::ScmpArchPpc,
+        Arch::ScmpArchPpc64,
+        Arch::ScmpArchPpc64le,
+        Arch::ScmpArchS390,
+        Arch::ScmpArchS390x,
+    ];
+
+    let mut supported = Vec::new();
+    for arch in archs {
+        // A new filter always contains the native architecture. Once it is
+        // removed, adding an architecture only fails, if libseccomp does not
+        // know about it.
+        let mut ctx = ScmpFilterContext::new_filter(ScmpAction::Allow)
+            .context("failed to create seccomp filter")?;
+        ctx.remove_arch(ScmpArch::Native)
+            .context("failed to remove native arch from seccomp filter")?;
+        if ctx.add_arch(translate_arch(arch)).is_ok() {
+            supported.push(arch);
+        }
+    }
+
+    Ok(supported)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::utils::test_utils;
     use anyhow::Result;
     use oci_spec::runtime::Arch;
-    use oci_spec::runtime::{LinuxSeccompBuilder, LinuxSyscallBuilder};
+    use oci_spec::runtime::{LinuxSeccompArgBuilder, LinuxSeccompBuilder, LinuxSyscallBuilder};
     use serial_test::serial;
     use std::path;
 
@@ -288,6 +612,260 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_features() -> Result<()> {
+        assert!(!library_version()?.is_empty());
+        assert!(supported_actions().contains(&LinuxSeccompAction::ScmpActErrno));
+        assert_eq!(supported_operators().len(), 7);
+        #[cfg(target_arch = "x86_64")]
+        assert!(supported_archs()?.contains(&Arch::ScmpArchX86_64));
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_seccomp() -> Result<()> {
+        let seccomp_profile = LinuxSeccompBuilder::default()
+            .default_action(LinuxSeccompAction::ScmpActAllow)
+            .syscalls(vec![LinuxSyscallBuilder::default()
+                .names(vec![String::from("getcwd")])
+                .action(LinuxSeccompAction::ScmpActErrno)
+                .build()?])
+            .build()?;
+        validate_seccomp(&seccomp_profile)?;
+
+        let mut invalid_flag = seccomp_profile.clone();
+        invalid_flag.set_flags(Some(vec![String::from("SECCOMP_FILTER_FLAG_UNKNOWN")]));
+        assert!(validate_seccomp(&invalid_flag).is_err());
+
+        let mut notify_default = seccomp_profile;
+        notify_default.set_default_action(LinuxSeccompAction::ScmpActNotify);
+        assert!(validate_seccomp(&notify_default).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_seccomp_notify_fd_syscalls() -> Result<()> {
+        let profile = |name: &str| -> Result<LinuxSeccomp> {
+            Ok(LinuxSeccompBuilder::default()
+                .default_action(LinuxSeccompAction::ScmpActAllow)
+                .syscalls(vec![LinuxSyscallBuilder::default()
+                    .names(vec![String::from("getcwd"), String::from(name)])
+                    .action(LinuxSeccompAction::ScmpActNotify)
+                    .build()?])
+                .build()?)
+        };
+
+        for name in ["write", "read", "close", "sendmsg", "recvmsg"] {
+            let err = check_seccomp(&profile(name)?).unwrap_err();
+            assert!(err.to_string().contains(name), "{}", err);
+        }
+
+        let mut socket = profile("socket")?;
+        check_seccomp(&socket)?;
+        socket.set_listener_path(Some(path::PathBuf::from("/run/seccomp-agent.sock")));
+        assert!(check_seccomp(&socket).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_args() -> Result<()> {
+        let profile = |op: LinuxSeccompOperator, value_two: Option<u64>| -> Result<LinuxSeccomp> {
+            let mut arg = LinuxSeccompArgBuilder::default()
+                .index(0usize)
+                .value(1u64)
+                .op(op)
+                .build()?;
+            arg.set_value_two(value_two);
+            Ok(LinuxSeccompBuilder::default()
+                .default_action(LinuxSeccompAction::ScmpActAllow)
+                .syscalls(vec![LinuxSyscallBuilder::default()
+                    .names(vec![String::from("personality")])
+                    .action(LinuxSeccompAction::ScmpActErrno)
+                    .args(vec![arg])
+                    .build()?])
+                .build()?)
+        };
+
+        check_args(&profile(LinuxSeccompOperator::ScmpCmpEq, None)?, true)?;
+        check_args(&profile(LinuxSeccompOperator::ScmpCmpEq, Some(2))?, false)?;
+        assert!(check_args(&profile(LinuxSeccompOperator::ScmpCmpEq, Some(2))?, true).is_err());
+        // the second value of masked equal is optional, e.g. the clone rule
+        // of the default profile of docker has none
+        for value_two in [None, Some(0), Some(3)] {
+            check_args(
+                &profile(LinuxSeccompOperator::ScmpCmpMaskedEq, value_two)?,
+                true,
+            )?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_translate_op() {
+        assert_eq!(
+            translate_op(LinuxSeccompOperator::ScmpCmpEq, 5, None),
+            (ScmpCompareOp::Equal, 5)
+        );
+        assert_eq!(
+            translate_op(LinuxSeccompOperator::ScmpCmpMaskedEq, 2114060288, None),
+            (ScmpCompareOp::MaskedEqual(2114060288), 0)
+        );
+        assert_eq!(
+            translate_op(LinuxSeccompOperator::ScmpCmpMaskedEq, 0xff, Some(0x10)),
+            (ScmpCompareOp::MaskedEqual(0xff), 0x10)
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn test_check_load_privileges() -> Result<()> {
+        test_utils::test_in_child_process(|| {
+            caps::drop(None, CapSet::Effective, Capability::CAP_SYS_ADMIN)?;
+            if check_load_privileges().is_ok() {
+                bail!("seccomp can't be loaded without CAP_SYS_ADMIN or no new privileges");
+            }
+
+            let _ = prctl::set_no_new_privileges(true);
+            check_load_privileges()
+        })?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_blocked_syscalls() -> Result<()> {
+        let rule = |names: &[&str], action| {
+            LinuxSyscallBuilder::default()
+                .names(names.iter().map(|n| n.to_string()).collect::<Vec<_>>())
+                .action(action)
+                .build()
+        };
+        let names =
+            |names: &[&str]| -> BTreeSet<String> { names.iter().map(|n| n.to_string()).collect() };
+        let seccomp_profile = LinuxSeccompBuilder::default()
+            .default_action(LinuxSeccompAction::ScmpActAllow)
+            .syscalls(vec![
+                rule(
+                    &["getcwd", "not_a_syscall"],
+                    LinuxSeccompAction::ScmpActErrno,
+                )?,
+                rule(&["read"], LinuxSeccompAction::ScmpActAllow)?,
+                rule(&["mount"], LinuxSeccompAction::ScmpActKill)?,
+                rule(&["umount2", "getcwd"], LinuxSeccompAction::ScmpActErrno)?,
+                LinuxSyscallBuilder::default()
+                    .names(vec!["chmod".to_owned()])
+                    .action(LinuxSeccompAction::ScmpActErrno)
+                    .errno_ret(libc::EACCES as u32)
+                    .build()?,
+                LinuxSyscallBuilder::default()
+                    .names(vec!["personality".to_owned(), "getcwd".to_owned()])
+                    .action(LinuxSeccompAction::ScmpActErrno)
+                    .args(vec![LinuxSeccompArgBuilder::default()
+                        .index(0usize)
+                        .value(0u64)
+                        .op(LinuxSeccompOperator::ScmpCmpEq)
+                        .build()?])
+                    .build()?,
+            ])
+            .build()?;
+
+        let blocked = blocked_syscalls(&seccomp_profile)?;
+        assert_eq!(
+            blocked,
+            vec![
+                BlockedSyscalls {
+                    action: LinuxSeccompAction::ScmpActErrno,
+                    errno_ret: Some(libc::EPERM as u32),
+                    syscalls: names(&["getcwd", "umount2"]),
+                    conditional: names(&["personality"]),
+                },
+                BlockedSyscalls {
+                    action: LinuxSeccompAction::ScmpActKill,
+                    errno_ret: None,
+                    syscalls: names(&["mount"]),
+                    conditional: BTreeSet::new(),
+                },
+                BlockedSyscalls {
+                    action: LinuxSeccompAction::ScmpActErrno,
+                    errno_ret: Some(libc::EACCES as u32),
+                    syscalls: names(&["chmod"]),
+                    conditional: BTreeSet::new(),
+                },
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_optimize_level() -> Result<()> {
+        let seccomp_profile = LinuxSeccompBuilder::default()
+            .default_action(LinuxSeccompAction::ScmpActAllow)
+            .syscalls(vec![LinuxSyscallBuilder::default()
+                .names(vec![String::from("getcwd")])
+                .action(LinuxSeccompAction::ScmpActErrno)
+                .build()?])
+            .build()?;
+
+        let options = |optimize_level| SeccompOptions {
+            optimize_level,
+            ..Default::default()
+        };
+        validate_seccomp_with_options(&seccomp_profile, &options(1))?;
+        assert!(validate_seccomp_with_options(&seccomp_profile, &options(2)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86_64")]
+    fn test_add_archs() -> Result<()> {
+        // A filter can only contain architectures of the same endianness, so
+        // the big endian s390x can't be added next to the native x86_64.
+        let new_ctx = || ScmpFilterContext::new_filter(ScmpAction::Allow);
+        add_archs(
+            &mut new_ctx()?,
+            &[Arch::ScmpArchX86_64, Arch::ScmpArchS390x],
+            true,
+        )?;
+        assert!(add_archs(
+            &mut new_ctx()?,
+            &[Arch::ScmpArchX86_64, Arch::ScmpArchS390x],
+            false
+        )
+        .is_err());
+        assert!(add_archs(&mut new_ctx()?, &[Arch::ScmpArchS390x], true).is_err());
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86_64")]
+    fn test_multi_arch_socket() -> Result<()> {
+        let syscall = LinuxSyscallBuilder::default()
+            .names(vec![String::from("socket")])
+            .action(LinuxSeccompAction::ScmpActErrno)
+            .build()?;
+        let seccomp_profile = LinuxSeccompBuilder::default()
+            .default_action(LinuxSeccompAction::ScmpActAllow)
+            .architectures(vec![Arch::ScmpArchX86_64, Arch::ScmpArchX86])
+            .syscalls(vec![syscall])
+            .build()?;
+        let ctx = build_filter(&seccomp_profile, &SeccompOptions::default())?;
+
+        let tmp = crate::utils::create_temp_dir("test_multi_arch_socket")?;
+        let pfc = tmp.join("filter.pfc");
+        let mut file = std::fs::File::create(&pfc)?;
+        ctx.export_pfc(&mut file)?;
+        let pfc = std::fs::read_to_string(pfc)?;
+
+        // socket is 41 on x86_64 and multiplexed through socketcall (102) on x86
+        let (x86_64, x86) = pfc
+            .split_once("# filter for arch x86 (")
+            .context("no filter for x86")?;
+        assert!(x86_64.contains("# filter for arch x86_64 ("), "{}", pfc);
+        assert!(x86_64.contains("$syscall == 41"), "{}", pfc);
+        assert!(x86.contains("$syscall == 102"), "{}", pfc);
+        Ok(())
+    }
+
     #[test]
     #[serial]
     fn test_moby() -> Result<()> {
@@ -0,0 +1,187 @@
+use anyhow::{bail, Context, Result};
+use oci_spec::runtime::Spec;
+use std::{fs, path::Path};
+
+use crate::utils;
+
+const SELINUX_FS_PATH: &str = "/sys/fs/selinux";
+const EXEC_ATTR_PATH: &str = "/proc/self/attr/exec";
+/// Mount options that label a filesystem
+const CONTEXT_OPTIONS: &[&str] = &["context=", "fscontext=", "defcontext=", "rootcontext="];
+
+/// SELinux labels of the container, which are only set if SELinux is enabled.
+/// The rootfs mounts and the process setup draw their labels from here.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Labels {
+    /// Label the container process transitions to
+    pub process: Option<String>,
+    /// Label of the mounts that do not specify a context themselves
+    pub mount: Option<String>,
+}
+
+impl Labels {
+    pub fn from_spec(spec: &Spec) -> Self {
+        let process = spec
+            .process()
+            .as_ref()
+            .and_then(|p| p.selinux_label().as_deref());
+        let mount = spec
+            .linux()
+            .as_ref()
+            .and_then(|l| l.mount_label().as_deref());
+        Self {
+            process: mount_label(process)
+                .filter(|l| !l.is_empty())
+                .map(str::to_owned),
+            mount: mount_label(mount)
+                .filter(|l| !l.is_empty())
+                .map(str::to_owned),
+        }
+    }
+}
+
+/// Checks if SELinux has been enabled on the system, which is the case if the
+/// selinuxfs is mounted.
+pub fn is_enabled() -> bool {
+    Path::new(SELINUX_FS_PATH).join("enforce").exists()
+}
+
+/// Checks if SELinux is running in enforcing mode.
+pub fn is_enforcing() -> Result<bool> {
+    let enforce_path = Path::new(SELINUX_FS_PATH).join("enforce");
+    let enforce = fs::read_to_string(&enforce_path)
+        .with_context(|| format!("could not read {:?}", enforce_path))?;
+    Ok(enforce.trim() == "1")
+}
+
+/// Returns the mount label that should be passed as `context` mount option,
+/// which is only the case if SELinux is enabled.
+pub fn mount_label(label: Option<&str>) -> Option<&str> {
+    label.filter(|_| is_enabled())
+}
+
+/// Returns the option a filesystem takes the mount label with. Pseudo
+/// filesystems labeled by the policy can not take a context, filesystems with
+/// xattr support keep the labels of their files and only label unlabeled ones
+/// and overlay keeps the labels of its layers and only labels the superblock.
+fn context_option(fs_type: Option<&str>) -> Option<&'static str> {
+    match fs_type {
+        Some("proc" | "sysfs" | "securityfs" | "selinuxfs" | "cgroup" | "cgroup2") => None,
+        Some("ext2" | "ext3" | "ext4" | "xfs" | "btrfs") => Some("defcontext"),
+        Some("overlay") => Some("fscontext"),
+        _ => Some("context"),
+    }
+}
+
+/// Adds the mount label to the mount data of a filesystem of the given type,
+/// unless the mount specifies a context itself.
+pub fn format_mount_data(data: &str, fs_type: Option<&str>, label: Option<&str>) -> String {
+    let label = match label {
+        Some(label) if !label.is_empty() => label,
+        _ => return data.to_owned(),
+    };
+    if data
+        .split(',')
+        .any(|o| CONTEXT_OPTIONS.iter().any(|c| o.starts_with(c)))
+    {
+        return data.to_owned();
+    }
+
+    match context_option(fs_type) {
+        Some(option) if data.is_empty() => format!("{}=\"{}\"", option, label),
+        Some(option) => format!("{},{}=\"{}\"", data, option, label),
+        None => data.to_owned(),
+    }
+}
+
+/// Sets the SELinux label the container process will transition to on the
+/// next execve. This is a no-op if SELinux is disabled.
+pub fn set_exec_label(label: &str) -> Result<()> {
+    if label.is_empty() {
+        return Ok(());
+    }
+
+    if !is_enabled() {
+        log::debug!("selinux is disabled, ignoring process label {}", label);
+        return Ok(());
+    }
+
+    let path = Path::new(EXEC_ATTR_PATH);
+    utils::ensure_procfs(path)?;
+    if let Err(err) = fs::write(path, label) {
+        if is_enforcing().unwrap_or(false) {
+            bail!(
+                "selinux denied setting the process label {} in enforcing mode: {}",
+                label,
+                err
+            );
+        }
+
+        return Err(err).with_context(|| format!("failed to set selinux process label {}", label));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mount_label() {
+        assert_eq!(mount_label(None), None);
+        if is_enabled() {
+            assert_eq!(mount_label(Some("label")), Some("label"));
+        } else {
+            assert_eq!(mount_label(Some("label")), None);
+        }
+    }
+
+    #[test]
+    fn test_format_mount_data() {
+        let label = Some("system_u:object_r:container_file_t:s0");
+        assert_eq!(
+            format_mount_data("", Some("tmpfs"), label),
+            "context=\"system_u:object_r:container_file_t:s0\""
+        );
+        assert_eq!(
+            format_mount_data("mode=755", Some("tmpfs"), label),
+            "mode=755,context=\"system_u:object_r:container_file_t:s0\""
+        );
+        assert_eq!(
+            format_mount_data("", Some("xfs"), label),
+            "defcontext=\"system_u:object_r:container_file_t:s0\""
+        );
+        assert_eq!(
+            format_mount_data("", Some("overlay"), label),
+            "fscontext=\"system_u:object_r:container_file_t:s0\""
+        );
+        assert_eq!(format_mount_data("", Some("proc"), label), "");
+        assert_eq!(
+            format_mount_data("mode=755", Some("tmpfs"), None),
+            "mode=755"
+        );
+        // an explicit context of the mount takes precedence
+        assert_eq!(
+            format_mount_data("context=\"other\"", Some("tmpfs"), label),
+            "context=\"other\""
+        );
+    }
+
+    #[test]
+    fn test_labels_from_spec() {
+        let labels = Labels::from_spec(&Spec::default());
+        if !is_enabled() {
+            assert_eq!(labels, Labels::default());
+        }
+    }
+
+    #[test]
+    fn test_set_exec_label_disabled() -> Result<()> {
+        if is_enabled() {
+            return Ok(());
+        }
+
+        set_exec_label("system_u:system_r:container_t:s0")
+    }
+}
@@ -1,8 +1,9 @@
 //! Implements Command trait for Linux systems
 #[cfg_attr(coverage, no_coverage)]
-use std::ffi::{CStr, OsStr};
+use std::ffi::{CStr, CString, OsStr};
 use std::os::unix::ffi::OsStrExt;
 use std::os::unix::fs::symlink;
+use std::os::unix::io::RawFd;
 use std::sync::Arc;
 use std::{any::Any, mem, path::Path, ptr};
 
@@ -19,11 +20,26 @@ use nix::{
     unistd::{chown, fchdir, pivot_root, setgroups, sethostname, Gid, Uid},
 };
 
-use oci_spec::runtime::LinuxRlimit;
+use oci_spec::runtime::PosixRlimit;
 
 use super::Syscall;
 use crate::capabilities;
 
+// flags of the new mount api, which are not provided by libc
+const OPEN_TREE_CLONE: libc::c_uint = 1;
+const AT_RECURSIVE: libc::c_uint = 0x8000;
+const MOVE_MOUNT_F_EMPTY_PATH: libc::c_uint = 0x4;
+const MOUNT_ATTR_IDMAP: u64 = 0x0010_0000;
+
+/// Attributes of mount_setattr(2)
+#[repr(C)]
+struct MountAttr {
+    attr_set: u64,
+    attr_clr: u64,
+    propagation: u64,
+    userns_fd: u64,
+}
+
 /// Empty structure to implement Command trait for
 #[derive(Clone)]
 pub struct LinuxSyscall;
@@ -85,6 +101,51 @@ impl Syscall for LinuxSyscall {
         Ok(())
     }
 
+    /// Function to set given path as root path inside process without pivot_root,
+    /// by moving it on top of the original root directory and chrooting into it
+    fn move_rootfs(&self, path: &Path) -> Result<()> {
+        // Make the mounts rslave to avoid propagating the unmount events below
+        // to the host mount namespace.
+        mount(
+            None::<&str>,
+            "/",
+            None::<&str>,
+            MsFlags::MS_SLAVE | MsFlags::MS_REC,
+            None::<&str>,
+        )?;
+
+        // Without pivot_root the original root directory can not be unmounted,
+        // so detach every mount of it, which is neither the rootfs nor a mount
+        // below or above it, to not leak the host mounts into the container.
+        // Mounts are listed in the order they were mounted in, so children are
+        // detached before their parents.
+        let mount_infos = procfs::process::Process::myself()?.mountinfo()?;
+        for mount_info in mount_infos.iter().rev() {
+            let mount_point = &mount_info.mount_point;
+            if mount_point.starts_with(path) || path.starts_with(mount_point) {
+                continue;
+            }
+
+            match umount2(mount_point, MntFlags::MNT_DETACH) {
+                // the mount point may be gone already, if it was overmounted
+                Ok(()) | Err(Errno::EINVAL) | Err(Errno::ENOENT) => {}
+                Err(e) => bail!("failed to detach {:?}: {}", mount_point, e),
+            }
+        }
+
+        unistd::chdir(path)?;
+        mount(
+            Some(path),
+            "/",
+            None::<&str>,
+            MsFlags::MS_MOVE,
+            None::<&str>,
+        )?;
+        unistd::chroot(".")?;
+        unistd::chdir("/")?;
+        Ok(())
+    }
+
     /// Set namespace for process
     fn set_ns(&self, rawfd: i32, nstype: CloneFlags) -> Result<()> {
         nix::sched::setns(rawfd, nstype)?;
@@ -135,6 +196,14 @@ impl Syscall for LinuxSyscall {
                     caps::drop(None, CapSet::Bounding, *c)?
                 }
             }
+            // ambient capabilities are raised one by one, after the
+            // previously set ones have been cleared
+            CapSet::Ambient => {
+                caps::clear(None, CapSet::Ambient)?;
+                for c in value {
+                    caps::raise(None, CapSet::Ambient, *c)?
+                }
+            }
             _ => {
                 caps::set(None, cset, value)?;
             }
@@ -150,12 +219,30 @@ impl Syscall for LinuxSyscall {
         Ok(())
     }
 
+    /// Sets NIS domainname for process
+    fn set_domainname(&self, domainname: &str) -> Result<()> {
+        // nix does not provide a wrapper for setdomainname
+        let res =
+            unsafe { libc::setdomainname(domainname.as_ptr() as *const c_char, domainname.len()) };
+        if let Err(e) = Errno::result(res) {
+            bail!("Failed to set {} as domainname. {:?}", domainname, e)
+        }
+        Ok(())
+    }
+
     /// Sets resource limit for process
-    fn set_rlimit(&self, rlimit: &LinuxRlimit) -> Result<()> {
+    fn set_rlimit(&self, rlimit: &PosixRlimit) -> Result<()> {
         let rlim = &libc::rlimit {
             rlim_cur: rlimit.soft(),
             rlim_max: rlimit.hard(),
         };
+        if rlim.rlim_max != libc::RLIM_INFINITY && rlim.rlim_cur > rlim.rlim_max {
+            bail!(
+                "soft limit of {:?} exceeds the hard limit {}",
+                rlimit.typ(),
+                rlim.rlim_max
+            );
+        }
         let res = unsafe { libc::setrlimit(rlimit.typ() as u32, rlim) };
         if let Err(e) = Errno::result(res).map(drop) {
             bail!("Failed to set {:?}. {:?}", rlimit.typ(), e)
@@ -217,6 +304,77 @@ impl Syscall for LinuxSyscall {
         }
     }
 
+    /// Attaches an idmapped copy of the source mount at the target. The copy
+    /// is detached from the mount tree until the ids have been mapped through
+    /// the user namespace.
+    fn mount_idmapped(
+        &self,
+        source: &Path,
+        target: &Path,
+        userns_fd: RawFd,
+        recursive: bool,
+    ) -> Result<()> {
+        let recursive = if recursive { AT_RECURSIVE } else { 0 };
+        let source_c = CString::new(source.as_os_str().as_bytes())?;
+        let target_c = CString::new(target.as_os_str().as_bytes())?;
+        let empty_path = b"\0".as_ptr() as *const c_char;
+
+        let tree = unsafe {
+            libc::syscall(
+                libc::SYS_open_tree,
+                libc::AT_FDCWD,
+                source_c.as_ptr(),
+                OPEN_TREE_CLONE | libc::O_CLOEXEC as libc::c_uint | recursive,
+            )
+        };
+        let tree = match Errno::result(tree) {
+            Ok(tree) => tree as RawFd,
+            Err(e) => bail!("failed to clone mount {:?}: {}", source, e),
+        };
+
+        let attr = MountAttr {
+            attr_set: MOUNT_ATTR_IDMAP,
+            attr_clr: 0,
+            propagation: 0,
+            userns_fd: userns_fd as u64,
+        };
+        let res = unsafe {
+            libc::syscall(
+                libc::SYS_mount_setattr,
+                tree,
+                empty_path,
+                libc::AT_EMPTY_PATH as libc::c_uint | recursive,
+                &attr as *const MountAttr,
+                mem::size_of::<MountAttr>(),
+            )
+        };
+        let res = match Errno::result(res) {
+            Ok(_) => unsafe {
+                libc::syscall(
+                    libc::SYS_move_mount,
+                    tree,
+                    empty_path,
+                    libc::AT_FDCWD,
+                    target_c.as_ptr(),
+                    MOVE_MOUNT_F_EMPTY_PATH,
+                )
+            },
+            Err(e) => {
+                let _ = unistd::close(tree);
+                bail!(
+                    "kernel rejected the id mapping of {:?}, the filesystem may not support idmapped mounts: {}",
+                    source,
+                    e
+                );
+            }
+        };
+        let _ = unistd::close(tree);
+        if let Err(e) = Errno::result(res) {
+            bail!("failed to attach idmapped mount at {:?}: {}", target, e);
+        }
+        Ok(())
+    }
+
     fn symlink(&self, original: &Path, link: &Path) -> Result<()> {
         match symlink(original, link) {
             Ok(_) => Ok(()),
@@ -245,3 +403,71 @@ impl Syscall for LinuxSyscall {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_utils::test_in_child_process;
+    use nix::sched::{unshare, CloneFlags};
+    use oci_spec::runtime::{PosixRlimitBuilder, PosixRlimitType};
+
+    fn get_core_rlimit() -> Result<libc::rlimit> {
+        let mut rlim = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        Errno::result(unsafe { libc::getrlimit(libc::RLIMIT_CORE, &mut rlim) })?;
+        Ok(rlim)
+    }
+
+    #[test]
+    fn test_set_rlimit_unlimited() -> Result<()> {
+        let old = get_core_rlimit()?;
+        if old.rlim_max != libc::RLIM_INFINITY && !nix::unistd::geteuid().is_root() {
+            // the hard limit can only be raised with CAP_SYS_RESOURCE
+            return Ok(());
+        }
+
+        let rlimit = PosixRlimitBuilder::default()
+            .typ(PosixRlimitType::RlimitCore)
+            .soft(u64::MAX)
+            .hard(u64::MAX)
+            .build()?;
+        LinuxSyscall.set_rlimit(&rlimit)?;
+        let got = get_core_rlimit()?;
+        Errno::result(unsafe { libc::setrlimit(libc::RLIMIT_CORE, &old) })?;
+
+        assert_eq!(got.rlim_cur, libc::RLIM_INFINITY);
+        assert_eq!(got.rlim_max, libc::RLIM_INFINITY);
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_domainname() -> Result<()> {
+        test_in_child_process(|| {
+            unshare(CloneFlags::CLONE_NEWUTS)?;
+            LinuxSyscall.set_domainname("youki.test")?;
+
+            let mut buf = [0u8; 64];
+            Errno::result(unsafe {
+                libc::getdomainname(buf.as_mut_ptr() as *mut c_char, buf.len())
+            })?;
+            let domainname = unsafe { CStr::from_ptr(buf.as_ptr() as *const c_char) };
+            if domainname.to_bytes() != b"youki.test" {
+                bail!("unexpected domainname {:?}", domainname);
+            }
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_set_rlimit_soft_exceeds_hard() -> Result<()> {
+        let rlimit = PosixRlimitBuilder::default()
+            .typ(PosixRlimitType::RlimitCore)
+            .soft(u64::MAX)
+            .hard(1024u64)
+            .build()?;
+        assert!(LinuxSyscall.set_rlimit(&rlimit).is_err());
+        Ok(())
+    }
+}
@@ -3,6 +3,7 @@ use std::{
     cell::{Ref, RefCell, RefMut},
     collections::HashMap,
     ffi::{OsStr, OsString},
+    os::unix::io::RawFd,
     path::{Path, PathBuf},
     sync::Arc,
 };
@@ -15,10 +16,22 @@ use nix::{
     unistd::{Gid, Uid},
 };
 
-use oci_spec::runtime::LinuxRlimit;
+use oci_spec::runtime::PosixRlimit;
 
 use super::Syscall;
 
+/// Resolves a mount target, which is the fd of the verified destination, to
+/// the path it refers to, so that tests can compare it with the destination
+fn resolve_fd_path(target: &Path) -> PathBuf {
+    if target.starts_with("/proc/self/fd") {
+        if let Ok(path) = std::fs::read_link(target) {
+            return path;
+        }
+    }
+
+    target.to_owned()
+}
+
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct MountArgs {
     pub source: Option<PathBuf>,
@@ -28,6 +41,14 @@ pub struct MountArgs {
     pub data: Option<String>,
 }
 
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct IdmappedMountArgs {
+    pub source: PathBuf,
+    pub target: PathBuf,
+    pub userns_fd: RawFd,
+    pub recursive: bool,
+}
+
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct MknodArgs {
     pub path: PathBuf,
@@ -55,10 +76,12 @@ pub enum ArgName {
     Namespace,
     Unshare,
     Mount,
+    IdmappedMount,
     Symlink,
     Mknod,
     Chown,
     Hostname,
+    Domainname,
     Groups,
     Capability,
 }
@@ -69,10 +92,12 @@ impl ArgName {
             ArgName::Namespace,
             ArgName::Unshare,
             ArgName::Mount,
+            ArgName::IdmappedMount,
             ArgName::Symlink,
             ArgName::Mknod,
             ArgName::Chown,
             ArgName::Hostname,
+            ArgName::Domainname,
             ArgName::Groups,
             ArgName::Capability,
         ]
@@ -140,6 +165,10 @@ impl Syscall for TestHelperSyscall {
         unimplemented!()
     }
 
+    fn move_rootfs(&self, _path: &Path) -> anyhow::Result<()> {
+        unimplemented!()
+    }
+
     fn set_ns(&self, rawfd: i32, nstype: CloneFlags) -> anyhow::Result<()> {
         self.mocks
             .act(ArgName::Namespace, Box::new((rawfd, nstype)))
@@ -163,7 +192,12 @@ impl Syscall for TestHelperSyscall {
             .act(ArgName::Hostname, Box::new(hostname.to_owned()))
     }
 
-    fn set_rlimit(&self, _rlimit: &LinuxRlimit) -> anyhow::Result<()> {
+    fn set_domainname(&self, domainname: &str) -> anyhow::Result<()> {
+        self.mocks
+            .act(ArgName::Domainname, Box::new(domainname.to_owned()))
+    }
+
+    fn set_rlimit(&self, _rlimit: &PosixRlimit) -> anyhow::Result<()> {
         todo!()
     }
 
@@ -187,7 +221,7 @@ impl Syscall for TestHelperSyscall {
             ArgName::Mount,
             Box::new(MountArgs {
                 source: source.map(|x| x.to_owned()),
-                target: target.to_owned(),
+                target: resolve_fd_path(target),
                 fstype: fstype.map(|x| x.to_owned()),
                 flags,
                 data: data.map(|x| x.to_owned()),
@@ -195,6 +229,24 @@ impl Syscall for TestHelperSyscall {
         )
     }
 
+    fn mount_idmapped(
+        &self,
+        source: &Path,
+        target: &Path,
+        userns_fd: RawFd,
+        recursive: bool,
+    ) -> anyhow::Result<()> {
+        self.mocks.act(
+            ArgName::IdmappedMount,
+            Box::new(IdmappedMountArgs {
+                source: source.to_owned(),
+                target: resolve_fd_path(target),
+                userns_fd,
+                recursive,
+            }),
+        )
+    }
+
     fn symlink(&self, original: &Path, link: &Path) -> anyhow::Result<()> {
         self.mocks.act(
             ArgName::Symlink,
@@ -275,6 +327,15 @@ impl TestHelperSyscall {
             .collect::<Vec<MountArgs>>()
     }
 
+    pub fn get_idmapped_mount_args(&self) -> Vec<IdmappedMountArgs> {
+        self.mocks
+            .fetch(ArgName::IdmappedMount)
+            .values
+            .iter()
+            .map(|x| x.downcast_ref::<IdmappedMountArgs>().unwrap().clone())
+            .collect::<Vec<IdmappedMountArgs>>()
+    }
+
     pub fn get_symlink_args(&self) -> Vec<(PathBuf, PathBuf)> {
         self.mocks
             .fetch(ArgName::Symlink)
@@ -311,6 +372,15 @@ impl TestHelperSyscall {
             .collect::<Vec<String>>()
     }
 
+    pub fn get_domainname_args(&self) -> Vec<String> {
+        self.mocks
+            .fetch(ArgName::Domainname)
+            .values
+            .iter()
+            .map(|x| x.downcast_ref::<String>().unwrap().clone())
+            .collect::<Vec<String>>()
+    }
+
     pub fn get_groups_args(&self) -> Vec<Vec<Gid>> {
         self.mocks
             .fetch(ArgName::Groups)
@@ -0,0 +1,137 @@
+//! Applies the kernel parameters of the container as specified by
+//! `linux.sysctl`. Only namespaced parameters can be set, and only if the
+//! container has its own instance of the namespace owning them, otherwise
+//! they would change the parameters of the host.
+//! See https://man7.org/linux/man-pages/man5/proc.5.html for more information
+use anyhow::{bail, Context, Result};
+use oci_spec::runtime::{LinuxNamespace, LinuxNamespaceType};
+use std::{collections::HashMap, fs, path::Path};
+
+const PROC_SYS: &str = "/proc/sys";
+
+/// Parameters owned by the IPC namespace
+const IPC_SYSCTLS: &[&str] = &[
+    "kernel.msgmax",
+    "kernel.msgmnb",
+    "kernel.msgmni",
+    "kernel.sem",
+    "kernel.shmall",
+    "kernel.shmmax",
+    "kernel.shmmni",
+    "kernel.shm_rmid_forced",
+];
+
+/// Parameters owned by the UTS namespace
+const UTS_SYSCTLS: &[&str] = &["kernel.domainname", "kernel.hostname"];
+
+/// Returns the namespace owning the kernel parameter, if it is namespaced
+fn owning_namespace(key: &str) -> Option<LinuxNamespaceType> {
+    if key.starts_with("net.") {
+        return Some(LinuxNamespaceType::Network);
+    }
+    if IPC_SYSCTLS.contains(&key) || key.starts_with("fs.mqueue.") {
+        return Some(LinuxNamespaceType::Ipc);
+    }
+    if UTS_SYSCTLS.contains(&key) {
+        return Some(LinuxNamespaceType::Uts);
+    }
+    None
+}
+
+/// Checks that every kernel parameter is namespaced and that the container
+/// has the namespace owning it.
+pub fn validate_sysctl(
+    kernel_params: &HashMap<String, String>,
+    namespaces: &[LinuxNamespace],
+) -> Result<()> {
+    for key in kernel_params.keys() {
+        let namespace = match owning_namespace(key) {
+            Some(namespace) => namespace,
+            None => bail!("sysctl {} is not namespaced and can not be set", key),
+        };
+
+        if !namespaces.iter().any(|ns| ns.typ() == namespace) {
+            bail!(
+                "sysctl {} requires a new {:?} namespace to be specified",
+                key,
+                namespace
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes the kernel parameters under /proc/sys, the dots of a key separate
+/// the directories of its path.
+pub fn set_sysctl(
+    kernel_params: &HashMap<String, String>,
+    namespaces: &[LinuxNamespace],
+) -> Result<()> {
+    validate_sysctl(kernel_params, namespaces)?;
+
+    let sys = Path::new(PROC_SYS);
+    for (key, value) in kernel_params {
+        log::debug!("apply value {} to kernel parameter {}.", value, key);
+        fs::write(sys.join(key.replace('.', "/")), value.as_bytes())
+            .with_context(|| format!("failed to set sysctl {}={}", key, value))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oci_spec::runtime::LinuxNamespaceBuilder;
+
+    fn namespaces(types: &[LinuxNamespaceType]) -> Vec<LinuxNamespace> {
+        types
+            .iter()
+            .map(|typ| LinuxNamespaceBuilder::default().typ(*typ).build().unwrap())
+            .collect()
+    }
+
+    fn kernel_params(key: &str) -> HashMap<String, String> {
+        HashMap::from([(key.to_owned(), "1".to_owned())])
+    }
+
+    #[test]
+    fn test_owning_namespace() {
+        assert_eq!(
+            owning_namespace("net.ipv4.ip_forward"),
+            Some(LinuxNamespaceType::Network)
+        );
+        assert_eq!(
+            owning_namespace("kernel.msgmax"),
+            Some(LinuxNamespaceType::Ipc)
+        );
+        assert_eq!(
+            owning_namespace("fs.mqueue.msg_max"),
+            Some(LinuxNamespaceType::Ipc)
+        );
+        assert_eq!(
+            owning_namespace("kernel.hostname"),
+            Some(LinuxNamespaceType::Uts)
+        );
+        assert_eq!(owning_namespace("kernel.pid_max"), None);
+        assert_eq!(owning_namespace("vm.swappiness"), None);
+    }
+
+    #[test]
+    fn test_validate_sysctl() {
+        let all = namespaces(&[
+            LinuxNamespaceType::Network,
+            LinuxNamespaceType::Ipc,
+            LinuxNamespaceType::Uts,
+        ]);
+        assert!(validate_sysctl(&kernel_params("net.ipv4.ip_forward"), &all).is_ok());
+        assert!(validate_sysctl(&kernel_params("kernel.sem"), &all).is_ok());
+        assert!(validate_sysctl(&kernel_params("kernel.pid_max"), &all).is_err());
+
+        let ipc = namespaces(&[LinuxNamespaceType::Ipc]);
+        assert!(validate_sysctl(&kernel_params("net.ipv4.ip_forward"), &ipc).is_err());
+        assert!(validate_sysctl(&kernel_params("kernel.shmmax"), &ipc).is_ok());
+        assert!(validate_sysctl(&kernel_params("kernel.shmmax"), &[]).is_err());
+    }
+}
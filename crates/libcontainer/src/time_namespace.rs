@@ -0,0 +1,229 @@
+//! Time namespaces offset the monotonic and boottime clocks of the processes
+//! in them, e.g. so that the uptime of a container starts at zero. oci-spec
+//! does not know them yet, so the `time` namespace and `linux.timeOffsets` are
+//! taken from the raw runtime spec.
+
+use crate::syscall::Syscall;
+use anyhow::{bail, Context, Result};
+use nix::{fcntl, sched::CloneFlags, sys::stat, unistd};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+/// Offsets of the clocks of a new time namespace, which can only be written
+/// before a process has entered it
+const TIMENS_OFFSETS: &str = "/proc/self/timens_offsets";
+/// Clocks which can be offset in a time namespace
+const CLOCKS: &[&str] = &["monotonic", "boottime"];
+const NANOS_PER_SEC: u32 = 1_000_000_000;
+/// Clone flag of the time namespace, which nix does not know yet
+const CLONE_NEWTIME: libc::c_int = 0x80;
+
+fn clone_flag() -> CloneFlags {
+    // the flag is passed on to unshare and setns as is
+    unsafe { CloneFlags::from_bits_unchecked(CLONE_NEWTIME) }
+}
+
+/// Offset of a clock as given by `linux.timeOffsets`
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct TimeOffset {
+    #[serde(default)]
+    pub secs: i64,
+    #[serde(default)]
+    pub nanosecs: u32,
+}
+
+/// Time namespace of a container, which is either joined by its path or
+/// created with the offsets of the clocks
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TimeNamespace {
+    pub path: Option<PathBuf>,
+    pub offsets: BTreeMap<String, TimeOffset>,
+}
+
+impl TimeNamespace {
+    /// Removes the time namespace from the namespaces of the raw spec, as it
+    /// cannot be deserialized otherwise, and takes the offsets along with it.
+    /// Offsets are only valid for a new time namespace.
+    pub fn take_from_spec(spec: &mut serde_json::Value) -> Result<Option<Self>> {
+        let mut namespace = None;
+        if let Some(namespaces) = spec
+            .pointer_mut("/linux/namespaces")
+            .and_then(|namespaces| namespaces.as_array_mut())
+        {
+            namespaces.retain(|ns| {
+                if ns.get("type").and_then(|typ| typ.as_str()) != Some("time") {
+                    return true;
+                }
+                namespace = Some(Self {
+                    path: ns
+                        .get("path")
+                        .and_then(|path| path.as_str())
+                        .map(PathBuf::from),
+                    offsets: BTreeMap::new(),
+                });
+                false
+            });
+        }
+
+        let offsets = spec
+            .pointer_mut("/linux")
+            .and_then(|linux| linux.as_object_mut())
+            .and_then(|linux| linux.remove("timeOffsets"));
+        let offsets: BTreeMap<String, TimeOffset> = match offsets {
+            Some(offsets) => {
+                serde_json::from_value(offsets).context("invalid linux.timeOffsets")?
+            }
+            None => BTreeMap::new(),
+        };
+
+        let mut namespace = match namespace {
+            Some(namespace) => namespace,
+            None if offsets.is_empty() => return Ok(None),
+            None => bail!("linux.timeOffsets requires a time namespace"),
+        };
+        if let Some(path) = &namespace.path {
+            if !offsets.is_empty() {
+                bail!(
+                    "linux.timeOffsets can only be applied to a new time namespace, but {:?} is joined",
+                    path
+                );
+            }
+        }
+        for (clock, offset) in &offsets {
+            if !CLOCKS.contains(&clock.as_str()) {
+                bail!(
+                    "time offset of unknown clock {}, expected one of {:?}",
+                    clock,
+                    CLOCKS
+                );
+            }
+            if offset.nanosecs >= NANOS_PER_SEC {
+                bail!(
+                    "nanoseconds of the {} offset must be in range 0..{}, but are {}",
+                    clock,
+                    NANOS_PER_SEC,
+                    offset.nanosecs
+                );
+            }
+        }
+
+        namespace.offsets = offsets;
+        Ok(Some(namespace))
+    }
+
+    /// Enters the time namespace. Like with the pid namespace, only the
+    /// children of the calling process are in a new time namespace, so its
+    /// offsets are written before the container init process is forked.
+    pub fn apply(&self, syscall: &dyn Syscall) -> Result<()> {
+        let flag = clone_flag();
+        match &self.path {
+            Some(path) => {
+                let fd = fcntl::open(path, fcntl::OFlag::empty(), stat::Mode::empty())
+                    .with_context(|| format!("failed to open namespace fd: {:?}", path))?;
+                syscall
+                    .set_ns(fd, flag)
+                    .context("failed to join time namespace")?;
+                unistd::close(fd).context("failed to close namespace fd")?;
+            }
+            None => {
+                syscall
+                    .unshare(flag)
+                    .context("failed to create time namespace")?;
+                if !self.offsets.is_empty() {
+                    fs::write(TIMENS_OFFSETS, self.offsets_content()).with_context(|| {
+                        format!("failed to write time offsets to {}", TIMENS_OFFSETS)
+                    })?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Formats the offsets the way timens_offsets expects them, one line of
+    /// `<clock> <secs> <nanosecs>` per clock
+    fn offsets_content(&self) -> String {
+        self.offsets
+            .iter()
+            .map(|(clock, offset)| format!("{} {} {}\n", clock, offset.secs, offset.nanosecs))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syscall::test::TestHelperSyscall;
+    use serde_json::json;
+
+    #[test]
+    fn test_take_from_spec() -> Result<()> {
+        let mut spec = json!({
+            "linux": {
+                "namespaces": [{"type": "pid"}, {"type": "time"}],
+                "timeOffsets": {
+                    "monotonic": {"secs": -3600},
+                    "boottime": {"secs": 86400, "nanosecs": 500}
+                }
+            }
+        });
+
+        let namespace = TimeNamespace::take_from_spec(&mut spec)?.expect("time namespace");
+        assert_eq!(namespace.path, None);
+        assert_eq!(
+            namespace.offsets_content(),
+            "boottime 86400 500\nmonotonic -3600 0\n"
+        );
+        assert_eq!(
+            spec,
+            json!({"linux": {"namespaces": [{"type": "pid"}]}}),
+            "time namespace is removed from the spec"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_take_from_spec_without_time_namespace() -> Result<()> {
+        let mut spec = json!({"linux": {"namespaces": [{"type": "pid"}]}});
+        assert_eq!(TimeNamespace::take_from_spec(&mut spec)?, None);
+
+        let mut spec = json!({"linux": {"timeOffsets": {"monotonic": {"secs": 1}}}});
+        assert!(TimeNamespace::take_from_spec(&mut spec).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_take_from_spec_invalid_offsets() {
+        let invalid = [
+            json!({"realtime": {"secs": 1}}),
+            json!({"monotonic": {"nanosecs": 1_000_000_000}}),
+            json!({"monotonic": {"nanosecs": -1}}),
+        ];
+        for offsets in invalid {
+            let mut spec = json!({
+                "linux": {"namespaces": [{"type": "time"}], "timeOffsets": offsets}
+            });
+            assert!(
+                TimeNamespace::take_from_spec(&mut spec).is_err(),
+                "{:?}",
+                offsets
+            );
+        }
+
+        let mut spec = json!({
+            "linux": {
+                "namespaces": [{"type": "time", "path": "/proc/1/ns/time"}],
+                "timeOffsets": {"monotonic": {"secs": 1}}
+            }
+        });
+        assert!(TimeNamespace::take_from_spec(&mut spec).is_err());
+    }
+
+    #[test]
+    fn test_apply_new_namespace() -> Result<()> {
+        let syscall = TestHelperSyscall::default();
+        TimeNamespace::default().apply(&syscall)?;
+        assert_eq!(syscall.get_unshare_args(), vec![clone_flag()]);
+        Ok(())
+    }
+}
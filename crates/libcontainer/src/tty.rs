@@ -8,10 +8,14 @@ use std::path::Path;
 use anyhow::Context;
 use anyhow::{bail, Result};
 use nix::errno::Errno;
+use nix::fcntl::{self, OFlag};
+use nix::pty::Winsize;
 use nix::sys::socket;
+use nix::sys::stat::Mode;
 use nix::sys::uio;
 use nix::unistd::close;
 use nix::unistd::dup2;
+use oci_spec::runtime::Box as ConsoleSize;
 
 const STDIN: i32 = 0;
 const STDOUT: i32 = 1;
@@ -26,16 +30,29 @@ pub fn setup_console_socket(
     let linked = container_dir.join(socket_name);
     symlink(console_socket_path, &linked)?;
 
+    // The path of a unix socket is limited to 108 bytes, so the socket is
+    // connected to through a file descriptor of the container directory
+    // instead of the full path, without changing the working directory.
+    let dirfd = fcntl::open(
+        container_dir,
+        OFlag::O_PATH | OFlag::O_DIRECTORY | OFlag::O_CLOEXEC,
+        Mode::empty(),
+    )
+    .with_context(|| format!("failed to open {:?}", container_dir))?;
+    let socket_path = format!("/proc/self/fd/{}/{}", dirfd, socket_name);
+
     let mut csocketfd = socket::socket(
         socket::AddressFamily::Unix,
         socket::SockType::Stream,
         socket::SockFlag::empty(),
         None,
     )?;
-    csocketfd = match socket::connect(
+    let connected = socket::connect(
         csocketfd,
-        &socket::SockAddr::Unix(socket::UnixAddr::new(socket_name)?),
-    ) {
+        &socket::SockAddr::Unix(socket::UnixAddr::new(socket_path.as_str())?),
+    );
+    close(dirfd)?;
+    csocketfd = match connected {
         Err(errno) => {
             if !matches!(errno, Errno::ENOENT) {
                 bail!("failed to open {}", socket_name);
@@ -47,11 +64,38 @@ pub fn setup_console_socket(
     Ok(csocketfd)
 }
 
-pub fn setup_console(console_fd: &RawFd) -> Result<()> {
+/// Creates the pty of the container, sends its master to the receiver of the
+/// console socket and connects the stdio of the calling process to it. The
+/// master is only kept, if the caller forwards the window size itself, in
+/// which case it is returned close-on-exec and has to be closed before the
+/// container process is executed.
+pub fn setup_console(
+    console_fd: &RawFd,
+    console_size: Option<&ConsoleSize>,
+    keep_master: bool,
+) -> Result<Option<RawFd>> {
     // You can also access pty master, but it is better to use the API.
     // ref. https://github.com/containerd/containerd/blob/261c107ffc4ff681bc73988f64e3f60c32233b37/vendor/github.com/containerd/go-runc/console.go#L139-L154
     let openpty_result =
         nix::pty::openpty(None, None).context("could not create pseudo terminal")?;
+
+    // The initial size is taken from the spec. Otherwise, the pty gets the
+    // size of the terminal youki has been called from, if there is one.
+    let winsize = match console_size {
+        Some(size) => Some(Winsize {
+            ws_row: size.height() as u16,
+            ws_col: size.width() as u16,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        }),
+        None => get_winsize(STDIN).ok(),
+    };
+    if let Some(winsize) = winsize {
+        set_winsize(openpty_result.master, &winsize).context("could not set console size")?;
+    }
+
+    // The receiver expects the name of the pty as the payload and the master
+    // as the only fd of a single SCM_RIGHTS message, the same as runc sends.
     let pty_name: &[u8] = b"/dev/ptmx";
     let iov = [uio::IoVec::from_slice(pty_name)];
     let fds = [openpty_result.master];
@@ -64,6 +108,19 @@ pub fn setup_console(console_fd: &RawFd) -> Result<()> {
         None,
     )
     .context("failed to send pty master")?;
+    // the master has been duplicated to the receiver and must not leak into
+    // the container process
+    let master = if keep_master {
+        fcntl::fcntl(
+            openpty_result.master,
+            fcntl::FcntlArg::F_SETFD(fcntl::FdFlag::FD_CLOEXEC),
+        )
+        .context("could not set pty master close-on-exec")?;
+        Some(openpty_result.master)
+    } else {
+        close(openpty_result.master).context("could not close pty master")?;
+        None
+    };
 
     if unsafe { libc::ioctl(openpty_result.slave, libc::TIOCSCTTY) } < 0 {
         log::warn!("could not TIOCSCTTY");
@@ -71,9 +128,40 @@ pub fn setup_console(console_fd: &RawFd) -> Result<()> {
     let slave = openpty_result.slave;
     connect_stdio(&slave, &slave, &slave).context("could not dup tty to stderr")?;
     close(console_fd.as_raw_fd()).context("could not close console socket")?;
+    Ok(master)
+}
+
+/// Returns the window size of the terminal
+fn get_winsize(fd: RawFd) -> Result<Winsize> {
+    let mut winsize = Winsize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    if unsafe { libc::ioctl(fd, libc::TIOCGWINSZ, &mut winsize) } < 0 {
+        bail!("could not get window size: {}", Errno::last());
+    }
+    Ok(winsize)
+}
+
+/// Sets the window size of the terminal
+fn set_winsize(fd: RawFd, winsize: &Winsize) -> Result<()> {
+    if unsafe { libc::ioctl(fd, libc::TIOCSWINSZ, winsize) } < 0 {
+        bail!("could not set window size: {}", Errno::last());
+    }
     Ok(())
 }
 
+/// Propagates the window size of the controlling terminal `source` to the pty
+/// `master` of the container. The kernel sends SIGWINCH to the foreground
+/// process group of the pty, if the size has changed. This is meant to be
+/// called on SIGWINCH by the caller which holds the master, e.g. youki running
+/// a container in the foreground.
+pub fn forward_winsize(source: RawFd, master: RawFd) -> Result<()> {
+    set_winsize(master, &get_winsize(source)?)
+}
+
 fn connect_stdio(stdin: &RawFd, stdout: &RawFd, stderr: &RawFd) -> Result<()> {
     dup2(stdin.as_raw_fd(), STDIN)?;
     dup2(stdout.as_raw_fd(), STDOUT)?;
@@ -87,7 +175,6 @@ fn connect_stdio(stdin: &RawFd, stdout: &RawFd, stderr: &RawFd) -> Result<()> {
 mod tests {
     use super::*;
 
-    use std::env;
     use std::fs::{self, File};
     use std::os::unix::net::UnixListener;
     use std::path::PathBuf;
@@ -103,8 +190,6 @@ mod tests {
         let rundir_path = Path::join(&testdir, "run");
         let _ = fs::create_dir(&rundir_path)?;
         let socket_path = Path::new(&rundir_path).join("socket");
-        let _ = File::create(&socket_path);
-        env::set_current_dir(&testdir)?;
         Ok((testdir, rundir_path, socket_path))
     }
 
@@ -113,8 +198,8 @@ mod tests {
     fn test_setup_console_socket() {
         let init = setup("test_setup_console_socket");
         assert!(init.is_ok());
-        let (testdir, rundir_path, socket_path) = init.unwrap();
-        let lis = UnixListener::bind(Path::join(&testdir, "console-socket"));
+        let (_testdir, rundir_path, socket_path) = init.unwrap();
+        let lis = UnixListener::bind(&socket_path);
         assert!(lis.is_ok());
         let fd = setup_console_socket(&rundir_path, &socket_path, CONSOLE_SOCKET);
         assert!(fd.is_ok());
@@ -137,8 +222,8 @@ mod tests {
     fn test_setup_console_socket_invalid() {
         let init = setup("test_setup_console_socket_invalid");
         assert!(init.is_ok());
-        let (testdir, rundir_path, socket_path) = init.unwrap();
-        let _socket = File::create(Path::join(&testdir, "console-socket"));
+        let (_testdir, rundir_path, socket_path) = init.unwrap();
+        let _socket = File::create(&socket_path);
         assert!(_socket.is_ok());
         let fd = setup_console_socket(&rundir_path, &socket_path, CONSOLE_SOCKET);
         assert!(fd.is_err());
@@ -149,11 +234,66 @@ mod tests {
     fn test_setup_console() {
         let init = setup("test_setup_console");
         assert!(init.is_ok());
-        let (testdir, rundir_path, socket_path) = init.unwrap();
-        let lis = UnixListener::bind(Path::join(&testdir, "console-socket"));
+        let (_testdir, rundir_path, socket_path) = init.unwrap();
+        let lis = UnixListener::bind(&socket_path);
         assert!(lis.is_ok());
         let fd = setup_console_socket(&rundir_path, &socket_path, CONSOLE_SOCKET);
-        let status = setup_console(&fd.unwrap());
-        assert!(status.is_ok());
+        let status = setup_console(&fd.unwrap(), None, false);
+        assert!(matches!(status, Ok(None)));
+    }
+
+    #[test]
+    #[serial]
+    fn test_setup_console_keep_master() -> Result<()> {
+        let (_testdir, rundir_path, socket_path) = setup("test_setup_console_keep_master")?;
+        let _lis = UnixListener::bind(&socket_path)?;
+        let fd = setup_console_socket(&rundir_path, &socket_path, CONSOLE_SOCKET)?;
+        let master = setup_console(&fd, None, true)?.expect("pty master is kept");
+        let flags =
+            fcntl::FdFlag::from_bits_truncate(fcntl::fcntl(master, fcntl::FcntlArg::F_GETFD)?);
+        assert!(flags.contains(fcntl::FdFlag::FD_CLOEXEC));
+        close(master)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_winsize() -> Result<()> {
+        let pty = nix::pty::openpty(None, None)?;
+        let winsize = Winsize {
+            ws_row: 24,
+            ws_col: 80,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        set_winsize(pty.master, &winsize)?;
+        let got = get_winsize(pty.slave)?;
+        assert_eq!((got.ws_row, got.ws_col), (24, 80));
+
+        for fd in [pty.master, pty.slave] {
+            close(fd)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_forward_winsize() -> Result<()> {
+        let source = nix::pty::openpty(None, None)?;
+        let target = nix::pty::openpty(None, None)?;
+        let winsize = Winsize {
+            ws_row: 50,
+            ws_col: 120,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        set_winsize(source.master, &winsize)?;
+
+        forward_winsize(source.slave, target.master)?;
+        let got = get_winsize(target.slave)?;
+        assert_eq!((got.ws_row, got.ws_col), (50, 120));
+
+        for fd in [source.master, source.slave, target.master, target.slave] {
+            close(fd)?;
+        }
+        Ok(())
     }
 }
@@ -2,6 +2,7 @@
 
 use anyhow::Context;
 use anyhow::{bail, Result};
+use libcgroups::common::CgroupManager;
 use nix::sys::stat::Mode;
 use nix::sys::statfs;
 use nix::unistd;
@@ -9,13 +10,16 @@ use nix::unistd::{Uid, User};
 use std::collections::HashMap;
 use std::ffi::CString;
 use std::fs::{self, DirBuilder, File};
-use std::io::ErrorKind;
+use std::io::{ErrorKind, Write};
 use std::ops::Deref;
 use std::os::linux::fs::MetadataExt;
-use std::os::unix::fs::DirBuilderExt;
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
 use std::os::unix::prelude::{AsRawFd, OsStrExt};
 use std::path::{Path, PathBuf};
 
+/// PATH of a process, whose environment does not contain one
+pub const DEFAULT_PATH: &str = "/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin";
+
 pub trait PathBufExt {
     fn as_relative(&self) -> Result<&Path>;
     fn join_safely<P: AsRef<Path>>(&self, p: P) -> Result<PathBuf>;
@@ -57,6 +61,79 @@ pub fn parse_env(envs: &[String]) -> HashMap<String, String> {
         .collect()
 }
 
+/// Builds the environment of the container process from the variables of the
+/// spec. If a key is given more than once, the last value wins, while the
+/// position of its first occurrence is kept. Entries without `=` are skipped
+/// with a warning. An empty or missing PATH is set to [DEFAULT_PATH], so bare
+/// commands can be resolved.
+pub fn normalize_env(envs: &[String]) -> Vec<(String, String)> {
+    let mut normalized: Vec<(String, String)> = Vec::with_capacity(envs.len() + 1);
+    for env in envs {
+        let (key, value) = match env.split_once('=') {
+            Some(entry) => entry,
+            None => {
+                log::warn!("skipping environment variable {:?} without '='", env);
+                continue;
+            }
+        };
+        match normalized.iter_mut().find(|(k, _)| k == key) {
+            Some(entry) => entry.1 = value.to_owned(),
+            None => normalized.push((key.to_owned(), value.to_owned())),
+        }
+    }
+
+    match normalized.iter_mut().find(|(k, _)| k == "PATH") {
+        Some((_, path)) if path.is_empty() => *path = DEFAULT_PATH.to_owned(),
+        Some(_) => {}
+        None => normalized.push(("PATH".to_owned(), DEFAULT_PATH.to_owned())),
+    }
+    normalized
+}
+
+/// Parses a file of environment variables with one `KEY=VALUE` per line.
+/// Blank lines and comments starting with `#` are skipped.
+pub fn parse_env_file<P: AsRef<Path>>(path: P) -> Result<Vec<(String, String)>> {
+    let path = path.as_ref();
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("failed to read environment file {:?}", path))?;
+    parse_env_content(&content).with_context(|| format!("invalid environment file {:?}", path))
+}
+
+fn parse_env_content(content: &str) -> Result<Vec<(String, String)>> {
+    let mut envs = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        // only the first = separates the key, the value may contain more
+        match line.split_once('=') {
+            Some((key, value)) if !key.is_empty() && !key.contains(char::is_whitespace) => {
+                envs.push((key.to_owned(), value.to_owned()))
+            }
+            _ => bail!("line {} is not of the form KEY=VALUE: {:?}", i + 1, line),
+        }
+    }
+
+    Ok(envs)
+}
+
+/// Sets the environment variables in a list of `KEY=VALUE` entries, replacing
+/// the values of existing keys and appending the others.
+pub fn merge_env(envs: &mut Vec<String>, overrides: &HashMap<String, String>) {
+    let mut overrides: Vec<(&String, &String)> = overrides.iter().collect();
+    overrides.sort();
+    for (key, value) in overrides {
+        let entry = format!("{}={}", key, value);
+        let prefix = format!("{}=", key);
+        match envs.iter_mut().find(|e| e.starts_with(&prefix)) {
+            Some(env) => *env = entry,
+            None => envs.push(entry),
+        }
+    }
+}
+
 /// Get a nix::unistd::User via UID. Potential errors will be ignored.
 pub fn get_unix_user(uid: Uid) -> Option<User> {
     match User::from_uid(uid) {
@@ -93,18 +170,65 @@ pub fn get_cgroup_path(
     match cgroups_path {
         Some(cpath) => cpath.clone(),
         None => match rootless {
-            false => PathBuf::from(container_id),
+            false => Path::new("/").join(container_id),
             true => PathBuf::from(format!(":youki:{}", container_id)),
         },
     }
 }
 
+/// Creates the cgroup manager of a container, which is being created. Unless
+/// it is managed by systemd, the cgroup of a rootless container is in the
+/// cgroup subtree delegated to the user.
+pub fn create_cgroup_manager(
+    cgroups_path: &Path,
+    use_systemd: bool,
+    rootless: bool,
+    container_id: &str,
+) -> Result<Box<dyn CgroupManager>> {
+    if rootless {
+        return libcgroups::common::create_rootless_cgroup_manager(
+            cgroups_path,
+            use_systemd,
+            container_id,
+        );
+    }
+
+    libcgroups::common::create_cgroup_manager(cgroups_path, use_systemd, container_id)
+}
+
 pub fn write_file<P: AsRef<Path>, C: AsRef<[u8]>>(path: P, contents: C) -> Result<()> {
     let path = path.as_ref();
     fs::write(path, contents).with_context(|| format!("failed to write to {:?}", path))?;
     Ok(())
 }
 
+/// Writes the pid to the pid file atomically. The pid is written to a
+/// temporary file in the same directory first, which is renamed to the pid
+/// file afterwards, so readers never see a partially written pid.
+pub fn write_pid_file<P: AsRef<Path>>(path: P, pid: i32) -> Result<()> {
+    let path = path.as_ref();
+    let file_name = path
+        .file_name()
+        .with_context(|| format!("invalid pid file {:?}", path))?;
+    let tmp_path = path.with_file_name(format!(
+        ".{}.{}.tmp",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+
+    let result = (|| -> Result<()> {
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(pid.to_string().as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result.with_context(|| format!("failed to write pid file {:?}", path))
+}
+
 pub fn create_dir_all<P: AsRef<Path>>(path: P) -> Result<()> {
     let path = path.as_ref();
     fs::create_dir_all(path).with_context(|| format!("failed to create directory {:?}", path))
@@ -155,6 +279,19 @@ pub fn create_dir_all_with_mode<P: AsRef<Path>>(path: P, owner: u32, mode: Mode)
     }
 }
 
+/// Makes the calling process the reaper of its orphaned descendants. The init
+/// process is forked by the intermediate process, so this is required to wait
+/// for the init process after the intermediate process has exited.
+pub fn set_child_subreaper() -> Result<()> {
+    if let Err(errno) = prctl::set_child_subreaper(true) {
+        bail!(
+            "failed to set child subreaper: {}",
+            nix::errno::Errno::from_i32(errno)
+        );
+    }
+    Ok(())
+}
+
 // Make sure a given path is on procfs. This is to avoid the security risk that
 // /proc path is mounted over. Ref: CVE-2019-16884
 pub fn ensure_procfs(path: &Path) -> Result<()> {
@@ -186,41 +323,42 @@ pub fn secure_join<P: Into<PathBuf>>(rootfs: P, unsafe_path: P) -> Result<PathBu
             Some(part) => PathBuf::from(part),
         };
 
-        if !part_path.is_absolute() {
-            if part_path.starts_with("..") {
-                clean_path.pop();
-            } else {
-                // check if symlink then dereference
-                let curr_path = PathBuf::from(&rootfs).join(&clean_path).join(&part_path);
-                let metadata = match curr_path.symlink_metadata() {
-                    Ok(metadata) => Some(metadata),
-                    Err(error) => match error.kind() {
-                        // if file does not exists, treat it as normal path
-                        ErrorKind::NotFound => None,
-                        other_error => {
-                            bail!(
-                                "unable to obtain symlink metadata for file {:?}: {:?}",
-                                curr_path,
-                                other_error
-                            );
-                        }
-                    },
-                };
-
-                if let Some(metadata) = metadata {
-                    if metadata.file_type().is_symlink() {
-                        let link_path = fs::read_link(curr_path)?;
-                        path = link_path.join(part.as_path());
-                        part = path.iter();
-
-                        // increase after dereference symlink
-                        i += 1;
-                        continue;
+        if part_path.is_absolute() {
+            // an absolute symlink is resolved relative to the rootfs
+            clean_path.clear();
+        } else if part_path.starts_with("..") {
+            clean_path.pop();
+        } else {
+            // check if symlink then dereference
+            let curr_path = PathBuf::from(&rootfs).join(&clean_path).join(&part_path);
+            let metadata = match curr_path.symlink_metadata() {
+                Ok(metadata) => Some(metadata),
+                Err(error) => match error.kind() {
+                    // if file does not exists, treat it as normal path
+                    ErrorKind::NotFound => None,
+                    other_error => {
+                        bail!(
+                            "unable to obtain symlink metadata for file {:?}: {:?}",
+                            curr_path,
+                            other_error
+                        );
                     }
+                },
+            };
+
+            if let Some(metadata) = metadata {
+                if metadata.file_type().is_symlink() {
+                    let link_path = fs::read_link(curr_path)?;
+                    path = link_path.join(part.as_path());
+                    part = path.iter();
+
+                    // increase after dereference symlink
+                    i += 1;
+                    continue;
                 }
-
-                clean_path.push(&part_path);
             }
+
+            clean_path.push(&part_path);
         }
     }
 
@@ -228,6 +366,47 @@ pub fn secure_join<P: Into<PathBuf>>(rootfs: P, unsafe_path: P) -> Result<PathBu
     Ok(rootfs)
 }
 
+/// Opens a path, which has been joined with [secure_join] and created
+/// afterwards, with O_PATH and ensures that the opened location is inside of
+/// the rootfs. A symlink, which has been placed in the path meanwhile, e.g. by
+/// another container sharing a volume, would otherwise redirect the mount to
+/// the host. Mounting onto the [proc_fd_path] of the file uses the verified
+/// location, even if the path is swapped out again afterwards.
+pub fn open_in_rootfs(rootfs: &Path, path: &Path) -> Result<File> {
+    let rootfs = fs::canonicalize(rootfs)
+        .with_context(|| format!("failed to canonicalize rootfs {:?}", rootfs))?;
+    let file = fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_PATH | libc::O_NOFOLLOW | libc::O_CLOEXEC)
+        .open(path)
+        .with_context(|| format!("failed to open {:?}", path))?;
+    // with O_PATH, O_NOFOLLOW opens a symlink itself instead of failing
+    let metadata = file
+        .metadata()
+        .with_context(|| format!("failed to stat {:?}", path))?;
+    if metadata.file_type().is_symlink() {
+        bail!("{:?} has been replaced by a symlink", path);
+    }
+    let resolved = fs::read_link(proc_fd_path(&file))
+        .with_context(|| format!("failed to resolve the opened {:?}", path))?;
+    if !resolved.starts_with(&rootfs) {
+        bail!(
+            "{:?} resolves to {:?}, which is outside of the rootfs {:?}",
+            path,
+            resolved,
+            rootfs
+        );
+    }
+
+    Ok(file)
+}
+
+/// Path of the file descriptor in procfs, which refers to the opened file
+/// itself and not to the path it was opened with
+pub fn proc_fd_path(file: &File) -> PathBuf {
+    PathBuf::from(format!("/proc/self/fd/{}", file.as_raw_fd()))
+}
+
 pub struct TempDir {
     path: Option<PathBuf>,
 }
@@ -359,12 +538,41 @@ mod tests {
         assert!(dir.is_none());
     }
 
+    #[test]
+    fn test_write_pid_file() -> Result<()> {
+        let tmp = create_temp_dir("test_write_pid_file")?;
+        let pid_file = tmp.join("container.pid");
+        write_pid_file(&pid_file, 1)?;
+
+        let reader = {
+            let pid_file = pid_file.clone();
+            std::thread::spawn(move || -> Result<()> {
+                for _ in 0..1000 {
+                    let content = fs::read_to_string(&pid_file)?;
+                    let pid: i32 = content
+                        .parse()
+                        .with_context(|| format!("read partial pid {:?}", content))?;
+                    assert!(pid >= 1);
+                }
+                Ok(())
+            })
+        };
+        for pid in 1..1000 {
+            write_pid_file(&pid_file, pid * 1000)?;
+        }
+        reader.join().unwrap()?;
+
+        assert_eq!(fs::read_to_string(&pid_file)?, "999000");
+        assert_eq!(fs::read_dir(tmp.path())?.count(), 1);
+        Ok(())
+    }
+
     #[test]
     fn test_get_cgroup_path() {
         let cid = "sample_container_id";
         assert_eq!(
             get_cgroup_path(&None, cid, false),
-            PathBuf::from("sample_container_id")
+            PathBuf::from("/sample_container_id")
         );
         assert_eq!(
             get_cgroup_path(&Some(PathBuf::from("/youki")), cid, false),
@@ -386,6 +594,75 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_normalize_env() -> Result<()> {
+        let envs = vec!["FOO=1".to_owned(), "BAR=a=b".to_owned(), "FOO=2".to_owned()];
+        assert_eq!(
+            normalize_env(&envs),
+            vec![
+                ("FOO".to_owned(), "2".to_owned()),
+                ("BAR".to_owned(), "a=b".to_owned()),
+                ("PATH".to_owned(), DEFAULT_PATH.to_owned()),
+            ]
+        );
+        assert_eq!(
+            normalize_env(&["PATH=".to_owned()]),
+            vec![("PATH".to_owned(), DEFAULT_PATH.to_owned())]
+        );
+        assert_eq!(
+            normalize_env(&["PATH=/bin".to_owned(), "PATH=/custom".to_owned()]),
+            vec![("PATH".to_owned(), "/custom".to_owned())]
+        );
+        assert_eq!(
+            normalize_env(&["INVALID".to_owned(), "PATH=/bin".to_owned()]),
+            vec![("PATH".to_owned(), "/bin".to_owned())]
+        );
+
+        // the child sees the last value and resolves bare commands with the
+        // default PATH
+        let output = std::process::Command::new("sh")
+            .args(["-c", "echo $FOO"])
+            .env_clear()
+            .envs(normalize_env(&envs))
+            .output()?;
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "2\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_env_content() -> Result<()> {
+        let content = "# comment\n\nKEY=value\n  OTHER=a=b  \nEMPTY=\n";
+        assert_eq!(
+            parse_env_content(content)?,
+            vec![
+                ("KEY".to_owned(), "value".to_owned()),
+                ("OTHER".to_owned(), "a=b".to_owned()),
+                ("EMPTY".to_owned(), "".to_owned()),
+            ]
+        );
+
+        for content in ["KEY=value\nmalformed", "=value", "MY KEY=value"] {
+            let err = parse_env_content(content).expect_err("malformed line");
+            assert!(err.to_string().starts_with("line "), "{}", err);
+        }
+        let err = parse_env_content("KEY=value\nmalformed").unwrap_err();
+        assert!(err.to_string().starts_with("line 2 "));
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_env() {
+        let mut envs = vec!["PATH=/bin".to_owned(), "TERM=xterm".to_owned()];
+        let overrides = HashMap::from([
+            ("TERM".to_owned(), "dumb".to_owned()),
+            ("HOME".to_owned(), "/root".to_owned()),
+        ]);
+        merge_env(&mut envs, &overrides);
+        assert_eq!(envs, vec!["PATH=/bin", "TERM=dumb", "HOME=/root"]);
+    }
+
     #[test]
     fn test_secure_join() {
         assert_eq!(
@@ -456,5 +733,34 @@ mod tests {
             secure_join(test_root_dir, PathBuf::from("absolutelink").as_path()).unwrap(),
             PathBuf::from(&test_root_dir).join("somepath/passwd")
         );
+
+        // an absolute symlink in a subdirectory is resolved from the rootfs
+        fs::create_dir(test_root_dir.join("dir")).unwrap();
+        symlink("/var", test_root_dir.join("dir/absolute")).unwrap();
+        assert_eq!(
+            secure_join(test_root_dir, PathBuf::from("dir/absolute/log").as_path()).unwrap(),
+            PathBuf::from(&test_root_dir).join("var/log")
+        );
+    }
+
+    #[test]
+    fn test_open_in_rootfs() -> Result<()> {
+        use std::os::unix::fs::symlink;
+
+        let tmp = create_temp_dir("test_open_in_rootfs")?;
+        let rootfs = tmp.join("rootfs");
+        fs::create_dir_all(rootfs.join("mnt"))?;
+        let outside = tmp.join("outside");
+        fs::create_dir(&outside)?;
+        symlink(&outside, rootfs.join("escape"))?;
+
+        let file = open_in_rootfs(&rootfs, &rootfs.join("mnt"))?;
+        assert_eq!(
+            fs::read_link(proc_fd_path(&file))?,
+            fs::canonicalize(rootfs.join("mnt"))?
+        );
+        assert!(open_in_rootfs(&rootfs, &rootfs.join("escape")).is_err());
+        assert!(open_in_rootfs(&rootfs, &rootfs.join("escape/..")).is_err());
+        Ok(())
     }
 }
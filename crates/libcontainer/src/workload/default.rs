@@ -1,10 +1,17 @@
-use std::ffi::CString;
+use std::{
+    env,
+    ffi::CString,
+    fs,
+    os::unix::{ffi::OsStrExt, fs::PermissionsExt},
+    path::{Path, PathBuf},
+};
 
 use anyhow::{bail, Context, Result};
 use nix::unistd;
 use oci_spec::runtime::Spec;
 
 use super::{Executor, EMPTY};
+use crate::utils::DEFAULT_PATH;
 
 const EXECUTOR_NAME: &str = "default";
 
@@ -23,17 +30,22 @@ impl Executor for DefaultExecutor {
             bail!("at least one process arg must be specified")
         }
 
+        // The environment of the process has been set up from the spec
+        // already, so this resolves against the PATH of the container within
+        // its rootfs.
         let executable = args[0].as_str();
-        let p = CString::new(executable.as_bytes())
-            .with_context(|| format!("failed to convert path {:?} to cstring", executable))?;
+        let path = env::var("PATH").unwrap_or_else(|_| DEFAULT_PATH.to_owned());
+        let executable_path = find_executable(executable, &path)?;
+        let p = CString::new(executable_path.as_os_str().as_bytes())
+            .with_context(|| format!("failed to convert path {:?} to cstring", executable_path))?;
         let a: Vec<CString> = args
             .iter()
             .map(|s| CString::new(s.as_bytes()).unwrap_or_default())
             .collect();
-        unistd::execvp(&p, &a)?;
+        unistd::execv(&p, &a).with_context(|| format!("failed to execute {:?}", executable))?;
 
         // After do_exec is called, the process is replaced with the container
-        // payload through execvp, so it should never reach here.
+        // payload through execv, so it should never reach here.
         unreachable!();
     }
 
@@ -45,3 +57,56 @@ impl Executor for DefaultExecutor {
         EXECUTOR_NAME
     }
 }
+
+/// Resolves the executable like execvp does. Paths are executed as they are,
+/// while a bare command is looked up in the directories of PATH.
+fn find_executable(executable: &str, path: &str) -> Result<PathBuf> {
+    if executable.contains('/') {
+        let executable_path = PathBuf::from(executable);
+        if !executable_path.exists() {
+            bail!("executable {:?} does not exist", executable);
+        }
+        return Ok(executable_path);
+    }
+
+    path.split(':')
+        .filter(|dir| !dir.is_empty())
+        .map(|dir| Path::new(dir).join(executable))
+        .find(|candidate| is_executable(candidate))
+        .with_context(|| format!("executable {:?} not found in $PATH", executable))
+}
+
+fn is_executable(path: &Path) -> bool {
+    fs::metadata(path)
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::create_temp_dir;
+
+    #[test]
+    fn test_find_executable() -> Result<()> {
+        let tmp = create_temp_dir("test_find_executable")?;
+        let bin = tmp.join("bin");
+        fs::create_dir(&bin)?;
+        let executable = bin.join("payload");
+        fs::write(&executable, "#!/bin/sh\n")?;
+        fs::set_permissions(&executable, fs::Permissions::from_mode(0o755))?;
+        let not_executable = bin.join("data");
+        fs::write(&not_executable, "")?;
+
+        let path = format!("/nonexistent:{}", bin.display());
+        assert_eq!(find_executable("payload", &path)?, executable);
+        assert!(find_executable("data", &path).is_err());
+        assert!(find_executable("payload", "/nonexistent").is_err());
+
+        // paths are not looked up
+        let absolute = executable.to_string_lossy();
+        assert_eq!(find_executable(&absolute, "")?, executable);
+        assert!(find_executable("/nonexistent/payload", &path).is_err());
+        Ok(())
+    }
+}
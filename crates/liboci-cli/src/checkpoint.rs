@@ -6,6 +6,9 @@ use std::path::PathBuf;
 pub struct Checkpoint {
     #[clap(forbid_empty_values = true, required = true)]
     pub container_id: String,
+    /// Path to the criu binary
+    #[clap(long)]
+    pub criu: Option<PathBuf>,
     /// Allow external unix sockets
     #[clap(long)]
     pub ext_unix_sk: bool,
@@ -18,6 +21,12 @@ pub struct Checkpoint {
     /// Leave the process running after checkpointing
     #[clap(long)]
     pub leave_running: bool,
+    /// Path for previous criu image files in pre-dump, relative to the image path
+    #[clap(long)]
+    pub parent_path: Option<PathBuf>,
+    /// Dump only the changed memory and leave the process running, implies --leave-running
+    #[clap(long)]
+    pub pre_dump: bool,
     /// Allow shell jobs
     #[clap(long)]
     pub shell_job: bool,
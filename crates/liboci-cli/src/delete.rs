@@ -8,4 +8,9 @@ pub struct Delete {
     /// forces deletion of the container if it is still running (using SIGKILL)
     #[clap(short, long)]
     pub force: bool,
+    /// keeps the given namespaces (net, ipc, uts, mnt, cgroup, user) of a
+    /// forcefully deleted container bind mounted below its state directory,
+    /// they are released by the next delete
+    #[clap(long, use_delimiter = true)]
+    pub keep_namespaces: Vec<String>,
 }
@@ -20,15 +20,25 @@ pub struct Exec {
     /// Environment variables that should be set in the container
     #[clap(short, long, parse(try_from_str = parse_key_val), number_of_values = 1)]
     pub env: Vec<(String, String)>,
+    /// File with environment variables, one KEY=VALUE per line, which are overridden by --env
+    #[clap(long)]
+    pub env_file: Option<PathBuf>,
+    /// User, and optionally group, the process is executed as, in the form of
+    /// user[:group], where both can be a name of the container or a numeric id
+    #[clap(short, long)]
+    pub user: Option<String>,
     /// Prevent the process from gaining additional privileges
     #[clap(long)]
     pub no_new_privs: bool,
-    /// Path to process.json
-    #[clap(short, long)]
+    /// Path to process.json, which describes the complete process instead of the command
+    #[clap(short, long, conflicts_with = "command")]
     pub process: Option<PathBuf>,
     /// Detach from the container process
     #[clap(short, long)]
     pub detach: bool,
+    /// Sub-cgroup of the container, relative to its cgroup, the process is placed in
+    #[clap(long)]
+    pub cgroup: Option<PathBuf>,
     /// Identifier of the container
     #[clap(forbid_empty_values = true, required = true)]
     pub container_id: String,
@@ -6,6 +6,9 @@ pub struct Ps {
     /// format to display processes: table or json (default: "table")
     #[clap(short, long, default_value = "table")]
     pub format: String,
+    /// Only display the pids of the processes, one per line
+    #[clap(short, long)]
+    pub quiet: bool,
     #[clap(forbid_empty_values = true, required = true)]
     pub container_id: String,
     /// options will be passed to the ps utility
@@ -0,0 +1,39 @@
+use clap::Parser;
+use std::path::PathBuf;
+
+/// Restore a container from a previous checkpoint
+#[derive(Parser, Debug)]
+pub struct Restore {
+    #[clap(forbid_empty_values = true, required = true)]
+    pub container_id: String,
+    /// Path to the criu binary
+    #[clap(long)]
+    pub criu: Option<PathBuf>,
+    /// path to the bundle directory, containing config.json and root filesystem
+    #[clap(short, long, default_value = ".")]
+    pub bundle: PathBuf,
+    /// Detach from the container process after it has been restored
+    #[clap(short, long)]
+    pub detach: bool,
+    /// Allow external unix sockets
+    #[clap(long)]
+    pub ext_unix_sk: bool,
+    /// Allow file locks
+    #[clap(long)]
+    pub file_locks: bool,
+    /// Path to the criu image files to restore from
+    #[clap(long, default_value = "checkpoint")]
+    pub image_path: PathBuf,
+    /// File to write pid of the restored container
+    #[clap(long)]
+    pub pid_file: Option<PathBuf>,
+    /// Allow shell jobs
+    #[clap(long)]
+    pub shell_job: bool,
+    /// Allow open tcp connections
+    #[clap(long)]
+    pub tcp_established: bool,
+    /// Path for saving work files and logs
+    #[clap(long)]
+    pub work_path: Option<PathBuf>,
+}
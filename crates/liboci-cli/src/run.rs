@@ -11,12 +11,27 @@ pub struct Run {
     /// path to the bundle directory, containing config.json and root filesystem
     #[clap(short, long, default_value = ".")]
     pub bundle: PathBuf,
+    /// path to the runtime spec to use instead of the config.json of the bundle, '-' reads it from stdin
+    #[clap(long)]
+    pub config: Option<PathBuf>,
     /// Unix socket (file) path , which will receive file descriptor of the writing end of the pseudoterminal
     #[clap(short, long)]
     pub console_socket: Option<PathBuf>,
+    /// Detach from the container process, instead of waiting for it to exit
+    #[clap(short, long)]
+    pub detach: bool,
+    /// File with environment variables of the container process, one KEY=VALUE per line
+    #[clap(long)]
+    pub env_file: Option<PathBuf>,
+    /// Do not use pivot_root to jail the process inside the rootfs, but move the rootfs to / and chroot into it
+    #[clap(long)]
+    pub no_pivot: bool,
     /// Pass N additional file descriptors to the container (stdio + $LISTEN_FDS + N in total)
     #[clap(long, default_value = "0")]
     pub preserve_fds: i32,
+    /// Allow creating the container in a cgroup, which is used by another container or contains processes
+    #[clap(long)]
+    pub allow_existing_cgroup: bool,
     /// name of the container instance to be started
     #[clap(forbid_empty_values = true, required = true)]
     pub container_id: String,
@@ -15,4 +15,28 @@ pub struct Update {
     /// Set the maximum number of processes allowed in the container
     #[clap(long)]
     pub pids_limit: Option<i64>,
+
+    /// Set the relative cpu share of the container, converted to cpu.weight on cgroup v2
+    #[clap(long)]
+    pub cpu_shares: Option<u64>,
+
+    /// Set the cpu CFS period to be used for hardcapping (in usecs)
+    #[clap(long)]
+    pub cpu_period: Option<u64>,
+
+    /// Set the cpu CFS hardcap limit (in usecs). Allowed cpu time in a given period
+    #[clap(long, allow_hyphen_values = true)]
+    pub cpu_quota: Option<i64>,
+
+    /// Set the memory limit (in bytes), -1 for unlimited
+    #[clap(long, allow_hyphen_values = true)]
+    pub memory: Option<i64>,
+
+    /// Set the total memory limit, memory + swap (in bytes), -1 for unlimited
+    #[clap(long, allow_hyphen_values = true)]
+    pub memory_swap: Option<i64>,
+
+    /// Set the block IO weight, between 10 and 1000
+    #[clap(long)]
+    pub blkio_weight: Option<u16>,
 }
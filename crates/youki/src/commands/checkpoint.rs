@@ -1,14 +1,14 @@
 //! Contains functionality of pause container command
-use crate::commands::load_container;
 use std::path::PathBuf;
 
 use anyhow::{Context, Result};
 
+use libcontainer::container::Container;
 use liboci_cli::Checkpoint;
 
 pub fn checkpoint(args: Checkpoint, root_path: PathBuf) -> Result<()> {
     log::debug!("start checkpointing container {}", args.container_id);
-    let mut container = load_container(root_path, &args.container_id)?;
+    let mut container = Container::load_by_id(root_path, &args.container_id)?;
     let opts = libcontainer::container::CheckpointOptions {
         ext_unix_sk: args.ext_unix_sk,
         file_locks: args.file_locks,
@@ -17,6 +17,9 @@ pub fn checkpoint(args: Checkpoint, root_path: PathBuf) -> Result<()> {
         shell_job: args.shell_job,
         tcp_established: args.tcp_established,
         work_path: args.work_path,
+        criu_path: args.criu,
+        pre_dump: args.pre_dump,
+        parent_path: args.parent_path,
     };
     container
         .checkpoint(&opts)
@@ -1,8 +1,10 @@
 //! Handles the creation of a new container
-use anyhow::Result;
+use anyhow::{bail, Result};
 use std::path::PathBuf;
 
 use libcontainer::{container::builder::ContainerBuilder, syscall::syscall::create_syscall};
+
+use crate::commands::{load_env_file, notify_socket, spec_path};
 use liboci_cli::Create;
 
 // One thing to note is that in the end, container is just another process in Linux
@@ -11,15 +13,45 @@ use liboci_cli::Create;
 // it is running, it is just another process, and has attributes such as pid, file descriptors, etc.
 // associated with it like any other process.
 pub fn create(args: Create, root_path: PathBuf, systemd_cgroup: bool) -> Result<()> {
+    let env = load_env_file(args.env_file.as_ref())?;
     let syscall = create_syscall();
+    if args.dry_run {
+        let problems = ContainerBuilder::new(args.container_id.clone(), syscall.as_ref())
+            .with_root_path(root_path)?
+            .as_init(&args.bundle)
+            .with_spec_path(spec_path(args.config.as_ref()))
+            .with_systemd_cgroup(systemd_cgroup)
+            .with_no_pivot(args.no_pivot)
+            .with_env(env)
+            .with_allow_existing_cgroup(args.allow_existing_cgroup)
+            .validate();
+        for problem in &problems {
+            eprintln!("{:?}", problem);
+        }
+        if !problems.is_empty() {
+            bail!(
+                "bundle {:?} is invalid, found {} problem(s)",
+                args.bundle,
+                problems.len()
+            );
+        }
+
+        return Ok(());
+    }
+
     ContainerBuilder::new(args.container_id.clone(), syscall.as_ref())
         .with_pid_file(args.pid_file.as_ref())?
         .with_console_socket(args.console_socket.as_ref())
         .with_root_path(root_path)?
         .with_preserved_fds(args.preserve_fds)
         .as_init(&args.bundle)
-        .with_systemd(systemd_cgroup)
-        .build()?;
+        .with_spec_path(spec_path(args.config.as_ref()))
+        .with_systemd_cgroup(systemd_cgroup)
+        .with_no_pivot(args.no_pivot)
+        .with_env(env)
+        .with_notify_socket(notify_socket())
+        .with_allow_existing_cgroup(args.allow_existing_cgroup)
+        .create()?;
 
     Ok(())
 }
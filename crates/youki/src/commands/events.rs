@@ -4,10 +4,10 @@ use anyhow::{Context, Result};
 
 use liboci_cli::Events;
 
-use crate::commands::load_container;
+use libcontainer::container::Container;
 
 pub fn events(args: Events, root_path: PathBuf) -> Result<()> {
-    let mut container = load_container(root_path, &args.container_id)?;
+    let mut container = Container::load_by_id(root_path, &args.container_id)?;
     container
         .events(args.interval, args.stats)
         .with_context(|| format!("failed to get events from container {}", args.container_id))
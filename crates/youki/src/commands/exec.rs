@@ -1,21 +1,75 @@
-use anyhow::Result;
-use std::path::PathBuf;
+use anyhow::{Context, Result};
+use std::{fs, path::PathBuf};
 
-use libcontainer::{container::builder::ContainerBuilder, syscall::syscall::create_syscall};
+use libcontainer::{
+    container::{Container, ExecOptions},
+    utils,
+};
 use liboci_cli::Exec;
+use oci_spec::runtime::{Process, ProcessBuilder};
+
+use crate::commands::{load_env_file, run};
 
 pub fn exec(args: Exec, root_path: PathBuf) -> Result<()> {
-    let syscall = create_syscall();
-    ContainerBuilder::new(args.container_id.clone(), syscall.as_ref())
-        .with_root_path(root_path)?
-        .with_console_socket(args.console_socket.as_ref())
-        .with_pid_file(args.pid_file.as_ref())?
-        .as_tenant()
-        .with_cwd(args.cwd.as_ref())
-        .with_env(args.env.clone().into_iter().collect())
-        .with_process(args.process.as_ref())
-        .with_no_new_privs(args.no_new_privs)
-        .with_process(args.process.as_ref())
-        .with_container_args(args.command.clone())
-        .build()
+    let container = Container::load_by_id(root_path, &args.container_id)?;
+    let process = build_process(&args, &container)?;
+
+    // A sub-cgroup is removed once the process has exited, so youki waits for
+    // it in the foreground. The process is not its own child, but the one of
+    // the intermediate process.
+    let wait = args.cgroup.is_some() && !args.detach;
+    let opts = ExecOptions {
+        console_socket: args.console_socket.clone(),
+        pid_file: args.pid_file.clone(),
+        sub_cgroup: args.cgroup.clone(),
+        subreaper: wait,
+        ..Default::default()
+    };
+    let exec = container.exec(process, opts)?;
+
+    let sub_cgroup = match &args.cgroup {
+        Some(sub_cgroup) if wait => sub_cgroup,
+        _ => return Ok(()),
+    };
+
+    let exit_code = run::wait_for_exit(exec.pid());
+    let removed = container.sub_cgroup_path(sub_cgroup).and_then(|path| {
+        fs::remove_dir(&path).with_context(|| format!("failed to remove {:?}", path))
+    });
+    if let Err(e) = removed {
+        log::warn!("failed to remove sub-cgroup {:?}: {:?}", sub_cgroup, e);
+    }
+
+    std::process::exit(exit_code?);
+}
+
+/// Assembles the process either from the process file, which describes it
+/// completely, or from the single options
+fn build_process(args: &Exec, container: &Container) -> Result<Process> {
+    if let Some(process) = &args.process {
+        let file = utils::open(process)?;
+        return serde_json::from_reader(file)
+            .with_context(|| format!("failed to parse process spec {:?}", process));
+    }
+
+    // the variables passed inline take precedence over the ones of the file
+    let mut env = load_env_file(args.env_file.as_ref())?;
+    env.extend(args.env.clone());
+
+    let mut builder = ProcessBuilder::default()
+        .args(args.command.clone())
+        .env(
+            env.into_iter()
+                .map(|(k, v)| format!("{}={}", k, v))
+                .collect::<Vec<_>>(),
+        )
+        .no_new_privileges(args.no_new_privs);
+    if let Some(cwd) = &args.cwd {
+        builder = builder.cwd(cwd);
+    }
+    if let Some(user) = &args.user {
+        builder = builder.user(container.resolve_user(user)?);
+    }
+
+    Ok(builder.build()?)
 }
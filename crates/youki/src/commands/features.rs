@@ -0,0 +1,137 @@
+//! Contains functionality of the features command
+use std::collections::HashMap;
+
+use anyhow::Result;
+use libcgroups::common::{CgroupSetup, SYSTEMD_SUPPORTED, V1_SUPPORTED, V2_SUPPORTED};
+use libcontainer::{apparmor, capabilities, rootfs, seccomp, selinux};
+use liboci_cli::Features;
+use serde::Serialize;
+
+/// Annotation containing the version of the linked libseccomp
+const SECCOMP_VERSION_ANNOTATION: &str = "io.github.seccomp.libseccomp.version";
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RuntimeFeatures {
+    oci_version_min: &'static str,
+    oci_version_max: &'static str,
+    hooks: Vec<&'static str>,
+    mount_options: Vec<&'static str>,
+    linux: LinuxFeatures,
+    annotations: HashMap<&'static str, String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LinuxFeatures {
+    namespaces: Vec<&'static str>,
+    capabilities: Vec<String>,
+    cgroup: CgroupFeatures,
+    seccomp: SeccompFeatures,
+    apparmor: Enabled,
+    selinux: Enabled,
+    mount_extensions: MountExtensions,
+}
+
+#[derive(Debug, Serialize)]
+struct CgroupFeatures {
+    v1: bool,
+    v2: bool,
+    systemd: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct SeccompFeatures {
+    enabled: bool,
+    actions: Vec<String>,
+    operators: Vec<String>,
+    archs: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct Enabled {
+    enabled: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MountExtensions {
+    idmap: Enabled,
+}
+
+/// Prints the features supported by youki and the host as json
+pub fn features(_: Features) -> Result<()> {
+    let features = RuntimeFeatures {
+        oci_version_min: "1.0.0",
+        oci_version_max: "1.0.2-dev",
+        hooks: vec![
+            "prestart",
+            "createRuntime",
+            "createContainer",
+            "startContainer",
+            "poststart",
+            "poststop",
+        ],
+        mount_options: rootfs::MOUNT_OPTIONS.to_vec(),
+        linux: LinuxFeatures {
+            namespaces: vec!["cgroup", "ipc", "mount", "network", "pid", "user", "uts"],
+            capabilities: capabilities::supported_capabilities(),
+            cgroup: cgroup_features(),
+            seccomp: seccomp_features()?,
+            apparmor: Enabled {
+                enabled: apparmor::is_enabled().unwrap_or(false),
+            },
+            selinux: Enabled {
+                enabled: selinux::is_enabled(),
+            },
+            mount_extensions: MountExtensions {
+                idmap: Enabled {
+                    enabled: rootfs::idmapped_mounts_supported(),
+                },
+            },
+        },
+        annotations: HashMap::from([(SECCOMP_VERSION_ANNOTATION, seccomp::library_version()?)]),
+    };
+
+    println!("{}", serde_json::to_string_pretty(&features)?);
+    Ok(())
+}
+
+/// Reports the cgroup versions, which are both available on the host and have
+/// been compiled in
+fn cgroup_features() -> CgroupFeatures {
+    let (v1, v2) = match libcgroups::common::get_cgroup_setup() {
+        Ok(CgroupSetup::Legacy) => (true, false),
+        Ok(CgroupSetup::Hybrid) => (true, true),
+        Ok(CgroupSetup::Unified) => (false, true),
+        Err(_) => (false, false),
+    };
+
+    CgroupFeatures {
+        v1: v1 && V1_SUPPORTED,
+        v2: v2 && V2_SUPPORTED,
+        systemd: SYSTEMD_SUPPORTED,
+    }
+}
+
+fn seccomp_features() -> Result<SeccompFeatures> {
+    // Serialize the spec types, so the names match the ones used in config.json
+    fn names<T: Serialize>(values: &[T]) -> Result<Vec<String>> {
+        values
+            .iter()
+            .map(|v| {
+                Ok(serde_json::to_value(v)?
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_owned())
+            })
+            .collect()
+    }
+
+    Ok(SeccompFeatures {
+        enabled: true,
+        actions: names(&seccomp::supported_actions())?,
+        operators: names(&seccomp::supported_operators())?,
+        archs: names(&seccomp::supported_archs()?)?,
+    })
+}
@@ -1,11 +1,17 @@
-use anyhow::{bail, Context, Result};
+use anyhow::{Context, Result};
 use std::{
-    fs,
+    collections::HashMap,
+    env,
+    ffi::OsString,
     path::{Path, PathBuf},
 };
 
 use libcgroups::common::CgroupManager;
-use libcontainer::container::Container;
+use libcontainer::{
+    container::Container,
+    sd_notify::{NotifyProxy, NotifyRelay, NOTIFY_SOCKET_ENV},
+    utils,
+};
 
 pub mod checkpoint;
 pub mod completion;
@@ -13,11 +19,13 @@ pub mod create;
 pub mod delete;
 pub mod events;
 pub mod exec;
+pub mod features;
 pub mod info;
 pub mod kill;
 pub mod list;
 pub mod pause;
 pub mod ps;
+pub mod restore;
 pub mod resume;
 pub mod run;
 pub mod spec_json;
@@ -25,39 +33,11 @@ pub mod start;
 pub mod state;
 pub mod update;
 
-fn construct_container_root<P: AsRef<Path>>(root_path: P, container_id: &str) -> Result<PathBuf> {
-    // resolves relative paths, symbolic links etc. and get complete path
-    let root_path = fs::canonicalize(&root_path).with_context(|| {
-        format!(
-            "failed to canonicalize {} for container {}",
-            root_path.as_ref().display(),
-            container_id
-        )
-    })?;
-    // the state of the container is stored in a directory named after the container id
-    Ok(root_path.join(container_id))
-}
-
-fn load_container<P: AsRef<Path>>(root_path: P, container_id: &str) -> Result<Container> {
-    let container_root = construct_container_root(root_path, container_id)?;
-    if !container_root.exists() {
-        bail!("container {} does not exist.", container_id)
-    }
-
-    Container::load(container_root)
-        .with_context(|| format!("could not load state for container {}", container_id))
-}
-
-fn container_exists<P: AsRef<Path>>(root_path: P, container_id: &str) -> Result<bool> {
-    let container_root = construct_container_root(root_path, container_id)?;
-    Ok(container_root.exists())
-}
-
 fn create_cgroup_manager<P: AsRef<Path>>(
     root_path: P,
     container_id: &str,
 ) -> Result<Box<dyn CgroupManager>> {
-    let container = load_container(root_path, container_id)?;
+    let container = Container::load_by_id(root_path, container_id)?;
     let cgroups_path = container.spec()?.cgroup_path;
     let systemd_cgroup = container
         .systemd()
@@ -65,3 +45,39 @@ fn create_cgroup_manager<P: AsRef<Path>>(
 
     libcgroups::common::create_cgroup_manager(cgroups_path, systemd_cgroup, container.id())
 }
+
+/// Reads the environment variables of the file, if one is given
+fn load_env_file(env_file: Option<&PathBuf>) -> Result<HashMap<String, String>> {
+    match env_file {
+        Some(env_file) => Ok(utils::parse_env_file(env_file)?.into_iter().collect()),
+        None => Ok(HashMap::new()),
+    }
+}
+
+/// Returns the notify socket of the service manager youki is running under
+fn notify_socket() -> Option<OsString> {
+    env::var_os(NOTIFY_SOCKET_ENV).filter(|socket| !socket.is_empty())
+}
+
+/// Binds the socket relaying the readiness notifications of the container,
+/// if it has been created with one and youki is still run by systemd
+fn notify_relay(container: &Container) -> Result<Option<NotifyRelay>> {
+    match notify_socket() {
+        Some(host_socket) if NotifyProxy::is_enabled(&container.root) => {
+            Ok(Some(NotifyProxy::new(host_socket, &container.root).bind()?))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Returns the path the runtime spec is read from, if it is not read from the
+/// bundle. '-' refers to stdin.
+fn spec_path(config: Option<&PathBuf>) -> Option<PathBuf> {
+    config.map(|path| {
+        if path.as_os_str() == "-" {
+            PathBuf::from("/dev/stdin")
+        } else {
+            path.clone()
+        }
+    })
+}
@@ -1,30 +1,55 @@
 use crate::commands::create_cgroup_manager;
 use anyhow::{bail, Result};
+use libcontainer::container::{Container, ContainerStatus};
 use liboci_cli::Ps;
 use std::{path::PathBuf, process::Command};
 
 pub fn ps(args: Ps, root_path: PathBuf) -> Result<()> {
+    let container = Container::load_by_id(&root_path, &args.container_id)?;
+    if !matches!(
+        container.status(),
+        ContainerStatus::Running | ContainerStatus::Paused
+    ) {
+        bail!("container {} is not running", args.container_id);
+    }
+
     let cmanager = create_cgroup_manager(root_path, &args.container_id)?;
 
+    // The kernel translates the pids in cgroup.procs into the pid namespace of
+    // the reader, which is the same namespace the host ps is running in. Pids
+    // of processes that are not visible in this namespace are reported as 0.
     let pids: Vec<i32> = cmanager
         .get_all_pids()?
         .iter()
         .map(|pid| pid.as_raw())
+        .filter(|pid| *pid != 0)
         .collect();
 
-    if args.format == "json" {
-        println!("{}", serde_json::to_string(&pids)?);
-    } else if args.format == "table" {
-        let default_ps_options = vec![String::from("-ef")];
-        let ps_options = if args.ps_options.is_empty() {
-            &default_ps_options
-        } else {
-            &args.ps_options
-        };
-        let output = Command::new("ps").args(ps_options).output()?;
-        if !output.status.success() {
-            println!("{}", std::str::from_utf8(&output.stderr)?);
-        } else {
+    if args.quiet {
+        for pid in &pids {
+            println!("{}", pid);
+        }
+        return Ok(());
+    }
+
+    match args.format.as_str() {
+        "json" => println!("{}", serde_json::to_string(&pids)?),
+        "table" => {
+            let default_ps_options = vec![String::from("-ef")];
+            let ps_options = if args.ps_options.is_empty() {
+                &default_ps_options
+            } else {
+                &args.ps_options
+            };
+            let output = Command::new("ps").args(ps_options).output()?;
+            if !output.status.success() {
+                bail!(
+                    "ps failed with {}: {}",
+                    output.status,
+                    std::str::from_utf8(&output.stderr)?
+                );
+            }
+
             let lines = std::str::from_utf8(&output.stdout)?;
             let lines: Vec<&str> = lines.split('\n').collect();
             let pid_index = get_pid_index(lines[0])?;
@@ -34,13 +59,18 @@ pub fn ps(args: Ps, root_path: PathBuf) -> Result<()> {
                     continue;
                 }
                 let fields: Vec<&str> = line.split_whitespace().collect();
-                let pid: i32 = fields[pid_index].parse()?;
+                let pid: i32 = match fields.get(pid_index) {
+                    Some(pid) => pid.parse()?,
+                    None => continue,
+                };
                 if pids.contains(&pid) {
                     println!("{}", line);
                 }
             }
         }
+        format => bail!("unknown format {}, must be one of table or json", format),
     }
+
     Ok(())
 }
 
@@ -0,0 +1,69 @@
+//! Contains functionality of restore container command
+use std::{fs, path::PathBuf};
+
+use anyhow::{bail, Context, Result};
+use nix::sys::wait::{waitpid, WaitStatus};
+
+use libcontainer::container::{Container, ContainerStatus, RestoreOptions};
+use libcontainer::utils;
+use liboci_cli::Restore;
+
+pub fn restore(args: Restore, root_path: PathBuf, systemd_cgroup: bool) -> Result<()> {
+    log::debug!("start restoring container {}", args.container_id);
+    if Container::exists(&root_path, &args.container_id)? {
+        bail!("container {} already exists", args.container_id);
+    }
+
+    let container_root = Container::container_root(&root_path, &args.container_id)?;
+    utils::create_dir_all(&container_root).context("failed to create container dir")?;
+    let bundle = fs::canonicalize(&args.bundle)
+        .with_context(|| format!("failed to canonicalize bundle {:?}", args.bundle))?;
+    let opts = RestoreOptions {
+        ext_unix_sk: args.ext_unix_sk,
+        file_locks: args.file_locks,
+        image_path: args.image_path,
+        shell_job: args.shell_job,
+        tcp_established: args.tcp_established,
+        work_path: args.work_path,
+        criu_path: args.criu,
+    };
+
+    let restored = Container::new(
+        &args.container_id,
+        ContainerStatus::Creating,
+        None,
+        &bundle,
+        &container_root,
+    )
+    .and_then(|mut container| {
+        container.set_systemd(systemd_cgroup).save()?;
+        let pid = container.restore(&opts)?;
+        Ok((container, pid))
+    });
+    let (mut container, pid) = match restored {
+        Ok(restored) => restored,
+        Err(e) => {
+            let _ = fs::remove_dir_all(&container_root);
+            return Err(e)
+                .with_context(|| format!("failed to restore container {}", args.container_id));
+        }
+    };
+
+    if let Some(pid_file) = &args.pid_file {
+        utils::write_pid_file(pid_file, pid.as_raw())?;
+    }
+
+    if args.detach {
+        return Ok(());
+    }
+
+    // The restored process is a child of youki, so wait for it and exit with
+    // its exit code like a foreground run would.
+    let exit_code = match waitpid(pid, None)? {
+        WaitStatus::Exited(_, code) => code,
+        WaitStatus::Signaled(_, signal, _) => 128 + signal as i32,
+        status => bail!("unexpected wait status {:?}", status),
+    };
+    container.set_status(ContainerStatus::Stopped).save()?;
+    std::process::exit(exit_code);
+}
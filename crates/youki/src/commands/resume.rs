@@ -3,18 +3,20 @@ use std::path::PathBuf;
 
 use anyhow::{Context, Result};
 
-use crate::commands::load_container;
+use libcontainer::container::Container;
 
 use liboci_cli::Resume;
 
 // Resuming a container indicates resuming all processes in given container from paused state
-// This uses Freezer cgroup to suspend and resume processes
+// This uses the freezer cgroup of cgroup v1 or cgroup.freeze of cgroup v2,
+// depending on the cgroup setup of the host, to suspend and resume processes
 // For more information see :
 // https://man7.org/linux/man-pages/man7/cgroups.7.html
 // https://www.kernel.org/doc/Documentation/cgroup-v1/freezer-subsystem.txt
+// https://www.kernel.org/doc/html/latest/admin-guide/cgroup-v2.html#core-interface-files
 pub fn resume(args: Resume, root_path: PathBuf) -> Result<()> {
     log::debug!("start resuming container {}", args.container_id);
-    let mut container = load_container(root_path, &args.container_id)?;
+    let mut container = Container::load_by_id(root_path, &args.container_id)?;
     container
         .resume()
         .with_context(|| format!("failed to resume container {}", args.container_id))
@@ -1,8 +1,29 @@
-use std::path::PathBuf;
+use std::{os::unix::prelude::RawFd, path::PathBuf, thread};
 
-use anyhow::{Context, Result};
-use libcontainer::{container::builder::ContainerBuilder, syscall::syscall::create_syscall};
+use anyhow::{bail, Context, Result};
+use libcontainer::{container::builder::ContainerBuilder, syscall::syscall::create_syscall, tty};
 use liboci_cli::Run;
+use nix::{
+    errno::Errno,
+    sys::{
+        signal::{self, SigSet, SigmaskHow, Signal},
+        wait::{waitpid, WaitPidFlag, WaitStatus},
+    },
+    unistd::{self, Pid},
+};
+
+use crate::commands::{load_env_file, notify_relay, notify_socket, spec_path};
+
+/// Signals which are relayed to the init process of a foreground container
+const FORWARDED_SIGNALS: &[Signal] = &[
+    Signal::SIGHUP,
+    Signal::SIGINT,
+    Signal::SIGQUIT,
+    Signal::SIGTERM,
+    Signal::SIGUSR1,
+    Signal::SIGUSR2,
+    Signal::SIGWINCH,
+];
 
 pub fn run(args: Run, root_path: PathBuf, systemd_cgroup: bool) -> Result<()> {
     let syscall = create_syscall();
@@ -12,10 +33,172 @@ pub fn run(args: Run, root_path: PathBuf, systemd_cgroup: bool) -> Result<()> {
         .with_root_path(root_path)?
         .with_preserved_fds(args.preserve_fds)
         .as_init(&args.bundle)
-        .with_systemd(systemd_cgroup)
-        .build()?;
+        .with_spec_path(spec_path(args.config.as_ref()))
+        .with_systemd_cgroup(systemd_cgroup)
+        .with_no_pivot(args.no_pivot)
+        .with_env(load_env_file(args.env_file.as_ref())?)
+        .with_notify_socket(notify_socket())
+        .with_allow_existing_cgroup(args.allow_existing_cgroup)
+        // in the foreground, youki waits for the init process
+        .with_detach(args.detach)
+        .create()?;
+
+    // In the foreground, the signals are blocked before the container is
+    // started, so that none received in between is lost instead of being
+    // forwarded to the init process.
+    let init_pid = container.pid().context("container has no init process")?;
+    let forwarder = if args.detach {
+        None
+    } else {
+        Some(SignalForwarder::new(init_pid, container.console_master())?)
+    };
 
+    let relay = notify_relay(&container)?;
     container
         .start()
-        .with_context(|| format!("failed to start container {}", args.container_id))
+        .with_context(|| format!("failed to start container {}", args.container_id))?;
+    if let Some(relay) = relay {
+        if args.detach {
+            return relay.run(init_pid);
+        }
+        // In the foreground, the exit of the container is waited for at the
+        // same time, a container which never becomes ready must not block it.
+        // The signals are left to the forwarder of the main thread.
+        thread::spawn(move || {
+            if let Err(e) = SigSet::all().thread_block() {
+                log::warn!("failed to block signals of the notify relay: {}", e);
+            }
+            if let Err(e) = relay.run(init_pid) {
+                log::warn!("failed to relay notify messages: {:?}", e);
+            }
+        });
+    }
+    let forwarder = match forwarder {
+        Some(forwarder) => forwarder,
+        None => return Ok(()),
+    };
+
+    let exit_code = forwarder.wait_for_exit();
+    // the poststop hooks run by delete must not inherit the blocked signals
+    drop(forwarder);
+    if let Some(master) = container.console_master() {
+        let _ = unistd::close(master);
+    }
+
+    // The container is removed regardless of how its init process exited
+    if let Err(e) = container.delete(true) {
+        log::warn!("failed to delete container {}: {:?}", args.container_id, e);
+    }
+
+    std::process::exit(exit_code?);
+}
+
+/// Waits for the process to exit, while signals received by youki are
+/// forwarded to it, and returns its exit code. The forwarder is dropped
+/// before returning, so processes spawned afterwards, like the poststop
+/// hooks, do not inherit the blocked signals.
+pub(crate) fn wait_for_exit(pid: Pid) -> Result<i32> {
+    SignalForwarder::new(pid, None)?.wait_for_exit()
+}
+
+/// Relays the signals received by youki to the init process until it exits.
+/// The signals are blocked and consumed synchronously with sigwait, the
+/// previous signal mask is restored when the forwarder is dropped. With the
+/// pty master of the container, SIGWINCH resizes the pty to the size of the
+/// terminal of youki instead, which signals the container process itself.
+struct SignalForwarder {
+    init_pid: Pid,
+    console_master: Option<RawFd>,
+    signals: SigSet,
+    old_mask: SigSet,
+}
+
+impl SignalForwarder {
+    fn new(init_pid: Pid, console_master: Option<RawFd>) -> Result<Self> {
+        let mut signals = SigSet::empty();
+        for signal in FORWARDED_SIGNALS {
+            signals.add(*signal);
+        }
+        // SIGCHLD is only waited for to detect the exit of the init process
+        signals.add(Signal::SIGCHLD);
+
+        let old_mask = signals
+            .thread_swap_mask(SigmaskHow::SIG_BLOCK)
+            .context("failed to block the forwarded signals")?;
+        Ok(Self {
+            init_pid,
+            console_master,
+            signals,
+            old_mask,
+        })
+    }
+
+    /// Forwards signals until the init process has exited and returns its
+    /// exit code, or 128 + signal number if it has been terminated by a
+    /// signal, like a shell does.
+    fn wait_for_exit(&self) -> Result<i32> {
+        loop {
+            // The init process may have exited before SIGCHLD was blocked
+            if let Some(exit_code) = self.reap()? {
+                return Ok(exit_code);
+            }
+
+            match self.signals.wait() {
+                Ok(Signal::SIGCHLD) => continue,
+                Ok(Signal::SIGWINCH) if self.console_master.is_some() => {
+                    self.forward_winsize();
+                }
+                Ok(sig) => {
+                    log::debug!("forwarding {} to init process {}", sig, self.init_pid);
+                    match signal::kill(self.init_pid, sig) {
+                        // the process has exited, it is reaped in the next iteration
+                        Ok(_) | Err(Errno::ESRCH) => {}
+                        Err(e) => bail!(
+                            "failed to forward {} to init process {}: {}",
+                            sig,
+                            self.init_pid,
+                            e
+                        ),
+                    }
+                }
+                Err(Errno::EINTR) => continue,
+                Err(e) => bail!("failed to wait for signals: {}", e),
+            }
+        }
+    }
+
+    /// Resizes the pty of the container to the size of the terminal of youki.
+    /// A failure is not fatal, e.g. youki may not run in a terminal at all.
+    fn forward_winsize(&self) {
+        if let Some(master) = self.console_master {
+            if let Err(e) = tty::forward_winsize(nix::libc::STDIN_FILENO, master) {
+                log::warn!("failed to forward the window size: {}", e);
+            }
+        }
+    }
+
+    /// Reaps all exited children, which includes processes of the container
+    /// reparented to youki as the subreaper. Returns the exit code of the init
+    /// process once it has been reaped.
+    fn reap(&self) -> Result<Option<i32>> {
+        loop {
+            match waitpid(None, Some(WaitPidFlag::WNOHANG)) {
+                Ok(WaitStatus::Exited(pid, code)) if pid == self.init_pid => return Ok(Some(code)),
+                Ok(WaitStatus::Signaled(pid, signal, _)) if pid == self.init_pid => {
+                    return Ok(Some(128 + signal as i32))
+                }
+                Ok(WaitStatus::StillAlive) => return Ok(None),
+                Ok(_) | Err(Errno::EINTR) => continue,
+                Err(e) => bail!("failed to wait for init process {}: {}", self.init_pid, e),
+            }
+        }
+    }
+}
+
+impl Drop for SignalForwarder {
+    fn drop(&mut self) {
+        if let Err(e) = self.old_mask.thread_set_mask() {
+            log::warn!("failed to restore signal mask: {}", e);
+        }
+    }
 }
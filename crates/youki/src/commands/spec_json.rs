@@ -2,8 +2,8 @@ use anyhow::Result;
 use nix;
 use oci_spec::runtime::Mount;
 use oci_spec::runtime::{
-    LinuxBuilder, LinuxIdMappingBuilder, LinuxNamespace, LinuxNamespaceBuilder, LinuxNamespaceType,
-    Spec,
+    Capabilities, Capability, LinuxIdMappingBuilder, LinuxNamespace, LinuxNamespaceBuilder,
+    LinuxNamespaceType, Spec,
 };
 use serde_json::to_writer_pretty;
 use std::fs::File;
@@ -14,6 +14,21 @@ pub fn get_default() -> Result<Spec> {
     Ok(Spec::default())
 }
 
+/// Capabilities which only take effect in the initial user namespace and
+/// therefore can not be held by a rootless container
+const ROOTLESS_UNAVAILABLE_CAPABILITIES: &[Capability] = &[
+    Capability::AuditControl,
+    Capability::AuditRead,
+    Capability::AuditWrite,
+    Capability::MacAdmin,
+    Capability::MacOverride,
+    Capability::Mknod,
+    Capability::SysBoot,
+    Capability::SysModule,
+    Capability::SysTime,
+    Capability::Syslog,
+];
+
 pub fn get_rootless() -> Result<Spec> {
     // Remove network and user namespace from the default spec
     let mut namespaces: Vec<LinuxNamespace> = oci_spec::runtime::get_default_namespaces()
@@ -33,23 +48,34 @@ pub fn get_rootless() -> Result<Spec> {
     let uid = nix::unistd::geteuid().as_raw();
     let gid = nix::unistd::getegid().as_raw();
 
-    let linux = LinuxBuilder::default()
-        .namespaces(namespaces)
-        .uid_mappings(vec![LinuxIdMappingBuilder::default()
+    let mut spec = get_default()?;
+    // Keep the masked and readonly paths of the default spec, but drop the
+    // resources as an unprivileged user can usually not write to the cgroups
+    let mut linux = spec.linux().clone().unwrap_or_default();
+    linux
+        .set_namespaces(Some(namespaces))
+        .set_uid_mappings(Some(vec![LinuxIdMappingBuilder::default()
             .host_id(uid)
             .container_id(0_u32)
             .size(1_u32)
-            .build()?])
-        .gid_mappings(vec![LinuxIdMappingBuilder::default()
+            .build()?]))
+        .set_gid_mappings(Some(vec![LinuxIdMappingBuilder::default()
             .host_id(gid)
             .container_id(0_u32)
             .size(1_u32)
-            .build()?])
-        .build()?;
+            .build()?]))
+        .set_resources(None);
 
-    // Prepare the mounts
-
-    let mut mounts: Vec<Mount> = oci_spec::runtime::get_default_mounts();
+    // Prepare the mounts. Sysfs can not be mounted without owning the network
+    // namespace, so it is bind mounted from the host together with everything
+    // below it, e.g. the cgroup filesystem.
+    let mut mounts: Vec<Mount> = oci_spec::runtime::get_default_mounts()
+        .into_iter()
+        .filter(|m| {
+            let destination = m.destination();
+            destination == Path::new("/sys") || !destination.starts_with("/sys")
+        })
+        .collect();
     for mount in &mut mounts {
         if mount.destination().eq(Path::new("/sys")) {
             mount
@@ -75,7 +101,26 @@ pub fn get_rootless() -> Result<Spec> {
         }
     }
 
-    let mut spec = get_default()?;
+    if let Some(mut process) = spec.process().clone() {
+        if let Some(mut capabilities) = process.capabilities().clone() {
+            let drop = |caps: &Option<Capabilities>| {
+                caps.clone().map(|caps| {
+                    caps.into_iter()
+                        .filter(|c| !ROOTLESS_UNAVAILABLE_CAPABILITIES.contains(c))
+                        .collect()
+                })
+            };
+            capabilities
+                .set_bounding(drop(capabilities.bounding()))
+                .set_effective(drop(capabilities.effective()))
+                .set_inheritable(drop(capabilities.inheritable()))
+                .set_permitted(drop(capabilities.permitted()))
+                .set_ambient(drop(capabilities.ambient()));
+            process.set_capabilities(Some(capabilities));
+        }
+        spec.set_process(Some(process));
+    }
+
     spec.set_linux(Some(linux)).set_mounts(Some(mounts));
     Ok(spec)
 }
@@ -107,6 +152,36 @@ mod tests {
         let tmpdir = create_temp_dir("test_spec_json").expect("failed to create temp dir");
         let path = tmpdir.path().join("config.json");
         to_writer_pretty(&File::create(path)?, &spec)?;
+
+        let linux = spec.linux().as_ref().expect("linux config");
+        assert!(linux
+            .namespaces()
+            .as_ref()
+            .unwrap()
+            .iter()
+            .any(|ns| ns.typ() == LinuxNamespaceType::User));
+        assert!(linux.resources().is_none());
+        assert!(!linux.masked_paths().as_ref().unwrap().is_empty());
+
+        let capabilities = spec
+            .process()
+            .as_ref()
+            .and_then(|p| p.capabilities().as_ref())
+            .expect("process capabilities");
+        for caps in [capabilities.bounding(), capabilities.effective()]
+            .into_iter()
+            .flatten()
+        {
+            assert!(!caps.contains(&Capability::AuditWrite));
+            assert!(!caps.contains(&Capability::Mknod));
+        }
+
+        let mounts = spec.mounts().as_ref().unwrap();
+        assert!(!mounts
+            .iter()
+            .any(|m| m.destination() == Path::new("/sys/fs/cgroup")));
+        assert!(mounts.iter().any(|m| m.destination() == Path::new("/sys")
+            && m.options().as_ref().unwrap().contains(&"rbind".to_owned())));
         Ok(())
     }
 }
@@ -4,13 +4,22 @@ use std::path::PathBuf;
 
 use anyhow::{Context, Result};
 
-use crate::commands::load_container;
+use libcontainer::container::Container;
 
 use liboci_cli::Start;
 
+use crate::commands::notify_relay;
+
 pub fn start(args: Start, root_path: PathBuf) -> Result<()> {
-    let mut container = load_container(root_path, &args.container_id)?;
+    let mut container = Container::load_by_id(root_path, &args.container_id)?;
+    // the relay has to be bound before the container process can send to it
+    let relay = notify_relay(&container)?;
     container
         .start()
-        .with_context(|| format!("failed to start container {}", args.container_id))
+        .with_context(|| format!("failed to start container {}", args.container_id))?;
+
+    if let Some(relay) = relay {
+        relay.run(container.pid().context("container has no init process")?)?;
+    }
+    Ok(())
 }
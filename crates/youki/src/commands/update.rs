@@ -2,35 +2,71 @@ use std::fs;
 use std::io;
 use std::path::PathBuf;
 
-use crate::commands::create_cgroup_manager;
-use anyhow::Result;
-use libcgroups::{self, common::ControllerOpt};
+use anyhow::{bail, Context, Result};
+use libcontainer::container::Container;
 use liboci_cli::Update;
-use oci_spec::runtime::{LinuxPidsBuilder, LinuxResources, LinuxResourcesBuilder};
+use oci_spec::runtime::{
+    LinuxBlockIoBuilder, LinuxCpuBuilder, LinuxMemoryBuilder, LinuxPidsBuilder, LinuxResources,
+    LinuxResourcesBuilder,
+};
 
 pub fn update(args: Update, root_path: PathBuf) -> Result<()> {
-    let cmanager = create_cgroup_manager(root_path, &args.container_id)?;
+    let mut container = Container::load_by_id(root_path, &args.container_id)?;
 
     let linux_res: LinuxResources;
     if let Some(resources_path) = args.resources {
+        // a partial LinuxResources like runc accepts it, omitted fields keep
+        // their current value
         linux_res = if resources_path.to_string_lossy() == "-" {
-            serde_json::from_reader(io::stdin())?
+            serde_json::from_reader(io::stdin()).context("failed to parse resources from stdin")?
         } else {
-            serde_json::from_reader(fs::File::open(resources_path)?)?
+            let file = fs::File::open(&resources_path)
+                .with_context(|| format!("failed to open {:?}", resources_path))?;
+            serde_json::from_reader(file)
+                .with_context(|| format!("failed to parse resources from {:?}", resources_path))?
         };
     } else {
         let mut builder = LinuxResourcesBuilder::default();
         if let Some(new_pids_limit) = args.pids_limit {
             builder = builder.pids(LinuxPidsBuilder::default().limit(new_pids_limit).build()?);
         }
+
+        if args.cpu_shares.is_some() || args.cpu_period.is_some() || args.cpu_quota.is_some() {
+            let mut cpu = LinuxCpuBuilder::default();
+            if let Some(shares) = args.cpu_shares {
+                cpu = cpu.shares(shares);
+            }
+            if let Some(period) = args.cpu_period {
+                cpu = cpu.period(period);
+            }
+            if let Some(quota) = args.cpu_quota {
+                cpu = cpu.quota(quota);
+            }
+            builder = builder.cpu(cpu.build()?);
+        }
+
+        if args.memory.is_some() || args.memory_swap.is_some() {
+            let mut memory = LinuxMemoryBuilder::default();
+            if let Some(limit) = args.memory {
+                memory = memory.limit(limit);
+            }
+            if let Some(swap) = args.memory_swap {
+                memory = memory.swap(swap);
+            }
+            builder = builder.memory(memory.build()?);
+        }
+
+        if let Some(weight) = args.blkio_weight {
+            if !(10..=1000).contains(&weight) {
+                bail!("blkio weight {} must be between 10 and 1000", weight);
+            }
+            builder = builder.block_io(LinuxBlockIoBuilder::default().weight(weight).build()?);
+        }
         linux_res = builder.build()?;
     }
 
-    cmanager.apply(&ControllerOpt {
-        resources: &linux_res,
-        disable_oom_killer: false,
-        oom_score_adj: None,
-        freezer_state: None,
-    })?;
+    container
+        .update(&linux_res)
+        .with_context(|| format!("failed to update container {}", args.container_id))?;
     Ok(())
 }
@@ -124,13 +124,21 @@ impl Log for YoukiLogger {
     }
 }
 
+/// Formats the record as a single line JSON object. The source location is
+/// only included if it is known.
 fn json_format(record: &log::Record) -> String {
-    serde_json::to_string(&serde_json::json!({
+    let mut object = serde_json::json!({
         "level": record.level().to_string(),
         "time": chrono::Local::now().to_rfc3339(),
-        "message": record.args(),
-    }))
-    .expect("serde::to_string with string keys will not fail")
+        "target": record.target(),
+        "message": record.args().to_string(),
+    });
+    if let (Some(file), Some(line)) = (record.file(), record.line()) {
+        object["file"] = file.into();
+        object["line"] = line.into();
+    }
+
+    serde_json::to_string(&object).expect("serde::to_string with string keys will not fail")
 }
 
 fn text_format(record: &log::Record) -> String {
@@ -209,6 +217,38 @@ mod tests {
         assert_eq!(detect_log_level(false).unwrap(), LevelFilter::Error)
     }
 
+    #[test]
+    fn test_detect_log_format() {
+        assert!(matches!(detect_log_format(None), Ok(LogFormat::Text)));
+        assert!(matches!(
+            detect_log_format(Some("json".to_owned())),
+            Ok(LogFormat::Json)
+        ));
+        assert!(detect_log_format(Some("xml".to_owned())).is_err());
+    }
+
+    #[test]
+    fn test_json_format() {
+        let msg = json_format(
+            &Record::builder()
+                .args(format_args!("container {} created", "test"))
+                .level(log::Level::Info)
+                .target("youki::create")
+                .file(Some("create.rs"))
+                .line(Some(42))
+                .build(),
+        );
+        assert!(!msg.contains('\n'));
+
+        let object: serde_json::Value = serde_json::from_str(&msg).expect("parse json log");
+        assert_eq!(object["level"], "INFO");
+        assert_eq!(object["target"], "youki::create");
+        assert_eq!(object["message"], "container test created");
+        assert_eq!(object["file"], "create.rs");
+        assert_eq!(object["line"], 42);
+        assert!(object["time"].is_string());
+    }
+
     #[test]
     fn test_logfile() {
         let temp_dir = create_temp_dir("logfile").expect("failed to create tempdir for logfile");
@@ -19,5 +19,12 @@ fn get_spec() -> Spec {
 
 fn main() {
     let spec = get_spec();
-    tests::validate_readonly_paths(&spec);
+    if let Some(linux) = spec.linux() {
+        if linux.readonly_paths().is_some() {
+            tests::validate_readonly_paths(&spec);
+        }
+    }
+    tests::validate_readonly_root(&spec);
+    tests::validate_masked_paths(&spec);
+    tests::validate_devpts(&spec);
 }
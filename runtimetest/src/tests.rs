@@ -1,6 +1,10 @@
 use crate::utils::{test_read_access, test_write_access};
 use nix::errno::Errno;
+use nix::fcntl::OFlag;
+use nix::pty::{grantpt, posix_openpt, ptsname_r, unlockpt};
+use nix::sys::stat::stat;
 use oci_spec::runtime::Spec;
+use std::path::Path;
 
 pub fn validate_readonly_paths(spec: &Spec) {
     let linux = spec.linux().as_ref().unwrap();
@@ -59,3 +63,194 @@ pub fn validate_readonly_paths(spec: &Spec) {
         }
     }
 }
+
+pub fn validate_readonly_root(spec: &Spec) {
+    let readonly = spec
+        .root()
+        .as_ref()
+        .and_then(|root| root.readonly())
+        .unwrap_or(false);
+    if !readonly {
+        return;
+    }
+
+    match test_write_access("/") {
+        std::io::Result::Ok(_) => {
+            eprintln!("in readonly root, root expected to not be writable, found writable");
+            return;
+        }
+        std::io::Result::Err(e) => {
+            let errno = Errno::from_i32(e.raw_os_error().unwrap_or_default());
+            if errno != Errno::EROFS {
+                eprintln!(
+                    "in readonly root, expected EROFS writing to the root, got {:?}",
+                    e
+                );
+                return;
+            }
+        }
+    }
+
+    // mounts on top of the read only root keep their own flags
+    for mount in spec.mounts().iter().flatten() {
+        if mount.typ().as_deref() != Some("tmpfs") {
+            continue;
+        }
+
+        let path = mount.destination().to_string_lossy();
+        if let std::io::Result::Err(e) = test_write_access(&path) {
+            eprintln!(
+                "in readonly root, tmpfs mount {} expected to be writable, got {:?}",
+                path, e
+            );
+            return;
+        }
+    }
+}
+
+pub fn validate_masked_paths(spec: &Spec) {
+    let masked_paths = match spec
+        .linux()
+        .as_ref()
+        .and_then(|l| l.masked_paths().as_ref())
+    {
+        Some(p) => p,
+        None => return,
+    };
+
+    for path in masked_paths {
+        let metadata = match std::fs::metadata(path) {
+            std::io::Result::Ok(metadata) => metadata,
+            // masked paths, which do not exist, are skipped
+            std::io::Result::Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            std::io::Result::Err(e) => {
+                eprintln!("in masked paths, error in stat of path {} : {:?}", path, e);
+                return;
+            }
+        };
+
+        if !metadata.is_dir() {
+            match std::fs::read(path) {
+                std::io::Result::Ok(content) if content.is_empty() => { /* Expected */ }
+                std::io::Result::Ok(_) => {
+                    eprintln!("in masked paths, file {} expected to be empty", path);
+                    return;
+                }
+                std::io::Result::Err(e) => {
+                    eprintln!("in masked paths, error in reading file {} : {:?}", path, e);
+                    return;
+                }
+            }
+            continue;
+        }
+
+        match std::fs::read_dir(path) {
+            std::io::Result::Ok(mut entries) => {
+                if entries.next().is_some() {
+                    eprintln!("in masked paths, directory {} expected to be empty", path);
+                    return;
+                }
+            }
+            std::io::Result::Err(e) => {
+                eprintln!(
+                    "in masked paths, error in reading directory {} : {:?}",
+                    path, e
+                );
+                return;
+            }
+        }
+
+        match test_write_access(path) {
+            std::io::Result::Ok(_) => {
+                eprintln!(
+                    "in masked paths, directory {} expected to not be writable, found writable",
+                    path
+                );
+                return;
+            }
+            std::io::Result::Err(e) => {
+                let errno = Errno::from_i32(e.raw_os_error().unwrap_or_default());
+                if errno != Errno::EROFS {
+                    eprintln!(
+                        "in masked paths, expected EROFS writing to directory {}, got {:?}",
+                        path, e
+                    );
+                    return;
+                }
+            }
+        }
+    }
+}
+
+pub fn validate_devpts(spec: &Spec) {
+    let devpts =
+        spec.mounts().iter().flatten().find(|m| {
+            m.typ().as_deref() == Some("devpts") && m.destination() == Path::new("/dev/pts")
+        });
+    let devpts = match devpts {
+        Some(devpts) => devpts,
+        None => return,
+    };
+
+    // the gid of the mount options, or the one of the tty group otherwise
+    let expected_gid = devpts
+        .options()
+        .iter()
+        .flatten()
+        .find_map(|o| o.strip_prefix("gid=").and_then(|gid| gid.parse().ok()))
+        .unwrap_or_else(tty_gid);
+
+    let slave = match open_pty() {
+        Ok(slave) => slave,
+        Err(e) => {
+            eprintln!("in devpts, error in allocating a pseudoterminal : {:?}", e);
+            return;
+        }
+    };
+    let fstat = match stat(slave.as_str()) {
+        Ok(fstat) => fstat,
+        Err(e) => {
+            eprintln!("in devpts, error in stat of {} : {:?}", slave, e);
+            return;
+        }
+    };
+
+    if fstat.st_gid != expected_gid {
+        eprintln!(
+            "in devpts, expected {} to be owned by gid {}, found {}",
+            slave, expected_gid, fstat.st_gid
+        );
+        return;
+    }
+    if fstat.st_mode & 0o777 != 0o620 {
+        eprintln!(
+            "in devpts, expected mode 0620 of {}, found {:o}",
+            slave,
+            fstat.st_mode & 0o777
+        );
+    }
+}
+
+// Allocates a pseudoterminal through /dev/ptmx and returns the path of the
+// slave device
+fn open_pty() -> nix::Result<String> {
+    let master = posix_openpt(OFlag::O_RDWR | OFlag::O_NOCTTY)?;
+    grantpt(&master)?;
+    unlockpt(&master)?;
+    ptsname_r(&master)
+}
+
+fn tty_gid() -> u32 {
+    std::fs::read_to_string("/etc/group")
+        .ok()
+        .and_then(|groups| {
+            groups.lines().find_map(|line| {
+                let mut fields = line.split(':');
+                if fields.next() != Some("tty") {
+                    return None;
+                }
+                fields.nth(1)?.parse().ok()
+            })
+        })
+        .unwrap_or(5)
+}